@@ -1,5 +1,5 @@
 use candid::{Encode, Principal};
-use did::deferred::{Contract, DeferredDataResult, GenericValue};
+use did::deferred::{Contract, DeferredDataResult, GenericValue, SignedMessage};
 use did::ID;
 
 use crate::actor::admin;
@@ -59,7 +59,7 @@ where
                 self.env.deferred_data(),
                 admin(),
                 "get_contract",
-                Encode!(contract_id).unwrap(),
+                Encode!(contract_id, &None::<SignedMessage>).unwrap(),
             )
             .await
             .unwrap()
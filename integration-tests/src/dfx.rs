@@ -102,7 +102,7 @@ impl DfxTestEnv {
         println!("EVM RPC canister: {}", evm_rpc);
 
         // install canisters
-        Self::install_deferred_data(&admin, deferred_data, deferred_minter).await;
+        Self::install_deferred_data(&admin, deferred_data, deferred_minter, &evm).await;
         println!("Deferred data canister installed");
         Self::install_deferred_minter(&admin, deferred_minter, deferred_data, evm_rpc, &evm).await;
         println!("Deferred minter canister installed");
@@ -159,6 +159,7 @@ impl DfxTestEnv {
         agent: &Agent,
         canister_id: Principal,
         deferred_minter: Principal,
+        evm: &Evm,
     ) {
         let wasm_bytes = Self::load_wasm(CanisterType::DeferredData);
 
@@ -170,6 +171,7 @@ impl DfxTestEnv {
                 log_filter: "debug".to_string(),
             },
             minter: deferred_minter,
+            chain_id: evm.chain_id,
         };
 
         Self::install_canister(agent, canister_id, wasm_bytes, (init_arg,)).await;
@@ -118,7 +118,7 @@ impl PocketIcTestEnv {
         let evm = EvmBuilder::setup().await.expect("Failed to setup EVM");
 
         // install canisters
-        Self::install_deferred_data(&pic, deferred_data, deferred_minter).await;
+        Self::install_deferred_data(&pic, deferred_data, deferred_minter, &evm).await;
         Self::install_deferred_minter(&pic, deferred_minter, deferred_data, evm_rpc, &evm).await;
         Self::configure_evm_rpc_canister(&pic, evm_rpc).await;
 
@@ -153,6 +153,7 @@ impl PocketIcTestEnv {
         pic: &PocketIc,
         canister_id: Principal,
         deferred_minter: Principal,
+        evm: &Evm,
     ) {
         pic.add_cycles(canister_id, DEFAULT_CYCLES).await;
         let wasm_bytes = Self::load_wasm(Canister::DeferredData);
@@ -165,6 +166,7 @@ impl PocketIcTestEnv {
                 log_filter: "debug".to_string(),
             },
             minter: deferred_minter,
+            chain_id: evm.chain_id,
         };
         let init_arg = Encode!(&init_arg).unwrap();
 
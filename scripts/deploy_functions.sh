@@ -6,6 +6,7 @@ deploy_deferred_data() {
   INSTALL_MODE="$1"
   NETWORK="$2"
   MINTER_ID="$3"
+  CHAIN_ID="$4"
 
   echo "deploying deferred data canister"
 
@@ -17,6 +18,7 @@ deploy_deferred_data() {
       log_filter = \"info\";
     };
     minter = principal \"$MINTER_ID\";
+    chain_id = $CHAIN_ID;
   })"
 
   dfx deploy --mode=$INSTALL_MODE --yes --network="$NETWORK" --argument="$init_args" deferred_data
@@ -34,7 +34,7 @@ cd ../
 case "$CANISTER" in
 
   "deferred_data")
-    deploy_deferred_data "reinstall" "ic" "$DEFERRED_MINTER"
+    deploy_deferred_data "reinstall" "ic" "$DEFERRED_MINTER" "$CHAIN_ID"
     ;;
   
   "deferred_minter")
@@ -26,7 +26,7 @@ cd ../
 set -e
 
 
-deploy_deferred_data "reinstall" "local" "$DEFERRED_MINTER_PRINCIPAL"
+deploy_deferred_data "reinstall" "local" "$DEFERRED_MINTER_PRINCIPAL" "$CHAIN_ID"
 deploy_deferred_minter \
     "reinstall" \
     "local" \
@@ -1,30 +1,70 @@
+mod access;
+mod certification;
 mod configuration;
+mod eip712;
+pub mod gallery;
+pub mod geo;
 mod inspect;
 mod memory;
+mod schema;
+pub mod search;
+mod siwe;
 mod storage;
 #[cfg(test)]
 pub mod test_utils;
 
+use std::collections::BTreeSet;
+
 use candid::{Nat, Principal};
 use did::deferred::{
-    Contract, ContractDocument, ContractDocumentData, DataContractError, DeferredDataError,
-    DeferredDataInitData, DeferredDataResult, GenericValue, RestrictedProperty, RestrictionLevel,
+    AccessGroup, AuditLogEntry, AuditOperation, Contract, ContractArchiveEntry, ContractDocument,
+    ContractDocumentData, ContractImage, ContractListQuery, ContractSession, ContractSortBy,
+    ContractSummaryPage, DataContractError, DeferredDataError, DeferredDataInitData,
+    DeferredDataResult, GenericValue, PropertyOperation, PropertySchema, RestrictedProperty,
+    RestrictionLevel, SignedMessage,
 };
-use did::ID;
-use ethers_core::abi::ethereum_types::H520;
+use did::{H160, ID};
 use ic_log::did::Pagination;
 use ic_log::writer::Logs;
 use ic_log::{init_log, take_memory_records};
 
+use self::access::Accessor;
+pub use self::certification::HttpCertification;
 use self::configuration::Configuration;
 pub use self::inspect::Inspect;
-pub use self::storage::ContractStorage;
-use crate::utils::{caller, cycles};
+use self::schema::Schema;
+use self::storage::{
+    AccessGroupStorage, AddressLinkStorage, AuditLogStorage, ContractCursor, NonceStorage, Session,
+    SessionStorage,
+};
+pub use self::storage::{
+    ContractEvent, ContractEventKind, ContractIndex, ContractIndexStorage, ContractStorage,
+    GeoIndexStorage, ImageStorage, SearchIndexStorage,
+};
+use crate::utils::{caller, cycles, random_bytes, time};
+
+/// Maximum amount of audit log entries returned by a single call
+const MAX_AUDIT_LOG_PAGE_SIZE: usize = 100;
+/// Amount of contracts in a page of the contract listing, when no limit is set
+const DEFAULT_CONTRACT_PAGE_SIZE: usize = 20;
+/// Maximum amount of contracts in a page of the contract listing
+const MAX_CONTRACT_PAGE_SIZE: usize = 100;
+/// Time a session is valid for (1 hour)
+const SESSION_TTL: u64 = 60 * 60 * 1_000_000_000;
+
+/// Credential provided by sellers and buyers to prove the ownership of a contract
+#[derive(Debug, Clone, PartialEq)]
+pub enum Credential {
+    /// A message signed by the seller or the buyer
+    Signature(SignedMessage),
+    /// The token of a session created with [`DeferredData::create_contract_session`]
+    Session(String),
+}
 
-/// A message used to verify the ownership of a contract (seller or buyer)
-pub struct SignedMessage {
-    pub message: String,
-    pub signature: H520,
+impl From<SignedMessage> for Credential {
+    fn from(signed_message: SignedMessage) -> Self {
+        Self::Signature(signed_message)
+    }
 }
 
 pub struct DeferredData;
@@ -33,6 +73,7 @@ impl DeferredData {
     pub fn init(init_args: DeferredDataInitData) {
         Configuration::set_minter(init_args.minter).expect("Failed to set minter");
         Configuration::set_owner(caller()).expect("Failed to set owner");
+        Configuration::set_chain_id(init_args.chain_id).expect("Failed to set chain id");
 
         // init logger
         if !cfg!(test) {
@@ -41,10 +82,13 @@ impl DeferredData {
         // set the log settings
         Configuration::set_log_settings(init_args.log_settings)
             .expect("failed to set log settings");
+        ContractStorage::certify_contracts();
     }
 
     pub fn post_upgrade() {
         init_log(&Configuration::get_log_settings()).expect("failed to init log");
+        ContractStorage::build_indexes().expect("failed to build indexes");
+        ContractStorage::certify_contracts();
     }
 
     /// Set the minter of the deferred data canister.
@@ -58,6 +102,50 @@ impl DeferredData {
         Configuration::set_minter(minter)
     }
 
+    /// Set the chain ID of the EIP-712 domain used by typed data signatures,
+    /// which SIWE messages must be issued for as well
+    pub fn admin_set_chain_id(chain_id: u64) -> DeferredDataResult<()> {
+        if !Inspect::inspect_is_owner(caller()) {
+            return Err(DeferredDataError::Unauthorized);
+        }
+
+        log::info!("Set chain id to {chain_id}");
+
+        Configuration::set_chain_id(chain_id)
+    }
+
+    /// Set the domain SIWE messages must be issued for
+    pub fn admin_set_siwe_domain(domain: String) -> DeferredDataResult<()> {
+        if !Inspect::inspect_is_owner(caller()) {
+            return Err(DeferredDataError::Unauthorized);
+        }
+
+        log::info!("Set SIWE domain to {domain}");
+
+        Configuration::set_siwe_domain(domain)
+    }
+
+    /// Set the schema used to validate the contract properties
+    pub fn admin_set_property_schema(schema: Vec<PropertySchema>) -> DeferredDataResult<()> {
+        if !Inspect::inspect_is_owner(caller()) {
+            return Err(DeferredDataError::Unauthorized);
+        }
+
+        log::info!("Set property schema with {} properties", schema.len());
+
+        Configuration::set_property_schema(schema)
+    }
+
+    /// Get the schema used to validate the contract properties
+    pub fn get_property_schema() -> Vec<PropertySchema> {
+        Configuration::get_property_schema()
+    }
+
+    /// Validate a contract against the property schema, without storing it
+    pub fn validate_contract(contract: &Contract) -> DeferredDataResult<()> {
+        Schema::validate_contract(contract)
+    }
+
     pub fn admin_cycles() -> Nat {
         if !Inspect::inspect_is_owner(caller()) {
             ic_cdk::trap("Unauthorized");
@@ -74,14 +162,17 @@ impl DeferredData {
     }
 
     /// Insert a contract into the ledger
-    pub fn create_contract(contract: Contract) -> DeferredDataResult<()> {
+    pub fn create_contract(mut contract: Contract) -> DeferredDataResult<()> {
         if !Inspect::inspect_is_minter(caller()) {
             return Err(DeferredDataError::Unauthorized);
         }
 
+        Schema::validate_contract(&contract)?;
+        contract.created_at = Some(time());
+
         let contract_id = contract.id.clone();
         log::debug!("Creating contract {contract_id}");
-        ContractStorage::insert_contract(contract);
+        ContractStorage::insert_contract(contract)?;
         log::info!("Contract {contract_id} created");
 
         Ok(())
@@ -105,19 +196,93 @@ impl DeferredData {
     /// # Arguments
     /// * `id` - The ID of the contract
     /// * `has_proven_ownership` - If the caller has proven ownership of the contract
-    pub fn get_contract(id: &ID, signature: Option<SignedMessage>) -> Option<Contract> {
+    pub fn get_contract(id: &ID, credential: Option<Credential>) -> Option<Contract> {
         let mut contract = ContractStorage::get_contract(id)?;
         // redact
-        Self::redact_restricted_properties(&mut contract, caller(), signature);
+        let accessor = Self::accessor(&contract, caller(), credential).ok();
+        Self::redact_restricted_properties(&mut contract, accessor.as_ref());
 
         Some(contract)
     }
 
+    /// Get contract data by ID, including closed contracts.
+    ///
+    /// Closed contracts can only be read by the agency and by the sellers and buyers proving their ownership.
+    /// Restricted properties are redacted if the caller is not allowed to access them.
+    pub fn get_archived_contract(
+        id: &ID,
+        credential: Option<Credential>,
+    ) -> DeferredDataResult<Contract> {
+        let (mut contract, accessor) = Self::get_readable_contract(id, caller(), credential)?;
+        Self::redact_restricted_properties(&mut contract, accessor.ok().as_ref());
+
+        Ok(contract)
+    }
+
     /// Get available contracts
     pub fn get_contracts() -> Vec<ID> {
         ContractStorage::get_contracts()
     }
 
+    /// List a page of the open contracts matching the filter, as summaries.
+    ///
+    /// Pages have [`DEFAULT_CONTRACT_PAGE_SIZE`] contracts, unless a limit is set; at most [`MAX_CONTRACT_PAGE_SIZE`].
+    /// If `candidates` is set, only those contracts are listed.
+    pub fn list_contracts(
+        query: ContractListQuery,
+        candidates: Option<&BTreeSet<ID>>,
+        filter: impl Fn(&Contract) -> bool,
+    ) -> DeferredDataResult<ContractSummaryPage> {
+        if query.sort_by == ContractSortBy::Distance && query.origin.is_none() {
+            return Err(DeferredDataError::InvalidQuery(
+                "sorting by distance requires an origin".to_string(),
+            ));
+        }
+        let cursor = query
+            .cursor
+            .as_deref()
+            .map(|cursor| ContractCursor::parse(query.sort_by, cursor))
+            .transpose()?;
+        let limit = query
+            .limit
+            .map(|limit| limit as usize)
+            .unwrap_or(DEFAULT_CONTRACT_PAGE_SIZE)
+            .clamp(1, MAX_CONTRACT_PAGE_SIZE);
+
+        let (contracts, next_cursor) = ContractStorage::list_contracts(
+            query.sort_by,
+            query.order,
+            query.origin,
+            cursor.as_ref(),
+            limit,
+            candidates,
+            filter,
+        );
+
+        Ok(ContractSummaryPage {
+            contracts,
+            next_cursor: next_cursor.map(|cursor| cursor.to_string()),
+        })
+    }
+
+    /// List active and closed contracts.
+    ///
+    /// The owner gets all the contracts, while an agency gets the contracts it has created.
+    pub fn get_contract_archive() -> Vec<ContractArchiveEntry> {
+        let caller = caller();
+        if Inspect::inspect_is_owner(caller) {
+            return ContractStorage::get_contract_archive(|_| true);
+        }
+
+        ContractStorage::get_contract_archive(|contract| {
+            contract
+                .agency
+                .as_ref()
+                .map(|agency| agency.owner == caller)
+                .unwrap_or_default()
+        })
+    }
+
     /// Update a contract property
     pub fn update_contract_property(
         contract_id: ID,
@@ -140,6 +305,73 @@ impl DeferredData {
         ContractStorage::update_restricted_contract_property(&contract_id, key, value)
     }
 
+    /// Apply a batch of operations on the contract properties, in a single update
+    pub fn update_contract_properties(
+        contract_id: ID,
+        operations: Vec<PropertyOperation>,
+    ) -> DeferredDataResult<()> {
+        Inspect::inspect_modify_contract(caller(), &contract_id)?;
+
+        ContractStorage::update_contract_properties(&contract_id, operations)
+    }
+
+    /// Update the access list of a restricted contract property
+    pub fn update_restricted_property_access(
+        contract_id: ID,
+        key: String,
+        access_list: Vec<RestrictionLevel>,
+    ) -> DeferredDataResult<()> {
+        Inspect::inspect_modify_contract(caller(), &contract_id)?;
+
+        ContractStorage::update_restricted_contract_property_access(
+            &contract_id,
+            &key,
+            access_list,
+        )?;
+        log::info!("Access to restricted property {key} updated for contract {contract_id}");
+
+        Ok(())
+    }
+
+    /// Update the access list of a contract document
+    pub fn update_document_access(
+        contract_id: ID,
+        document_id: u64,
+        access_list: Vec<RestrictionLevel>,
+    ) -> DeferredDataResult<()> {
+        Inspect::inspect_modify_contract(caller(), &contract_id)?;
+
+        ContractStorage::update_contract_document_access(&contract_id, document_id, access_list)?;
+        log::info!("Access to document {document_id} updated for contract {contract_id}");
+
+        Ok(())
+    }
+
+    /// Create, replace or remove an access group of a contract.
+    ///
+    /// A group without members is removed.
+    pub fn update_contract_access_group(
+        contract_id: ID,
+        group: AccessGroup,
+    ) -> DeferredDataResult<()> {
+        Inspect::inspect_modify_contract(caller(), &contract_id)?;
+
+        log::info!(
+            "Access group {} updated for contract {contract_id}",
+            group.name
+        );
+        AccessGroupStorage::set_access_group(&contract_id, group);
+
+        Ok(())
+    }
+
+    /// Get the access groups of a contract
+    pub fn get_contract_access_groups(contract_id: ID) -> DeferredDataResult<Vec<AccessGroup>> {
+        Inspect::inspect_modify_contract(caller(), &contract_id)?;
+
+        Ok(AccessGroupStorage::get_access_groups(&contract_id))
+    }
+
     /// Upload a contract document
     pub fn upload_contract_document(
         contract_id: ID,
@@ -151,16 +383,157 @@ impl DeferredData {
         ContractStorage::upload_contract_document(&contract_id, document, data)
     }
 
+    /// Replace a contract document with a new revision.
+    ///
+    /// Returns the ID of the new document revision
+    pub fn replace_contract_document(
+        contract_id: ID,
+        document_id: u64,
+        document: ContractDocument,
+        data: Vec<u8>,
+    ) -> DeferredDataResult<u64> {
+        Inspect::inspect_modify_contract(caller(), &contract_id)?;
+
+        let new_document_id =
+            ContractStorage::replace_contract_document(&contract_id, document_id, document, data)?;
+        log::info!(
+            "Document {document_id} replaced by {new_document_id} for contract {contract_id}"
+        );
+
+        Ok(new_document_id)
+    }
+
+    /// Delete the latest revision of a contract document; its previous revisions are kept
+    pub fn delete_contract_document(contract_id: ID, document_id: u64) -> DeferredDataResult<()> {
+        Inspect::inspect_modify_contract(caller(), &contract_id)?;
+
+        ContractStorage::delete_contract_document(&contract_id, document_id)?;
+        log::info!("Document {document_id} deleted for contract {contract_id}");
+
+        Ok(())
+    }
+
+    /// Begin a chunked upload of a contract document.
+    ///
+    /// Returns the ID of the upload session, which must be used to upload the chunks
+    /// and to finalize the upload.
+    pub fn begin_contract_document_upload(
+        contract_id: ID,
+        document: ContractDocument,
+    ) -> DeferredDataResult<u64> {
+        Inspect::inspect_modify_contract(caller(), &contract_id)?;
+
+        ContractStorage::begin_contract_document_upload(&contract_id, document)
+    }
+
+    /// Upload a chunk of a contract document
+    pub fn upload_contract_document_chunk(
+        upload_id: u64,
+        chunk_index: u64,
+        data: Vec<u8>,
+    ) -> DeferredDataResult<()> {
+        Inspect::inspect_modify_upload(caller(), upload_id)?;
+
+        ContractStorage::upload_contract_document_chunk(upload_id, chunk_index, data)
+    }
+
+    /// Finalize a chunked upload of a contract document.
+    ///
+    /// Returns the ID of the uploaded document
+    pub fn finalize_contract_document_upload(upload_id: u64) -> DeferredDataResult<u64> {
+        let contract_id = Inspect::inspect_modify_upload(caller(), upload_id)?;
+
+        let document_id = ContractStorage::finalize_contract_document_upload(upload_id)?;
+        log::info!("Document {document_id} uploaded for contract {contract_id}");
+
+        Ok(document_id)
+    }
+
+    /// Upload an image to the gallery of a contract.
+    ///
+    /// The image must be a JPEG, PNG or WebP; its thumbnail is generated on upload.
+    ///
+    /// Returns the ID of the uploaded image
+    pub fn upload_contract_image(
+        contract_id: ID,
+        name: String,
+        data: Vec<u8>,
+    ) -> DeferredDataResult<u64> {
+        Inspect::inspect_modify_contract(caller(), &contract_id)?;
+
+        let gallery_image = gallery::process_image(name, &data)?;
+        let image_id = ImageStorage::insert_image(
+            &contract_id,
+            gallery_image.image,
+            data,
+            gallery_image.thumbnail,
+        )?;
+        log::info!("Image {image_id} uploaded for contract {contract_id}");
+
+        Ok(image_id)
+    }
+
+    /// Delete an image from the gallery of a contract
+    pub fn delete_contract_image(contract_id: ID, image_id: u64) -> DeferredDataResult<()> {
+        Inspect::inspect_modify_contract(caller(), &contract_id)?;
+
+        ImageStorage::remove_image(&contract_id, image_id)?;
+        log::info!("Image {image_id} deleted for contract {contract_id}");
+
+        Ok(())
+    }
+
+    /// Get the gallery of an open contract.
+    ///
+    /// Images are public, like the listing of the contract; closed contracts have no gallery.
+    pub fn get_contract_images(contract_id: &ID) -> Vec<ContractImage> {
+        if ContractStorage::get_contract(contract_id).is_none() {
+            return vec![];
+        }
+
+        ImageStorage::get_images(contract_id)
+    }
+
+    /// Verify that the SHA-256 digest of a downloaded document matches the one of the stored document.
+    ///
+    /// The digest is hex-encoded, optionally prefixed with `0x`.
+    pub fn verify_contract_document(
+        contract_id: ID,
+        document_id: u64,
+        digest: &str,
+        credential: Option<Credential>,
+    ) -> DeferredDataResult<bool> {
+        // check if we can access document
+        Self::get_contract_document_info(&contract_id, document_id, credential)?;
+
+        let stored_digest =
+            ContractStorage::get_contract_document_digest(&contract_id, document_id)?;
+        let digest = digest.trim_start_matches("0x");
+
+        Ok(digest.eq_ignore_ascii_case(&hex::encode(stored_digest)))
+    }
+
     /// Get a contract document
     pub fn get_contract_document(
         contract_id: ID,
         document_id: u64,
-        signature: Option<SignedMessage>,
+        credential: Option<Credential>,
     ) -> DeferredDataResult<ContractDocumentData> {
         // check if we can access document
-        let contract = ContractStorage::get_contract(&contract_id).ok_or(
-            DeferredDataError::Contract(DataContractError::ContractNotFound(contract_id.clone())),
-        )?;
+        Self::get_contract_document_info(&contract_id, document_id, credential)?;
+
+        ContractStorage::get_contract_document(&contract_id, document_id)
+    }
+
+    /// Get the header of a contract document without loading its data.
+    ///
+    /// Fails if the caller is not allowed to access the document
+    pub fn get_contract_document_info(
+        contract_id: &ID,
+        document_id: u64,
+        credential: Option<Credential>,
+    ) -> DeferredDataResult<ContractDocument> {
+        let (contract, accessor) = Self::get_readable_contract(contract_id, caller(), credential)?;
 
         let document_props = contract
             .documents
@@ -171,79 +544,247 @@ impl DeferredData {
                 DataContractError::DocumentNotFound(document_id),
             ))?;
 
-        // get caller access level
-        let access_level = if contract
-            .agency
-            .as_ref()
-            .map(|agency| agency.owner == caller())
-            .unwrap_or_default()
-        {
-            RestrictionLevel::Agent
-        } else if let Some(signature) = signature {
-            Inspect::inspect_signature(&contract.id, signature.signature, signature.message)?
-        } else {
-            RestrictionLevel::Public
-        };
+        // get caller identity
+        let accessor = accessor?;
 
         // check if we have access
-        if document_props.access_list.contains(&access_level) {
-            ContractStorage::get_contract_document(&contract_id, document_id)
+        if accessor.can_access(&document_props.access_list) {
+            Ok(document_props.clone())
         } else {
             Err(DeferredDataError::Unauthorized)
         }
     }
 
-    /// Redact restricted properties from contract based on access level
-    fn redact_restricted_properties(
-        contract: &mut Contract,
+    /// Link the address which signed the message to the caller.
+    ///
+    /// Once linked, the caller gets the seller or buyer access level on the contracts of the address,
+    /// without providing a signature. An existing link is replaced.
+    pub fn link_address(signature: SignedMessage) -> DeferredDataResult<H160> {
+        let caller = caller();
+        let nonce = Inspect::signed_message_nonce(&signature)?;
+        let address = H160::from(Inspect::inspect_address_link(caller, signature)?);
+        NonceStorage::use_nonce(&nonce, time())?;
+
+        log::info!("Linking address {address} to {caller}");
+        AddressLinkStorage::link_address(caller, address);
+
+        Ok(address)
+    }
+
+    /// Remove the address linked to the caller
+    pub fn unlink_address() -> DeferredDataResult<()> {
+        let caller = caller();
+        let Some(address) = AddressLinkStorage::unlink_address(caller) else {
+            return Err(DeferredDataError::AddressNotLinked);
+        };
+        log::info!("Unlinked address {address} from {caller}");
+
+        Ok(())
+    }
+
+    /// Get the address linked to the caller
+    pub fn get_linked_address() -> Option<H160> {
+        AddressLinkStorage::get_linked_address(caller())
+    }
+
+    /// Issue a nonce for a signed message.
+    ///
+    /// Signed messages are accepted only with a nonce issued by the canister, which expires after a few minutes.
+    /// Linking an address or creating a session uses the nonce, so the message can't be replayed.
+    /// Nonces can't be issued to the anonymous principal, and only a limited amount of nonces can be pending.
+    pub async fn create_nonce() -> DeferredDataResult<String> {
+        if caller() == Principal::anonymous() {
+            return Err(DeferredDataError::Unauthorized);
+        }
+
+        let nonce = hex::encode(random_bytes().await?);
+        NonceStorage::insert_nonce(&nonce, time())?;
+
+        Ok(nonce)
+    }
+
+    /// Exchange a signed message for a session granting read access to a contract.
+    ///
+    /// The session token can be provided instead of the signed message until the session expires,
+    /// so the signature is verified only once. The nonce of the message is used, so it can't create another session.
+    pub async fn create_contract_session(
+        contract_id: ID,
+        signature: SignedMessage,
+    ) -> DeferredDataResult<ContractSession> {
+        let nonce = Inspect::signed_message_nonce(&signature)?;
+        let (access_level, address) = Inspect::inspect_signature(&contract_id, signature)?;
+        // use the nonce before awaiting, so concurrent calls can't reuse it
+        NonceStorage::use_nonce(&nonce, time())?;
+
+        let token = hex::encode(random_bytes().await?);
+        let now = time();
+        let expires_at = now.saturating_add(SESSION_TTL);
+        SessionStorage::insert_session(
+            &token,
+            Session {
+                contract_id: contract_id.clone(),
+                address: address.into(),
+                expires_at,
+            },
+            now,
+        );
+        log::debug!("Created session for {address:?} on contract {contract_id}");
+
+        Ok(ContractSession {
+            token,
+            contract_id,
+            access_level,
+            expires_at,
+        })
+    }
+
+    /// Get the audit log of a contract, from the oldest entry.
+    ///
+    /// Restricted values are redacted based on the access level of the caller,
+    /// while the entries of the documents the caller can't access are left out.
+    pub fn get_contract_audit_log(
+        contract_id: &ID,
+        pagination: Pagination,
+        credential: Option<Credential>,
+    ) -> DeferredDataResult<Vec<AuditLogEntry>> {
+        let (_, accessor) = Self::get_readable_contract(contract_id, caller(), credential)?;
+        // an invalid credential gives no access to restricted values
+        let accessor = accessor.ok();
+
+        let can_access = |access_list: &[RestrictionLevel]| {
+            accessor
+                .as_ref()
+                .map(|accessor| accessor.can_access(access_list))
+                .unwrap_or_default()
+        };
+
+        let count = pagination.count.min(MAX_AUDIT_LOG_PAGE_SIZE);
+        let mut entries = AuditLogStorage::get_audit_log(contract_id, pagination.offset, count);
+
+        // entries of documents which the caller can't access are dropped, like the documents of the contract
+        entries.retain(|entry| match &entry.operation {
+            AuditOperation::UploadDocument { document, .. }
+            | AuditOperation::ReplaceDocument { document, .. } => can_access(&document.access_list),
+            AuditOperation::UpdateDocumentAccess { previous, new, .. } => {
+                can_access(previous) || can_access(new)
+            }
+            _ => true,
+        });
+
+        for entry in entries.iter_mut() {
+            if let AuditOperation::SetRestrictedProperty { previous, new, .. } =
+                &mut entry.operation
+            {
+                for value in [previous, new].into_iter().flatten() {
+                    if !can_access(&value.access_list) {
+                        value.value = None;
+                    }
+                }
+            }
+        }
+
+        Ok(entries)
+    }
+
+    /// Get a contract, including closed contracts, with the identity of the caller.
+    ///
+    /// Closed contracts are reported as not found, unless the caller is the agency or a seller or buyer
+    /// proving the ownership of the contract.
+    fn get_readable_contract(
+        contract_id: &ID,
         caller: Principal,
-        signature: Option<SignedMessage>,
-    ) {
-        let mut redacted_properties = Vec::with_capacity(contract.restricted_properties.len());
+        credential: Option<Credential>,
+    ) -> DeferredDataResult<(Contract, DeferredDataResult<Accessor>)> {
+        let not_found = || {
+            DeferredDataError::Contract(DataContractError::ContractNotFound(contract_id.clone()))
+        };
+
+        let contract = ContractStorage::get_any_contract(contract_id).ok_or_else(not_found)?;
+        let accessor = Self::accessor(&contract, caller, credential);
+
+        if contract.closed
+            && !matches!(accessor, Ok(ref accessor) if accessor.level != RestrictionLevel::Public)
+        {
+            return Err(not_found());
+        }
+
+        Ok((contract, accessor))
+    }
 
-        // get caller access level
-        let access_level = if contract
+    /// Get the identity of the caller for the contract.
+    ///
+    /// The agency is identified by the caller, while sellers and buyers by the credential
+    /// or, if missing, by the address linked to the caller.
+    /// Addresses which are not a party to the contract get the public level, but can still be granted access by address.
+    fn accessor(
+        contract: &Contract,
+        caller: Principal,
+        credential: Option<Credential>,
+    ) -> DeferredDataResult<Accessor> {
+        let (access_level, address) = if contract
             .agency
             .as_ref()
             .map(|agency| agency.owner == caller)
             .unwrap_or_default()
         {
-            Some(RestrictionLevel::Agent)
-        } else if let Some(signature) = signature {
-            Inspect::inspect_signature(&contract.id, signature.signature, signature.message).ok()
+            (RestrictionLevel::Agent, None)
+        } else if let Some(credential) = credential {
+            let (access_level, address) = match credential {
+                Credential::Signature(signed_message) => {
+                    Inspect::inspect_signature(&contract.id, signed_message)?
+                }
+                Credential::Session(token) => Inspect::inspect_session(contract, &token)?,
+            };
+            (access_level, Some(address.into()))
+        } else if let Some(address) = AddressLinkStorage::get_linked_address(caller) {
+            // a linked address which doesn't participate in the contract has public access
+            let access_level =
+                Inspect::inspect_address(contract, address.0).unwrap_or(RestrictionLevel::Public);
+            (access_level, Some(address))
         } else {
-            Some(RestrictionLevel::Public)
+            (RestrictionLevel::Public, None)
         };
 
-        // if no access level, redact all
-        let Some(access_level) = access_level else {
-            // redact all
+        Ok(Accessor::new(
+            access_level,
+            caller,
+            address,
+            &AccessGroupStorage::get_access_groups(&contract.id),
+        ))
+    }
+
+    /// Redact restricted properties and documents from contract based on the identity of the caller.
+    ///
+    /// Documents the caller can't access are removed, so their metadata is not disclosed.
+    fn redact_restricted_properties(contract: &mut Contract, accessor: Option<&Accessor>) {
+        // if no identity, redact all
+        let Some(accessor) = accessor else {
             contract.restricted_properties = vec![];
+            contract.documents = vec![];
             return;
         };
 
-        // otherwise, redact based on our level iterate and check whether we have access
-        for (name, property) in contract.restricted_properties.iter() {
-            if property.access_list.contains(&access_level) {
-                redacted_properties.push((name.clone(), property.clone()));
-            }
-        }
-
-        contract.restricted_properties = redacted_properties;
+        // otherwise, redact based on the access lists
+        contract
+            .restricted_properties
+            .retain(|(_, property)| accessor.can_access(&property.access_list));
+        contract
+            .documents
+            .retain(|(_, document)| accessor.can_access(&document.access_list));
     }
 }
 
 #[cfg(test)]
 mod test {
 
-    use std::str::FromStr as _;
-
     use candid::Nat;
-    use did::deferred::RestrictionLevel;
-    use did::H160;
+    use did::deferred::{AccessGroupMember, RestrictionLevel, Seller};
     use ic_log::LogSettingsV2;
     use pretty_assertions::assert_eq;
-    use test_utils::{mock_contract, store_mock_contract_with, with_mock_contract};
+    use test_utils::{
+        mock_contract, mock_png, sign, signed_link_message, signed_message, signer_address,
+        store_mock_contract_with, with_mock_contract,
+    };
 
     use super::*;
 
@@ -252,11 +793,13 @@ mod test {
         let data = DeferredDataInitData {
             minter: caller(),
             log_settings: Default::default(),
+            chain_id: 1,
         };
 
         DeferredData::init(data);
 
         assert_eq!(Configuration::get_minter(), caller());
+        assert_eq!(Configuration::get_chain_id(), 1);
     }
 
     #[test]
@@ -269,7 +812,14 @@ mod test {
 
         let stored_contract =
             ContractStorage::get_contract(&contract.id).expect("Failed to get contract");
-        assert_eq!(contract, stored_contract);
+        assert!(stored_contract.created_at.is_some());
+        assert_eq!(
+            contract,
+            Contract {
+                created_at: None,
+                ..stored_contract
+            }
+        );
     }
 
     #[test]
@@ -299,7 +849,254 @@ mod test {
         let stored_contract =
             DeferredData::get_contract(&contract.id, None).expect("Failed to get contract");
 
-        assert_eq!(contract, stored_contract);
+        assert_eq!(
+            contract,
+            Contract {
+                created_at: None,
+                ..stored_contract
+            }
+        );
+    }
+
+    #[test]
+    fn test_should_get_archived_contract() {
+        init();
+
+        let contract = with_mock_contract(1, 100, |contract| {
+            contract.buyers = vec![signer_address()];
+        });
+        DeferredData::create_contract(contract.clone()).expect("Failed to create contract");
+        let document_id = DeferredData::upload_contract_document(
+            contract.id.clone(),
+            ContractDocument {
+                access_list: vec![RestrictionLevel::Buyer],
+                mime_type: "application/pdf".to_string(),
+                name: "deed.pdf".to_string(),
+                size: 2,
+                previous_version: None,
+                superseded_by: None,
+                digest: None,
+            },
+            vec![1, 2],
+        )
+        .expect("Failed to upload document");
+        DeferredData::close_contract(contract.id.clone()).expect("Failed to close contract");
+
+        // closed contracts are hidden from the public
+        assert!(DeferredData::get_contract(&contract.id, None).is_none());
+        assert_eq!(
+            DeferredData::get_archived_contract(&contract.id, None).unwrap_err(),
+            DeferredDataError::Contract(DataContractError::ContractNotFound(contract.id.clone()))
+        );
+
+        // but the buyer can still read the contract and its documents
+        let archived =
+            DeferredData::get_archived_contract(&contract.id, Some(signed_message(1).into()))
+                .expect("Failed to get archived contract");
+        assert!(archived.closed);
+        let document = DeferredData::get_contract_document(
+            contract.id.clone(),
+            document_id,
+            Some(signed_message(1).into()),
+        )
+        .expect("Failed to get document");
+        assert_eq!(document.data, vec![1, 2]);
+    }
+
+    #[test]
+    fn test_should_list_contract_archive() {
+        init();
+
+        DeferredData::create_contract(mock_contract(1, 100)).expect("Failed to create contract");
+        DeferredData::create_contract(mock_contract(2, 100)).expect("Failed to create contract");
+        DeferredData::close_contract(2u64.into()).expect("Failed to close contract");
+
+        // caller is the owner, so it gets all the contracts
+        let archive = DeferredData::get_contract_archive();
+        assert_eq!(
+            archive,
+            vec![
+                ContractArchiveEntry {
+                    id: Nat::from(1u64),
+                    closed: false,
+                },
+                ContractArchiveEntry {
+                    id: Nat::from(2u64),
+                    closed: true,
+                },
+            ]
+        );
+    }
+
+    #[test]
+    fn test_should_not_create_contract_with_invalid_properties() {
+        init();
+
+        let contract = with_mock_contract(1, 100, |contract| {
+            contract.properties.push((
+                "contract:rooms".to_string(),
+                GenericValue::TextContent("three".to_string()),
+            ));
+        });
+
+        assert_eq!(
+            DeferredData::create_contract(contract.clone()).unwrap_err(),
+            DeferredDataError::Contract(DataContractError::PropertyTypeMismatch(
+                "contract:rooms".to_string()
+            ))
+        );
+        assert!(ContractStorage::get_contract(&contract.id).is_none());
+    }
+
+    #[test]
+    fn test_should_not_set_invalid_property() {
+        init();
+
+        let contract = mock_contract(1, 100);
+        DeferredData::create_contract(contract.clone()).expect("Failed to create contract");
+
+        assert_eq!(
+            DeferredData::update_contract_property(
+                contract.id.clone(),
+                "contract:rooms".to_string(),
+                GenericValue::TextContent("three".to_string()),
+            )
+            .unwrap_err(),
+            DeferredDataError::Contract(DataContractError::PropertyTypeMismatch(
+                "contract:rooms".to_string()
+            ))
+        );
+    }
+
+    #[test]
+    fn test_should_update_contract_stored_before_schema() {
+        init();
+
+        // stored before the schema was configured, so it's not validated
+        let contract = with_mock_contract(1, 100, |contract| {
+            contract.properties.push((
+                "contract:rooms".to_string(),
+                GenericValue::TextContent("three".to_string()),
+            ));
+        });
+        ContractStorage::insert_contract(contract.clone()).unwrap();
+
+        // only the changed properties are validated
+        assert!(DeferredData::update_contract_property(
+            contract.id.clone(),
+            "contract:name".to_string(),
+            GenericValue::TextContent("villa".to_string()),
+        )
+        .is_ok());
+    }
+
+    #[test]
+    fn test_should_get_contract_audit_log() {
+        init();
+
+        let contract = with_mock_contract(1, 100, |contract| {
+            contract.restricted_properties.push((
+                "contract:notes".to_string(),
+                RestrictedProperty {
+                    access_list: vec![RestrictionLevel::Buyer],
+                    value: GenericValue::TextContent("old".to_string()),
+                },
+            ));
+        });
+        DeferredData::create_contract(contract.clone()).expect("Failed to create contract");
+
+        DeferredData::update_contract_property(
+            contract.id.clone(),
+            "contract:city".to_string(),
+            GenericValue::TextContent("Milan".to_string()),
+        )
+        .unwrap();
+        DeferredData::update_restricted_contract_property(
+            contract.id.clone(),
+            "contract:notes".to_string(),
+            RestrictedProperty {
+                access_list: vec![RestrictionLevel::Buyer],
+                value: GenericValue::TextContent("new".to_string()),
+            },
+        )
+        .unwrap();
+
+        let log = DeferredData::get_contract_audit_log(
+            &contract.id,
+            Pagination {
+                offset: 0,
+                count: 10,
+            },
+            None,
+        )
+        .unwrap();
+        assert_eq!(log.len(), 2);
+        assert_eq!(log[0].caller, caller());
+        assert_eq!(
+            log[0].operation,
+            AuditOperation::SetProperty {
+                key: "contract:city".to_string(),
+                previous: Some(GenericValue::TextContent("Rome".to_string())),
+                new: Some(GenericValue::TextContent("Milan".to_string())),
+            }
+        );
+        // restricted values are redacted for the public
+        let AuditOperation::SetRestrictedProperty { previous, new, .. } = &log[1].operation else {
+            panic!("unexpected operation");
+        };
+        assert_eq!(previous.as_ref().unwrap().value, None);
+        assert_eq!(new.as_ref().unwrap().value, None);
+    }
+
+    #[test]
+    fn test_should_hide_restricted_documents_from_audit_log() {
+        init();
+
+        let contract = mock_contract(1, 100);
+        DeferredData::create_contract(contract.clone()).expect("Failed to create contract");
+
+        let document = |access_list| ContractDocument {
+            access_list,
+            mime_type: "application/pdf".to_string(),
+            name: "deed.pdf".to_string(),
+            size: 2,
+            previous_version: None,
+            superseded_by: None,
+            digest: None,
+        };
+        let public_id = DeferredData::upload_contract_document(
+            contract.id.clone(),
+            document(vec![RestrictionLevel::Public]),
+            vec![1, 2],
+        )
+        .unwrap();
+        let restricted_id = DeferredData::upload_contract_document(
+            contract.id.clone(),
+            document(vec![RestrictionLevel::Buyer]),
+            vec![3, 4],
+        )
+        .unwrap();
+        DeferredData::update_document_access(
+            contract.id.clone(),
+            restricted_id,
+            vec![RestrictionLevel::Seller],
+        )
+        .unwrap();
+
+        let log = DeferredData::get_contract_audit_log(
+            &contract.id,
+            Pagination {
+                offset: 0,
+                count: 10,
+            },
+            None,
+        )
+        .unwrap();
+        assert_eq!(log.len(), 1);
+        assert!(matches!(
+            log[0].operation,
+            AuditOperation::UploadDocument { document_id, .. } if document_id == public_id
+        ));
     }
 
     #[test]
@@ -310,7 +1107,7 @@ mod test {
 
         DeferredData::create_contract(contract.clone()).expect("Failed to create contract");
 
-        let key = "key".to_string();
+        let key = "contract:key".to_string();
         let value = GenericValue::TextContent("value".to_string());
 
         DeferredData::update_contract_property(contract.id.clone(), key.clone(), value.clone())
@@ -338,7 +1135,7 @@ mod test {
 
         DeferredData::create_contract(contract.clone()).expect("Failed to create contract");
 
-        let key = "key".to_string();
+        let key = "contract:key".to_string();
         let value = GenericValue::TextContent("value".to_string());
 
         DeferredData::update_restricted_contract_property(
@@ -363,11 +1160,200 @@ mod test {
         assert_eq!(find.1.value, value);
     }
 
+    #[test]
+    fn test_should_upload_contract_document_in_chunks() {
+        init();
+
+        let contract = mock_contract(1, 100);
+        DeferredData::create_contract(contract.clone()).expect("Failed to create contract");
+
+        let upload_id = DeferredData::begin_contract_document_upload(
+            contract.id.clone(),
+            ContractDocument {
+                access_list: vec![RestrictionLevel::Public],
+                mime_type: "application/pdf".to_string(),
+                name: "deed.pdf".to_string(),
+                size: 4,
+                previous_version: None,
+                superseded_by: None,
+                digest: None,
+            },
+        )
+        .expect("Failed to begin upload");
+
+        DeferredData::upload_contract_document_chunk(upload_id, 0, vec![1, 2])
+            .expect("Failed to upload chunk");
+        DeferredData::upload_contract_document_chunk(upload_id, 1, vec![3, 4])
+            .expect("Failed to upload chunk");
+
+        let document_id = DeferredData::finalize_contract_document_upload(upload_id)
+            .expect("Failed to finalize upload");
+
+        let document = DeferredData::get_contract_document(contract.id, document_id, None)
+            .expect("Failed to get document");
+        assert_eq!(document.data, vec![1, 2, 3, 4]);
+    }
+
+    #[test]
+    fn test_should_upload_and_delete_contract_image() {
+        init();
+
+        let contract = mock_contract(1, 100);
+        DeferredData::create_contract(contract.clone()).expect("Failed to create contract");
+
+        let image_id = DeferredData::upload_contract_image(
+            contract.id.clone(),
+            "facade.png".to_string(),
+            mock_png(800, 600),
+        )
+        .expect("Failed to upload image");
+        let images = DeferredData::get_contract_images(&contract.id);
+        assert_eq!(images.len(), 1);
+        assert_eq!(images[0].id, image_id);
+        assert_eq!(images[0].name, "facade.png");
+        assert_eq!((images[0].width, images[0].height), (800, 600));
+
+        // not an image
+        assert!(DeferredData::upload_contract_image(
+            contract.id.clone(),
+            "deed.pdf".to_string(),
+            b"%PDF-1.7".to_vec(),
+        )
+        .is_err());
+
+        DeferredData::delete_contract_image(contract.id.clone(), image_id)
+            .expect("Failed to delete image");
+        assert!(DeferredData::get_contract_images(&contract.id).is_empty());
+    }
+
+    #[test]
+    fn test_should_replace_and_delete_contract_document() {
+        init();
+
+        let contract = mock_contract(1, 100);
+        DeferredData::create_contract(contract.clone()).expect("Failed to create contract");
+
+        let document = ContractDocument {
+            access_list: vec![RestrictionLevel::Public],
+            mime_type: "application/pdf".to_string(),
+            name: "deed.pdf".to_string(),
+            size: 2,
+            previous_version: None,
+            superseded_by: None,
+            digest: None,
+        };
+        let document_id = DeferredData::upload_contract_document(
+            contract.id.clone(),
+            document.clone(),
+            vec![1, 2],
+        )
+        .expect("Failed to upload document");
+        let new_document_id = DeferredData::replace_contract_document(
+            contract.id.clone(),
+            document_id,
+            document,
+            vec![3, 4],
+        )
+        .expect("Failed to replace document");
+
+        let data = DeferredData::get_contract_document(contract.id.clone(), new_document_id, None)
+            .expect("Failed to get document");
+        assert_eq!(data.data, vec![3, 4]);
+
+        DeferredData::delete_contract_document(contract.id.clone(), new_document_id)
+            .expect("Failed to delete document");
+        // the replaced revision is kept
+        let documents = DeferredData::get_contract(&contract.id, None)
+            .unwrap()
+            .documents;
+        assert_eq!(documents.len(), 1);
+        assert_eq!(documents[0].0, document_id);
+        assert_eq!(documents[0].1.superseded_by, None);
+    }
+
+    #[test]
+    fn test_should_verify_contract_document() {
+        init();
+
+        let contract = mock_contract(1, 100);
+        DeferredData::create_contract(contract.clone()).expect("Failed to create contract");
+
+        let document_id = DeferredData::upload_contract_document(
+            contract.id.clone(),
+            ContractDocument {
+                access_list: vec![RestrictionLevel::Public],
+                mime_type: "text/plain".to_string(),
+                name: "hello.txt".to_string(),
+                size: 5,
+                previous_version: None,
+                superseded_by: None,
+                digest: None,
+            },
+            b"hello".to_vec(),
+        )
+        .expect("Failed to upload document");
+
+        assert!(DeferredData::verify_contract_document(
+            contract.id.clone(),
+            document_id,
+            "0x2CF24DBA5FB0A30E26E83B2AC5B9E29E1B161E5C1FA7425E73043362938B9824",
+            None
+        )
+        .unwrap());
+        assert!(!DeferredData::verify_contract_document(
+            contract.id.clone(),
+            document_id,
+            "0000000000000000000000000000000000000000000000000000000000000000",
+            None
+        )
+        .unwrap());
+    }
+
+    #[test]
+    fn test_should_update_document_access() {
+        init();
+
+        let contract = mock_contract(1, 100);
+        DeferredData::create_contract(contract.clone()).expect("Failed to create contract");
+
+        let document_id = DeferredData::upload_contract_document(
+            contract.id.clone(),
+            ContractDocument {
+                access_list: vec![RestrictionLevel::Agent],
+                mime_type: "application/pdf".to_string(),
+                name: "deed.pdf".to_string(),
+                size: 2,
+                previous_version: None,
+                superseded_by: None,
+                digest: None,
+            },
+            vec![1, 2],
+        )
+        .expect("Failed to upload document");
+
+        // not public yet
+        assert_eq!(
+            DeferredData::get_contract_document(contract.id.clone(), document_id, None)
+                .unwrap_err(),
+            DeferredDataError::Unauthorized
+        );
+
+        DeferredData::update_document_access(
+            contract.id.clone(),
+            document_id,
+            vec![RestrictionLevel::Agent, RestrictionLevel::Public],
+        )
+        .expect("Failed to update document access");
+
+        let document = DeferredData::get_contract_document(contract.id, document_id, None)
+            .expect("Failed to get document");
+        assert_eq!(document.data, vec![1, 2]);
+    }
+
     #[test]
     fn test_should_redact_properties() {
-        let (eth_addr, signature) = signature();
         store_mock_contract_with(1, 100, |contract| {
-            contract.buyers = vec![eth_addr];
+            contract.buyers = vec![signer_address()];
             contract.restricted_properties.push((
                 "contract::restricted1".to_string(),
                 RestrictedProperty {
@@ -385,24 +1371,210 @@ mod test {
         });
 
         // redact
-        let contract = DeferredData::get_contract(&Nat::from(1u64), Some(signature)).unwrap();
+        let contract =
+            DeferredData::get_contract(&Nat::from(1u64), Some(signed_message(1).into())).unwrap();
 
         assert_eq!(contract.restricted_properties.len(), 1);
     }
 
-    fn signature() -> (H160, SignedMessage) {
-        let message = "Hello, Ethereum!".to_string();
-        let signature = H520::from_str("0x0e9293c16d57e3ea35118a52cc7209871d07db4b74183fbd6758306c2475586a2f64a5837cd7b787bff49e9432aab76de43080b9d98675e8890e16ffc669e6cb1b").unwrap();
+    #[test]
+    fn test_should_redact_documents() {
+        let document = |name: &str, access_list: Vec<RestrictionLevel>| ContractDocument {
+            access_list,
+            mime_type: "application/pdf".to_string(),
+            name: name.to_string(),
+            size: 2,
+            previous_version: None,
+            superseded_by: None,
+            digest: None,
+        };
+        store_mock_contract_with(1, 100, |contract| {
+            contract.buyers = vec![signer_address()];
+            contract.documents = vec![
+                (1, document("brochure.pdf", vec![RestrictionLevel::Public])),
+                (
+                    2,
+                    document(
+                        "mortgage.pdf",
+                        vec![RestrictionLevel::Agent, RestrictionLevel::Buyer],
+                    ),
+                ),
+                (3, document("deed.pdf", vec![RestrictionLevel::Seller])),
+            ];
+        });
+
+        let contract = DeferredData::get_contract(&Nat::from(1u64), None).unwrap();
+        assert_eq!(
+            contract
+                .documents
+                .iter()
+                .map(|(id, _)| *id)
+                .collect::<Vec<_>>(),
+            vec![1]
+        );
+
+        let contract =
+            DeferredData::get_contract(&Nat::from(1u64), Some(signed_message(1).into())).unwrap();
+        assert_eq!(
+            contract
+                .documents
+                .iter()
+                .map(|(id, _)| *id)
+                .collect::<Vec<_>>(),
+            vec![2]
+        );
+    }
 
-        (
-            H160::from_hex_str("0x8fd379246834eac74B8419FfdA202CF8051F7A03").unwrap(),
-            SignedMessage { message, signature },
+    #[test]
+    fn test_should_grant_documents_by_address_principal_and_group() {
+        let document = |name: &str, access_list: Vec<RestrictionLevel>| ContractDocument {
+            access_list,
+            mime_type: "application/pdf".to_string(),
+            name: name.to_string(),
+            size: 2,
+            previous_version: None,
+            superseded_by: None,
+            digest: None,
+        };
+        // the signer is not a party to the contract
+        store_mock_contract_with(1, 100, |contract| {
+            contract.documents = vec![
+                (
+                    1,
+                    document(
+                        "appraisal.pdf",
+                        vec![RestrictionLevel::Address(signer_address())],
+                    ),
+                ),
+                (
+                    2,
+                    document("survey.pdf", vec![RestrictionLevel::Principal(caller())]),
+                ),
+                (
+                    3,
+                    document(
+                        "mortgage.pdf",
+                        vec![RestrictionLevel::Group("notaries".to_string())],
+                    ),
+                ),
+            ];
+        });
+        let document_ids = |contract: Contract| {
+            contract
+                .documents
+                .iter()
+                .map(|(id, _)| *id)
+                .collect::<Vec<_>>()
+        };
+
+        let contract = DeferredData::get_contract(&Nat::from(1u64), None).unwrap();
+        assert_eq!(document_ids(contract), vec![2]);
+        let contract =
+            DeferredData::get_contract(&Nat::from(1u64), Some(signed_message(1).into())).unwrap();
+        assert_eq!(document_ids(contract), vec![1, 2]);
+
+        AccessGroupStorage::set_access_group(
+            &Nat::from(1u64),
+            AccessGroup {
+                name: "notaries".to_string(),
+                members: vec![AccessGroupMember::Address(signer_address())],
+            },
+        );
+        let contract =
+            DeferredData::get_contract(&Nat::from(1u64), Some(signed_message(1).into())).unwrap();
+        assert_eq!(document_ids(contract), vec![1, 2, 3]);
+    }
+
+    #[test]
+    fn test_should_not_accept_message_bound_to_another_contract() {
+        store_mock_contract_with(1, 100, |contract| {
+            contract.buyers = vec![signer_address()];
+        });
+
+        // a message granting access to contract 2 redacts all the restricted properties of contract 1
+        let contract =
+            DeferredData::get_contract(&Nat::from(1u64), Some(signed_message(2).into())).unwrap();
+        assert!(contract.restricted_properties.is_empty());
+
+        let plain_message = SignedMessage::Siwe {
+            message: "Hello, Ethereum!".to_string(),
+            signature: sign("Hello, Ethereum!"),
+        };
+        assert!(matches!(
+            Inspect::inspect_signature(&Nat::from(1u64), plain_message),
+            Err(DeferredDataError::InvalidSignedMessage(_))
+        ));
+    }
+
+    #[test]
+    fn test_should_access_contract_with_linked_address() {
+        init();
+
+        let contract = with_mock_contract(1, 100, |contract| {
+            contract.sellers = vec![Seller {
+                address: signer_address(),
+                quota: 100,
+            }];
+        });
+        DeferredData::create_contract(contract.clone()).expect("Failed to create contract");
+        let document_id = DeferredData::upload_contract_document(
+            contract.id.clone(),
+            ContractDocument {
+                access_list: vec![RestrictionLevel::Seller],
+                mime_type: "application/pdf".to_string(),
+                name: "deed.pdf".to_string(),
+                size: 2,
+                previous_version: None,
+                superseded_by: None,
+                digest: None,
+            },
+            vec![1, 2],
         )
+        .expect("Failed to upload document");
+
+        // not linked yet
+        let redacted = DeferredData::get_contract(&contract.id, None).unwrap();
+        assert!(redacted.restricted_properties.is_empty());
+        assert_eq!(
+            DeferredData::get_contract_document(contract.id.clone(), document_id, None)
+                .unwrap_err(),
+            DeferredDataError::Unauthorized
+        );
+
+        let signature = signed_link_message(caller());
+        assert_eq!(
+            DeferredData::link_address(signature.clone()).unwrap(),
+            signer_address()
+        );
+        assert_eq!(DeferredData::get_linked_address(), Some(signer_address()));
+        // the nonce has been used
+        assert!(matches!(
+            DeferredData::link_address(signature),
+            Err(DeferredDataError::InvalidSignedMessage(_))
+        ));
+
+        let stored_contract = DeferredData::get_contract(&contract.id, None).unwrap();
+        assert_eq!(
+            stored_contract.restricted_properties,
+            contract.restricted_properties
+        );
+        let document =
+            DeferredData::get_contract_document(contract.id.clone(), document_id, None).unwrap();
+        assert_eq!(document.data, vec![1, 2]);
+
+        DeferredData::unlink_address().unwrap();
+        assert_eq!(
+            DeferredData::unlink_address().unwrap_err(),
+            DeferredDataError::AddressNotLinked
+        );
+        let redacted = DeferredData::get_contract(&contract.id, None).unwrap();
+        assert!(redacted.restricted_properties.is_empty());
     }
 
     fn init() {
         DeferredData::init(DeferredDataInitData {
             minter: caller(),
+            chain_id: 1,
             log_settings: LogSettingsV2 {
                 enable_console: true,
                 log_filter: "debug".to_string(),
@@ -0,0 +1,136 @@
+use candid::Principal;
+use did::deferred::{AccessGroup, AccessGroupMember, RestrictionLevel};
+use did::H160;
+
+/// The identity of a caller accessing a contract
+#[derive(Debug, Clone, PartialEq)]
+pub struct Accessor {
+    /// The role of the caller in the contract; public if the caller is not a party to the contract
+    pub level: RestrictionLevel,
+    pub principal: Principal,
+    /// The address proven by the caller, if any
+    pub address: Option<H160>,
+    /// The names of the access groups of the contract the caller is member of
+    pub groups: Vec<String>,
+}
+
+impl Accessor {
+    pub fn new(
+        level: RestrictionLevel,
+        principal: Principal,
+        address: Option<H160>,
+        groups: &[AccessGroup],
+    ) -> Self {
+        let mut accessor = Self {
+            level,
+            principal,
+            address,
+            groups: vec![],
+        };
+        accessor.groups = groups
+            .iter()
+            .filter(|group| {
+                group
+                    .members
+                    .iter()
+                    .any(|member| accessor.is_member(member))
+            })
+            .map(|group| group.name.clone())
+            .collect();
+
+        accessor
+    }
+
+    /// Returns whether the caller is granted access by any of the levels of the access list
+    pub fn can_access(&self, access_list: &[RestrictionLevel]) -> bool {
+        access_list.iter().any(|granted| match granted {
+            RestrictionLevel::Address(address) => self.address.as_ref() == Some(address),
+            RestrictionLevel::Principal(principal) => self.is_principal(*principal),
+            RestrictionLevel::Group(name) => self.groups.contains(name),
+            level => *level == self.level,
+        })
+    }
+
+    fn is_member(&self, member: &AccessGroupMember) -> bool {
+        match member {
+            AccessGroupMember::Address(address) => self.address.as_ref() == Some(address),
+            AccessGroupMember::Principal(principal) => self.is_principal(*principal),
+        }
+    }
+
+    /// Returns whether the caller is `principal`.
+    ///
+    /// The anonymous principal is never granted access, since it is shared by all the HTTP callers.
+    fn is_principal(&self, principal: Principal) -> bool {
+        principal != Principal::anonymous() && self.principal == principal
+    }
+}
+
+#[cfg(test)]
+mod test {
+
+    use pretty_assertions::assert_eq;
+
+    use super::*;
+    use crate::app::test_utils::{alice, signer_address};
+
+    #[test]
+    fn test_should_access_by_level() {
+        let accessor = Accessor::new(RestrictionLevel::Buyer, alice(), None, &[]);
+
+        assert!(accessor.can_access(&[RestrictionLevel::Agent, RestrictionLevel::Buyer]));
+        assert!(!accessor.can_access(&[RestrictionLevel::Seller]));
+        assert!(!accessor.can_access(&[]));
+    }
+
+    #[test]
+    fn test_should_access_by_address_and_principal() {
+        let accessor = Accessor::new(
+            RestrictionLevel::Public,
+            alice(),
+            Some(signer_address()),
+            &[],
+        );
+
+        assert!(accessor.can_access(&[RestrictionLevel::Address(signer_address())]));
+        assert!(!accessor.can_access(&[RestrictionLevel::Address(H160::zero())]));
+        assert!(accessor.can_access(&[RestrictionLevel::Principal(alice())]));
+        assert!(
+            !accessor.can_access(&[RestrictionLevel::Principal(Principal::management_canister())])
+        );
+    }
+
+    #[test]
+    fn test_should_not_grant_anonymous_principal() {
+        let accessor = Accessor::new(RestrictionLevel::Public, Principal::anonymous(), None, &[]);
+
+        assert!(!accessor.can_access(&[RestrictionLevel::Principal(Principal::anonymous())]));
+    }
+
+    #[test]
+    fn test_should_access_by_group() {
+        let groups = vec![
+            AccessGroup {
+                name: "bank".to_string(),
+                members: vec![AccessGroupMember::Address(signer_address())],
+            },
+            AccessGroup {
+                name: "notary".to_string(),
+                members: vec![AccessGroupMember::Principal(alice())],
+            },
+        ];
+
+        let accessor = Accessor::new(
+            RestrictionLevel::Public,
+            Principal::anonymous(),
+            Some(signer_address()),
+            &groups,
+        );
+        assert_eq!(accessor.groups, vec!["bank".to_string()]);
+        assert!(accessor.can_access(&[RestrictionLevel::Group("bank".to_string())]));
+        assert!(!accessor.can_access(&[RestrictionLevel::Group("notary".to_string())]));
+
+        let accessor = Accessor::new(RestrictionLevel::Public, alice(), None, &groups);
+        assert_eq!(accessor.groups, vec!["notary".to_string()]);
+    }
+}
@@ -0,0 +1,515 @@
+//! Certification of the HTTP responses with the response verification (version 2) standard.
+//!
+//! The responses served to the public by `/contracts`, `/contract/:id` and `/contract/:id/document/:document_id`
+//! are certified, while all the other responses are explicitly left uncertified.
+//!
+//! Only the full responses to `GET` requests are certified, so `HEAD` and `Range` requests are answered
+//! with partial responses only by the raw domain. Documents larger than [`MAX_CERTIFIED_DOCUMENT_SIZE`]
+//! and documents without digest are not certified either, so they can only be downloaded through the raw domain.
+
+use std::cell::RefCell;
+use std::collections::BTreeSet;
+
+use base64::engine::general_purpose::STANDARD as BASE64;
+use base64::Engine as _;
+use candid::Principal;
+use did::deferred::{Contract, RestrictionLevel};
+use did::{HttpResponse, ID};
+use ic_certification::{merge_hash_trees, AsHashTree, HashTree, NestedTree};
+use serde::Serialize;
+use sha2::{Digest as _, Sha256};
+
+use super::access::Accessor;
+use super::DeferredData;
+use crate::utils;
+
+/// Label of the subtree of the certification tree with the certified responses
+const HTTP_EXPR_LABEL: &str = "http_expr";
+/// Terminates the paths matching exactly a request path
+const EXACT_PATH_TERMINATOR: &str = "<$>";
+/// Terminates the paths matching all the request paths they are a prefix of
+const WILDCARD_PATH_TERMINATOR: &str = "<*>";
+/// Certifies the status code, the content type and the body of the response
+const RESPONSE_CERTIFICATION: &str = "default_certification(ValidationArgs{certification:Certification{no_request_certification:Empty{},response_certification:ResponseCertification{certified_response_headers:ResponseHeaderList{headers:[\"content-type\"]}}}})";
+/// Leaves the response uncertified
+const SKIP_CERTIFICATION: &str = "default_certification(ValidationArgs{no_certification:Empty{}})";
+const CERTIFICATE_HEADER: &str = "ic-certificate";
+const CERTIFICATE_EXPRESSION_HEADER: &str = "ic-certificateexpression";
+const CONTENT_TYPE_HEADER: &str = "content-type";
+/// Pseudo header certifying the status code of the response
+const STATUS_CODE_PSEUDO_HEADER: &str = ":ic-cert-status";
+const JSON_CONTENT_TYPE: &str = "application/json";
+const HTTP_OK: u16 = 200;
+/// Documents larger than a response body are streamed, so they can't be certified
+const MAX_CERTIFIED_DOCUMENT_SIZE: u64 = 1024 * 1024;
+
+thread_local! {
+    /// Certified responses; they are kept in the heap and certified again after upgrades
+    static CERTIFICATION: RefCell<CertifiedResponses> = RefCell::new(CertifiedResponses::default());
+}
+
+/// A value of the response headers, hashed with its type
+enum HeaderValue<'a> {
+    String(&'a str),
+    Number(u64),
+}
+
+/// The certification tree and the contracts listed by the certified `/contracts` response
+struct CertifiedResponses {
+    tree: NestedTree<Vec<u8>, Vec<u8>>,
+    /// IDs of the open contracts
+    contracts: BTreeSet<ID>,
+}
+
+impl Default for CertifiedResponses {
+    fn default() -> Self {
+        let mut tree = NestedTree::default();
+        // the responses of all the other routes are not certified
+        let wildcard = expr_path(&[], WILDCARD_PATH_TERMINATOR);
+        tree.insert(&tree_path(&wildcard, SKIP_CERTIFICATION, None), vec![]);
+
+        let mut responses = Self {
+            tree,
+            contracts: BTreeSet::new(),
+        };
+        responses.certify_contract_list();
+
+        responses
+    }
+}
+
+impl CertifiedResponses {
+    /// Certify the responses of an open contract: the contract and its documents, as seen by the public
+    fn insert_contract(&mut self, contract: &Contract) {
+        let contract = public_view(contract);
+        let contract_segments = contract_segments(&contract.id);
+
+        if let Ok(body) = serde_json::to_vec(&contract) {
+            self.insert_response(
+                &contract_segments,
+                Some(JSON_CONTENT_TYPE),
+                Sha256::digest(body).into(),
+            );
+        }
+
+        for (document_id, document) in contract.documents.iter() {
+            // streamed and undigested documents fall under the uncertified wildcard
+            if document.size > MAX_CERTIFIED_DOCUMENT_SIZE {
+                continue;
+            }
+            let Some(digest) = document
+                .digest
+                .as_ref()
+                .and_then(|digest| hex::decode(digest).ok())
+                .and_then(|digest| <[u8; 32]>::try_from(digest).ok())
+            else {
+                continue;
+            };
+
+            let mut segments = contract_segments.clone();
+            segments.extend(["document".to_string(), document_id.to_string()]);
+            self.insert_response(&segments, Some(&document.mime_type), digest);
+        }
+
+        self.contracts.insert(contract.id);
+    }
+
+    /// Remove the certified responses of a contract
+    fn remove_contract(&mut self, id: &ID) {
+        // remove the whole contract subtree, including the documents
+        let path = std::iter::once(HTTP_EXPR_LABEL.to_string())
+            .chain(contract_segments(id))
+            .collect::<Vec<_>>();
+        self.tree.delete(&tree_keys(&path));
+        self.contracts.remove(id);
+    }
+
+    /// Certify the response of `/contracts` without filters
+    fn certify_contract_list(&mut self) {
+        let segments = ["contracts".to_string()];
+        self.tree
+            .delete(&tree_keys(&expr_path(&segments, EXACT_PATH_TERMINATOR)));
+
+        let contracts = self.contracts.iter().collect::<Vec<_>>();
+        if let Ok(body) = serde_json::to_vec(&contracts) {
+            self.insert_response(
+                &segments,
+                Some(JSON_CONTENT_TYPE),
+                Sha256::digest(body).into(),
+            );
+        }
+    }
+
+    /// Certify the response with status OK served at `segments`
+    fn insert_response(
+        &mut self,
+        segments: &[String],
+        content_type: Option<&str>,
+        body_hash: [u8; 32],
+    ) {
+        let path = expr_path(segments, EXACT_PATH_TERMINATOR);
+        let response_hash = response_hash(HTTP_OK, content_type, body_hash);
+        self.tree.insert(
+            &tree_path(&path, RESPONSE_CERTIFICATION, Some(response_hash)),
+            vec![],
+        );
+    }
+
+    /// Get the witness of the response served at `path`, with the expression path and the expression certifying it.
+    ///
+    /// Returns `None` if a response is certified at `path`, but it's not `response`.
+    fn witness(
+        &self,
+        path: &str,
+        response: &HttpResponse,
+    ) -> Option<(HashTree, Vec<String>, &'static str)> {
+        let segments = request_segments(path);
+        let exact_path = expr_path(&segments, EXACT_PATH_TERMINATOR);
+
+        if self.tree.contains_path(&tree_keys(&exact_path)) {
+            let content_type = response
+                .headers
+                .iter()
+                .find(|(key, _)| key.eq_ignore_ascii_case(CONTENT_TYPE_HEADER))
+                .map(|(_, value)| value.as_ref());
+            let response_hash = response_hash(
+                response.status_code,
+                content_type,
+                Sha256::digest(&response.body).into(),
+            );
+            let tree_path = tree_path(&exact_path, RESPONSE_CERTIFICATION, Some(response_hash));
+            if !self.tree.contains_leaf(&tree_path) {
+                return None;
+            }
+
+            return Some((
+                self.tree.witness(&tree_path),
+                exact_path,
+                RESPONSE_CERTIFICATION,
+            ));
+        }
+
+        // the most specific wildcard applies, so the witness must prove the absence of the more specific paths
+        let mut witness = self.tree.witness(&tree_keys(&exact_path));
+        for depth in (0..=segments.len()).rev() {
+            let wildcard_path = expr_path(&segments[..depth], WILDCARD_PATH_TERMINATOR);
+            if self.tree.contains_path(&tree_keys(&wildcard_path)) {
+                let tree_path = tree_path(&wildcard_path, SKIP_CERTIFICATION, None);
+                witness = merge_hash_trees(witness, self.tree.witness(&tree_path));
+
+                return Some((witness, wildcard_path, SKIP_CERTIFICATION));
+            }
+            witness = merge_hash_trees(witness, self.tree.witness(&tree_keys(&wildcard_path)));
+        }
+
+        None
+    }
+
+    /// Set the root hash of the tree as the certified data of the canister
+    fn set_certified_data(&self) {
+        utils::set_certified_data(&self.tree.root_hash());
+    }
+}
+
+pub struct HttpCertification;
+
+impl HttpCertification {
+    /// Certify the responses of the open `contracts`, replacing all the certified responses
+    pub fn certify_contracts(contracts: impl Iterator<Item = Contract>) {
+        CERTIFICATION.with_borrow_mut(|responses| {
+            *responses = CertifiedResponses::default();
+            for contract in contracts.filter(|contract| !contract.closed) {
+                responses.insert_contract(&contract);
+            }
+            responses.certify_contract_list();
+            responses.set_certified_data();
+        })
+    }
+
+    /// Certify again the responses of a contract which has changed.
+    ///
+    /// The responses of closed contracts are no longer certified.
+    pub fn update_contract(contract: &Contract) {
+        CERTIFICATION.with_borrow_mut(|responses| {
+            responses.remove_contract(&contract.id);
+            if !contract.closed {
+                responses.insert_contract(contract);
+            }
+            responses.certify_contract_list();
+            responses.set_certified_data();
+        })
+    }
+
+    /// Attach the certificate of the response served at `path`, if the certificate is available.
+    ///
+    /// Responses differing from the certified ones, such as the ones served to the callers providing a credential,
+    /// are left without certificate, so they can only be served by the raw domain.
+    pub fn certify_response(path: &str, response: &mut HttpResponse) {
+        let Some(certificate) = utils::data_certificate() else {
+            return;
+        };
+
+        let Some((witness, expr_path, expression)) =
+            CERTIFICATION.with_borrow(|responses| responses.witness(path, response))
+        else {
+            return;
+        };
+        let (Some(tree), Some(expr_path)) = (cbor(&witness), cbor(&expr_path)) else {
+            return;
+        };
+
+        response.headers.insert(
+            CERTIFICATE_HEADER.into(),
+            format!(
+                "certificate=:{}:, tree=:{}:, expr_path=:{}:, version=2",
+                BASE64.encode(certificate),
+                BASE64.encode(tree),
+                BASE64.encode(expr_path)
+            )
+            .into(),
+        );
+        response
+            .headers
+            .insert(CERTIFICATE_EXPRESSION_HEADER.into(), expression.into());
+    }
+}
+
+/// The contract as seen by the public, with the restricted properties and documents accessible by anyone
+fn public_view(contract: &Contract) -> Contract {
+    let mut contract = contract.clone();
+    let accessor = Accessor::new(RestrictionLevel::Public, Principal::anonymous(), None, &[]);
+    DeferredData::redact_restricted_properties(&mut contract, Some(&accessor));
+
+    contract
+}
+
+fn contract_segments(id: &ID) -> Vec<String> {
+    vec!["contract".to_string(), id.0.to_string()]
+}
+
+/// Split a request path into its decoded segments
+fn request_segments(path: &str) -> Vec<String> {
+    path.strip_prefix('/')
+        .unwrap_or(path)
+        .split('/')
+        .map(|segment| {
+            percent_encoding::percent_decode_str(segment)
+                .decode_utf8_lossy()
+                .into_owned()
+        })
+        .collect()
+}
+
+/// The expression path of the responses served at `segments`
+fn expr_path(segments: &[String], terminator: &str) -> Vec<String> {
+    std::iter::once(HTTP_EXPR_LABEL)
+        .chain(segments.iter().map(String::as_str))
+        .chain(std::iter::once(terminator))
+        .map(str::to_string)
+        .collect()
+}
+
+fn tree_keys(path: &[String]) -> Vec<Vec<u8>> {
+    path.iter().map(|label| label.as_bytes().to_vec()).collect()
+}
+
+/// The path of the tree leaf certifying a response with `expression`.
+///
+/// Requests are never certified, so their hash is empty, as well as the hash of uncertified responses.
+fn tree_path(
+    expr_path: &[String],
+    expression: &str,
+    response_hash: Option<[u8; 32]>,
+) -> Vec<Vec<u8>> {
+    let mut path = tree_keys(expr_path);
+    path.push(Sha256::digest(expression).to_vec());
+    path.push(vec![]);
+    path.push(response_hash.map(|hash| hash.to_vec()).unwrap_or_default());
+
+    path
+}
+
+/// Hash of a response certified by [`RESPONSE_CERTIFICATION`]
+fn response_hash(status_code: u16, content_type: Option<&str>, body_hash: [u8; 32]) -> [u8; 32] {
+    let mut headers = vec![
+        (
+            CERTIFICATE_EXPRESSION_HEADER,
+            HeaderValue::String(RESPONSE_CERTIFICATION),
+        ),
+        (
+            STATUS_CODE_PSEUDO_HEADER,
+            HeaderValue::Number(status_code.into()),
+        ),
+    ];
+    if let Some(content_type) = content_type {
+        headers.push((CONTENT_TYPE_HEADER, HeaderValue::String(content_type)));
+    }
+
+    let mut hasher = Sha256::new();
+    hasher.update(representation_independent_hash(&headers));
+    hasher.update(body_hash);
+
+    hasher.finalize().into()
+}
+
+/// Hash of a map, which doesn't depend on the order of its entries
+fn representation_independent_hash(map: &[(&str, HeaderValue)]) -> [u8; 32] {
+    let mut entries = map
+        .iter()
+        .map(|(key, value)| {
+            let value_hash = match value {
+                HeaderValue::String(value) => Sha256::digest(value.as_bytes()),
+                HeaderValue::Number(value) => Sha256::digest(leb128(*value)),
+            };
+            let mut entry = Sha256::digest(key.as_bytes()).to_vec();
+            entry.extend_from_slice(&value_hash);
+            entry
+        })
+        .collect::<Vec<_>>();
+    entries.sort();
+
+    Sha256::digest(entries.concat()).into()
+}
+
+/// Encode a number as unsigned LEB128
+fn leb128(mut value: u64) -> Vec<u8> {
+    let mut bytes = vec![];
+    loop {
+        let byte = (value & 0x7f) as u8;
+        value >>= 7;
+        if value == 0 {
+            bytes.push(byte);
+            return bytes;
+        }
+        bytes.push(byte | 0x80);
+    }
+}
+
+/// Encode a value as self-described CBOR
+fn cbor(value: &impl Serialize) -> Option<Vec<u8>> {
+    let mut serializer = serde_cbor::Serializer::new(Vec::new());
+    serializer.self_describe().ok()?;
+    value.serialize(&mut serializer).ok()?;
+
+    Some(serializer.into_inner())
+}
+
+#[cfg(test)]
+mod test {
+
+    use std::collections::HashMap;
+
+    use did::deferred::ContractDocument;
+    use ic_certification::LookupResult;
+    use pretty_assertions::assert_eq;
+    use serde_bytes::ByteBuf;
+
+    use super::*;
+    use crate::app::test_utils::with_mock_contract;
+
+    fn json_response(body: &impl Serialize) -> HttpResponse {
+        HttpResponse::ok(body)
+    }
+
+    fn public_document(data: &[u8]) -> ContractDocument {
+        ContractDocument {
+            access_list: vec![RestrictionLevel::Public],
+            mime_type: "application/pdf".to_string(),
+            name: "deed.pdf".to_string(),
+            size: data.len() as u64,
+            previous_version: None,
+            superseded_by: None,
+            digest: Some(hex::encode(Sha256::digest(data))),
+        }
+    }
+
+    #[test]
+    fn test_should_encode_leb128() {
+        assert_eq!(leb128(0), vec![0x00]);
+        assert_eq!(leb128(200), vec![0xc8, 0x01]);
+        assert_eq!(leb128(624_485), vec![0xe5, 0x8e, 0x26]);
+    }
+
+    #[test]
+    fn test_should_certify_contract_responses() {
+        let data = b"deed".to_vec();
+        let contract = with_mock_contract(1, 100, |contract| {
+            contract.documents = vec![(0, public_document(&data))];
+        });
+        let mut responses = CertifiedResponses::default();
+        responses.insert_contract(&contract);
+        responses.certify_contract_list();
+
+        let (witness, expr_path, expression) = responses
+            .witness("/contract/1", &json_response(&public_view(&contract)))
+            .unwrap();
+        assert_eq!(expression, RESPONSE_CERTIFICATION);
+        assert_eq!(expr_path, vec!["http_expr", "contract", "1", "<$>"]);
+        assert_eq!(witness.digest(), responses.tree.root_hash());
+
+        let (witness, _, _) = responses
+            .witness("/contracts", &json_response(&vec![contract.id.clone()]))
+            .unwrap();
+        assert_eq!(witness.digest(), responses.tree.root_hash());
+
+        let document = HttpResponse::new(
+            HTTP_OK,
+            HashMap::from([("content-type".into(), "application/pdf".into())]),
+            ByteBuf::from(data),
+            None,
+        );
+        assert!(responses
+            .witness("/contract/1/document/0", &document)
+            .is_some());
+
+        // responses differing from the certified ones are not certified
+        assert!(responses
+            .witness("/contract/1", &json_response(&contract.id))
+            .is_none());
+        assert!(responses
+            .witness("/contracts", &json_response(&Vec::<ID>::new()))
+            .is_none());
+    }
+
+    #[test]
+    fn test_should_skip_certification_of_other_responses() {
+        let contract = with_mock_contract(1, 100, |_| {});
+        let mut responses = CertifiedResponses::default();
+        responses.insert_contract(&contract);
+
+        let (witness, wildcard_path, expression) = responses
+            .witness("/contract/1/audit", &json_response(&"audit"))
+            .unwrap();
+        assert_eq!(expression, SKIP_CERTIFICATION);
+        assert_eq!(wildcard_path, vec!["http_expr", "<*>"]);
+        assert_eq!(witness.digest(), responses.tree.root_hash());
+        // the witness proves there is no more specific path
+        assert_eq!(
+            witness.lookup_path(tree_keys(&expr_path(
+                &request_segments("/contract/1/audit"),
+                EXACT_PATH_TERMINATOR
+            ))),
+            LookupResult::Absent
+        );
+    }
+
+    #[test]
+    fn test_should_remove_closed_contract() {
+        let contract = with_mock_contract(1, 100, |_| {});
+        HttpCertification::certify_contracts(vec![contract.clone()].into_iter());
+        let certified_tree = CERTIFICATION.with_borrow(|responses| responses.tree.root_hash());
+
+        HttpCertification::update_contract(&Contract {
+            closed: true,
+            ..contract
+        });
+        CERTIFICATION.with_borrow(|responses| {
+            assert_ne!(responses.tree.root_hash(), certified_tree);
+            assert!(responses.contracts.is_empty());
+            assert!(!responses.tree.contains_path(&tree_keys(&expr_path(
+                &contract_segments(&1u64.into()),
+                EXACT_PATH_TERMINATOR
+            ))));
+        });
+    }
+}
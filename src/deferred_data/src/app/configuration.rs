@@ -1,16 +1,23 @@
 use std::cell::RefCell;
 
 use candid::Principal;
-use did::deferred::{DeferredDataError, DeferredDataResult};
+use did::deferred::{
+    DeferredDataError, DeferredDataResult, PropertySchema, StorablePropertySchema,
+};
 use did::{StorableLogSettings, StorablePrincipal};
 use ic_log::LogSettingsV2;
 use ic_stable_structures::memory_manager::VirtualMemory;
 use ic_stable_structures::{DefaultMemoryImpl, StableCell};
 
+use super::schema::Schema;
 use crate::app::memory::{
-    LOG_SETTINGS_MEMORY_ID, MEMORY_MANAGER, MINTER_MEMORY_ID, OWNER_MEMORY_ID,
+    CHAIN_ID_MEMORY_ID, LOG_SETTINGS_MEMORY_ID, MEMORY_MANAGER, MINTER_MEMORY_ID, OWNER_MEMORY_ID,
+    PROPERTY_SCHEMA_MEMORY_ID, SIWE_DOMAIN_MEMORY_ID,
 };
 
+/// Domain of the dapp requesting the SIWE signatures, unless set with `admin_set_siwe_domain`
+const DEFAULT_SIWE_DOMAIN: &str = "ekoke.io";
+
 thread_local! {
     /// Deferred minter memory ID
     static MINTER: RefCell<StableCell<StorablePrincipal, VirtualMemory<DefaultMemoryImpl>>> =
@@ -27,6 +34,21 @@ thread_local! {
         RefCell::new(StableCell::new(MEMORY_MANAGER.with(|mm| mm.get(LOG_SETTINGS_MEMORY_ID)), StorableLogSettings::default()).unwrap()
     );
 
+    /// Contract property schema
+    static PROPERTY_SCHEMA: RefCell<StableCell<StorablePropertySchema, VirtualMemory<DefaultMemoryImpl>>> =
+        RefCell::new(StableCell::new(MEMORY_MANAGER.with(|mm| mm.get(PROPERTY_SCHEMA_MEMORY_ID)), StorablePropertySchema(Schema::default_schema())).unwrap()
+    );
+
+    /// Chain ID of the EIP-712 domain used by typed data signatures
+    static CHAIN_ID: RefCell<StableCell<u64, VirtualMemory<DefaultMemoryImpl>>> =
+        RefCell::new(StableCell::new(MEMORY_MANAGER.with(|mm| mm.get(CHAIN_ID_MEMORY_ID)), 0).unwrap()
+    );
+
+    /// Domain which SIWE messages must be issued for
+    static SIWE_DOMAIN: RefCell<StableCell<String, VirtualMemory<DefaultMemoryImpl>>> =
+        RefCell::new(StableCell::new(MEMORY_MANAGER.with(|mm| mm.get(SIWE_DOMAIN_MEMORY_ID)), DEFAULT_SIWE_DOMAIN.to_string()).unwrap()
+    );
+
 }
 
 pub struct Configuration;
@@ -70,6 +92,45 @@ impl Configuration {
     pub fn get_log_settings() -> LogSettingsV2 {
         LOG_SETTINGS.with_borrow(|cell| cell.get().0.clone())
     }
+
+    pub fn get_property_schema() -> Vec<PropertySchema> {
+        PROPERTY_SCHEMA.with_borrow(|cell| cell.get().0.clone())
+    }
+
+    pub fn set_property_schema(schema: Vec<PropertySchema>) -> DeferredDataResult<()> {
+        PROPERTY_SCHEMA.with_borrow_mut(|cell| {
+            cell.set(StorablePropertySchema(schema))
+                .map_err(|_| DeferredDataError::StorageError)
+        })?;
+
+        Ok(())
+    }
+
+    pub fn get_chain_id() -> u64 {
+        CHAIN_ID.with_borrow(|cell| *cell.get())
+    }
+
+    pub fn set_chain_id(chain_id: u64) -> DeferredDataResult<()> {
+        CHAIN_ID.with_borrow_mut(|cell| {
+            cell.set(chain_id)
+                .map_err(|_| DeferredDataError::StorageError)
+        })?;
+
+        Ok(())
+    }
+
+    pub fn get_siwe_domain() -> String {
+        SIWE_DOMAIN.with_borrow(|cell| cell.get().clone())
+    }
+
+    pub fn set_siwe_domain(domain: String) -> DeferredDataResult<()> {
+        SIWE_DOMAIN.with_borrow_mut(|cell| {
+            cell.set(domain)
+                .map_err(|_| DeferredDataError::StorageError)
+        })?;
+
+        Ok(())
+    }
 }
 
 #[cfg(test)]
@@ -109,4 +170,33 @@ mod test {
         assert!(Configuration::set_log_settings(settings.clone()).is_ok());
         assert_eq!(Configuration::get_log_settings(), settings);
     }
+
+    #[test]
+    fn test_should_set_and_get_property_schema() {
+        assert_eq!(
+            Configuration::get_property_schema(),
+            Schema::default_schema()
+        );
+
+        let schema = vec![PropertySchema::optional(
+            "contract:rooms",
+            did::deferred::GenericValueKind::Nat8Content,
+        )];
+        assert!(Configuration::set_property_schema(schema.clone()).is_ok());
+        assert_eq!(Configuration::get_property_schema(), schema);
+    }
+
+    #[test]
+    fn test_should_set_and_get_chain_id() {
+        assert_eq!(Configuration::get_chain_id(), 0);
+        assert!(Configuration::set_chain_id(1).is_ok());
+        assert_eq!(Configuration::get_chain_id(), 1);
+    }
+
+    #[test]
+    fn test_should_set_and_get_siwe_domain() {
+        assert_eq!(Configuration::get_siwe_domain(), DEFAULT_SIWE_DOMAIN);
+        assert!(Configuration::set_siwe_domain("app.ekoke.io".to_string()).is_ok());
+        assert_eq!(Configuration::get_siwe_domain(), "app.ekoke.io");
+    }
 }
@@ -0,0 +1,333 @@
+//! EIP-712 typed data used by sellers and buyers to access restricted data and to link their address to a principal.
+//!
+//! The domain is bound to the canister through its `salt` (the keccak256 of the canister principal)
+//! and to the configured chain ID:
+//!
+//! ```txt
+//! EIP712Domain(string name,string version,uint256 chainId,bytes32 salt)
+//! ContractAccess(uint256 contractId,string statement,uint256 issuedAt,uint256 expiresAt,string nonce)
+//! AddressLink(string principal,string statement,uint256 issuedAt,uint256 expiresAt,string nonce)
+//! ```
+
+use candid::Principal;
+use did::deferred::{DeferredDataError, DeferredDataResult};
+use did::ID;
+use ethers_core::utils::keccak256;
+
+use super::siwe::{verify_nonce, verify_validity};
+
+const DOMAIN_NAME: &str = "EKOKE Deferred Data";
+const DOMAIN_VERSION: &str = "1";
+const DOMAIN_TYPE: &str = "EIP712Domain(string name,string version,uint256 chainId,bytes32 salt)";
+const CONTRACT_ACCESS_TYPE: &str = "ContractAccess(uint256 contractId,string statement,uint256 issuedAt,uint256 expiresAt,string nonce)";
+const ADDRESS_LINK_TYPE: &str =
+    "AddressLink(string principal,string statement,uint256 issuedAt,uint256 expiresAt,string nonce)";
+
+const NANOS_PER_SECOND: u64 = 1_000_000_000;
+
+/// Typed data granting read access to a contract
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct ContractAccess<'a> {
+    pub contract_id: &'a ID,
+    /// Seconds since UNIX epoch
+    pub issued_at: u64,
+    /// Seconds since UNIX epoch
+    pub expires_at: u64,
+    pub nonce: &'a str,
+}
+
+impl ContractAccess<'_> {
+    /// The statement shown by the wallet when signing
+    pub fn statement(&self) -> String {
+        format!("Grant read access to contract #{}", self.contract_id.0)
+    }
+
+    /// Verify that the access is valid at time `now` (nanoseconds).
+    ///
+    /// The same rules of SIWE messages apply.
+    pub fn verify(&self, now: u64) -> DeferredDataResult<()> {
+        verify(self.issued_at, self.expires_at, self.nonce, now)
+    }
+
+    /// Get the hash signed with `eth_signTypedData_v4`
+    pub fn signing_hash(
+        &self,
+        canister_id: Principal,
+        chain_id: u64,
+    ) -> DeferredDataResult<[u8; 32]> {
+        Ok(signing_hash(canister_id, chain_id, self.struct_hash()?))
+    }
+
+    fn struct_hash(&self) -> DeferredDataResult<[u8; 32]> {
+        // contract IDs are encoded as uint256
+        let contract_id = self.contract_id.0.to_bytes_be();
+        if contract_id.len() > 32 {
+            return Err(DeferredDataError::InvalidSignedMessage(
+                "contract ID exceeds uint256".to_string(),
+            ));
+        }
+        let mut encoded_contract_id = [0; 32];
+        encoded_contract_id[32 - contract_id.len()..].copy_from_slice(&contract_id);
+
+        Ok(keccak256(
+            [
+                keccak256(CONTRACT_ACCESS_TYPE),
+                encoded_contract_id,
+                keccak256(self.statement()),
+                encode_u64(self.issued_at),
+                encode_u64(self.expires_at),
+                keccak256(self.nonce),
+            ]
+            .concat(),
+        ))
+    }
+}
+
+/// Typed data linking the signer address to a principal
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct AddressLink<'a> {
+    pub principal: Principal,
+    /// Seconds since UNIX epoch
+    pub issued_at: u64,
+    /// Seconds since UNIX epoch
+    pub expires_at: u64,
+    pub nonce: &'a str,
+}
+
+impl AddressLink<'_> {
+    /// The statement shown by the wallet when signing
+    pub fn statement(&self) -> String {
+        format!("Link this address to principal {}", self.principal)
+    }
+
+    /// Verify that the link is valid at time `now` (nanoseconds).
+    ///
+    /// The same rules of SIWE messages apply.
+    pub fn verify(&self, now: u64) -> DeferredDataResult<()> {
+        verify(self.issued_at, self.expires_at, self.nonce, now)
+    }
+
+    /// Get the hash signed with `eth_signTypedData_v4`
+    pub fn signing_hash(&self, canister_id: Principal, chain_id: u64) -> [u8; 32] {
+        signing_hash(canister_id, chain_id, self.struct_hash())
+    }
+
+    fn struct_hash(&self) -> [u8; 32] {
+        keccak256(
+            [
+                keccak256(ADDRESS_LINK_TYPE),
+                keccak256(self.principal.to_text()),
+                keccak256(self.statement()),
+                encode_u64(self.issued_at),
+                encode_u64(self.expires_at),
+                keccak256(self.nonce),
+            ]
+            .concat(),
+        )
+    }
+}
+
+/// Hash of the EIP-712 domain of the canister
+fn domain_separator(canister_id: Principal, chain_id: u64) -> [u8; 32] {
+    keccak256(
+        [
+            keccak256(DOMAIN_TYPE),
+            keccak256(DOMAIN_NAME),
+            keccak256(DOMAIN_VERSION),
+            encode_u64(chain_id),
+            keccak256(canister_id.as_slice()),
+        ]
+        .concat(),
+    )
+}
+
+/// Get the hash of a struct signed for the domain of the canister
+fn signing_hash(canister_id: Principal, chain_id: u64, struct_hash: [u8; 32]) -> [u8; 32] {
+    let mut data = Vec::with_capacity(2 + 32 + 32);
+    data.extend_from_slice(&[0x19, 0x01]);
+    data.extend_from_slice(&domain_separator(canister_id, chain_id));
+    data.extend_from_slice(&struct_hash);
+
+    keccak256(data)
+}
+
+/// Verify validity period (seconds) and nonce of typed data at time `now` (nanoseconds)
+fn verify(issued_at: u64, expires_at: u64, nonce: &str, now: u64) -> DeferredDataResult<()> {
+    verify_nonce(nonce)?;
+    verify_validity(
+        issued_at.saturating_mul(NANOS_PER_SECOND),
+        expires_at.saturating_mul(NANOS_PER_SECOND),
+        now,
+    )
+}
+
+/// ABI-encode a `u64` as a `uint256`
+fn encode_u64(value: u64) -> [u8; 32] {
+    let mut encoded = [0; 32];
+    encoded[24..].copy_from_slice(&value.to_be_bytes());
+
+    encoded
+}
+
+#[cfg(test)]
+mod test {
+
+    use std::str::FromStr as _;
+
+    use ethers_core::types::transaction::eip712::{Eip712 as _, TypedData};
+    use ethers_core::types::{Address, RecoveryMessage, Signature};
+    use pretty_assertions::{assert_eq, assert_ne};
+
+    use super::*;
+
+    const NOW: u64 = 1_733_070_324;
+    /// keccak256 of the principal of the canister
+    const CANISTER_SALT: &str = "ed7166c1eb72ed629460df00c29a8ea29ab9caeadfb5823656b28aae12e16023";
+    /// Digest of the access to contract #1 signed for chain 1
+    const ACCESS_DIGEST: &str = "a7ba1f7bc0641ce1a7db5b9b932b768840ea0e6d0f63fb079c7b0d0f39f62980";
+    /// Signature of [`ACCESS_DIGEST`] by the wallet with private key 0xaa..aa
+    const ACCESS_SIGNATURE: &str = "0xd0aedca64c57137045bfec78e9cee2fbaec3366b45e0722ffd116e27493600ac1d27da3302d2b75194a5670a69b86dc83010cbb53138ca8e32c80524a046eaa01b";
+
+    #[test]
+    fn test_should_format_statement() {
+        assert_eq!(
+            access(&1u64.into()).statement(),
+            "Grant read access to contract #1"
+        );
+    }
+
+    #[test]
+    fn test_should_bind_hash_to_canister_and_chain() {
+        let contract_id = ID::from(1u64);
+        let access = access(&contract_id);
+        let hash = access.signing_hash(canister(), 1).unwrap();
+
+        assert_ne!(hash, access.signing_hash(canister(), 2).unwrap());
+        assert_ne!(
+            hash,
+            access.signing_hash(Principal::anonymous(), 1).unwrap()
+        );
+        assert_ne!(
+            hash,
+            ContractAccess {
+                contract_id: &2u64.into(),
+                ..access.clone()
+            }
+            .signing_hash(canister(), 1)
+            .unwrap()
+        );
+    }
+
+    #[test]
+    fn test_should_verify_access() {
+        let contract_id = ID::from(1u64);
+        let access = access(&contract_id);
+
+        assert!(access.verify(NOW * NANOS_PER_SECOND).is_ok());
+        assert_eq!(
+            access.verify((NOW + 3600) * NANOS_PER_SECOND).unwrap_err(),
+            DeferredDataError::SignedMessageExpired
+        );
+        assert!(ContractAccess {
+            nonce: "123",
+            ..access
+        }
+        .verify(NOW * NANOS_PER_SECOND)
+        .is_err());
+    }
+
+    #[test]
+    fn test_should_bind_address_link_to_principal() {
+        let link = AddressLink {
+            principal: Principal::anonymous(),
+            issued_at: NOW,
+            expires_at: NOW + 3600,
+            nonce: "32891756",
+        };
+        assert_eq!(link.statement(), "Link this address to principal 2vxsx-fae");
+        assert!(link.verify(NOW * NANOS_PER_SECOND).is_ok());
+
+        let hash = link.signing_hash(canister(), 1);
+        assert_ne!(
+            hash,
+            AddressLink {
+                principal: canister(),
+                ..link.clone()
+            }
+            .signing_hash(canister(), 1)
+        );
+        assert_ne!(hash, link.signing_hash(canister(), 2));
+    }
+
+    #[test]
+    fn test_should_match_eth_sign_typed_data() {
+        // the payload a dapp passes to `eth_signTypedData_v4`
+        let typed_data: TypedData = serde_json::from_value(serde_json::json!({
+            "domain": {
+                "name": "EKOKE Deferred Data",
+                "version": "1",
+                "chainId": 1,
+                "salt": hex::decode(CANISTER_SALT).unwrap(),
+            },
+            "types": {
+                "EIP712Domain": [
+                    { "name": "name", "type": "string" },
+                    { "name": "version", "type": "string" },
+                    { "name": "chainId", "type": "uint256" },
+                    { "name": "salt", "type": "bytes32" },
+                ],
+                "ContractAccess": [
+                    { "name": "contractId", "type": "uint256" },
+                    { "name": "statement", "type": "string" },
+                    { "name": "issuedAt", "type": "uint256" },
+                    { "name": "expiresAt", "type": "uint256" },
+                    { "name": "nonce", "type": "string" },
+                ],
+            },
+            "primaryType": "ContractAccess",
+            "message": {
+                "contractId": 1,
+                "statement": "Grant read access to contract #1",
+                "issuedAt": NOW,
+                "expiresAt": NOW + 3600,
+                "nonce": "32891756",
+            },
+        }))
+        .unwrap();
+
+        let contract_id = ID::from(1u64);
+        let hash = access(&contract_id).signing_hash(canister(), 1).unwrap();
+        assert_eq!(hash, typed_data.encode_eip712().unwrap());
+        assert_eq!(hex::encode(hash), ACCESS_DIGEST);
+
+        let signature = Signature::from_str(ACCESS_SIGNATURE).unwrap();
+        assert_eq!(
+            signature
+                .recover(RecoveryMessage::Hash(hash.into()))
+                .unwrap(),
+            "0x8fd379246834eac74B8419FfdA202CF8051F7A03"
+                .parse::<Address>()
+                .unwrap()
+        );
+    }
+
+    #[test]
+    fn test_should_encode_u64() {
+        let encoded = encode_u64(0x0102);
+        assert_eq!(&encoded[..30], &[0; 30]);
+        assert_eq!(&encoded[30..], &[0x01, 0x02]);
+    }
+
+    fn access(contract_id: &ID) -> ContractAccess<'_> {
+        ContractAccess {
+            contract_id,
+            issued_at: NOW,
+            expires_at: NOW + 3600,
+            nonce: "32891756",
+        }
+    }
+
+    fn canister() -> Principal {
+        Principal::from_text("2m6dw-uaaaa-aaaal-arumq-cai").unwrap()
+    }
+}
@@ -0,0 +1,169 @@
+//! Decoding of the images uploaded to the contract galleries and generation of their thumbnails
+
+use std::io::Cursor;
+
+use did::deferred::{ContractImage, DataContractError, DeferredDataError, DeferredDataResult};
+use did::ID;
+use image::codecs::jpeg::JpegEncoder;
+use image::{DynamicImage, ImageFormat, ImageReader, ImageResult, Limits};
+use sha2::{Digest as _, Sha256};
+
+use crate::utils;
+
+/// Maximum width and height of the thumbnails, in pixels
+const THUMBNAIL_SIZE: u32 = 400;
+/// Quality of the JPEG encoding of the thumbnails
+const THUMBNAIL_QUALITY: u8 = 80;
+/// Mime type of the thumbnails
+pub const THUMBNAIL_MIME_TYPE: &str = "image/jpeg";
+/// Maximum width and height of the uploaded images, to bound the memory used to decode them
+const MAX_IMAGE_SIZE: u32 = 4096;
+/// Maximum memory the decoder may allocate, enough for a RGBA image of [`MAX_IMAGE_SIZE`] pixels per side
+const MAX_IMAGE_ALLOC: u64 = MAX_IMAGE_SIZE as u64 * MAX_IMAGE_SIZE as u64 * 4;
+/// Formats of the images accepted in the galleries
+const SUPPORTED_FORMATS: [ImageFormat; 3] =
+    [ImageFormat::Jpeg, ImageFormat::Png, ImageFormat::WebP];
+
+/// The stored versions of a gallery image
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum ImageVariant {
+    /// The uploaded image
+    Original,
+    /// The downscaled image
+    Thumbnail,
+}
+
+/// An uploaded image, decoded, with its thumbnail
+#[derive(Debug)]
+pub struct GalleryImage {
+    /// The image description; the ID is set once the image is stored
+    pub image: ContractImage,
+    /// The JPEG encoded thumbnail
+    pub thumbnail: Vec<u8>,
+}
+
+/// Decode an uploaded image and generate its thumbnail.
+///
+/// Fails if the image is malformed, larger than [`MAX_IMAGE_SIZE`] or not in a supported format.
+pub fn process_image(name: String, data: &[u8]) -> DeferredDataResult<GalleryImage> {
+    let invalid =
+        |reason: String| DeferredDataError::Contract(DataContractError::InvalidImage(reason));
+
+    let mut reader = ImageReader::new(Cursor::new(data))
+        .with_guessed_format()
+        .map_err(|err| invalid(err.to_string()))?;
+    let format = reader
+        .format()
+        .filter(|format| SUPPORTED_FORMATS.contains(format))
+        .ok_or_else(|| invalid("supported formats are JPEG, PNG and WebP".to_string()))?;
+
+    let mut limits = Limits::default();
+    limits.max_image_width = Some(MAX_IMAGE_SIZE);
+    limits.max_image_height = Some(MAX_IMAGE_SIZE);
+    limits.max_alloc = Some(MAX_IMAGE_ALLOC);
+    reader.limits(limits);
+    let image = reader.decode().map_err(|err| invalid(err.to_string()))?;
+
+    let thumbnail = thumbnail(&image).map_err(|err| invalid(err.to_string()))?;
+
+    Ok(GalleryImage {
+        image: ContractImage {
+            id: 0,
+            name,
+            mime_type: format.to_mime_type().to_string(),
+            size: data.len() as u64,
+            width: image.width(),
+            height: image.height(),
+            digest: hex::encode(Sha256::digest(data)),
+        },
+        thumbnail,
+    })
+}
+
+/// Get the URL a version of a gallery image is served at
+pub fn image_url(contract_id: &ID, image_id: u64, variant: ImageVariant) -> String {
+    let url = format!(
+        "https://{}.raw.icp0.io/contract/{}/image/{image_id}",
+        utils::id(),
+        contract_id.0
+    );
+
+    match variant {
+        ImageVariant::Original => url,
+        ImageVariant::Thumbnail => format!("{url}/thumbnail"),
+    }
+}
+
+/// Scale the image down to fit [`THUMBNAIL_SIZE`], keeping its aspect ratio, and encode it as JPEG.
+///
+/// Smaller images are only re-encoded.
+fn thumbnail(image: &DynamicImage) -> ImageResult<Vec<u8>> {
+    let thumbnail = if image.width() > THUMBNAIL_SIZE || image.height() > THUMBNAIL_SIZE {
+        image.thumbnail(THUMBNAIL_SIZE, THUMBNAIL_SIZE)
+    } else {
+        image.clone()
+    };
+
+    // JPEG has no alpha channel
+    let mut data = Vec::new();
+    DynamicImage::ImageRgb8(thumbnail.to_rgb8())
+        .write_with_encoder(JpegEncoder::new_with_quality(&mut data, THUMBNAIL_QUALITY))?;
+
+    Ok(data)
+}
+
+#[cfg(test)]
+mod test {
+
+    use pretty_assertions::assert_eq;
+
+    use super::*;
+    use crate::app::test_utils::mock_png;
+
+    #[test]
+    fn test_should_process_image() {
+        let data = mock_png(1600, 1200);
+
+        let gallery_image = process_image("living-room.png".to_string(), &data).unwrap();
+        assert_eq!(gallery_image.image.mime_type, "image/png");
+        assert_eq!(gallery_image.image.size, data.len() as u64);
+        assert_eq!(
+            (gallery_image.image.width, gallery_image.image.height),
+            (1600, 1200)
+        );
+        assert_eq!(gallery_image.image.digest.len(), 64);
+
+        let thumbnail = image::load_from_memory(&gallery_image.thumbnail).unwrap();
+        assert_eq!((thumbnail.width(), thumbnail.height()), (400, 300));
+    }
+
+    #[test]
+    fn test_should_not_upscale_small_images() {
+        let gallery_image = process_image("icon.png".to_string(), &mock_png(40, 20)).unwrap();
+
+        let thumbnail = image::load_from_memory(&gallery_image.thumbnail).unwrap();
+        assert_eq!((thumbnail.width(), thumbnail.height()), (40, 20));
+    }
+
+    #[test]
+    fn test_should_reject_invalid_images() {
+        assert!(matches!(
+            process_image("deed.pdf".to_string(), b"%PDF-1.7"),
+            Err(DeferredDataError::Contract(
+                DataContractError::InvalidImage(_)
+            ))
+        ));
+
+        // truncated image
+        let data = mock_png(100, 100);
+        assert!(process_image("broken.png".to_string(), &data[..data.len() / 2]).is_err());
+
+        // too large
+        assert!(matches!(
+            process_image("panorama.png".to_string(), &mock_png(MAX_IMAGE_SIZE + 1, 1)),
+            Err(DeferredDataError::Contract(
+                DataContractError::InvalidImage(_)
+            ))
+        ));
+    }
+}
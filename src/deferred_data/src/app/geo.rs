@@ -0,0 +1,259 @@
+//! Positions of the contracts and geographic computations
+
+use std::str::FromStr;
+
+use did::deferred::{Contract, GeoPoint};
+
+const CONTRACT_LATITUDE: &str = "contract:latitude";
+const CONTRACT_LONGITUDE: &str = "contract:longitude";
+
+const EARTH_RADIUS_KM: f64 = 6371.0;
+/// Length of a degree of latitude
+const KM_PER_DEGREE: f64 = 111.32;
+
+/// Get the position of a contract from the `contract:latitude` and `contract:longitude` properties
+pub fn contract_position(contract: &Contract) -> Option<GeoPoint> {
+    let property = |key: &str| {
+        contract
+            .properties
+            .iter()
+            .find(|(k, _)| k == key)
+            .and_then(|(_, v)| v.to_string().parse::<f64>().ok())
+    };
+
+    let latitude = property(CONTRACT_LATITUDE)?;
+    let longitude = property(CONTRACT_LONGITUDE)?;
+    if !(-90.0..=90.0).contains(&latitude) || !(-180.0..=180.0).contains(&longitude) {
+        return None;
+    }
+
+    Some(GeoPoint {
+        latitude,
+        longitude,
+    })
+}
+
+/// Get the distance in km between two points, with the haversine formula
+pub fn distance_km(a: GeoPoint, b: GeoPoint) -> f64 {
+    let latitude = a.latitude.to_radians();
+    let other_latitude = b.latitude.to_radians();
+    let delta_latitude = other_latitude - latitude;
+    let delta_longitude = (b.longitude - a.longitude).to_radians();
+
+    let a = (delta_latitude / 2.0).sin().powi(2)
+        + latitude.cos() * other_latitude.cos() * (delta_longitude / 2.0).sin().powi(2);
+    let c = 2.0 * a.sqrt().atan2((1.0 - a).sqrt());
+
+    EARTH_RADIUS_KM * c
+}
+
+/// A rectangular area, in degrees.
+///
+/// If `min_longitude` is greater than `max_longitude`, the box crosses the antimeridian.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct BoundingBox {
+    pub min_latitude: f64,
+    pub min_longitude: f64,
+    pub max_latitude: f64,
+    pub max_longitude: f64,
+}
+
+impl BoundingBox {
+    /// The whole earth surface
+    pub const WORLD: Self = Self {
+        min_latitude: -90.0,
+        min_longitude: -180.0,
+        max_latitude: 90.0,
+        max_longitude: 180.0,
+    };
+
+    /// Get the smallest box containing the circle of `radius` km around `center`
+    pub fn around(center: GeoPoint, radius: f64) -> Self {
+        let delta_latitude = radius / KM_PER_DEGREE;
+        let min_latitude = (center.latitude - delta_latitude).max(-90.0);
+        let max_latitude = (center.latitude + delta_latitude).min(90.0);
+
+        // the circle includes a pole, or the longitude degrees are too short to bound it
+        let delta_longitude = radius / (KM_PER_DEGREE * center.latitude.to_radians().cos());
+        if min_latitude <= -90.0
+            || max_latitude >= 90.0
+            || !delta_longitude.is_finite()
+            || delta_longitude >= 180.0
+        {
+            return Self {
+                min_latitude,
+                max_latitude,
+                ..Self::WORLD
+            };
+        }
+
+        Self {
+            min_latitude,
+            min_longitude: wrap_longitude(center.longitude - delta_longitude),
+            max_latitude,
+            max_longitude: wrap_longitude(center.longitude + delta_longitude),
+        }
+    }
+
+    /// Returns whether the box crosses the antimeridian
+    pub fn crosses_antimeridian(&self) -> bool {
+        self.min_longitude > self.max_longitude
+    }
+
+    /// Returns whether the point is inside the box
+    pub fn contains(&self, point: GeoPoint) -> bool {
+        let in_longitude = if self.crosses_antimeridian() {
+            point.longitude >= self.min_longitude || point.longitude <= self.max_longitude
+        } else {
+            (self.min_longitude..=self.max_longitude).contains(&point.longitude)
+        };
+
+        in_longitude && (self.min_latitude..=self.max_latitude).contains(&point.latitude)
+    }
+}
+
+impl FromStr for BoundingBox {
+    type Err = String;
+
+    /// Parse a box formatted as `minLat,minLng,maxLat,maxLng`
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        let coordinates = s
+            .split(',')
+            .map(|coordinate| coordinate.trim().parse::<f64>())
+            .collect::<Result<Vec<_>, _>>()
+            .map_err(|_| "bounding box coordinates must be numbers".to_string())?;
+        let [min_latitude, min_longitude, max_latitude, max_longitude] = coordinates[..] else {
+            return Err("bounding box must be minLat,minLng,maxLat,maxLng".to_string());
+        };
+
+        let valid_latitude = |latitude: f64| (-90.0..=90.0).contains(&latitude);
+        let valid_longitude = |longitude: f64| (-180.0..=180.0).contains(&longitude);
+        if !valid_latitude(min_latitude)
+            || !valid_latitude(max_latitude)
+            || !valid_longitude(min_longitude)
+            || !valid_longitude(max_longitude)
+        {
+            return Err("bounding box coordinates are out of range".to_string());
+        }
+        if min_latitude > max_latitude {
+            return Err("bounding box minimum latitude exceeds the maximum".to_string());
+        }
+
+        Ok(Self {
+            min_latitude,
+            min_longitude,
+            max_latitude,
+            max_longitude,
+        })
+    }
+}
+
+/// Wrap a longitude into `[-180, 180]`
+fn wrap_longitude(longitude: f64) -> f64 {
+    if longitude < -180.0 {
+        longitude + 360.0
+    } else if longitude > 180.0 {
+        longitude - 360.0
+    } else {
+        longitude
+    }
+}
+
+#[cfg(test)]
+mod test {
+
+    use did::deferred::GenericValue;
+    use pretty_assertions::assert_eq;
+
+    use super::*;
+    use crate::app::test_utils::with_mock_contract;
+
+    #[test]
+    fn test_should_get_contract_position() {
+        let contract = with_mock_contract(1, 1, |contract| {
+            contract.properties = vec![
+                (
+                    CONTRACT_LATITUDE.to_string(),
+                    GenericValue::TextContent("45.0".to_string()),
+                ),
+                (
+                    CONTRACT_LONGITUDE.to_string(),
+                    GenericValue::FloatContent(9.0),
+                ),
+            ];
+        });
+
+        assert_eq!(
+            contract_position(&contract),
+            Some(GeoPoint {
+                latitude: 45.0,
+                longitude: 9.0
+            })
+        );
+        assert_eq!(
+            contract_position(&with_mock_contract(1, 1, |contract| contract
+                .properties
+                .clear())),
+            None
+        );
+    }
+
+    #[test]
+    fn test_should_compute_distance() {
+        let milan = point(45.4642, 9.19);
+        let rome = point(41.9028, 12.4964);
+
+        let distance = distance_km(milan, rome);
+        assert!((475.0..485.0).contains(&distance), "{distance}");
+        assert_eq!(distance_km(milan, milan), 0.0);
+    }
+
+    #[test]
+    fn test_should_parse_bounding_box() {
+        assert_eq!(
+            "44.5,8.5,45.5,9.5".parse::<BoundingBox>().unwrap(),
+            BoundingBox {
+                min_latitude: 44.5,
+                min_longitude: 8.5,
+                max_latitude: 45.5,
+                max_longitude: 9.5,
+            }
+        );
+        assert!("44.5,8.5,45.5".parse::<BoundingBox>().is_err());
+        assert!("44.5,8.5,95.5,9.5".parse::<BoundingBox>().is_err());
+        assert!("45.5,8.5,44.5,9.5".parse::<BoundingBox>().is_err());
+        assert!("a,8.5,45.5,9.5".parse::<BoundingBox>().is_err());
+    }
+
+    #[test]
+    fn test_should_contain_point_across_antimeridian() {
+        let bbox: BoundingBox = "-20,170,-10,-170".parse().unwrap();
+
+        assert!(bbox.crosses_antimeridian());
+        assert!(bbox.contains(point(-15.0, 179.0)));
+        assert!(bbox.contains(point(-15.0, -175.0)));
+        assert!(!bbox.contains(point(-15.0, 0.0)));
+    }
+
+    #[test]
+    fn test_should_bound_circle() {
+        let bbox = BoundingBox::around(point(45.0, 9.0), 10.0);
+        assert!(bbox.contains(point(45.08, 9.0)));
+        assert!(bbox.contains(point(45.0, 9.12)));
+        assert!(!bbox.contains(point(45.1, 9.0)));
+
+        let bbox = BoundingBox::around(point(0.0, 179.95), 20.0);
+        assert!(bbox.crosses_antimeridian());
+        assert!(bbox.contains(point(0.0, -179.95)));
+
+        let bbox = BoundingBox::around(point(89.95, 0.0), 20.0);
+        assert!(bbox.contains(point(89.9, 180.0)));
+    }
+
+    fn point(latitude: f64, longitude: f64) -> GeoPoint {
+        GeoPoint {
+            latitude,
+            longitude,
+        }
+    }
+}
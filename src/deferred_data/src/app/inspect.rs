@@ -1,11 +1,19 @@
+use std::str::FromStr as _;
+
 use candid::Principal;
-use did::deferred::{DataContractError, DeferredDataError, DeferredDataResult, RestrictionLevel};
+use did::deferred::{
+    Contract, DataContractError, DeferredDataError, DeferredDataResult, RestrictionLevel,
+    SignedMessage,
+};
 use did::ID;
 use ethers_core::abi::ethereum_types::H520;
-use ethers_core::types::{Signature, H160};
+use ethers_core::types::{RecoveryMessage, Signature, H160, H256};
 
 use super::configuration::Configuration;
-use super::storage::ContractStorage;
+use super::eip712::{AddressLink, ContractAccess};
+use super::siwe::SiweMessage;
+use super::storage::{ContractStorage, NonceStorage, SessionStorage};
+use crate::utils::{id, time};
 
 pub struct Inspect;
 
@@ -33,43 +41,190 @@ impl Inspect {
         Ok(())
     }
 
-    /// Inspect whether a signature is valid for a contract.
+    /// Inspects if the caller can modify the contract a pending document upload belongs to.
     ///
-    /// Returns the restriction level of the caller.
+    /// Returns the ID of the contract.
+    pub fn inspect_modify_upload(caller: Principal, upload_id: u64) -> DeferredDataResult<ID> {
+        let contract_id = ContractStorage::get_contract_document_upload_contract(upload_id)?;
+        Inspect::inspect_modify_contract(caller, &contract_id)?;
+
+        Ok(contract_id)
+    }
+
+    /// Inspect whether a signed message is valid for a contract.
+    ///
+    /// The message must be either a SIWE message bound to the contract and to this canister, signed by the address it declares,
+    /// or EIP-712 typed data granting access to the contract, signed for the domain of this canister.
+    /// In both cases the message must not be expired.
+    ///
+    /// Returns the restriction level and the address of the signer.
+    /// The level is public if the signer is neither a seller nor a buyer, since it may still be granted access by address.
     pub fn inspect_signature(
         contract: &ID,
-        signature: H520,
-        message: String,
-    ) -> DeferredDataResult<RestrictionLevel> {
-        let contract = ContractStorage::get_contract(contract).ok_or(
+        signed_message: SignedMessage,
+    ) -> DeferredDataResult<(RestrictionLevel, H160)> {
+        // participants keep access to closed contracts
+        let contract = ContractStorage::get_any_contract(contract).ok_or(
             DeferredDataError::Contract(DataContractError::ContractNotFound(contract.clone())),
         )?;
 
-        // try to get the pubkey from the signature
-        let sender_address = Inspect::recover_address(message, signature)?;
+        let sender_address = Inspect::recover_signer(
+            signed_message,
+            &SiweMessage::contract_resource(id(), &contract.id),
+            |issued_at, expires_at, nonce| {
+                let access = ContractAccess {
+                    contract_id: &contract.id,
+                    issued_at,
+                    expires_at,
+                    nonce,
+                };
+                access.verify(time())?;
+
+                access.signing_hash(id(), Configuration::get_chain_id())
+            },
+        )?;
+
+        let access_level =
+            Inspect::inspect_address(&contract, sender_address).unwrap_or(RestrictionLevel::Public);
+
+        Ok((access_level, sender_address))
+    }
 
-        // check if the sender is a buyer or seller
+    /// Inspect whether a session token grants access to the contract.
+    ///
+    /// The session must not be expired and must have been created for the contract.
+    ///
+    /// Returns the restriction level and the address of the signer, like [`Inspect::inspect_signature`].
+    /// The level is evaluated again, so sellers and buyers removed from the contract lose their level.
+    pub fn inspect_session(
+        contract: &Contract,
+        token: &str,
+    ) -> DeferredDataResult<(RestrictionLevel, H160)> {
+        let session = SessionStorage::get_session(token, time())?;
+        if session.contract_id != contract.id {
+            return Err(DeferredDataError::Unauthorized);
+        }
+
+        let access_level = Inspect::inspect_address(contract, session.address.0)
+            .unwrap_or(RestrictionLevel::Public);
+
+        Ok((access_level, session.address.0))
+    }
+
+    /// Inspect whether a signed message links the signer address to `principal`.
+    ///
+    /// The message must be either a SIWE message bound to the principal and to this canister,
+    /// or EIP-712 typed data linking the address to the principal.
+    /// The anonymous principal can't be linked.
+    ///
+    /// Returns the address to link.
+    pub fn inspect_address_link(
+        principal: Principal,
+        signed_message: SignedMessage,
+    ) -> DeferredDataResult<H160> {
+        if principal == Principal::anonymous() {
+            return Err(DeferredDataError::Unauthorized);
+        }
+
+        Inspect::recover_signer(
+            signed_message,
+            &SiweMessage::principal_resource(id(), principal),
+            |issued_at, expires_at, nonce| {
+                let link = AddressLink {
+                    principal,
+                    issued_at,
+                    expires_at,
+                    nonce,
+                };
+                link.verify(time())?;
+
+                Ok(link.signing_hash(id(), Configuration::get_chain_id()))
+            },
+        )
+    }
+
+    /// Get the restriction level of an address for the contract.
+    ///
+    /// Fails if the address is neither a seller nor a buyer.
+    pub fn inspect_address(
+        contract: &Contract,
+        address: H160,
+    ) -> DeferredDataResult<RestrictionLevel> {
         if contract
             .sellers
             .iter()
-            .any(|seller| seller.address.0 == sender_address)
+            .any(|seller| seller.address.0 == address)
         {
             return Ok(RestrictionLevel::Seller);
         }
 
-        if contract
-            .buyers
-            .iter()
-            .any(|buyer| buyer.0 == sender_address)
-        {
+        if contract.buyers.iter().any(|buyer| buyer.0 == address) {
             return Ok(RestrictionLevel::Buyer);
         }
 
         Err(DeferredDataError::Unauthorized)
     }
 
-    /// Recover the public key from a signature
-    fn recover_address(message: String, signature: H520) -> DeferredDataResult<H160> {
+    /// Get the nonce of a signed message, so it can be used once verified
+    pub fn signed_message_nonce(signed_message: &SignedMessage) -> DeferredDataResult<String> {
+        match signed_message {
+            SignedMessage::Siwe { message, .. } => Ok(SiweMessage::from_str(message)?.nonce),
+            SignedMessage::TypedData { nonce, .. } => Ok(nonce.clone()),
+        }
+    }
+
+    /// Verify a signed message and recover the address of the signer.
+    ///
+    /// SIWE messages must be bound to `resource`, to the configured domain and to the configured chain,
+    /// while typed data is hashed with `typed_data_hash`, which receives the validity period and the nonce.
+    /// In both cases the nonce must have been issued by the canister and not used yet.
+    fn recover_signer<F>(
+        signed_message: SignedMessage,
+        resource: &str,
+        typed_data_hash: F,
+    ) -> DeferredDataResult<H160>
+    where
+        F: FnOnce(u64, u64, &str) -> DeferredDataResult<[u8; 32]>,
+    {
+        // verify the message before the signature, then get the pubkey from the signature
+        match signed_message {
+            SignedMessage::Siwe { message, signature } => {
+                let siwe_message = SiweMessage::from_str(&message)?;
+                siwe_message.verify(
+                    resource,
+                    &Configuration::get_siwe_domain(),
+                    Configuration::get_chain_id(),
+                    time(),
+                )?;
+                NonceStorage::verify_nonce(&siwe_message.nonce, time())?;
+
+                let sender_address = Inspect::recover_address(message, &signature)?;
+                if sender_address != siwe_message.address {
+                    return Err(DeferredDataError::InvalidSignature);
+                }
+
+                Ok(sender_address)
+            }
+            SignedMessage::TypedData {
+                issued_at,
+                expires_at,
+                nonce,
+                signature,
+            } => {
+                let hash = typed_data_hash(issued_at, expires_at, &nonce)?;
+                NonceStorage::verify_nonce(&nonce, time())?;
+                Inspect::recover_address(H256::from(hash), &signature)
+            }
+        }
+    }
+
+    /// Recover the public key from a hex-encoded signature of a message or of a hash
+    fn recover_address(
+        message: impl Into<RecoveryMessage>,
+        signature: &str,
+    ) -> DeferredDataResult<H160> {
+        let signature =
+            H520::from_str(signature).map_err(|_| DeferredDataError::InvalidSignature)?;
         let signature = Signature::try_from(signature.as_bytes())
             .map_err(|_| DeferredDataError::InvalidSignature)?;
         let address = signature
@@ -83,14 +238,16 @@ impl Inspect {
 #[cfg(test)]
 mod test {
 
-    use std::str::FromStr;
-
     use candid::Nat;
-    use did::deferred::Seller;
-    use pretty_assertions::assert_eq;
+    use did::deferred::{ContractDocument, Seller};
+    use pretty_assertions::{assert_eq, assert_ne};
 
     use super::*;
-    use crate::app::test_utils::{alice, store_mock_contract, store_mock_contract_with};
+    use crate::app::storage::Session;
+    use crate::app::test_utils::{
+        alice, sign, signed_link_message, signed_link_typed_data, signed_message,
+        signed_typed_data, signer_address, store_mock_contract, store_mock_contract_with,
+    };
 
     #[test]
     fn test_should_inspect_if_minter() {
@@ -143,52 +300,228 @@ mod test {
     }
 
     #[test]
-    fn test_should_verify_signature_if_seller() {
-        // private key is: 0xaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaa
-        let eth_address =
-            did::H160::from_hex_str("0x8fd379246834eac74B8419FfdA202CF8051F7A03").unwrap();
+    fn test_should_inspect_modify_upload() {
+        Configuration::set_minter(alice()).expect("Failed to set minter");
+
+        store_mock_contract(1, 60);
+        let upload_id = ContractStorage::begin_contract_document_upload(
+            &Nat::from(1u64),
+            ContractDocument {
+                access_list: vec![RestrictionLevel::Public],
+                mime_type: "application/pdf".to_string(),
+                name: "deed.pdf".to_string(),
+                size: 4,
+                previous_version: None,
+                superseded_by: None,
+                digest: None,
+            },
+        )
+        .expect("Failed to begin upload");
+
+        assert_eq!(
+            Inspect::inspect_modify_upload(alice(), upload_id),
+            Ok(Nat::from(1u64))
+        );
+        assert_eq!(
+            Inspect::inspect_modify_upload(Principal::anonymous(), upload_id),
+            Err(DeferredDataError::Unauthorized)
+        );
+        assert_eq!(
+            Inspect::inspect_modify_upload(alice(), upload_id + 1),
+            Err(DeferredDataError::Contract(
+                DataContractError::UploadNotFound(upload_id + 1)
+            ))
+        );
+    }
 
+    #[test]
+    fn test_should_verify_signature_if_seller() {
         store_mock_contract_with(1, 60, |contract| {
             contract.sellers = vec![Seller {
-                address: eth_address,
+                address: signer_address(),
                 quota: 100,
             }]
         });
 
-        let message = "Hello, Ethereum!".to_string();
-        let signature = H520::from_str("0x0e9293c16d57e3ea35118a52cc7209871d07db4b74183fbd6758306c2475586a2f64a5837cd7b787bff49e9432aab76de43080b9d98675e8890e16ffc669e6cb1b").unwrap();
-
         assert_eq!(
-            Inspect::inspect_signature(&Nat::from(1u64), signature, message),
-            Ok(RestrictionLevel::Seller)
+            Inspect::inspect_signature(&Nat::from(1u64), signed_message(1)),
+            Ok((RestrictionLevel::Seller, signer_address().0))
         );
     }
 
     #[test]
     fn test_should_verify_signature_if_buyer() {
-        let eth_address =
-            did::H160::from_hex_str("0x8fd379246834eac74B8419FfdA202CF8051F7A03").unwrap();
+        store_mock_contract_with(1, 60, |contract| contract.buyers = vec![signer_address()]);
 
-        store_mock_contract_with(1, 60, |contract| contract.buyers = vec![eth_address]);
+        assert_eq!(
+            Inspect::inspect_signature(&Nat::from(1u64), signed_message(1)),
+            Ok((RestrictionLevel::Buyer, signer_address().0))
+        );
+    }
 
-        let message = "Hello, Ethereum!".to_string();
-        let signature = H520::from_str("0x0e9293c16d57e3ea35118a52cc7209871d07db4b74183fbd6758306c2475586a2f64a5837cd7b787bff49e9432aab76de43080b9d98675e8890e16ffc669e6cb1b").unwrap();
+    #[test]
+    fn test_should_verify_signature_if_not_party() {
+        store_mock_contract(1, 60);
 
+        // the signer may still be granted access by address
         assert_eq!(
-            Inspect::inspect_signature(&Nat::from(1u64), signature, message),
-            Ok(RestrictionLevel::Buyer)
+            Inspect::inspect_signature(&Nat::from(1u64), signed_message(1)),
+            Ok((RestrictionLevel::Public, signer_address().0))
         );
     }
 
     #[test]
-    fn test_should_verify_signature_if_invalid() {
-        store_mock_contract(1, 60);
+    fn test_should_not_verify_signature_of_another_address() {
+        store_mock_contract_with(1, 60, |contract| contract.buyers = vec![signer_address()]);
+
+        // the message declares a different address from the signer
+        let SignedMessage::Siwe { message, .. } = signed_message(1) else {
+            panic!("expected SIWE message");
+        };
+        let message = message.replace(
+            &signer_address().to_hex_str(),
+            "0xe46a267b65ed8cbaeba9adc3171063179b642e7a",
+        );
+        let signed_message = SignedMessage::Siwe {
+            signature: sign(&message),
+            message,
+        };
 
-        let message = "Hello, Ethereum!".to_string();
-        let signature = H520::from_str("0x0e9293c16d57e3ea35118a52cc7209871d07db4b74183fbd6758306c2475586a2f64a5837cd7b787bff49e9432aab76de43080b9d98675e8890e16ffc669e6cb1b").unwrap();
+        assert_eq!(
+            Inspect::inspect_signature(&Nat::from(1u64), signed_message),
+            Err(DeferredDataError::InvalidSignature)
+        );
+    }
 
+    #[test]
+    fn test_should_not_verify_signature_without_issued_nonce() {
+        store_mock_contract_with(1, 60, |contract| contract.buyers = vec![signer_address()]);
+
+        let message = signed_message(1);
+        assert_eq!(
+            Inspect::signed_message_nonce(&message),
+            Ok("32891756".to_string())
+        );
+        NonceStorage::use_nonce("32891756", time()).unwrap();
+
+        assert!(matches!(
+            Inspect::inspect_signature(&Nat::from(1u64), message),
+            Err(DeferredDataError::InvalidSignedMessage(_))
+        ));
+    }
+
+    #[test]
+    fn test_should_not_verify_signature_for_another_chain_or_domain() {
+        store_mock_contract_with(1, 60, |contract| contract.buyers = vec![signer_address()]);
+
+        let message = signed_message(1);
+        Configuration::set_chain_id(5).expect("Failed to set chain id");
+        assert!(matches!(
+            Inspect::inspect_signature(&Nat::from(1u64), message),
+            Err(DeferredDataError::InvalidSignedMessage(_))
+        ));
+
+        let message = signed_message(1);
+        Configuration::set_siwe_domain("app.ekoke.io".to_string()).expect("Failed to set domain");
+        assert!(matches!(
+            Inspect::inspect_signature(&Nat::from(1u64), message),
+            Err(DeferredDataError::InvalidSignedMessage(_))
+        ));
+    }
+
+    #[test]
+    fn test_should_verify_typed_data_signature() {
+        Configuration::set_chain_id(1).expect("Failed to set chain id");
+        store_mock_contract_with(1, 60, |contract| contract.buyers = vec![signer_address()]);
+        store_mock_contract_with(2, 60, |contract| contract.buyers = vec![signer_address()]);
+
+        assert_eq!(
+            Inspect::inspect_signature(&Nat::from(1u64), signed_typed_data(1, 1)),
+            Ok((RestrictionLevel::Buyer, signer_address().0))
+        );
+        // signed for another contract, so another address is recovered
+        let (access_level, address) =
+            Inspect::inspect_signature(&Nat::from(2u64), signed_typed_data(1, 1)).unwrap();
+        assert_eq!(access_level, RestrictionLevel::Public);
+        assert_ne!(address, signer_address().0);
+        // signed for another chain
+        let (access_level, address) =
+            Inspect::inspect_signature(&Nat::from(1u64), signed_typed_data(1, 5)).unwrap();
+        assert_eq!(access_level, RestrictionLevel::Public);
+        assert_ne!(address, signer_address().0);
+    }
+
+    #[test]
+    fn test_should_inspect_address_link() {
+        Configuration::set_chain_id(1).expect("Failed to set chain id");
+
+        assert_eq!(
+            Inspect::inspect_address_link(alice(), signed_link_message(alice())),
+            Ok(signer_address().0)
+        );
+        assert_eq!(
+            Inspect::inspect_address_link(alice(), signed_link_typed_data(alice(), 1)),
+            Ok(signer_address().0)
+        );
+    }
+
+    #[test]
+    fn test_should_not_inspect_address_link_of_another_principal() {
+        Configuration::set_chain_id(1).expect("Failed to set chain id");
+
+        assert!(matches!(
+            Inspect::inspect_address_link(
+                alice(),
+                signed_link_message(Principal::management_canister())
+            ),
+            Err(DeferredDataError::InvalidSignedMessage(_))
+        ));
+        assert_ne!(
+            Inspect::inspect_address_link(
+                alice(),
+                signed_link_typed_data(Principal::management_canister(), 1)
+            ),
+            Ok(signer_address().0)
+        );
+        // contract access can't be used as link
+        assert!(Inspect::inspect_address_link(alice(), signed_message(1)).is_err());
+        // anonymous can't be linked
+        assert_eq!(
+            Inspect::inspect_address_link(
+                Principal::anonymous(),
+                signed_link_message(Principal::anonymous())
+            ),
+            Err(DeferredDataError::Unauthorized)
+        );
+    }
+
+    #[test]
+    fn test_should_inspect_session() {
+        store_mock_contract_with(1, 60, |contract| contract.buyers = vec![signer_address()]);
+        store_mock_contract(2, 60);
+        let contract = ContractStorage::get_contract(&Nat::from(1u64)).unwrap();
+        let another_contract = ContractStorage::get_contract(&Nat::from(2u64)).unwrap();
+
+        SessionStorage::insert_session(
+            "token",
+            Session {
+                contract_id: 1u64.into(),
+                address: signer_address(),
+                expires_at: time() + 60 * 1_000_000_000,
+            },
+            time(),
+        );
+
+        assert_eq!(
+            Inspect::inspect_session(&contract, "token"),
+            Ok((RestrictionLevel::Buyer, signer_address().0))
+        );
+        assert_eq!(
+            Inspect::inspect_session(&another_contract, "token"),
+            Err(DeferredDataError::Unauthorized)
+        );
         assert_eq!(
-            Inspect::inspect_signature(&Nat::from(1u64), signature, message),
+            Inspect::inspect_session(&contract, "unknown"),
             Err(DeferredDataError::Unauthorized)
         );
     }
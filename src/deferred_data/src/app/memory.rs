@@ -4,10 +4,35 @@ use ic_stable_structures::DefaultMemoryImpl;
 pub const CONTRACTS_MEMORY_ID: MemoryId = MemoryId::new(10);
 pub const DOCUMENTS_MEMORY_ID: MemoryId = MemoryId::new(11);
 pub const NEXT_DOCUMENT_ID_MEMORY_ID: MemoryId = MemoryId::new(12);
+pub const UPLOADS_MEMORY_ID: MemoryId = MemoryId::new(13);
+pub const UPLOAD_CHUNKS_MEMORY_ID: MemoryId = MemoryId::new(14);
+pub const NEXT_UPLOAD_ID_MEMORY_ID: MemoryId = MemoryId::new(15);
+pub const DOCUMENT_DIGESTS_MEMORY_ID: MemoryId = MemoryId::new(16);
+pub const DOCUMENT_BLOBS_MEMORY_ID: MemoryId = MemoryId::new(17);
+pub const AUDIT_LOG_MEMORY_ID: MemoryId = MemoryId::new(18);
+pub const NEXT_AUDIT_ID_MEMORY_ID: MemoryId = MemoryId::new(19);
+pub const ADDRESS_LINKS_MEMORY_ID: MemoryId = MemoryId::new(25);
+pub const SESSIONS_MEMORY_ID: MemoryId = MemoryId::new(26);
+pub const ACCESS_GROUPS_MEMORY_ID: MemoryId = MemoryId::new(27);
+pub const CONTRACT_INDEX_MEMORY_ID: MemoryId = MemoryId::new(28);
+pub const GEO_INDEX_MEMORY_ID: MemoryId = MemoryId::new(29);
+pub const SEARCH_INDEX_MEMORY_ID: MemoryId = MemoryId::new(30);
+pub const CONTRACT_IMAGES_MEMORY_ID: MemoryId = MemoryId::new(31);
+pub const IMAGES_MEMORY_ID: MemoryId = MemoryId::new(32);
+pub const IMAGE_THUMBNAILS_MEMORY_ID: MemoryId = MemoryId::new(33);
+pub const NEXT_IMAGE_ID_MEMORY_ID: MemoryId = MemoryId::new(34);
+pub const DOCUMENT_CHUNKS_MEMORY_ID: MemoryId = MemoryId::new(35);
+pub const NONCES_MEMORY_ID: MemoryId = MemoryId::new(36);
+pub const CONTRACT_EVENTS_MEMORY_ID: MemoryId = MemoryId::new(38);
+pub const NONCE_EXPIRATIONS_MEMORY_ID: MemoryId = MemoryId::new(39);
+pub const SESSION_EXPIRATIONS_MEMORY_ID: MemoryId = MemoryId::new(40);
 
 pub const MINTER_MEMORY_ID: MemoryId = MemoryId::new(20);
 pub const OWNER_MEMORY_ID: MemoryId = MemoryId::new(21);
 pub const LOG_SETTINGS_MEMORY_ID: MemoryId = MemoryId::new(22);
+pub const PROPERTY_SCHEMA_MEMORY_ID: MemoryId = MemoryId::new(23);
+pub const CHAIN_ID_MEMORY_ID: MemoryId = MemoryId::new(24);
+pub const SIWE_DOMAIN_MEMORY_ID: MemoryId = MemoryId::new(37);
 
 thread_local! {
     /// Memory manager
@@ -0,0 +1,359 @@
+use did::deferred::{
+    Contract, DataContractError, DeferredDataError, DeferredDataResult, GenericValue,
+    GenericValueKind, PropertySchema,
+};
+
+use super::configuration::Configuration;
+
+/// Prefix which must be used by all the contract properties
+const PROPERTY_PREFIX: &str = "contract:";
+
+/// Validates the contract properties against the configured [`PropertySchema`]
+pub struct Schema;
+
+impl Schema {
+    /// Default property schema, based on the documented contract properties
+    pub fn default_schema() -> Vec<PropertySchema> {
+        use GenericValueKind::{BoolContent, FloatContent, Nat64Content, TextContent};
+
+        vec![
+            PropertySchema::optional("contract:name", TextContent),
+            PropertySchema::optional("contract:description", TextContent),
+            PropertySchema::optional("contract:image", TextContent),
+            PropertySchema::optional("contract:address", TextContent),
+            PropertySchema::optional("contract:country", TextContent),
+            PropertySchema::optional("contract:continent", TextContent),
+            PropertySchema::optional("contract:region", TextContent),
+            PropertySchema::optional("contract:zipCode", TextContent),
+            PropertySchema::optional("contract:latitude", FloatContent).with_range(-90.0, 90.0),
+            PropertySchema::optional("contract:longitude", FloatContent).with_range(-180.0, 180.0),
+            PropertySchema::optional("contract:zone", TextContent),
+            PropertySchema::optional("contract:city", TextContent),
+            PropertySchema::optional("contract:squareMeters", Nat64Content),
+            PropertySchema::optional("contract:rooms", Nat64Content),
+            PropertySchema::optional("contract:bathrooms", Nat64Content),
+            PropertySchema::optional("contract:floors", Nat64Content),
+            PropertySchema::optional("contract:balconies", Nat64Content),
+            PropertySchema::optional("contract:garden", BoolContent),
+            PropertySchema::optional("contract:pool", BoolContent),
+            PropertySchema::optional("contract:garage", BoolContent),
+            PropertySchema::optional("contract:parking", BoolContent),
+            PropertySchema::optional("contract:energyClass", TextContent),
+            PropertySchema::optional("contract:youtubeUrl", TextContent),
+        ]
+    }
+
+    /// Validate the properties of a contract against the configured schema
+    pub fn validate_contract(contract: &Contract) -> DeferredDataResult<()> {
+        Self::validate(&Configuration::get_property_schema(), contract)
+    }
+
+    /// Validate the properties of a contract changed by an update against the configured schema.
+    ///
+    /// Only the `changed` keys are checked, so contracts stored before the schema was changed can still be updated.
+    pub fn validate_changes(contract: &Contract, changed: &[String]) -> DeferredDataResult<()> {
+        Self::validate_properties(&Configuration::get_property_schema(), contract, |key| {
+            changed.iter().any(|changed| changed == key)
+        })
+    }
+
+    /// Validate the properties of a contract against the given schema.
+    ///
+    /// Both public and restricted properties are checked. Properties without a schema are accepted,
+    /// as long as they start with `contract:`.
+    fn validate(schema: &[PropertySchema], contract: &Contract) -> DeferredDataResult<()> {
+        Self::validate_properties(schema, contract, |_| true)
+    }
+
+    /// Validate the properties of a contract whose key is accepted by `is_checked` against the given schema
+    fn validate_properties<F>(
+        schema: &[PropertySchema],
+        contract: &Contract,
+        is_checked: F,
+    ) -> DeferredDataResult<()>
+    where
+        F: Fn(&str) -> bool,
+    {
+        let properties = contract
+            .properties
+            .iter()
+            .map(|(key, value)| (key, value))
+            .chain(
+                contract
+                    .restricted_properties
+                    .iter()
+                    .map(|(key, property)| (key, &property.value)),
+            )
+            .filter(|(key, _)| is_checked(key));
+
+        for (key, value) in properties {
+            if !key.starts_with(PROPERTY_PREFIX) {
+                return Err(DeferredDataError::Contract(
+                    DataContractError::BadContractProperty,
+                ));
+            }
+
+            if let Some(property_schema) = schema.iter().find(|schema| &schema.key == key) {
+                Self::validate_value(property_schema, value)?;
+            }
+        }
+
+        // check required properties
+        for property_schema in schema.iter().filter(|schema| {
+            schema.required_for.contains(&contract.r#type) && is_checked(&schema.key)
+        }) {
+            let is_set = contract
+                .properties
+                .iter()
+                .any(|(key, _)| key == &property_schema.key)
+                || contract
+                    .restricted_properties
+                    .iter()
+                    .any(|(key, _)| key == &property_schema.key);
+
+            if !is_set {
+                return Err(DeferredDataError::Contract(
+                    DataContractError::MissingRequiredProperty(property_schema.key.clone()),
+                ));
+            }
+        }
+
+        Ok(())
+    }
+
+    /// Validate a property value against its schema.
+    ///
+    /// Float properties may also be set as a text holding a number, which is checked by its value.
+    fn validate_value(schema: &PropertySchema, value: &GenericValue) -> DeferredDataResult<()> {
+        let type_mismatch = || {
+            DeferredDataError::Contract(DataContractError::PropertyTypeMismatch(schema.key.clone()))
+        };
+
+        // numeric values are checked by value, text values by length
+        let measure = match (schema.kind, value) {
+            (GenericValueKind::FloatContent, GenericValue::TextContent(text)) => {
+                Some(text.trim().parse::<f64>().map_err(|_| type_mismatch())?)
+            }
+            (kind, value) if GenericValueKind::from(value) != kind => {
+                return Err(type_mismatch());
+            }
+            (_, GenericValue::TextContent(text)) => Some(text.chars().count() as f64),
+            (_, value) => value.as_f64(),
+        };
+        let Some(measure) = measure else {
+            return Ok(());
+        };
+
+        let below_min = schema.min.map(|min| measure < min).unwrap_or_default();
+        let above_max = schema.max.map(|max| measure > max).unwrap_or_default();
+        if measure.is_nan() || below_min || above_max {
+            return Err(DeferredDataError::Contract(
+                DataContractError::PropertyOutOfRange(schema.key.clone()),
+            ));
+        }
+
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod test {
+
+    use did::deferred::{ContractType, RestrictedProperty, RestrictionLevel};
+    use pretty_assertions::assert_eq;
+
+    use super::*;
+    use crate::app::test_utils::with_mock_contract;
+
+    #[test]
+    fn test_should_accept_valid_contract() {
+        let contract = with_mock_contract(1, 1, |contract| {
+            contract.properties = vec![
+                (
+                    "contract:latitude".to_string(),
+                    GenericValue::TextContent("41.9".to_string()),
+                ),
+                ("contract:rooms".to_string(), GenericValue::Nat64Content(3)),
+                (
+                    "contract:custom".to_string(),
+                    GenericValue::BoolContent(true),
+                ),
+            ];
+        });
+
+        assert!(Schema::validate(&Schema::default_schema(), &contract).is_ok());
+    }
+
+    #[test]
+    fn test_should_reject_property_without_prefix() {
+        let contract = with_mock_contract(1, 1, |contract| {
+            contract.properties = vec![("rooms".to_string(), GenericValue::Nat64Content(3))];
+        });
+
+        assert_eq!(
+            Schema::validate(&Schema::default_schema(), &contract).unwrap_err(),
+            DeferredDataError::Contract(DataContractError::BadContractProperty)
+        );
+    }
+
+    #[test]
+    fn test_should_reject_property_with_wrong_type() {
+        let contract = with_mock_contract(1, 1, |contract| {
+            contract.restricted_properties = vec![(
+                "contract:rooms".to_string(),
+                RestrictedProperty {
+                    access_list: vec![RestrictionLevel::Agent],
+                    value: GenericValue::TextContent("three".to_string()),
+                },
+            )];
+        });
+
+        assert_eq!(
+            Schema::validate(&Schema::default_schema(), &contract).unwrap_err(),
+            DeferredDataError::Contract(DataContractError::PropertyTypeMismatch(
+                "contract:rooms".to_string()
+            ))
+        );
+    }
+
+    #[test]
+    fn test_should_reject_property_out_of_range() {
+        let schema =
+            vec![
+                PropertySchema::optional("contract:latitude", GenericValueKind::FloatContent)
+                    .with_range(-90.0, 90.0),
+            ];
+        let contract = with_mock_contract(1, 1, |contract| {
+            contract.properties = vec![(
+                "contract:latitude".to_string(),
+                GenericValue::FloatContent(91.0),
+            )];
+        });
+
+        assert_eq!(
+            Schema::validate(&schema, &contract).unwrap_err(),
+            DeferredDataError::Contract(DataContractError::PropertyOutOfRange(
+                "contract:latitude".to_string()
+            ))
+        );
+
+        let schema = vec![
+            PropertySchema::optional("contract:name", GenericValueKind::TextContent)
+                .with_range(1.0, 4.0),
+        ];
+        let contract = with_mock_contract(1, 1, |contract| {
+            contract.properties = vec![(
+                "contract:name".to_string(),
+                GenericValue::TextContent("villa".to_string()),
+            )];
+        });
+        assert_eq!(
+            Schema::validate(&schema, &contract).unwrap_err(),
+            DeferredDataError::Contract(DataContractError::PropertyOutOfRange(
+                "contract:name".to_string()
+            ))
+        );
+    }
+
+    #[test]
+    fn test_should_validate_coordinates() {
+        let schema = Schema::default_schema();
+        let coordinates = |latitude: GenericValue, longitude: GenericValue| {
+            with_mock_contract(1, 1, |contract| {
+                contract.properties = vec![
+                    ("contract:latitude".to_string(), latitude),
+                    ("contract:longitude".to_string(), longitude),
+                ];
+            })
+        };
+
+        let contract = coordinates(
+            GenericValue::FloatContent(41.9),
+            GenericValue::TextContent(" 12.49 ".to_string()),
+        );
+        assert!(Schema::validate(&schema, &contract).is_ok());
+
+        let contract = coordinates(
+            GenericValue::TextContent("north".to_string()),
+            GenericValue::FloatContent(12.49),
+        );
+        assert_eq!(
+            Schema::validate(&schema, &contract).unwrap_err(),
+            DeferredDataError::Contract(DataContractError::PropertyTypeMismatch(
+                "contract:latitude".to_string()
+            ))
+        );
+
+        let contract = coordinates(
+            GenericValue::FloatContent(41.9),
+            GenericValue::TextContent("190".to_string()),
+        );
+        assert_eq!(
+            Schema::validate(&schema, &contract).unwrap_err(),
+            DeferredDataError::Contract(DataContractError::PropertyOutOfRange(
+                "contract:longitude".to_string()
+            ))
+        );
+
+        let contract = coordinates(
+            GenericValue::Nat64Content(41),
+            GenericValue::FloatContent(12.49),
+        );
+        assert_eq!(
+            Schema::validate(&schema, &contract).unwrap_err(),
+            DeferredDataError::Contract(DataContractError::PropertyTypeMismatch(
+                "contract:latitude".to_string()
+            ))
+        );
+    }
+
+    #[test]
+    fn test_should_require_property_by_contract_type() {
+        let schema = vec![PropertySchema {
+            required_for: vec![ContractType::Sell],
+            ..PropertySchema::optional("contract:address", GenericValueKind::TextContent)
+        }];
+
+        let contract = with_mock_contract(1, 1, |contract| {
+            contract.r#type = ContractType::Sell;
+            contract.properties = vec![];
+        });
+        assert_eq!(
+            Schema::validate(&schema, &contract).unwrap_err(),
+            DeferredDataError::Contract(DataContractError::MissingRequiredProperty(
+                "contract:address".to_string()
+            ))
+        );
+
+        let contract = with_mock_contract(1, 1, |contract| {
+            contract.r#type = ContractType::Financing;
+            contract.properties = vec![];
+        });
+        assert!(Schema::validate(&schema, &contract).is_ok());
+    }
+
+    #[test]
+    fn test_should_validate_only_changed_properties() {
+        // stored before the schema was configured
+        let contract = with_mock_contract(1, 1, |contract| {
+            contract.properties = vec![
+                (
+                    "contract:rooms".to_string(),
+                    GenericValue::TextContent("three".to_string()),
+                ),
+                (
+                    "contract:name".to_string(),
+                    GenericValue::TextContent("villa".to_string()),
+                ),
+            ];
+        });
+
+        assert!(Schema::validate_changes(&contract, &["contract:name".to_string()]).is_ok());
+        assert_eq!(
+            Schema::validate_changes(&contract, &["contract:rooms".to_string()]).unwrap_err(),
+            DeferredDataError::Contract(DataContractError::PropertyTypeMismatch(
+                "contract:rooms".to_string()
+            ))
+        );
+    }
+}
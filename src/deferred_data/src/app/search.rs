@@ -0,0 +1,146 @@
+//! Text normalization and tokenization of the searchable contract properties
+
+use did::deferred::{Contract, GenericValue};
+
+/// A property of the contracts indexed for the full-text search
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord)]
+pub enum SearchField {
+    Name,
+    Description,
+    Address,
+    City,
+    Zone,
+}
+
+impl SearchField {
+    pub const ALL: [Self; 5] = [
+        Self::Name,
+        Self::Description,
+        Self::Address,
+        Self::City,
+        Self::Zone,
+    ];
+
+    /// The fields suggested by the autocomplete
+    pub const PLACES: [Self; 2] = [Self::City, Self::Zone];
+
+    /// Key of the contract property
+    pub fn property(&self) -> &'static str {
+        match self {
+            Self::Name => "contract:name",
+            Self::Description => "contract:description",
+            Self::Address => "contract:address",
+            Self::City => "contract:city",
+            Self::Zone => "contract:zone",
+        }
+    }
+
+    /// Relevance of a match in the field
+    pub fn weight(&self) -> f64 {
+        match self {
+            Self::Name => 3.0,
+            Self::City | Self::Zone => 2.0,
+            Self::Description | Self::Address => 1.0,
+        }
+    }
+
+    pub fn to_byte(self) -> u8 {
+        self as u8
+    }
+
+    pub fn from_byte(byte: u8) -> Option<Self> {
+        Self::ALL.get(byte as usize).copied()
+    }
+
+    /// Get the text value of the field in the contract
+    pub fn value(self, contract: &Contract) -> Option<&str> {
+        contract
+            .properties
+            .iter()
+            .find_map(|(key, value)| match value {
+                GenericValue::TextContent(text) if key == self.property() => Some(text.as_str()),
+                _ => None,
+            })
+    }
+}
+
+/// Split a text into words, lowercased and without accents
+pub fn tokenize(text: &str) -> Vec<String> {
+    normalize(text)
+        .split(|c: char| !c.is_alphanumeric())
+        .filter(|word| !word.is_empty())
+        .map(str::to_string)
+        .collect()
+}
+
+/// Lowercase the text and remove the accents of the latin letters
+fn normalize(text: &str) -> String {
+    text.chars()
+        .flat_map(char::to_lowercase)
+        .map(fold)
+        .collect()
+}
+
+/// Remove the accent of a lowercase latin letter
+fn fold(c: char) -> char {
+    match c {
+        'à' | 'á' | 'â' | 'ã' | 'ä' | 'å' | 'ā' | 'ă' | 'ą' => 'a',
+        'ç' | 'ć' | 'č' => 'c',
+        'ď' | 'đ' => 'd',
+        'è' | 'é' | 'ê' | 'ë' | 'ē' | 'ė' | 'ę' | 'ě' => 'e',
+        'ì' | 'í' | 'î' | 'ï' | 'ī' | 'į' => 'i',
+        'ł' => 'l',
+        'ñ' | 'ń' | 'ň' => 'n',
+        'ò' | 'ó' | 'ô' | 'õ' | 'ö' | 'ø' | 'ō' | 'ő' => 'o',
+        'ŕ' | 'ř' => 'r',
+        'ś' | 'š' | 'ş' | 'ß' => 's',
+        'ť' | 'ţ' => 't',
+        'ù' | 'ú' | 'û' | 'ü' | 'ū' | 'ů' | 'ű' => 'u',
+        'ý' | 'ÿ' => 'y',
+        'ź' | 'ż' | 'ž' => 'z',
+        c => c,
+    }
+}
+
+#[cfg(test)]
+mod test {
+
+    use pretty_assertions::assert_eq;
+
+    use super::*;
+    use crate::app::test_utils::with_mock_contract;
+
+    #[test]
+    fn test_should_tokenize_text() {
+        assert_eq!(
+            tokenize("Attico in Via Città di Castello, 12 - ÉLITE"),
+            vec!["attico", "in", "via", "citta", "di", "castello", "12", "elite"]
+        );
+        assert!(tokenize(" -- ").is_empty());
+    }
+
+    #[test]
+    fn test_should_get_field_value() {
+        let contract = with_mock_contract(1, 1, |contract| {
+            contract.properties = vec![
+                (
+                    "contract:city".to_string(),
+                    GenericValue::TextContent("Milano".to_string()),
+                ),
+                ("contract:name".to_string(), GenericValue::Nat64Content(1)),
+            ];
+        });
+
+        assert_eq!(SearchField::City.value(&contract), Some("Milano"));
+        assert_eq!(SearchField::Name.value(&contract), None);
+        assert_eq!(SearchField::Zone.value(&contract), None);
+    }
+
+    #[test]
+    fn test_should_convert_field_from_byte() {
+        for field in SearchField::ALL {
+            assert_eq!(SearchField::from_byte(field.to_byte()), Some(field));
+        }
+        assert_eq!(SearchField::from_byte(5), None);
+    }
+}
@@ -0,0 +1,398 @@
+//! Sign-In With Ethereum (EIP-4361) messages used by sellers and buyers to access restricted data
+//! and to link their address to a principal.
+//!
+//! The rules on nonce and validity period are shared with the EIP-712 typed data signatures.
+//! Nonces must be issued by the canister with `create_nonce`.
+//!
+//! A message looks like this:
+//!
+//! ```txt
+//! ekoke.io wants you to sign in with your Ethereum account:
+//! 0x8fd379246834eac74B8419FfdA202CF8051F7A03
+//!
+//! Grant read access to contract #1
+//!
+//! URI: https://ekoke.io
+//! Version: 1
+//! Chain ID: 1
+//! Nonce: 32891756
+//! Issued At: 2024-12-01T16:25:24Z
+//! Expiration Time: 2024-12-02T16:25:24Z
+//! Resources:
+//! - ic://2m6dw-uaaaa-aaaal-arumq-cai/contract/1
+//! ```
+
+use std::str::FromStr;
+
+use candid::Principal;
+use did::deferred::{DeferredDataError, DeferredDataResult};
+use did::ID;
+use ethers_core::types::H160;
+use time::format_description::well_known::Rfc3339;
+use time::OffsetDateTime;
+use url::Url;
+
+const PREAMBLE: &str = " wants you to sign in with your Ethereum account:";
+const SUPPORTED_VERSION: &str = "1";
+const MIN_NONCE_LENGTH: usize = 8;
+
+/// Maximum time a message can be valid for, from `Issued At` to `Expiration Time` (1 day)
+const MAX_MESSAGE_VALIDITY: u64 = 24 * 60 * 60 * 1_000_000_000;
+/// Tolerated clock drift between the signer and the canister (5 minutes)
+const MAX_CLOCK_DRIFT: u64 = 5 * 60 * 1_000_000_000;
+
+/// A parsed EIP-4361 message
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct SiweMessage {
+    /// The domain of the dapp requesting the signature
+    pub domain: String,
+    /// The address which signed the message
+    pub address: H160,
+    pub uri: String,
+    pub version: String,
+    pub chain_id: u64,
+    pub nonce: String,
+    /// Nanoseconds since UNIX epoch
+    pub issued_at: u64,
+    /// Nanoseconds since UNIX epoch
+    pub expiration_time: Option<u64>,
+    /// Nanoseconds since UNIX epoch
+    pub not_before: Option<u64>,
+    pub resources: Vec<String>,
+}
+
+impl SiweMessage {
+    /// The resource which binds a message to a contract of this canister
+    pub fn contract_resource(canister_id: Principal, contract_id: &ID) -> String {
+        format!("ic://{canister_id}/contract/{}", contract_id.0)
+    }
+
+    /// The resource which binds a message to the link between a principal and the signer address
+    pub fn principal_resource(canister_id: Principal, principal: Principal) -> String {
+        format!("ic://{canister_id}/principal/{principal}")
+    }
+
+    /// Verify that the message is issued by `domain` for `chain_id`, is bound to `resource`
+    /// and is valid at time `now`.
+    ///
+    /// The signature and whether the nonce has been issued are verified separately.
+    pub fn verify(
+        &self,
+        resource: &str,
+        domain: &str,
+        chain_id: u64,
+        now: u64,
+    ) -> DeferredDataResult<()> {
+        if self.version != SUPPORTED_VERSION {
+            return Err(invalid("unsupported version"));
+        }
+        if self.chain_id != chain_id {
+            return Err(invalid("message is not issued for this chain"));
+        }
+        if self.domain != domain || uri_authority(&self.uri).as_deref() != Some(domain) {
+            return Err(invalid("message is not issued for this domain"));
+        }
+        verify_nonce(&self.nonce)?;
+        let expiration_time = self
+            .expiration_time
+            .ok_or_else(|| invalid("missing expiration time"))?;
+        verify_validity(self.issued_at, expiration_time, now)?;
+        if self.not_before.is_some_and(|not_before| not_before > now) {
+            return Err(invalid("message is not valid yet"));
+        }
+
+        if !self.resources.iter().any(|r| r == resource) {
+            return Err(invalid("message is not bound to this resource"));
+        }
+
+        Ok(())
+    }
+}
+
+impl FromStr for SiweMessage {
+    type Err = DeferredDataError;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        let mut lines = s.lines();
+
+        let domain = lines
+            .next()
+            .and_then(|line| line.strip_suffix(PREAMBLE))
+            .filter(|domain| !domain.is_empty())
+            .ok_or_else(|| invalid("missing domain"))?;
+        let address = lines
+            .next()
+            .and_then(|line| H160::from_str(line).ok())
+            .ok_or_else(|| invalid("invalid address"))?;
+
+        let mut statement = None;
+        let mut uri = None;
+        let mut version = None;
+        let mut chain_id = None;
+        let mut nonce = None;
+        let mut issued_at = None;
+        let mut expiration_time = None;
+        let mut not_before = None;
+        let mut resources: Option<Vec<String>> = None;
+
+        for line in lines {
+            // resources are the last field
+            if let Some(resources) = resources.as_mut() {
+                let resource = line
+                    .strip_prefix("- ")
+                    .ok_or_else(|| invalid("invalid resource"))?;
+                resources.push(resource.to_string());
+                continue;
+            }
+            if line.is_empty() {
+                continue;
+            }
+            if line == "Resources:" {
+                resources = Some(vec![]);
+                continue;
+            }
+
+            match line.split_once(": ") {
+                Some(("URI", value)) => uri = Some(value),
+                Some(("Version", value)) => version = Some(value.to_string()),
+                Some(("Chain ID", value)) => {
+                    chain_id = Some(
+                        value
+                            .parse::<u64>()
+                            .map_err(|_| invalid("invalid chain ID"))?,
+                    )
+                }
+                Some(("Nonce", value)) => nonce = Some(value.to_string()),
+                Some(("Issued At", value)) => issued_at = Some(parse_time(value)?),
+                Some(("Expiration Time", value)) => expiration_time = Some(parse_time(value)?),
+                Some(("Not Before", value)) => not_before = Some(parse_time(value)?),
+                Some(("Request ID", _)) => {}
+                _ if statement.is_none() && uri.is_none() => statement = Some(line),
+                _ => return Err(invalid(&format!("unexpected line: {line}"))),
+            }
+        }
+
+        let (Some(uri), Some(chain_id)) = (uri, chain_id) else {
+            return Err(invalid("missing URI or chain ID"));
+        };
+
+        Ok(Self {
+            domain: domain.to_string(),
+            address,
+            uri: uri.to_string(),
+            version: version.ok_or_else(|| invalid("missing version"))?,
+            chain_id,
+            nonce: nonce.ok_or_else(|| invalid("missing nonce"))?,
+            issued_at: issued_at.ok_or_else(|| invalid("missing issued at"))?,
+            expiration_time,
+            not_before,
+            resources: resources.unwrap_or_default(),
+        })
+    }
+}
+
+/// Get the authority (host and port) of a URI, which must match the domain of the message
+fn uri_authority(uri: &str) -> Option<String> {
+    let uri = Url::parse(uri).ok()?;
+    let host = uri.host_str()?;
+
+    Some(match uri.port() {
+        Some(port) => format!("{host}:{port}"),
+        None => host.to_string(),
+    })
+}
+
+/// Verify that the nonce has at least 8 alphanumeric characters
+pub fn verify_nonce(nonce: &str) -> DeferredDataResult<()> {
+    if nonce.len() < MIN_NONCE_LENGTH || !nonce.chars().all(|c| c.is_ascii_alphanumeric()) {
+        return Err(invalid("nonce must be at least 8 alphanumeric characters"));
+    }
+
+    Ok(())
+}
+
+/// Verify that a message issued at `issued_at` and expiring at `expiration_time` is valid at `now`.
+///
+/// Messages can't be valid for more than 1 day. All the times are in nanoseconds since UNIX epoch.
+pub fn verify_validity(issued_at: u64, expiration_time: u64, now: u64) -> DeferredDataResult<()> {
+    if issued_at > now.saturating_add(MAX_CLOCK_DRIFT) {
+        return Err(invalid("message is issued in the future"));
+    }
+    if expiration_time.saturating_sub(issued_at) > MAX_MESSAGE_VALIDITY {
+        return Err(invalid("message is valid for more than 1 day"));
+    }
+    if expiration_time <= now {
+        return Err(DeferredDataError::SignedMessageExpired);
+    }
+
+    Ok(())
+}
+
+/// Parse a RFC 3339 timestamp into nanoseconds since UNIX epoch
+fn parse_time(value: &str) -> DeferredDataResult<u64> {
+    OffsetDateTime::parse(value, &Rfc3339)
+        .ok()
+        .and_then(|time| u64::try_from(time.unix_timestamp_nanos()).ok())
+        .ok_or_else(|| invalid(&format!("invalid timestamp: {value}")))
+}
+
+fn invalid(reason: &str) -> DeferredDataError {
+    DeferredDataError::InvalidSignedMessage(reason.to_string())
+}
+
+#[cfg(test)]
+mod test {
+
+    use pretty_assertions::assert_eq;
+
+    use super::*;
+
+    const NOW: u64 = 1_733_070_324_000_000_000; // 2024-12-01T16:25:24Z
+    const DOMAIN: &str = "ekoke.io";
+
+    #[test]
+    fn test_should_parse_message() {
+        let message = SiweMessage::from_str(&message(
+            "2024-12-01T16:25:24Z",
+            "2024-12-02T16:25:24Z",
+            "ic://2m6dw-uaaaa-aaaal-arumq-cai/contract/1",
+        ))
+        .unwrap();
+
+        assert_eq!(
+            message,
+            SiweMessage {
+                domain: "ekoke.io".to_string(),
+                address: H160::from_str("0x8fd379246834eac74B8419FfdA202CF8051F7A03").unwrap(),
+                uri: "https://ekoke.io".to_string(),
+                version: "1".to_string(),
+                chain_id: 1,
+                nonce: "32891756".to_string(),
+                issued_at: NOW,
+                expiration_time: Some(NOW + MAX_MESSAGE_VALIDITY),
+                not_before: None,
+                resources: vec!["ic://2m6dw-uaaaa-aaaal-arumq-cai/contract/1".to_string()],
+            }
+        );
+    }
+
+    #[test]
+    fn test_should_not_parse_plain_message() {
+        assert!(SiweMessage::from_str("Hello, Ethereum!").is_err());
+    }
+
+    #[test]
+    fn test_should_verify_message() {
+        let message = SiweMessage::from_str(&message(
+            "2024-12-01T16:25:24Z",
+            "2024-12-01T17:25:24Z",
+            "ic://2m6dw-uaaaa-aaaal-arumq-cai/contract/1",
+        ))
+        .unwrap();
+
+        assert!(message.verify(&resource(1), DOMAIN, 1, NOW).is_ok());
+        // bound to another contract
+        assert_eq!(
+            message.verify(&resource(2), DOMAIN, 1, NOW).unwrap_err(),
+            invalid("message is not bound to this resource")
+        );
+        // bound to another canister
+        assert_eq!(
+            message
+                .verify(
+                    &SiweMessage::contract_resource(Principal::anonymous(), &1u64.into()),
+                    DOMAIN,
+                    1,
+                    NOW
+                )
+                .unwrap_err(),
+            invalid("message is not bound to this resource")
+        );
+        // stale
+        assert_eq!(
+            message
+                .verify(&resource(1), DOMAIN, 1, NOW + MAX_MESSAGE_VALIDITY)
+                .unwrap_err(),
+            DeferredDataError::SignedMessageExpired
+        );
+        // issued for another chain
+        assert_eq!(
+            message.verify(&resource(1), DOMAIN, 5, NOW).unwrap_err(),
+            invalid("message is not issued for this chain")
+        );
+        // issued for another dapp
+        assert_eq!(
+            message
+                .verify(&resource(1), "evil.example", 1, NOW)
+                .unwrap_err(),
+            invalid("message is not issued for this domain")
+        );
+    }
+
+    #[test]
+    fn test_should_not_verify_message_with_uri_of_another_domain() {
+        let message = SiweMessage::from_str(
+            &message(
+                "2024-12-01T16:25:24Z",
+                "2024-12-01T17:25:24Z",
+                "ic://2m6dw-uaaaa-aaaal-arumq-cai/contract/1",
+            )
+            .replace("URI: https://ekoke.io", "URI: https://evil.example"),
+        )
+        .unwrap();
+
+        assert_eq!(
+            message.verify(&resource(1), DOMAIN, 1, NOW).unwrap_err(),
+            invalid("message is not issued for this domain")
+        );
+    }
+
+    #[test]
+    fn test_should_not_verify_message_valid_for_too_long() {
+        let message = SiweMessage::from_str(&message(
+            "2024-12-01T16:25:24Z",
+            "2025-12-01T16:25:24Z",
+            "ic://2m6dw-uaaaa-aaaal-arumq-cai/contract/1",
+        ))
+        .unwrap();
+
+        assert_eq!(
+            message.verify(&resource(1), DOMAIN, 1, NOW).unwrap_err(),
+            invalid("message is valid for more than 1 day")
+        );
+    }
+
+    #[test]
+    fn test_should_format_principal_resource() {
+        assert_eq!(
+            SiweMessage::principal_resource(canister(), Principal::anonymous()),
+            "ic://2m6dw-uaaaa-aaaal-arumq-cai/principal/2vxsx-fae"
+        );
+    }
+
+    fn canister() -> Principal {
+        Principal::from_text("2m6dw-uaaaa-aaaal-arumq-cai").unwrap()
+    }
+
+    fn resource(contract_id: u64) -> String {
+        SiweMessage::contract_resource(canister(), &contract_id.into())
+    }
+
+    fn message(issued_at: &str, expiration_time: &str, resource: &str) -> String {
+        format!(
+            r#"ekoke.io wants you to sign in with your Ethereum account:
+0x8fd379246834eac74B8419FfdA202CF8051F7A03
+
+Grant read access to contract #1
+
+URI: https://ekoke.io
+Version: 1
+Chain ID: 1
+Nonce: 32891756
+Issued At: {issued_at}
+Expiration Time: {expiration_time}
+Resources:
+- {resource}"#
+        )
+    }
+}
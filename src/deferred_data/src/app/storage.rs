@@ -1,19 +1,66 @@
 use std::cell::RefCell;
 
-use did::deferred::{Contract, DataContractError, DeferredDataError, DeferredDataResult};
-use did::{StorableNat, ID};
+use did::deferred::{
+    AuditLogEntry, Contract, DataContractError, DeferredDataError, DeferredDataResult,
+};
+use did::{StorableNat, StorablePrincipal, H160, ID};
 use ic_stable_structures::memory_manager::VirtualMemory;
 use ic_stable_structures::{BTreeMap, DefaultMemoryImpl, StableCell};
 
+use crate::app::certification::HttpCertification;
 use crate::app::memory::{
-    CONTRACTS_MEMORY_ID, DOCUMENTS_MEMORY_ID, MEMORY_MANAGER, NEXT_DOCUMENT_ID_MEMORY_ID,
+    ACCESS_GROUPS_MEMORY_ID, ADDRESS_LINKS_MEMORY_ID, AUDIT_LOG_MEMORY_ID, CONTRACTS_MEMORY_ID,
+    CONTRACT_EVENTS_MEMORY_ID, CONTRACT_IMAGES_MEMORY_ID, CONTRACT_INDEX_MEMORY_ID,
+    DOCUMENTS_MEMORY_ID, DOCUMENT_BLOBS_MEMORY_ID, DOCUMENT_CHUNKS_MEMORY_ID,
+    DOCUMENT_DIGESTS_MEMORY_ID, GEO_INDEX_MEMORY_ID, IMAGES_MEMORY_ID, IMAGE_THUMBNAILS_MEMORY_ID,
+    MEMORY_MANAGER, NEXT_AUDIT_ID_MEMORY_ID, NEXT_DOCUMENT_ID_MEMORY_ID, NEXT_IMAGE_ID_MEMORY_ID,
+    NEXT_UPLOAD_ID_MEMORY_ID, NONCES_MEMORY_ID, NONCE_EXPIRATIONS_MEMORY_ID,
+    SEARCH_INDEX_MEMORY_ID, SESSIONS_MEMORY_ID, SESSION_EXPIRATIONS_MEMORY_ID, UPLOADS_MEMORY_ID,
+    UPLOAD_CHUNKS_MEMORY_ID,
 };
+use crate::utils::{caller, time};
 
+mod access_groups;
+mod address_links;
+mod audit;
+mod contract_index;
 mod contracts;
 mod documents;
+mod events;
+mod geo_index;
+mod images;
+mod listing;
+mod nonces;
+mod search_index;
+mod sessions;
+mod uploads;
 
+pub use access_groups::AccessGroupStorage;
+use access_groups::AccessGroups;
+pub use address_links::AddressLinkStorage;
+use audit::AuditKey;
+pub use audit::AuditLogStorage;
+use contract_index::ContractIndexKey;
+pub use contract_index::{ContractIndex, ContractIndexStorage};
 pub use contracts::ContractStorage;
-use documents::DocumentStorage;
+use documents::{DocumentBlob, DocumentChunkKey, DocumentStorage};
+pub use events::{ContractEvent, ContractEventKind};
+use events::{ContractEventStorage, EventKey};
+pub use geo_index::GeoIndexStorage;
+use geo_index::{GeoEntry, GeoKey};
+use images::ContractImages;
+pub use images::ImageStorage;
+pub use listing::ContractCursor;
+use nonces::NonceExpirationKey;
+pub use nonces::NonceStorage;
+pub use search_index::SearchIndexStorage;
+use search_index::{SearchKey, SearchPosting};
+use sessions::SessionExpirationKey;
+pub use sessions::{Session, SessionStorage};
+use uploads::{UploadChunkKey, UploadSession, UploadStorage};
+
+/// Size of the contract IDs in the keys of the audit log and of the indexes
+const CONTRACT_ID_KEY_SIZE: usize = 24;
 
 thread_local! {
 
@@ -21,7 +68,23 @@ thread_local! {
     static CONTRACTS: RefCell<BTreeMap<StorableNat, Contract, VirtualMemory<DefaultMemoryImpl>>> =
         RefCell::new(BTreeMap::new(MEMORY_MANAGER.with(|mm| mm.get(CONTRACTS_MEMORY_ID))));
 
-    /// Documents storage storage (assoc between ID and document data)
+    /// Time index of the contract creations and closures (assoc between time, contract ID and kind of the event, and the contract ID)
+    static CONTRACT_EVENTS: RefCell<BTreeMap<EventKey, StorableNat, VirtualMemory<DefaultMemoryImpl>>> =
+        RefCell::new(BTreeMap::new(MEMORY_MANAGER.with(|mm| mm.get(CONTRACT_EVENTS_MEMORY_ID))));
+
+    /// Secondary indexes of the open contracts (assoc between indexed value and contract ID, and the contract ID)
+    static CONTRACT_INDEX: RefCell<BTreeMap<ContractIndexKey, StorableNat, VirtualMemory<DefaultMemoryImpl>>> =
+        RefCell::new(BTreeMap::new(MEMORY_MANAGER.with(|mm| mm.get(CONTRACT_INDEX_MEMORY_ID))));
+
+    /// Grid index of the positions of the open contracts (assoc between grid cell and contract ID, and the position)
+    static GEO_INDEX: RefCell<BTreeMap<GeoKey, GeoEntry, VirtualMemory<DefaultMemoryImpl>>> =
+        RefCell::new(BTreeMap::new(MEMORY_MANAGER.with(|mm| mm.get(GEO_INDEX_MEMORY_ID))));
+
+    /// Full-text index of the open contracts (assoc between word, field and contract ID, and the word occurrences)
+    static SEARCH_INDEX: RefCell<BTreeMap<SearchKey, SearchPosting, VirtualMemory<DefaultMemoryImpl>>> =
+        RefCell::new(BTreeMap::new(MEMORY_MANAGER.with(|mm| mm.get(SEARCH_INDEX_MEMORY_ID))));
+
+    /// Documents storage storage (assoc between ID and document data); only holds the documents stored before chunking
     static DOCUMENTS: RefCell<BTreeMap<u64, Vec<u8>, VirtualMemory<DefaultMemoryImpl>>> =
         RefCell::new(BTreeMap::new(MEMORY_MANAGER.with(|mm| mm.get(DOCUMENTS_MEMORY_ID))));
 
@@ -30,6 +93,96 @@ thread_local! {
         RefCell::new(StableCell::new(MEMORY_MANAGER.with(|mm| mm.get(NEXT_DOCUMENT_ID_MEMORY_ID)), 0u64).unwrap()
     );
 
+    /// Documents digests (assoc between document ID and the SHA-256 digest of its data)
+    static DOCUMENT_DIGESTS: RefCell<BTreeMap<u64, [u8; 32], VirtualMemory<DefaultMemoryImpl>>> =
+        RefCell::new(BTreeMap::new(MEMORY_MANAGER.with(|mm| mm.get(DOCUMENT_DIGESTS_MEMORY_ID))));
+
+    /// Deduplicated documents data (assoc between SHA-256 digest and the stored data)
+    static DOCUMENT_BLOBS: RefCell<BTreeMap<[u8; 32], DocumentBlob, VirtualMemory<DefaultMemoryImpl>>> =
+        RefCell::new(BTreeMap::new(MEMORY_MANAGER.with(|mm| mm.get(DOCUMENT_BLOBS_MEMORY_ID))));
+
+    /// Chunks of the documents data (assoc between (blob ID, chunk index) and chunk data)
+    static DOCUMENT_CHUNKS: RefCell<BTreeMap<DocumentChunkKey, Vec<u8>, VirtualMemory<DefaultMemoryImpl>>> =
+        RefCell::new(BTreeMap::new(MEMORY_MANAGER.with(|mm| mm.get(DOCUMENT_CHUNKS_MEMORY_ID))));
+
+    /// Galleries of the contracts (assoc between contract ID and its images)
+    static CONTRACT_IMAGES: RefCell<BTreeMap<StorableNat, ContractImages, VirtualMemory<DefaultMemoryImpl>>> =
+        RefCell::new(BTreeMap::new(MEMORY_MANAGER.with(|mm| mm.get(CONTRACT_IMAGES_MEMORY_ID))));
+
+    /// Gallery images storage (assoc between image ID and the original image data)
+    static IMAGES: RefCell<BTreeMap<u64, Vec<u8>, VirtualMemory<DefaultMemoryImpl>>> =
+        RefCell::new(BTreeMap::new(MEMORY_MANAGER.with(|mm| mm.get(IMAGES_MEMORY_ID))));
+
+    /// Gallery thumbnails storage (assoc between image ID and the thumbnail data)
+    static IMAGE_THUMBNAILS: RefCell<BTreeMap<u64, Vec<u8>, VirtualMemory<DefaultMemoryImpl>>> =
+        RefCell::new(BTreeMap::new(MEMORY_MANAGER.with(|mm| mm.get(IMAGE_THUMBNAILS_MEMORY_ID))));
+
+    /// Next image ID
+    static NEXT_IMAGE_ID: RefCell<StableCell<u64, VirtualMemory<DefaultMemoryImpl>>> =
+        RefCell::new(StableCell::new(MEMORY_MANAGER.with(|mm| mm.get(NEXT_IMAGE_ID_MEMORY_ID)), 0u64).unwrap()
+    );
+
+    /// Audit log of the changes made to the contracts
+    static AUDIT_LOG: RefCell<BTreeMap<AuditKey, AuditLogEntry, VirtualMemory<DefaultMemoryImpl>>> =
+        RefCell::new(BTreeMap::new(MEMORY_MANAGER.with(|mm| mm.get(AUDIT_LOG_MEMORY_ID))));
+
+    /// Next audit log entry ID
+    static NEXT_AUDIT_ID: RefCell<StableCell<u64, VirtualMemory<DefaultMemoryImpl>>> =
+        RefCell::new(StableCell::new(MEMORY_MANAGER.with(|mm| mm.get(NEXT_AUDIT_ID_MEMORY_ID)), 0u64).unwrap()
+    );
+
+    /// Ethereum addresses linked to principals (assoc between principal and address)
+    static ADDRESS_LINKS: RefCell<BTreeMap<StorablePrincipal, H160, VirtualMemory<DefaultMemoryImpl>>> =
+        RefCell::new(BTreeMap::new(MEMORY_MANAGER.with(|mm| mm.get(ADDRESS_LINKS_MEMORY_ID))));
+
+    /// Contract sessions (assoc between the SHA-256 digest of the token and the session)
+    static SESSIONS: RefCell<BTreeMap<[u8; 32], Session, VirtualMemory<DefaultMemoryImpl>>> =
+        RefCell::new(BTreeMap::new(MEMORY_MANAGER.with(|mm| mm.get(SESSIONS_MEMORY_ID))));
+
+    /// Expiration index of the contract sessions (assoc between the expiration time and the digest of the token, and nothing)
+    static SESSION_EXPIRATIONS: RefCell<BTreeMap<SessionExpirationKey, (), VirtualMemory<DefaultMemoryImpl>>> =
+        RefCell::new(BTreeMap::new(MEMORY_MANAGER.with(|mm| mm.get(SESSION_EXPIRATIONS_MEMORY_ID))));
+
+    /// Nonces issued for the signed messages (assoc between the SHA-256 digest of the nonce and its expiration time)
+    static NONCES: RefCell<BTreeMap<[u8; 32], u64, VirtualMemory<DefaultMemoryImpl>>> =
+        RefCell::new(BTreeMap::new(MEMORY_MANAGER.with(|mm| mm.get(NONCES_MEMORY_ID))));
+
+    /// Expiration index of the nonces (assoc between the expiration time and the digest of the nonce, and nothing)
+    static NONCE_EXPIRATIONS: RefCell<BTreeMap<NonceExpirationKey, (), VirtualMemory<DefaultMemoryImpl>>> =
+        RefCell::new(BTreeMap::new(MEMORY_MANAGER.with(|mm| mm.get(NONCE_EXPIRATIONS_MEMORY_ID))));
+
+    /// Access groups defined for the contracts (assoc between contract ID and its groups)
+    static ACCESS_GROUPS: RefCell<BTreeMap<StorableNat, AccessGroups, VirtualMemory<DefaultMemoryImpl>>> =
+        RefCell::new(BTreeMap::new(MEMORY_MANAGER.with(|mm| mm.get(ACCESS_GROUPS_MEMORY_ID))));
+
+    /// Pending document uploads (assoc between upload ID and upload session)
+    static UPLOADS: RefCell<BTreeMap<u64, UploadSession, VirtualMemory<DefaultMemoryImpl>>> =
+        RefCell::new(BTreeMap::new(MEMORY_MANAGER.with(|mm| mm.get(UPLOADS_MEMORY_ID))));
+
+    /// Chunks of the pending document uploads (assoc between (upload ID, chunk index) and chunk data)
+    static UPLOAD_CHUNKS: RefCell<BTreeMap<UploadChunkKey, Vec<u8>, VirtualMemory<DefaultMemoryImpl>>> =
+        RefCell::new(BTreeMap::new(MEMORY_MANAGER.with(|mm| mm.get(UPLOAD_CHUNKS_MEMORY_ID))));
+
+    /// Next upload ID
+    static NEXT_UPLOAD_ID: RefCell<StableCell<u64, VirtualMemory<DefaultMemoryImpl>>> =
+        RefCell::new(StableCell::new(MEMORY_MANAGER.with(|mm| mm.get(NEXT_UPLOAD_ID_MEMORY_ID)), 0u64).unwrap()
+    );
+
+}
+
+/// Get the key bytes of a contract ID: the big-endian ID, left-padded so keys are sorted by contract ID.
+///
+/// Fails if the ID doesn't fit [`CONTRACT_ID_KEY_SIZE`] bytes.
+fn contract_id_key(id: &ID) -> DeferredDataResult<[u8; CONTRACT_ID_KEY_SIZE]> {
+    let bytes = id.0.to_bytes_be();
+    if bytes.len() > CONTRACT_ID_KEY_SIZE {
+        return Err(DeferredDataError::StorageError);
+    }
+
+    let mut key = [0; CONTRACT_ID_KEY_SIZE];
+    key[CONTRACT_ID_KEY_SIZE - bytes.len()..].copy_from_slice(&bytes);
+
+    Ok(key)
 }
 
 fn with_contract<T, F>(id: &ID, f: F) -> DeferredDataResult<T>
@@ -53,7 +206,15 @@ where
 {
     CONTRACTS.with_borrow_mut(|contracts| {
         if let Some(mut contract) = contracts.get(&StorableNat::from(id.clone())) {
+            let before = contract.clone();
             let res = f(&mut contract)?;
+            // trace the changes
+            AuditLogStorage::record_changes(&before, &contract, caller(), time())?;
+            ContractIndexStorage::update_contract(Some(&before), &contract)?;
+            GeoIndexStorage::update_contract(Some(&before), &contract)?;
+            SearchIndexStorage::update_contract(Some(&before), &contract)?;
+            ContractEventStorage::update_contract(Some(&before), &contract, time())?;
+            HttpCertification::update_contract(&contract);
             // update contract
             contracts.insert(StorableNat::from(id.clone()), contract.clone());
 
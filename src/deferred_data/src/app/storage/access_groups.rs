@@ -0,0 +1,104 @@
+use candid::{CandidType, Decode, Deserialize, Encode};
+use did::deferred::AccessGroup;
+use did::{StorableNat, ID};
+use ic_stable_structures::storable::Bound;
+use ic_stable_structures::Storable;
+
+use super::ACCESS_GROUPS;
+
+/// The access groups defined for a contract
+#[derive(Clone, Debug, Default, CandidType, Deserialize, PartialEq)]
+pub struct AccessGroups(pub Vec<AccessGroup>);
+
+impl Storable for AccessGroups {
+    const BOUND: Bound = Bound::Unbounded;
+
+    fn to_bytes(&self) -> std::borrow::Cow<[u8]> {
+        Encode!(&self).unwrap().into()
+    }
+
+    fn from_bytes(bytes: std::borrow::Cow<[u8]>) -> Self {
+        Decode!(&bytes, Self).unwrap()
+    }
+}
+
+pub struct AccessGroupStorage;
+
+impl AccessGroupStorage {
+    /// Get the access groups of a contract
+    pub fn get_access_groups(contract_id: &ID) -> Vec<AccessGroup> {
+        ACCESS_GROUPS
+            .with_borrow(|groups| groups.get(&StorableNat::from(contract_id.clone())))
+            .unwrap_or_default()
+            .0
+    }
+
+    /// Insert or replace an access group of a contract.
+    ///
+    /// A group without members is removed.
+    pub fn set_access_group(contract_id: &ID, group: AccessGroup) {
+        let key = StorableNat::from(contract_id.clone());
+
+        ACCESS_GROUPS.with_borrow_mut(|groups| {
+            let mut contract_groups = groups.get(&key).unwrap_or_default();
+            contract_groups
+                .0
+                .retain(|contract_group| contract_group.name != group.name);
+            if !group.members.is_empty() {
+                contract_groups.0.push(group);
+            }
+
+            if contract_groups.0.is_empty() {
+                groups.remove(&key);
+            } else {
+                groups.insert(key, contract_groups);
+            }
+        });
+    }
+}
+
+#[cfg(test)]
+mod test {
+
+    use did::deferred::AccessGroupMember;
+    use pretty_assertions::assert_eq;
+
+    use super::*;
+    use crate::app::test_utils::{alice, signer_address};
+
+    #[test]
+    fn test_should_set_access_group() {
+        let contract_id = ID::from(1u64);
+        let bank = AccessGroup {
+            name: "bank".to_string(),
+            members: vec![AccessGroupMember::Address(signer_address())],
+        };
+        AccessGroupStorage::set_access_group(&contract_id, bank.clone());
+        assert_eq!(
+            AccessGroupStorage::get_access_groups(&contract_id),
+            vec![bank.clone()]
+        );
+        assert!(AccessGroupStorage::get_access_groups(&2u64.into()).is_empty());
+
+        // replace
+        let bank = AccessGroup {
+            members: vec![AccessGroupMember::Principal(alice())],
+            ..bank
+        };
+        AccessGroupStorage::set_access_group(&contract_id, bank.clone());
+        assert_eq!(
+            AccessGroupStorage::get_access_groups(&contract_id),
+            vec![bank.clone()]
+        );
+
+        // remove
+        AccessGroupStorage::set_access_group(
+            &contract_id,
+            AccessGroup {
+                members: vec![],
+                ..bank
+            },
+        );
+        assert!(AccessGroupStorage::get_access_groups(&contract_id).is_empty());
+    }
+}
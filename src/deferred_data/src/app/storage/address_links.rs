@@ -0,0 +1,57 @@
+use candid::Principal;
+use did::H160;
+
+use super::ADDRESS_LINKS;
+
+pub struct AddressLinkStorage;
+
+impl AddressLinkStorage {
+    /// Get the address linked to a principal
+    pub fn get_linked_address(principal: Principal) -> Option<H160> {
+        ADDRESS_LINKS.with_borrow(|links| links.get(&principal.into()))
+    }
+
+    /// Link an address to a principal, replacing the previous link
+    pub fn link_address(principal: Principal, address: H160) {
+        ADDRESS_LINKS.with_borrow_mut(|links| {
+            links.insert(principal.into(), address);
+        });
+    }
+
+    /// Remove the address linked to a principal.
+    ///
+    /// Returns the removed address.
+    pub fn unlink_address(principal: Principal) -> Option<H160> {
+        ADDRESS_LINKS.with_borrow_mut(|links| links.remove(&principal.into()))
+    }
+}
+
+#[cfg(test)]
+mod test {
+
+    use pretty_assertions::assert_eq;
+
+    use super::*;
+    use crate::app::test_utils::{alice, signer_address};
+
+    #[test]
+    fn test_should_link_address() {
+        assert_eq!(AddressLinkStorage::get_linked_address(alice()), None);
+
+        AddressLinkStorage::link_address(alice(), signer_address());
+        assert_eq!(
+            AddressLinkStorage::get_linked_address(alice()),
+            Some(signer_address())
+        );
+        assert_eq!(
+            AddressLinkStorage::get_linked_address(Principal::anonymous()),
+            None
+        );
+
+        assert_eq!(
+            AddressLinkStorage::unlink_address(alice()),
+            Some(signer_address())
+        );
+        assert_eq!(AddressLinkStorage::get_linked_address(alice()), None);
+    }
+}
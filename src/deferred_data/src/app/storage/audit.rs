@@ -0,0 +1,318 @@
+use std::borrow::Cow;
+
+use candid::Principal;
+use did::deferred::{
+    AuditLogEntry, AuditOperation, AuditRestrictedValue, Contract, DeferredDataError,
+    DeferredDataResult, RestrictedProperty,
+};
+use did::ID;
+use ic_stable_structures::storable::Bound;
+use ic_stable_structures::Storable;
+
+use super::{contract_id_key, AUDIT_LOG, CONTRACT_ID_KEY_SIZE, NEXT_AUDIT_ID};
+
+/// Key of the audit log, sorted by contract ID and then by entry ID
+#[derive(Clone, Debug, PartialEq, Eq, PartialOrd, Ord)]
+pub struct AuditKey {
+    contract_id: [u8; CONTRACT_ID_KEY_SIZE],
+    entry_id: u64,
+}
+
+impl AuditKey {
+    fn new(contract_id: &ID, entry_id: u64) -> DeferredDataResult<Self> {
+        Ok(Self {
+            contract_id: contract_id_key(contract_id)?,
+            entry_id,
+        })
+    }
+
+    /// Get the range of the keys of the entries of a contract
+    fn contract_range(contract_id: &ID) -> DeferredDataResult<std::ops::RangeInclusive<Self>> {
+        Ok(Self::new(contract_id, 0)?..=Self::new(contract_id, u64::MAX)?)
+    }
+}
+
+impl Storable for AuditKey {
+    const BOUND: Bound = Bound::Bounded {
+        max_size: CONTRACT_ID_KEY_SIZE as u32 + 8,
+        is_fixed_size: true,
+    };
+
+    fn to_bytes(&self) -> Cow<[u8]> {
+        let mut bytes = Vec::with_capacity(CONTRACT_ID_KEY_SIZE + 8);
+        bytes.extend_from_slice(&self.contract_id);
+        bytes.extend_from_slice(&self.entry_id.to_be_bytes());
+
+        bytes.into()
+    }
+
+    fn from_bytes(bytes: Cow<[u8]>) -> Self {
+        let mut contract_id = [0; CONTRACT_ID_KEY_SIZE];
+        contract_id.copy_from_slice(&bytes[..CONTRACT_ID_KEY_SIZE]);
+        let mut entry_id = [0; 8];
+        entry_id.copy_from_slice(&bytes[CONTRACT_ID_KEY_SIZE..]);
+
+        Self {
+            contract_id,
+            entry_id: u64::from_be_bytes(entry_id),
+        }
+    }
+}
+
+/// Append-only audit log of the changes made to the contracts
+pub struct AuditLogStorage;
+
+impl AuditLogStorage {
+    /// Record the changes between two versions of a contract
+    pub fn record_changes(
+        before: &Contract,
+        after: &Contract,
+        caller: Principal,
+        timestamp: u64,
+    ) -> DeferredDataResult<()> {
+        for operation in Self::diff(before, after) {
+            let id = Self::next_audit_id()?;
+            let entry = AuditLogEntry {
+                id,
+                contract_id: after.id.clone(),
+                caller,
+                timestamp,
+                operation,
+            };
+
+            let key = AuditKey::new(&after.id, id)?;
+            AUDIT_LOG.with_borrow_mut(|log| {
+                log.insert(key, entry);
+            });
+        }
+
+        Ok(())
+    }
+
+    /// Get the audit log of a contract, from the oldest entry
+    pub fn get_audit_log(contract_id: &ID, offset: usize, count: usize) -> Vec<AuditLogEntry> {
+        let Ok(range) = AuditKey::contract_range(contract_id) else {
+            return vec![];
+        };
+
+        AUDIT_LOG.with_borrow(|log| {
+            log.range(range)
+                .skip(offset)
+                .take(count)
+                .map(|(_, entry)| entry)
+                .collect()
+        })
+    }
+
+    /// Get the time the contract has been closed, if it has been closed
+    pub fn get_closing_time(contract_id: &ID) -> Option<u64> {
+        let range = AuditKey::contract_range(contract_id).ok()?;
+
+        AUDIT_LOG.with_borrow(|log| {
+            log.range(range)
+                .map(|(_, entry)| entry)
+                .find(|entry| entry.operation == AuditOperation::CloseContract)
+                .map(|entry| entry.timestamp)
+        })
+    }
+
+    /// Get the operations which turn `before` into `after`
+    fn diff(before: &Contract, after: &Contract) -> Vec<AuditOperation> {
+        let mut operations = vec![];
+
+        // properties
+        for (key, new) in &after.properties {
+            let previous = Self::find(&before.properties, key);
+            if previous != Some(new) {
+                operations.push(AuditOperation::SetProperty {
+                    key: key.clone(),
+                    previous: previous.cloned(),
+                    new: Some(new.clone()),
+                });
+            }
+        }
+        for (key, previous) in &before.properties {
+            if Self::find(&after.properties, key).is_none() {
+                operations.push(AuditOperation::SetProperty {
+                    key: key.clone(),
+                    previous: Some(previous.clone()),
+                    new: None,
+                });
+            }
+        }
+
+        // restricted properties
+        for (key, new) in &after.restricted_properties {
+            let previous = Self::find(&before.restricted_properties, key);
+            if previous != Some(new) {
+                operations.push(AuditOperation::SetRestrictedProperty {
+                    key: key.clone(),
+                    previous: previous.map(Self::restricted_value),
+                    new: Some(Self::restricted_value(new)),
+                });
+            }
+        }
+        for (key, previous) in &before.restricted_properties {
+            if Self::find(&after.restricted_properties, key).is_none() {
+                operations.push(AuditOperation::SetRestrictedProperty {
+                    key: key.clone(),
+                    previous: Some(Self::restricted_value(previous)),
+                    new: None,
+                });
+            }
+        }
+
+        // documents
+        for (document_id, document) in &after.documents {
+            match Self::find(&before.documents, document_id) {
+                None => match document.previous_version {
+                    Some(previous_document_id) => {
+                        operations.push(AuditOperation::ReplaceDocument {
+                            previous_document_id,
+                            document_id: *document_id,
+                            document: document.clone(),
+                        })
+                    }
+                    None => operations.push(AuditOperation::UploadDocument {
+                        document_id: *document_id,
+                        document: document.clone(),
+                    }),
+                },
+                Some(previous) if previous.access_list != document.access_list => {
+                    operations.push(AuditOperation::UpdateDocumentAccess {
+                        document_id: *document_id,
+                        previous: previous.access_list.clone(),
+                        new: document.access_list.clone(),
+                    })
+                }
+                Some(_) => {}
+            }
+        }
+        for (document_id, _) in &before.documents {
+            if Self::find(&after.documents, document_id).is_none() {
+                operations.push(AuditOperation::DeleteDocument {
+                    document_id: *document_id,
+                });
+            }
+        }
+
+        if !before.closed && after.closed {
+            operations.push(AuditOperation::CloseContract);
+        }
+
+        operations
+    }
+
+    fn find<'a, K, V>(entries: &'a [(K, V)], key: &K) -> Option<&'a V>
+    where
+        K: PartialEq,
+    {
+        entries.iter().find(|(k, _)| k == key).map(|(_, v)| v)
+    }
+
+    fn restricted_value(property: &RestrictedProperty) -> AuditRestrictedValue {
+        AuditRestrictedValue {
+            access_list: property.access_list.clone(),
+            value: Some(property.value.clone()),
+        }
+    }
+
+    /// Get next audit log entry ID and increment it
+    fn next_audit_id() -> DeferredDataResult<u64> {
+        NEXT_AUDIT_ID.with_borrow_mut(|id| {
+            let next_id = *id.get();
+            id.set(next_id + 1u64)
+                .map_err(|_| DeferredDataError::StorageError)?;
+
+            Ok(next_id)
+        })
+    }
+}
+
+#[cfg(test)]
+mod test {
+
+    use did::deferred::{GenericValue, RestrictionLevel};
+    use pretty_assertions::assert_eq;
+
+    use super::*;
+    use crate::app::test_utils::with_mock_contract;
+
+    #[test]
+    fn test_should_sort_audit_keys_by_contract() {
+        let key = AuditKey::new(&ID::from(256u64), 1).unwrap();
+        assert_eq!(AuditKey::from_bytes(key.to_bytes()), key);
+
+        let keys = [
+            AuditKey::new(&ID::from(1u64), 5)
+                .unwrap()
+                .to_bytes()
+                .to_vec(),
+            AuditKey::new(&ID::from(2u64), 0)
+                .unwrap()
+                .to_bytes()
+                .to_vec(),
+            AuditKey::new(&ID::from(256u64), 0)
+                .unwrap()
+                .to_bytes()
+                .to_vec(),
+        ];
+        assert!(keys.windows(2).all(|pair| pair[0] < pair[1]));
+    }
+
+    #[test]
+    fn test_should_record_and_paginate_changes() {
+        let before = with_mock_contract(1, 1, |contract| {
+            contract.properties = vec![
+                (
+                    "contract:city".to_string(),
+                    GenericValue::TextContent("Rome".to_string()),
+                ),
+                ("contract:rooms".to_string(), GenericValue::Nat64Content(3)),
+            ];
+        });
+        let after = with_mock_contract(1, 1, |contract| {
+            contract.properties = vec![(
+                "contract:city".to_string(),
+                GenericValue::TextContent("Milan".to_string()),
+            )];
+            contract.restricted_properties[0].1.access_list = vec![RestrictionLevel::Agent];
+            contract.closed = true;
+        });
+
+        AuditLogStorage::record_changes(&before, &after, Principal::anonymous(), 42).unwrap();
+
+        let log = AuditLogStorage::get_audit_log(&before.id, 0, 100);
+        assert_eq!(log.len(), 4);
+        assert_eq!(
+            log[0].operation,
+            AuditOperation::SetProperty {
+                key: "contract:city".to_string(),
+                previous: Some(GenericValue::TextContent("Rome".to_string())),
+                new: Some(GenericValue::TextContent("Milan".to_string())),
+            }
+        );
+        assert_eq!(
+            log[1].operation,
+            AuditOperation::SetProperty {
+                key: "contract:rooms".to_string(),
+                previous: Some(GenericValue::Nat64Content(3)),
+                new: None,
+            }
+        );
+        assert!(matches!(
+            log[2].operation,
+            AuditOperation::SetRestrictedProperty { .. }
+        ));
+        assert_eq!(log[3].operation, AuditOperation::CloseContract);
+        assert_eq!(log[3].timestamp, 42);
+        assert_eq!(AuditLogStorage::get_closing_time(&before.id), Some(42));
+
+        let page = AuditLogStorage::get_audit_log(&before.id, 1, 2);
+        assert_eq!(page, log[1..3].to_vec());
+
+        // other contracts have no entries
+        assert!(AuditLogStorage::get_audit_log(&ID::from(2u64), 0, 100).is_empty());
+        assert_eq!(AuditLogStorage::get_closing_time(&ID::from(2u64)), None);
+    }
+}
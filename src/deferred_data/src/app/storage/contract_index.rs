@@ -0,0 +1,251 @@
+use std::borrow::Cow;
+use std::collections::BTreeSet;
+
+use candid::Principal;
+use did::deferred::{Contract, DeferredDataResult};
+use did::{StorableNat, H160, ID};
+use ic_stable_structures::storable::Bound;
+use ic_stable_structures::Storable;
+use sha2::{Digest as _, Sha256};
+
+use super::{contract_id_key, CONTRACT_ID_KEY_SIZE, CONTRACT_INDEX};
+
+/// A value contracts are indexed by
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub enum ContractIndex {
+    Seller(H160),
+    Buyer(H160),
+    /// The owner of the agency which has created the contract
+    Agency(Principal),
+    /// Currency symbol, case insensitive
+    Currency(String),
+}
+
+impl ContractIndex {
+    /// Get the values a contract is indexed by
+    fn of(contract: &Contract) -> Vec<Self> {
+        contract
+            .sellers
+            .iter()
+            .map(|seller| Self::Seller(seller.address))
+            .chain(contract.buyers.iter().copied().map(Self::Buyer))
+            .chain(
+                contract
+                    .agency
+                    .as_ref()
+                    .map(|agency| Self::Agency(agency.owner)),
+            )
+            .chain(std::iter::once(Self::Currency(contract.currency.clone())))
+            .collect()
+    }
+
+    /// Hash of the indexed value, prefixed by the index it belongs to
+    fn digest(&self) -> [u8; 32] {
+        let mut hasher = Sha256::new();
+        match self {
+            Self::Seller(address) => {
+                hasher.update([0]);
+                hasher.update(address.0.as_bytes());
+            }
+            Self::Buyer(address) => {
+                hasher.update([1]);
+                hasher.update(address.0.as_bytes());
+            }
+            Self::Agency(owner) => {
+                hasher.update([2]);
+                hasher.update(owner.as_slice());
+            }
+            Self::Currency(currency) => {
+                hasher.update([3]);
+                hasher.update(currency.to_uppercase().as_bytes());
+            }
+        }
+
+        hasher.finalize().into()
+    }
+}
+
+/// Key of the contract index, sorted by indexed value and then by contract ID
+#[derive(Clone, Debug, PartialEq, Eq, PartialOrd, Ord)]
+pub struct ContractIndexKey {
+    index: [u8; 32],
+    contract_id: [u8; CONTRACT_ID_KEY_SIZE],
+}
+
+impl ContractIndexKey {
+    fn new(index: &ContractIndex, contract_id: &ID) -> DeferredDataResult<Self> {
+        Ok(Self {
+            index: index.digest(),
+            contract_id: contract_id_key(contract_id)?,
+        })
+    }
+}
+
+impl Storable for ContractIndexKey {
+    const BOUND: Bound = Bound::Bounded {
+        max_size: 32 + CONTRACT_ID_KEY_SIZE as u32,
+        is_fixed_size: true,
+    };
+
+    fn to_bytes(&self) -> Cow<[u8]> {
+        let mut bytes = Vec::with_capacity(32 + CONTRACT_ID_KEY_SIZE);
+        bytes.extend_from_slice(&self.index);
+        bytes.extend_from_slice(&self.contract_id);
+
+        bytes.into()
+    }
+
+    fn from_bytes(bytes: Cow<[u8]>) -> Self {
+        let mut index = [0; 32];
+        index.copy_from_slice(&bytes[..32]);
+        let mut contract_id = [0; CONTRACT_ID_KEY_SIZE];
+        contract_id.copy_from_slice(&bytes[32..]);
+
+        Self { index, contract_id }
+    }
+}
+
+/// Secondary indexes of the open contracts
+pub struct ContractIndexStorage;
+
+impl ContractIndexStorage {
+    /// Get the IDs of the open contracts indexed by `index`
+    pub fn get_contracts(index: &ContractIndex) -> BTreeSet<ID> {
+        let from = ContractIndexKey {
+            index: index.digest(),
+            contract_id: [0; CONTRACT_ID_KEY_SIZE],
+        };
+        let to = ContractIndexKey {
+            index: from.index,
+            contract_id: [u8::MAX; CONTRACT_ID_KEY_SIZE],
+        };
+
+        CONTRACT_INDEX.with_borrow(|contract_index| {
+            contract_index
+                .range(from..=to)
+                .map(|(_, contract_id)| contract_id.0)
+                .collect()
+        })
+    }
+
+    /// Update the indexes after a contract has changed.
+    ///
+    /// Contracts are indexed while they are open.
+    pub fn update_contract(
+        previous: Option<&Contract>,
+        contract: &Contract,
+    ) -> DeferredDataResult<()> {
+        let indexes = |contract: &Contract| {
+            if contract.closed {
+                vec![]
+            } else {
+                ContractIndex::of(contract)
+            }
+        };
+        let previous_indexes = previous.map(indexes).unwrap_or_default();
+        let indexes = indexes(contract);
+        if previous_indexes == indexes {
+            return Ok(());
+        }
+
+        CONTRACT_INDEX.with_borrow_mut(|contract_index| {
+            for index in previous_indexes
+                .iter()
+                .filter(|index| !indexes.contains(index))
+            {
+                contract_index.remove(&ContractIndexKey::new(index, &contract.id)?);
+            }
+            for index in indexes {
+                contract_index.insert(
+                    ContractIndexKey::new(&index, &contract.id)?,
+                    StorableNat::from(contract.id.clone()),
+                );
+            }
+
+            Ok(())
+        })
+    }
+
+    /// Returns whether no contract is indexed
+    pub fn is_empty() -> bool {
+        CONTRACT_INDEX.with_borrow(|contract_index| contract_index.is_empty())
+    }
+}
+
+#[cfg(test)]
+mod test {
+
+    use did::deferred::Seller;
+    use pretty_assertions::assert_eq;
+
+    use super::*;
+    use crate::app::storage::ContractStorage;
+    use crate::app::test_utils::{alice, with_mock_contract};
+
+    #[test]
+    fn test_should_index_contracts() {
+        let seller = H160::from_hex_str("0x8fd379246834eac74B8419FfdA202CF8051F7A03").unwrap();
+        let buyer = H160::from_hex_str("0x0b24F78CF0033FAbf1977D9aA61f583fBF7586D9").unwrap();
+        ContractStorage::insert_contract(with_mock_contract(1, 1, |contract| {
+            contract.sellers = vec![Seller {
+                address: seller,
+                quota: 100,
+            }];
+            contract.buyers = vec![buyer];
+            contract.currency = "EUR".to_string();
+        }))
+        .unwrap();
+        ContractStorage::insert_contract(with_mock_contract(2, 1, |contract| {
+            contract.buyers = vec![buyer];
+            contract.currency = "USD".to_string();
+        }))
+        .unwrap();
+
+        assert_eq!(
+            ContractIndexStorage::get_contracts(&ContractIndex::Seller(seller)),
+            BTreeSet::from([ID::from(1u64)])
+        );
+        assert_eq!(
+            ContractIndexStorage::get_contracts(&ContractIndex::Buyer(buyer)),
+            BTreeSet::from([ID::from(1u64), 2u64.into()])
+        );
+        assert_eq!(
+            ContractIndexStorage::get_contracts(&ContractIndex::Agency(alice())),
+            BTreeSet::from([ID::from(1u64), 2u64.into()])
+        );
+        assert_eq!(
+            ContractIndexStorage::get_contracts(&ContractIndex::Currency("usd".to_string())),
+            BTreeSet::from([ID::from(2u64)])
+        );
+        // a seller is not indexed as buyer
+        assert!(ContractIndexStorage::get_contracts(&ContractIndex::Buyer(seller)).is_empty());
+
+        // closed contracts are removed from the indexes
+        ContractStorage::close_contract(&2u64.into()).unwrap();
+        assert_eq!(
+            ContractIndexStorage::get_contracts(&ContractIndex::Buyer(buyer)),
+            BTreeSet::from([ID::from(1u64)])
+        );
+    }
+
+    #[test]
+    fn test_should_reindex_replaced_contract() {
+        ContractStorage::insert_contract(with_mock_contract(1, 1, |contract| {
+            contract.currency = "EUR".to_string();
+        }))
+        .unwrap();
+        ContractStorage::insert_contract(with_mock_contract(1, 1, |contract| {
+            contract.currency = "USD".to_string();
+        }))
+        .unwrap();
+
+        assert!(
+            ContractIndexStorage::get_contracts(&ContractIndex::Currency("EUR".to_string()))
+                .is_empty()
+        );
+        assert_eq!(
+            ContractIndexStorage::get_contracts(&ContractIndex::Currency("USD".to_string())),
+            BTreeSet::from([ID::from(1u64)])
+        );
+    }
+}
@@ -1,12 +1,24 @@
+use std::collections::BTreeSet;
+use std::ops::Bound;
+
 use did::deferred::{
-    Contract, ContractDocument, ContractDocumentData, DataContractError, DeferredDataError,
-    DeferredDataResult, GenericValue, RestrictedProperty,
+    Contract, ContractArchiveEntry, ContractDocument, ContractDocumentData, ContractSortBy,
+    ContractSummary, DataContractError, DeferredDataError, DeferredDataResult, GenericValue,
+    GeoPoint, PropertyOperation, RestrictedProperty, RestrictionLevel, SortOrder,
 };
-use did::ID;
+use did::{StorableNat, ID};
+use ic_stable_structures::memory_manager::VirtualMemory;
+use ic_stable_structures::{BTreeMap, DefaultMemoryImpl};
 
+use super::listing::contract_summary;
 use super::{
-    with_contract, with_contract_mut, with_contracts, with_contracts_mut, DocumentStorage,
+    contract_id_key, with_contract, with_contract_mut, with_contracts, with_contracts_mut,
+    ContractCursor, ContractEvent, ContractEventStorage, ContractIndexStorage, DocumentStorage,
+    GeoIndexStorage, SearchIndexStorage, UploadStorage,
 };
+use crate::app::certification::HttpCertification;
+use crate::app::schema::Schema;
+use crate::utils::time;
 
 pub struct ContractStorage;
 
@@ -24,12 +36,30 @@ impl ContractStorage {
         .flatten()
     }
 
-    /// Insert contract
-    pub fn insert_contract(contract: Contract) {
-        with_contracts_mut(|contracts| contracts.insert(contract.id.clone().into(), contract));
+    /// Get contract by id, including closed contracts
+    pub fn get_any_contract(id: &ID) -> Option<Contract> {
+        with_contract(id, |contract| Ok(contract.clone())).ok()
+    }
+
+    /// Insert contract, updating the secondary indexes
+    pub fn insert_contract(contract: Contract) -> DeferredDataResult<()> {
+        // the contract ID must fit the keys of the indexes
+        contract_id_key(&contract.id)?;
+
+        let previous = with_contracts_mut(|contracts| {
+            contracts.insert(contract.id.clone().into(), contract.clone())
+        });
+
+        ContractIndexStorage::update_contract(previous.as_ref(), &contract)?;
+        GeoIndexStorage::update_contract(previous.as_ref(), &contract)?;
+        SearchIndexStorage::update_contract(previous.as_ref(), &contract)?;
+        ContractEventStorage::update_contract(previous.as_ref(), &contract, time())?;
+        HttpCertification::update_contract(&contract);
+
+        Ok(())
     }
 
-    /// Close a contract
+    /// Close a contract, removing it from the secondary indexes
     pub fn close_contract(id: &ID) -> DeferredDataResult<()> {
         with_contract_mut(id, |contract| {
             contract.closed = true;
@@ -37,6 +67,51 @@ impl ContractStorage {
         })
     }
 
+    /// Index the contracts into the secondary indexes which are empty.
+    ///
+    /// Contracts inserted before the indexes were introduced are not indexed yet.
+    /// Only the open contracts are indexed, except for the event index.
+    pub fn build_indexes() -> DeferredDataResult<()> {
+        let build_contract_index = ContractIndexStorage::is_empty();
+        let build_geo_index = GeoIndexStorage::is_empty();
+        let build_search_index = SearchIndexStorage::is_empty();
+        let build_event_index = ContractEventStorage::is_empty();
+        if !build_contract_index && !build_geo_index && !build_search_index && !build_event_index {
+            return Ok(());
+        }
+
+        with_contracts(|contracts| {
+            for (_, contract) in contracts.iter() {
+                if build_event_index {
+                    ContractEventStorage::index_contract(&contract)?;
+                }
+                if contract.closed {
+                    continue;
+                }
+                if build_contract_index {
+                    ContractIndexStorage::update_contract(None, &contract)?;
+                }
+                if build_geo_index {
+                    GeoIndexStorage::update_contract(None, &contract)?;
+                }
+                if build_search_index {
+                    SearchIndexStorage::update_contract(None, &contract)?;
+                }
+            }
+
+            Ok(())
+        })
+    }
+
+    /// Certify the HTTP responses of the open contracts.
+    ///
+    /// The certified responses are not kept in stable memory, so they must be certified again after upgrades.
+    pub fn certify_contracts() {
+        with_contracts(|contracts| {
+            HttpCertification::certify_contracts(contracts.iter().map(|(_, contract)| contract))
+        });
+    }
+
     /// get contracts
     /// closed contracts are not returned
     pub fn get_contracts() -> Vec<ID> {
@@ -50,16 +125,151 @@ impl ContractStorage {
     }
 
     /// get contracts by filter
-    pub fn get_contracts_filter(filter: impl Fn(&Contract) -> bool) -> Vec<ID> {
+    ///
+    /// If `candidates` is set, only those contracts are read from the storage.
+    pub fn get_contracts_filter(
+        candidates: Option<&BTreeSet<ID>>,
+        filter: impl Fn(&Contract) -> bool,
+    ) -> Vec<ID> {
+        with_contracts(|contracts| {
+            Self::scan(contracts, candidates, None, SortOrder::Ascending)
+                .filter(|contract| !contract.closed && filter(contract))
+                .map(|contract| contract.id)
+                .collect()
+        })
+    }
+
+    /// List a page of the open contracts matching the filter, sorted by `sort_by` in `order`.
+    /// Distances are measured from `origin`.
+    ///
+    /// Contracts are read one at a time from the storage, keeping at most twice the page size in memory;
+    /// when sorting by ID, the iteration starts after the cursor and stops once the page is full.
+    ///
+    /// If `candidates` is set, only those contracts are read from the storage.
+    ///
+    /// Returns the summaries of the page and the cursor of the next page, if any.
+    pub fn list_contracts(
+        sort_by: ContractSortBy,
+        order: SortOrder,
+        origin: Option<GeoPoint>,
+        cursor: Option<&ContractCursor>,
+        limit: usize,
+        candidates: Option<&BTreeSet<ID>>,
+        filter: impl Fn(&Contract) -> bool,
+    ) -> (Vec<ContractSummary>, Option<ContractCursor>) {
+        // an extra contract tells whether there is a next page
+        let window = limit.saturating_add(1);
+        let sorted_by_key = sort_by == ContractSortBy::Id;
+        let compare = move |a: &ContractCursor, b: &ContractCursor| a.compare(b, order);
+
+        with_contracts(|contracts| {
+            let after = cursor.filter(|_| sorted_by_key).map(|cursor| &cursor.id);
+
+            let mut page = vec![];
+            for contract in Self::scan(contracts, candidates, after, order) {
+                if contract.closed || !filter(&contract) {
+                    continue;
+                }
+                let position = ContractCursor::new(sort_by, origin, &contract);
+                if cursor.is_some_and(|cursor| compare(&position, cursor).is_le()) {
+                    continue;
+                }
+
+                page.push(position);
+                if sorted_by_key && page.len() == window {
+                    break;
+                }
+                if page.len() >= window.saturating_mul(2) {
+                    page.sort_by(compare);
+                    page.truncate(window);
+                }
+            }
+            page.sort_by(compare);
+            page.truncate(window);
+
+            let next_cursor = if page.len() > limit {
+                page.truncate(limit);
+                page.last().cloned()
+            } else {
+                None
+            };
+            let summaries = page
+                .iter()
+                .filter_map(|position| contracts.get(&StorableNat::from(position.id.clone())))
+                .map(|contract| contract_summary(&contract))
+                .collect();
+
+            (summaries, next_cursor)
+        })
+    }
+
+    /// Iterate the contracts which come after the ID `after`, sorted by ID in `order`.
+    ///
+    /// If `candidates` is set, only those contracts are read from the storage.
+    fn scan<'a>(
+        contracts: &'a BTreeMap<StorableNat, Contract, VirtualMemory<DefaultMemoryImpl>>,
+        candidates: Option<&'a BTreeSet<ID>>,
+        after: Option<&ID>,
+        order: SortOrder,
+    ) -> Box<dyn Iterator<Item = Contract> + 'a> {
+        let after = after.map_or(Bound::Unbounded, |id| Bound::Excluded(id.clone()));
+        let range = match order {
+            SortOrder::Ascending => (after, Bound::Unbounded),
+            SortOrder::Descending => (Bound::Unbounded, after),
+        };
+        let stored_range = (
+            range.0.clone().map(StorableNat::from),
+            range.1.clone().map(StorableNat::from),
+        );
+
+        match (candidates, order) {
+            (Some(candidates), SortOrder::Ascending) => Box::new(
+                candidates
+                    .range(range)
+                    .filter_map(|id| contracts.get(&StorableNat::from(id.clone()))),
+            ),
+            (Some(candidates), SortOrder::Descending) => Box::new(
+                candidates
+                    .range(range)
+                    .rev()
+                    .filter_map(|id| contracts.get(&StorableNat::from(id.clone()))),
+            ),
+            (None, SortOrder::Ascending) => {
+                Box::new(contracts.range(stored_range).map(|(_, contract)| contract))
+            }
+            (None, SortOrder::Descending) => Box::new(
+                contracts
+                    .range(stored_range)
+                    .rev()
+                    .map(|(_, contract)| contract),
+            ),
+        }
+    }
+
+    /// List both active and closed contracts matching the filter
+    pub fn get_contract_archive(filter: impl Fn(&Contract) -> bool) -> Vec<ContractArchiveEntry> {
         with_contracts(|contracts| {
             contracts
                 .iter()
-                .filter(|(_, contract)| !contract.closed && filter(contract))
-                .map(|(key, _)| key.0.clone())
+                .filter(|(_, contract)| filter(contract))
+                .map(|(key, contract)| ContractArchiveEntry {
+                    id: key.0.clone(),
+                    closed: contract.closed,
+                })
                 .collect()
         })
     }
 
+    /// Get the latest creations and closures of the contracts matching the filter, from the newest.
+    ///
+    /// Contracts created before the creation time was recorded have no creation event.
+    pub fn get_contract_events(
+        filter: impl Fn(&Contract) -> bool,
+        limit: usize,
+    ) -> Vec<ContractEvent> {
+        ContractEventStorage::get_events(filter, limit)
+    }
+
     /// Update contract property
     pub fn update_contract_property(
         contract_id: &ID,
@@ -67,18 +277,9 @@ impl ContractStorage {
         value: GenericValue,
     ) -> DeferredDataResult<()> {
         with_contract_mut(contract_id, |contract| {
-            let mut found = false;
-            for (k, v) in &mut contract.properties {
-                if k == &key {
-                    *v = value.clone();
-                    found = true;
-                    break;
-                }
-            }
-            if !found {
-                contract.properties.push((key, value));
-            }
-            Ok(())
+            let changed = [key.clone()];
+            Self::set_property(&mut contract.properties, key, value);
+            Schema::validate_changes(contract, &changed)
         })
     }
 
@@ -89,17 +290,107 @@ impl ContractStorage {
         value: RestrictedProperty,
     ) -> DeferredDataResult<()> {
         with_contract_mut(contract_id, |contract| {
-            let mut found = false;
-            for (k, v) in &mut contract.restricted_properties {
-                if k == &key {
-                    *v = value.clone();
-                    found = true;
-                    break;
+            let changed = [key.clone()];
+            Self::set_property(&mut contract.restricted_properties, key, value);
+            Schema::validate_changes(contract, &changed)
+        })
+    }
+
+    /// Apply a batch of operations on the contract properties.
+    ///
+    /// Operations are applied in order; if any of them fails, or the changed properties don't match
+    /// the property schema, the contract is left unchanged.
+    pub fn update_contract_properties(
+        contract_id: &ID,
+        operations: Vec<PropertyOperation>,
+    ) -> DeferredDataResult<()> {
+        with_contract_mut(contract_id, |contract| {
+            let mut changed = Vec::with_capacity(operations.len());
+            for operation in operations {
+                match &operation {
+                    PropertyOperation::Set { key, .. }
+                    | PropertyOperation::Remove { key }
+                    | PropertyOperation::SetRestricted { key, .. }
+                    | PropertyOperation::RemoveRestricted { key } => changed.push(key.clone()),
+                }
+                match operation {
+                    PropertyOperation::Set { key, value } => {
+                        Self::set_property(&mut contract.properties, key, value);
+                    }
+                    PropertyOperation::Remove { key } => {
+                        Self::remove_property(&mut contract.properties, &key)?;
+                    }
+                    PropertyOperation::SetRestricted { key, value } => {
+                        Self::set_property(&mut contract.restricted_properties, key, value);
+                    }
+                    PropertyOperation::RemoveRestricted { key } => {
+                        Self::remove_property(&mut contract.restricted_properties, &key)?;
+                    }
                 }
             }
-            if !found {
-                contract.restricted_properties.push((key, value));
-            }
+
+            Schema::validate_changes(contract, &changed)
+        })
+    }
+
+    /// Insert or update a property
+    fn set_property<V>(properties: &mut Vec<(String, V)>, key: String, value: V) {
+        match properties.iter_mut().find(|(k, _)| k == &key) {
+            Some((_, v)) => *v = value,
+            None => properties.push((key, value)),
+        }
+    }
+
+    /// Remove a property, failing if it doesn't exist
+    fn remove_property<V>(properties: &mut Vec<(String, V)>, key: &str) -> DeferredDataResult<()> {
+        let Some(index) = properties.iter().position(|(k, _)| k == key) else {
+            return Err(DeferredDataError::Contract(
+                DataContractError::PropertyNotFound(key.to_string()),
+            ));
+        };
+        properties.remove(index);
+
+        Ok(())
+    }
+
+    /// Update the access list of a restricted contract property, keeping its value
+    pub fn update_restricted_contract_property_access(
+        contract_id: &ID,
+        key: &str,
+        access_list: Vec<RestrictionLevel>,
+    ) -> DeferredDataResult<()> {
+        with_contract_mut(contract_id, |contract| {
+            let (_, property) = contract
+                .restricted_properties
+                .iter_mut()
+                .find(|(k, _)| k == key)
+                .ok_or_else(|| {
+                    DeferredDataError::Contract(DataContractError::PropertyNotFound(
+                        key.to_string(),
+                    ))
+                })?;
+            property.access_list = access_list;
+
+            Ok(())
+        })
+    }
+
+    /// Update the access list of a contract document
+    pub fn update_contract_document_access(
+        contract_id: &ID,
+        document_id: u64,
+        access_list: Vec<RestrictionLevel>,
+    ) -> DeferredDataResult<()> {
+        with_contract_mut(contract_id, |contract| {
+            let (_, document) = contract
+                .documents
+                .iter_mut()
+                .find(|(id, _)| *id == document_id)
+                .ok_or(DeferredDataError::Contract(
+                    DataContractError::DocumentNotFound(document_id),
+                ))?;
+            document.access_list = access_list;
+
             Ok(())
         })
     }
@@ -110,6 +401,30 @@ impl ContractStorage {
         document: ContractDocument,
         data: Vec<u8>,
     ) -> DeferredDataResult<u64> {
+        Self::store_contract_document(contract_id, document, data, None)
+    }
+
+    /// Replace a contract document with a new revision.
+    ///
+    /// The replaced document is kept in the contract with `superseded_by` set to the new document ID,
+    /// while the new document has `previous_version` set to the replaced document ID.
+    ///
+    /// Returns the ID of the new document revision
+    pub fn replace_contract_document(
+        contract_id: &ID,
+        document_id: u64,
+        document: ContractDocument,
+        data: Vec<u8>,
+    ) -> DeferredDataResult<u64> {
+        Self::store_contract_document(contract_id, document, data, Some(document_id))
+    }
+
+    /// Delete the latest revision of a contract document.
+    ///
+    /// The previous revisions are kept, so the history of the document stays auditable:
+    /// the previous revision, if any, becomes the latest revision again.
+    /// The data of the deleted revision is removed from the storage.
+    pub fn delete_contract_document(contract_id: &ID, document_id: u64) -> DeferredDataResult<()> {
         // check if contract exists
         if Self::get_contract(contract_id).is_none() {
             return Err(DeferredDataError::Contract(
@@ -117,6 +432,48 @@ impl ContractStorage {
             ));
         }
 
+        with_contract_mut(contract_id, |contract| {
+            let document = Self::find_latest_revision(contract, document_id)?;
+
+            if let Some((_, previous)) = contract
+                .documents
+                .iter_mut()
+                .find(|(id, _)| Some(*id) == document.previous_version)
+            {
+                previous.superseded_by = None;
+            }
+            contract.documents.retain(|(id, _)| *id != document_id);
+
+            Ok(())
+        })?;
+
+        // reclaim document data
+        DocumentStorage::remove_document(document_id);
+
+        Ok(())
+    }
+
+    /// Store a document into the document storage and associate it to the contract.
+    ///
+    /// If `replaces` is set, the new document becomes the latest revision of the replaced document.
+    fn store_contract_document(
+        contract_id: &ID,
+        mut document: ContractDocument,
+        data: Vec<u8>,
+        replaces: Option<u64>,
+    ) -> DeferredDataResult<u64> {
+        // check if contract exists
+        let Some(contract) = Self::get_contract(contract_id) else {
+            return Err(DeferredDataError::Contract(
+                DataContractError::ContractNotFound(contract_id.clone()),
+            ));
+        };
+
+        // check if the replaced document is the latest revision
+        if let Some(replaced_id) = replaces {
+            Self::find_latest_revision(&contract, replaced_id)?;
+        }
+
         // check if document size matches data size
         if document.size != data.len() as u64 {
             return Err(DeferredDataError::Contract(
@@ -125,25 +482,154 @@ impl ContractStorage {
         }
 
         // insert document into document storage
-        let document_id = DocumentStorage::upload_document(data)?;
+        let (document_id, digest) = DocumentStorage::upload_document(data)?;
+
+        // revision fields and digest are managed by the canister
+        document.previous_version = replaces;
+        document.superseded_by = None;
+        document.digest = Some(hex::encode(digest));
 
         // update contract with document id
-        with_contract_mut(contract_id, |contract| {
+        let result = with_contract_mut(contract_id, |contract| {
+            if let Some(replaced_id) = replaces {
+                if let Some((_, replaced)) = contract
+                    .documents
+                    .iter_mut()
+                    .find(|(id, _)| *id == replaced_id)
+                {
+                    replaced.superseded_by = Some(document_id);
+                }
+            }
             contract.documents.push((document_id, document));
 
             Ok(())
-        })?;
+        });
+
+        // reclaim document data if the contract could not be updated
+        if let Err(err) = result {
+            DocumentStorage::remove_document(document_id);
+            return Err(err);
+        }
+
+        Ok(document_id)
+    }
+
+    /// Find a document in the contract, which must be the latest revision
+    fn find_latest_revision(
+        contract: &Contract,
+        document_id: u64,
+    ) -> DeferredDataResult<ContractDocument> {
+        let document = contract
+            .documents
+            .iter()
+            .find(|(id, _)| *id == document_id)
+            .map(|(_, document)| document.clone())
+            .ok_or(DeferredDataError::Contract(
+                DataContractError::DocumentNotFound(document_id),
+            ))?;
+
+        if document.superseded_by.is_some() {
+            return Err(DeferredDataError::Contract(
+                DataContractError::DocumentSuperseded(document_id),
+            ));
+        }
+
+        Ok(document)
+    }
+
+    /// Begin a chunked upload of a contract document.
+    ///
+    /// Returns the ID of the upload session
+    pub fn begin_contract_document_upload(
+        contract_id: &ID,
+        document: ContractDocument,
+    ) -> DeferredDataResult<u64> {
+        // check if contract exists
+        if Self::get_contract(contract_id).is_none() {
+            return Err(DeferredDataError::Contract(
+                DataContractError::ContractNotFound(contract_id.clone()),
+            ));
+        }
+
+        UploadStorage::begin_upload(contract_id.clone(), document, time())
+    }
+
+    /// Get the ID of the contract associated to a pending upload
+    pub fn get_contract_document_upload_contract(upload_id: u64) -> DeferredDataResult<ID> {
+        UploadStorage::get_upload(upload_id, time()).map(|session| session.contract_id)
+    }
+
+    /// Append a chunk of data to a pending upload
+    pub fn upload_contract_document_chunk(
+        upload_id: u64,
+        chunk_index: u64,
+        data: Vec<u8>,
+    ) -> DeferredDataResult<()> {
+        UploadStorage::put_chunk(upload_id, chunk_index, data, time())
+    }
+
+    /// Finalize a chunked upload, storing the document into the contract.
+    ///
+    /// Returns the ID of the uploaded document
+    pub fn finalize_contract_document_upload(upload_id: u64) -> DeferredDataResult<u64> {
+        let (session, data) = UploadStorage::assemble_upload(upload_id, time())?;
+
+        let document_id =
+            Self::upload_contract_document(&session.contract_id, session.document, data)?;
+        UploadStorage::remove_upload(upload_id);
 
         Ok(document_id)
     }
 
+    /// Get the SHA-256 digest of a contract document
+    pub fn get_contract_document_digest(
+        contract_id: &ID,
+        document_id: u64,
+    ) -> DeferredDataResult<[u8; 32]> {
+        // check if `document_id` belongs to `contract_id`
+        with_contract(contract_id, |contract| {
+            if contract.documents.iter().any(|(id, _)| *id == document_id) {
+                Ok(())
+            } else {
+                Err(DeferredDataError::Contract(
+                    DataContractError::DocumentNotFound(document_id),
+                ))
+            }
+        })?;
+
+        DocumentStorage::get_document_digest(document_id)
+    }
+
+    /// Get the bytes of a contract document between `start` and `end` (exclusive).
+    ///
+    /// Only the requested range is read from the storage.
+    pub fn get_contract_document_range(
+        contract_id: &ID,
+        document_id: u64,
+        start: u64,
+        end: u64,
+    ) -> DeferredDataResult<Vec<u8>> {
+        // check if `document_id` belongs to `contract_id`; documents of closed contracts are still readable
+        with_contract(contract_id, |contract| {
+            if contract.documents.iter().any(|(id, _)| *id == document_id) {
+                Ok(())
+            } else {
+                Err(DeferredDataError::Contract(
+                    DataContractError::DocumentNotFound(document_id),
+                ))
+            }
+        })?;
+
+        DocumentStorage::get_document_range(document_id, start, end)
+    }
+
     /// Get contract document
     pub fn get_contract_document(
         contract_id: &ID,
         document_id: u64,
     ) -> DeferredDataResult<ContractDocumentData> {
-        // check if contract exists
-        let contract = Self::get_contract(contract_id).ok_or_else(|| {
+        // check if contract exists; documents of closed contracts are still readable
+        let contract = Self::get_any_contract(contract_id).ok_or_else(|| {
             DeferredDataError::Contract(DataContractError::ContractNotFound(contract_id.clone()))
         })?;
 
@@ -174,11 +660,12 @@ impl ContractStorage {
 mod test {
 
     use candid::Nat;
-    use did::deferred::{RestrictionLevel, Seller};
+    use did::deferred::Seller;
     use did::H160;
     use pretty_assertions::assert_eq;
 
     use super::*;
+    use crate::app::storage::ContractEventKind;
     use crate::app::test_utils::with_mock_contract;
 
     #[test]
@@ -193,11 +680,23 @@ mod test {
         });
 
         assert!(ContractStorage::get_contract(&contract.id).is_none());
-        ContractStorage::insert_contract(contract.clone());
+        ContractStorage::insert_contract(contract.clone()).unwrap();
 
         assert!(ContractStorage::get_contract(&contract.id).is_some());
     }
 
+    #[test]
+    fn test_should_not_insert_contract_with_too_large_id() {
+        let mut contract = with_mock_contract(1, 2, |_| {});
+        contract.id = ID::from(Nat::from(u128::MAX) * u128::MAX);
+
+        assert_eq!(
+            ContractStorage::insert_contract(contract.clone()),
+            Err(DeferredDataError::StorageError)
+        );
+        assert!(ContractStorage::get_any_contract(&contract.id).is_none());
+    }
+
     #[test]
     fn test_should_insert_and_get_contract_with_no_buyers() {
         let seller = vec![Seller {
@@ -211,7 +710,7 @@ mod test {
         });
 
         assert!(ContractStorage::get_contract(&contract.id).is_none());
-        ContractStorage::insert_contract(contract.clone());
+        ContractStorage::insert_contract(contract.clone()).unwrap();
         assert!(ContractStorage::get_contract(&contract.id).is_some());
         assert_eq!(ContractStorage::get_contracts(), vec![contract.id]);
     }
@@ -228,7 +727,7 @@ mod test {
             contract.buyers = vec![];
         });
         // store
-        ContractStorage::insert_contract(contract.clone());
+        ContractStorage::insert_contract(contract.clone()).unwrap();
 
         // other contract
         let contract = with_mock_contract(2, 2, |contract| {
@@ -242,10 +741,10 @@ mod test {
         });
 
         // store
-        ContractStorage::insert_contract(contract.clone());
+        ContractStorage::insert_contract(contract.clone()).unwrap();
 
         // filter
-        let contracts = ContractStorage::get_contracts_filter(|contract| {
+        let contracts = ContractStorage::get_contracts_filter(None, |contract| {
             contract
                 .properties
                 .iter()
@@ -254,6 +753,106 @@ mod test {
         assert_eq!(contracts, vec![Nat::from(2u64)]);
     }
 
+    #[test]
+    fn test_should_list_contracts_by_page() {
+        for (id, value) in [(1u64, 300u64), (2, 100), (3, 200), (4, 100), (5, 500)] {
+            ContractStorage::insert_contract(with_mock_contract(id, 2, |contract| {
+                contract.value = value;
+                contract.closed = id == 5;
+            }))
+            .unwrap();
+        }
+        let ids = |summaries: &[ContractSummary]| {
+            summaries
+                .iter()
+                .map(|summary| summary.id.clone())
+                .collect::<Vec<_>>()
+        };
+
+        // by ascending id
+        let (page, cursor) = ContractStorage::list_contracts(
+            ContractSortBy::Id,
+            SortOrder::Ascending,
+            None,
+            None,
+            3,
+            None,
+            |_| true,
+        );
+        assert_eq!(ids(&page), vec![ID::from(1u64), 2u64.into(), 3u64.into()]);
+        let (page, cursor) = ContractStorage::list_contracts(
+            ContractSortBy::Id,
+            SortOrder::Ascending,
+            None,
+            cursor.as_ref(),
+            3,
+            None,
+            |_| true,
+        );
+        assert_eq!(ids(&page), vec![ID::from(4u64)]);
+        assert!(cursor.is_none());
+
+        // by descending id, also among candidates
+        let (page, cursor) = ContractStorage::list_contracts(
+            ContractSortBy::Id,
+            SortOrder::Descending,
+            None,
+            None,
+            3,
+            None,
+            |_| true,
+        );
+        assert_eq!(ids(&page), vec![ID::from(4u64), 3u64.into(), 2u64.into()]);
+        let candidates = [1u64, 2, 4, 5].into_iter().map(ID::from).collect();
+        let (page, cursor) = ContractStorage::list_contracts(
+            ContractSortBy::Id,
+            SortOrder::Descending,
+            None,
+            cursor.as_ref(),
+            3,
+            Some(&candidates),
+            |_| true,
+        );
+        assert_eq!(ids(&page), vec![ID::from(1u64)]);
+        assert!(cursor.is_none());
+
+        // by descending value, ties sorted by id
+        let (page, cursor) = ContractStorage::list_contracts(
+            ContractSortBy::Value,
+            SortOrder::Descending,
+            None,
+            None,
+            2,
+            None,
+            |_| true,
+        );
+        assert_eq!(ids(&page), vec![ID::from(1u64), 3u64.into()]);
+        assert_eq!(cursor.as_ref().unwrap().to_string(), "v:200:3");
+        let (page, cursor) = ContractStorage::list_contracts(
+            ContractSortBy::Value,
+            SortOrder::Descending,
+            None,
+            cursor.as_ref(),
+            2,
+            None,
+            |_| true,
+        );
+        assert_eq!(ids(&page), vec![ID::from(4u64), 2u64.into()]);
+        assert!(cursor.is_none());
+
+        // with filter
+        let (page, _) = ContractStorage::list_contracts(
+            ContractSortBy::Value,
+            SortOrder::Ascending,
+            None,
+            None,
+            10,
+            None,
+            |contract| contract.value >= 200,
+        );
+        assert_eq!(ids(&page), vec![ID::from(3u64), 1u64.into()]);
+    }
+
     #[test]
     fn test_should_update_contract_property() {
         let contract = with_mock_contract(1, 1, |contract| {
@@ -266,7 +865,7 @@ mod test {
                 GenericValue::TextContent("Italy".to_string()),
             ));
         });
-        ContractStorage::insert_contract(contract);
+        ContractStorage::insert_contract(contract).unwrap();
 
         assert!(ContractStorage::update_contract_property(
             &1_u64.into(),
@@ -314,7 +913,7 @@ mod test {
                 },
             ));
         });
-        ContractStorage::insert_contract(contract);
+        ContractStorage::insert_contract(contract).unwrap();
 
         assert!(ContractStorage::update_restricted_contract_property(
             &1_u64.into(),
@@ -366,23 +965,129 @@ mod test {
     #[test]
     fn test_should_close_contract_and_not_return_it() {
         let contract = with_mock_contract(1, 1, |_| {});
-        ContractStorage::insert_contract(contract.clone());
+        ContractStorage::insert_contract(contract.clone()).unwrap();
 
         assert!(ContractStorage::get_contract(&contract.id).is_some());
         assert!(ContractStorage::close_contract(&contract.id).is_ok());
         assert!(ContractStorage::get_contract(&contract.id).is_none());
+        assert!(
+            ContractStorage::get_any_contract(&contract.id)
+                .unwrap()
+                .closed
+        );
+    }
+
+    #[test]
+    fn test_should_list_contract_archive() {
+        ContractStorage::insert_contract(with_mock_contract(1, 1, |_| {})).unwrap();
+        ContractStorage::insert_contract(with_mock_contract(2, 1, |contract| {
+            contract.closed = true;
+        }))
+        .unwrap();
+
+        assert_eq!(
+            ContractStorage::get_contract_archive(|_| true),
+            vec![
+                ContractArchiveEntry {
+                    id: Nat::from(1u64),
+                    closed: false,
+                },
+                ContractArchiveEntry {
+                    id: Nat::from(2u64),
+                    closed: true,
+                },
+            ]
+        );
+        assert_eq!(ContractStorage::get_contract_archive(|_| false), vec![]);
+    }
+
+    #[test]
+    fn test_should_get_contract_events() {
+        ContractStorage::insert_contract(with_mock_contract(1, 1, |contract| {
+            contract.created_at = Some(10);
+        }))
+        .unwrap();
+        ContractStorage::insert_contract(with_mock_contract(2, 1, |contract| {
+            contract.created_at = Some(20);
+        }))
+        .unwrap();
+        ContractStorage::insert_contract(with_mock_contract(3, 1, |_| {})).unwrap();
+        ContractStorage::close_contract(&1_u64.into()).unwrap();
+
+        let events = ContractStorage::get_contract_events(|_| true, 10)
+            .into_iter()
+            .map(|event| (event.kind, event.contract.id))
+            .collect::<Vec<_>>();
+        assert_eq!(
+            events,
+            vec![
+                (ContractEventKind::Closed, Nat::from(1u64)),
+                (ContractEventKind::Created, Nat::from(2u64)),
+                (ContractEventKind::Created, Nat::from(1u64)),
+            ]
+        );
+
+        let events = ContractStorage::get_contract_events(|contract| contract.id == 2u64, 10);
+        assert_eq!(events.len(), 1);
+        assert_eq!(events[0].timestamp, 20);
+        assert_eq!(ContractStorage::get_contract_events(|_| true, 2).len(), 2);
+
+        // the creation event follows the creation time
+        ContractStorage::insert_contract(with_mock_contract(2, 1, |contract| {
+            contract.created_at = Some(5);
+        }))
+        .unwrap();
+        let events = ContractStorage::get_contract_events(|_| true, 10)
+            .into_iter()
+            .map(|event| (event.timestamp, event.contract.id))
+            .collect::<Vec<_>>();
+        assert_eq!(
+            events[1..].to_vec(),
+            vec![(10, Nat::from(1u64)), (5, Nat::from(2u64))]
+        );
+    }
+
+    #[test]
+    fn test_should_build_event_index() {
+        // contracts stored before the event index was introduced
+        with_contracts_mut(|contracts| {
+            for (id, created_at) in [(1u64, 10), (2, 20)] {
+                let contract = with_mock_contract(id, 1, |contract| {
+                    contract.created_at = Some(created_at);
+                });
+                contracts.insert(contract.id.clone().into(), contract);
+            }
+        });
+        assert!(ContractStorage::get_contract_events(|_| true, 10).is_empty());
+
+        ContractStorage::build_indexes().unwrap();
+
+        let events = ContractStorage::get_contract_events(|_| true, 10)
+            .into_iter()
+            .map(|event| (event.kind, event.timestamp))
+            .collect::<Vec<_>>();
+        assert_eq!(
+            events,
+            vec![
+                (ContractEventKind::Created, 20),
+                (ContractEventKind::Created, 10)
+            ]
+        );
     }
 
     #[test]
     fn test_should_upload_contract_document() {
         let contract = with_mock_contract(1, 1, |_| {});
-        ContractStorage::insert_contract(contract.clone());
+        ContractStorage::insert_contract(contract.clone()).unwrap();
 
         let document = ContractDocument {
             mime_type: "application/pdf".to_string(),
             access_list: vec![RestrictionLevel::Seller],
             name: "contract.pdf".to_string(),
             size: 4,
+            previous_version: None,
+            superseded_by: None,
+            digest: None,
         };
 
         let document_id =
@@ -400,13 +1105,16 @@ mod test {
     #[test]
     fn test_should_not_upload_contract_document_if_size_is_wrong() {
         let contract = with_mock_contract(1, 1, |_| {});
-        ContractStorage::insert_contract(contract.clone());
+        ContractStorage::insert_contract(contract.clone()).unwrap();
 
         let document = ContractDocument {
             mime_type: "application/pdf".to_string(),
             access_list: vec![RestrictionLevel::Seller],
             name: "contract.pdf".to_string(),
             size: 100,
+            previous_version: None,
+            superseded_by: None,
+            digest: None,
         };
 
         let result =
@@ -417,4 +1125,431 @@ mod test {
             DeferredDataError::Contract(DataContractError::DocumentSizeMismatch(100, 4))
         );
     }
+
+    #[test]
+    fn test_should_upload_contract_document_in_chunks() {
+        let contract = with_mock_contract(1, 1, |_| {});
+        ContractStorage::insert_contract(contract.clone()).unwrap();
+
+        let document = ContractDocument {
+            mime_type: "application/pdf".to_string(),
+            access_list: vec![RestrictionLevel::Seller],
+            name: "contract.pdf".to_string(),
+            size: 6,
+            previous_version: None,
+            superseded_by: None,
+            digest: None,
+        };
+
+        let upload_id = ContractStorage::begin_contract_document_upload(&1_u64.into(), document)
+            .expect("Failed to begin upload");
+        assert_eq!(
+            ContractStorage::get_contract_document_upload_contract(upload_id).unwrap(),
+            Nat::from(1u64)
+        );
+        ContractStorage::upload_contract_document_chunk(upload_id, 0, vec![1, 2, 3]).unwrap();
+
+        // size mismatch; session is kept
+        assert_eq!(
+            ContractStorage::finalize_contract_document_upload(upload_id).unwrap_err(),
+            DeferredDataError::Contract(DataContractError::DocumentSizeMismatch(6, 3))
+        );
+
+        ContractStorage::upload_contract_document_chunk(upload_id, 1, vec![4, 5, 6]).unwrap();
+        let document_id = ContractStorage::finalize_contract_document_upload(upload_id)
+            .expect("Failed to finalize upload");
+
+        let contract_document = ContractStorage::get_contract_document(&1_u64.into(), document_id)
+            .expect("Failed to get contract document");
+        assert_eq!(contract_document.data, vec![1, 2, 3, 4, 5, 6]);
+
+        // session is removed
+        assert_eq!(
+            ContractStorage::finalize_contract_document_upload(upload_id).unwrap_err(),
+            DeferredDataError::Contract(DataContractError::UploadNotFound(upload_id))
+        );
+    }
+
+    #[test]
+    fn test_should_not_begin_upload_for_unexisting_contract() {
+        let document = ContractDocument {
+            mime_type: "application/pdf".to_string(),
+            access_list: vec![RestrictionLevel::Seller],
+            name: "contract.pdf".to_string(),
+            size: 6,
+            previous_version: None,
+            superseded_by: None,
+            digest: None,
+        };
+
+        assert_eq!(
+            ContractStorage::begin_contract_document_upload(&1_u64.into(), document).unwrap_err(),
+            DeferredDataError::Contract(DataContractError::ContractNotFound(1_u64.into()))
+        );
+    }
+
+    #[test]
+    fn test_should_replace_contract_document() {
+        let contract = with_mock_contract(1, 1, |_| {});
+        ContractStorage::insert_contract(contract.clone()).unwrap();
+
+        let document = ContractDocument {
+            mime_type: "application/pdf".to_string(),
+            access_list: vec![RestrictionLevel::Seller],
+            name: "contract.pdf".to_string(),
+            size: 4,
+            previous_version: None,
+            superseded_by: None,
+            digest: None,
+        };
+
+        let first_id = ContractStorage::upload_contract_document(
+            &1_u64.into(),
+            document.clone(),
+            vec![1, 2, 3, 4],
+        )
+        .unwrap();
+        let second_id = ContractStorage::replace_contract_document(
+            &1_u64.into(),
+            first_id,
+            document.clone(),
+            vec![4, 3, 2, 1],
+        )
+        .unwrap();
+
+        let contract = ContractStorage::get_contract(&1_u64.into()).unwrap();
+        assert_eq!(contract.documents.len(), 2);
+        assert_eq!(contract.documents[0].1.superseded_by, Some(second_id));
+        assert_eq!(contract.documents[1].1.previous_version, Some(first_id));
+        assert_eq!(contract.documents[1].1.superseded_by, None);
+
+        // previous revision data is still available
+        assert_eq!(
+            ContractStorage::get_contract_document(&1_u64.into(), first_id)
+                .unwrap()
+                .data,
+            vec![1, 2, 3, 4]
+        );
+        assert_eq!(
+            ContractStorage::get_contract_document(&1_u64.into(), second_id)
+                .unwrap()
+                .data,
+            vec![4, 3, 2, 1]
+        );
+
+        // superseded revisions can't be replaced
+        assert_eq!(
+            ContractStorage::replace_contract_document(
+                &1_u64.into(),
+                first_id,
+                document.clone(),
+                vec![1, 2, 3, 4]
+            )
+            .unwrap_err(),
+            DeferredDataError::Contract(DataContractError::DocumentSuperseded(first_id))
+        );
+        assert_eq!(
+            ContractStorage::replace_contract_document(
+                &1_u64.into(),
+                999,
+                document,
+                vec![1, 2, 3, 4]
+            )
+            .unwrap_err(),
+            DeferredDataError::Contract(DataContractError::DocumentNotFound(999))
+        );
+    }
+
+    #[test]
+    fn test_should_delete_latest_contract_document_revision() {
+        let contract = with_mock_contract(1, 1, |_| {});
+        ContractStorage::insert_contract(contract.clone()).unwrap();
+
+        let document = ContractDocument {
+            mime_type: "application/pdf".to_string(),
+            access_list: vec![RestrictionLevel::Seller],
+            name: "contract.pdf".to_string(),
+            size: 4,
+            previous_version: None,
+            superseded_by: None,
+            digest: None,
+        };
+
+        let other_id = ContractStorage::upload_contract_document(
+            &1_u64.into(),
+            document.clone(),
+            vec![0, 0, 0, 0],
+        )
+        .unwrap();
+        let first_id = ContractStorage::upload_contract_document(
+            &1_u64.into(),
+            document.clone(),
+            vec![1, 2, 3, 4],
+        )
+        .unwrap();
+        let second_id = ContractStorage::replace_contract_document(
+            &1_u64.into(),
+            first_id,
+            document,
+            vec![4, 3, 2, 1],
+        )
+        .unwrap();
+
+        // superseded revisions can't be deleted
+        assert_eq!(
+            ContractStorage::delete_contract_document(&1_u64.into(), first_id).unwrap_err(),
+            DeferredDataError::Contract(DataContractError::DocumentSuperseded(first_id))
+        );
+
+        ContractStorage::delete_contract_document(&1_u64.into(), second_id).unwrap();
+
+        // the previous revision is kept and becomes the latest revision again
+        let contract = ContractStorage::get_contract(&1_u64.into()).unwrap();
+        assert_eq!(contract.documents.len(), 2);
+        assert_eq!(contract.documents[0].0, other_id);
+        assert_eq!(contract.documents[1].0, first_id);
+        assert_eq!(contract.documents[1].1.superseded_by, None);
+
+        assert!(DocumentStorage::get_document(first_id).is_ok());
+        assert!(DocumentStorage::get_document(second_id).is_err());
+        assert!(DocumentStorage::get_document(other_id).is_ok());
+
+        // which can be deleted in turn
+        ContractStorage::delete_contract_document(&1_u64.into(), first_id).unwrap();
+        let contract = ContractStorage::get_contract(&1_u64.into()).unwrap();
+        assert_eq!(contract.documents.len(), 1);
+        assert!(DocumentStorage::get_document(first_id).is_err());
+    }
+
+    #[test]
+    fn test_should_deduplicate_documents_across_contracts() {
+        ContractStorage::insert_contract(with_mock_contract(1, 1, |_| {})).unwrap();
+        ContractStorage::insert_contract(with_mock_contract(2, 1, |_| {})).unwrap();
+
+        let document = ContractDocument {
+            mime_type: "text/plain".to_string(),
+            access_list: vec![RestrictionLevel::Seller],
+            name: "hello.txt".to_string(),
+            size: 5,
+            previous_version: None,
+            superseded_by: None,
+            digest: None,
+        };
+
+        let first_id = ContractStorage::upload_contract_document(
+            &1_u64.into(),
+            document.clone(),
+            b"hello".to_vec(),
+        )
+        .unwrap();
+        let second_id =
+            ContractStorage::upload_contract_document(&2_u64.into(), document, b"hello".to_vec())
+                .unwrap();
+
+        let expected_digest = "2cf24dba5fb0a30e26e83b2ac5b9e29e1b161e5c1fa7425e73043362938b9824";
+        let contract = ContractStorage::get_contract(&2_u64.into()).unwrap();
+        assert_eq!(
+            contract.documents[0].1.digest.as_deref(),
+            Some(expected_digest)
+        );
+        assert_eq!(
+            hex::encode(
+                ContractStorage::get_contract_document_digest(&1_u64.into(), first_id).unwrap()
+            ),
+            expected_digest
+        );
+
+        // deleting a document doesn't affect the other contract
+        ContractStorage::delete_contract_document(&1_u64.into(), first_id).unwrap();
+        assert_eq!(
+            ContractStorage::get_contract_document(&2_u64.into(), second_id)
+                .unwrap()
+                .data,
+            b"hello".to_vec()
+        );
+
+        // document must belong to the contract
+        assert_eq!(
+            ContractStorage::get_contract_document_digest(&1_u64.into(), second_id).unwrap_err(),
+            DeferredDataError::Contract(DataContractError::DocumentNotFound(second_id))
+        );
+    }
+
+    #[test]
+    fn test_should_update_restricted_contract_property_access() {
+        let contract = with_mock_contract(1, 1, |contract| {
+            contract.restricted_properties.push((
+                "contract:address".to_string(),
+                RestrictedProperty {
+                    access_list: vec![RestrictionLevel::Agent],
+                    value: GenericValue::TextContent("via roma 1".to_string()),
+                },
+            ));
+        });
+        ContractStorage::insert_contract(contract.clone()).unwrap();
+
+        ContractStorage::update_restricted_contract_property_access(
+            &contract.id,
+            "contract:address",
+            vec![RestrictionLevel::Agent, RestrictionLevel::Buyer],
+        )
+        .unwrap();
+
+        let contract = ContractStorage::get_contract(&contract.id).unwrap();
+        let (_, property) = contract
+            .restricted_properties
+            .iter()
+            .find(|(key, _)| key == "contract:address")
+            .unwrap();
+        assert_eq!(
+            property.access_list,
+            vec![RestrictionLevel::Agent, RestrictionLevel::Buyer]
+        );
+        assert_eq!(
+            property.value,
+            GenericValue::TextContent("via roma 1".to_string())
+        );
+
+        assert_eq!(
+            ContractStorage::update_restricted_contract_property_access(
+                &contract.id,
+                "contract:unknown",
+                vec![]
+            )
+            .unwrap_err(),
+            DeferredDataError::Contract(DataContractError::PropertyNotFound(
+                "contract:unknown".to_string()
+            ))
+        );
+    }
+
+    #[test]
+    fn test_should_update_contract_document_access() {
+        let contract = with_mock_contract(1, 1, |_| {});
+        ContractStorage::insert_contract(contract.clone()).unwrap();
+
+        let document = ContractDocument {
+            mime_type: "application/pdf".to_string(),
+            access_list: vec![RestrictionLevel::Agent],
+            name: "contract.pdf".to_string(),
+            size: 4,
+            previous_version: None,
+            superseded_by: None,
+            digest: None,
+        };
+        let document_id =
+            ContractStorage::upload_contract_document(&contract.id, document, vec![1, 2, 3, 4])
+                .unwrap();
+
+        ContractStorage::update_contract_document_access(
+            &contract.id,
+            document_id,
+            vec![RestrictionLevel::Agent, RestrictionLevel::Buyer],
+        )
+        .unwrap();
+
+        let contract = ContractStorage::get_contract(&contract.id).unwrap();
+        assert_eq!(
+            contract.documents[0].1.access_list,
+            vec![RestrictionLevel::Agent, RestrictionLevel::Buyer]
+        );
+
+        assert_eq!(
+            ContractStorage::update_contract_document_access(&contract.id, 999, vec![])
+                .unwrap_err(),
+            DeferredDataError::Contract(DataContractError::DocumentNotFound(999))
+        );
+    }
+
+    #[test]
+    fn test_should_update_contract_properties_in_batch() {
+        let contract = with_mock_contract(1, 1, |contract| {
+            contract.properties = vec![
+                (
+                    "contract:city".to_string(),
+                    GenericValue::TextContent("Rome".to_string()),
+                ),
+                ("contract:rooms".to_string(), GenericValue::Nat64Content(3)),
+            ];
+            contract.restricted_properties = vec![(
+                "contract:address".to_string(),
+                RestrictedProperty {
+                    access_list: vec![RestrictionLevel::Agent],
+                    value: GenericValue::TextContent("via roma 1".to_string()),
+                },
+            )];
+        });
+        ContractStorage::insert_contract(contract.clone()).unwrap();
+
+        let restricted = RestrictedProperty {
+            access_list: vec![RestrictionLevel::Buyer],
+            value: GenericValue::TextContent("secret".to_string()),
+        };
+        ContractStorage::update_contract_properties(
+            &contract.id,
+            vec![
+                PropertyOperation::Set {
+                    key: "contract:city".to_string(),
+                    value: GenericValue::TextContent("Milan".to_string()),
+                },
+                PropertyOperation::Remove {
+                    key: "contract:rooms".to_string(),
+                },
+                PropertyOperation::RemoveRestricted {
+                    key: "contract:address".to_string(),
+                },
+                PropertyOperation::SetRestricted {
+                    key: "contract:notes".to_string(),
+                    value: restricted.clone(),
+                },
+            ],
+        )
+        .unwrap();
+
+        let stored = ContractStorage::get_contract(&contract.id).unwrap();
+        assert_eq!(
+            stored.properties,
+            vec![(
+                "contract:city".to_string(),
+                GenericValue::TextContent("Milan".to_string())
+            )]
+        );
+        assert_eq!(
+            stored.restricted_properties,
+            vec![("contract:notes".to_string(), restricted)]
+        );
+    }
+
+    #[test]
+    fn test_should_not_apply_batch_partially() {
+        let contract = with_mock_contract(1, 1, |contract| {
+            contract.properties = vec![(
+                "contract:city".to_string(),
+                GenericValue::TextContent("Rome".to_string()),
+            )];
+        });
+        ContractStorage::insert_contract(contract.clone()).unwrap();
+
+        assert_eq!(
+            ContractStorage::update_contract_properties(
+                &contract.id,
+                vec![
+                    PropertyOperation::Set {
+                        key: "contract:city".to_string(),
+                        value: GenericValue::TextContent("Milan".to_string()),
+                    },
+                    PropertyOperation::Remove {
+                        key: "contract:unknown".to_string(),
+                    },
+                ],
+            )
+            .unwrap_err(),
+            DeferredDataError::Contract(DataContractError::PropertyNotFound(
+                "contract:unknown".to_string()
+            ))
+        );
+
+        let stored = ContractStorage::get_contract(&contract.id).unwrap();
+        assert_eq!(stored.properties, contract.properties);
+    }
 }
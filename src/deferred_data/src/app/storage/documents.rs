@@ -1,30 +1,227 @@
+use candid::{CandidType, Decode, Deserialize, Encode};
 use did::deferred::{DataContractError, DeferredDataError, DeferredDataResult};
+use ic_stable_structures::storable::Bound;
+use ic_stable_structures::Storable;
+use sha2::{Digest as _, Sha256};
 
-use super::{DOCUMENTS, NEXT_DOCUMENT_ID};
+use super::{DOCUMENTS, DOCUMENT_BLOBS, DOCUMENT_CHUNKS, DOCUMENT_DIGESTS, NEXT_DOCUMENT_ID};
+
+/// Size of the chunks the documents data is stored in (1 MiB)
+const DOCUMENT_CHUNK_SIZE: u64 = 1024 * 1024;
+
+/// Key of a chunk of the documents data (blob ID, chunk index)
+pub type DocumentChunkKey = (u64, u64);
+
+/// Reference to the data shared by all the documents with the same digest
+#[derive(Clone, Debug, CandidType, Deserialize, PartialEq)]
+pub struct DocumentBlob {
+    /// The key of the data in the documents storage
+    pub blob_id: u64,
+    /// Amount of documents referencing the data
+    pub references: u64,
+    /// Size of the data, which is stored in chunks.
+    /// Not set for the data stored as a single value before chunking was introduced.
+    pub size: Option<u64>,
+}
+
+/// Where the data of a document is stored
+enum DocumentData {
+    /// A single value of the documents storage
+    Single { blob_id: u64 },
+    /// Chunks of [`DOCUMENT_CHUNK_SIZE`] bytes
+    Chunked { blob_id: u64, size: u64 },
+}
+
+impl Storable for DocumentBlob {
+    const BOUND: Bound = Bound::Unbounded;
+
+    fn to_bytes(&self) -> std::borrow::Cow<[u8]> {
+        Encode!(&self).unwrap().into()
+    }
+
+    fn from_bytes(bytes: std::borrow::Cow<[u8]>) -> Self {
+        Decode!(&bytes, Self).unwrap()
+    }
+}
 
 pub struct DocumentStorage;
 
 impl DocumentStorage {
     /// Get a document from the storage
     pub fn get_document(id: u64) -> DeferredDataResult<Vec<u8>> {
-        DOCUMENTS.with_borrow(|documents| {
-            documents.get(&id).ok_or(DeferredDataError::Contract(
-                DataContractError::DocumentNotFound(id),
-            ))
-        })
+        Self::get_document_range(id, 0, u64::MAX)
+    }
+
+    /// Get the bytes of a document between `start` and `end` (exclusive).
+    ///
+    /// Only the chunks overlapping the range are read; the range is clamped to the document size.
+    pub fn get_document_range(id: u64, start: u64, end: u64) -> DeferredDataResult<Vec<u8>> {
+        match Self::document_data(id) {
+            DocumentData::Single { blob_id } => {
+                let data = DOCUMENTS
+                    .with_borrow(|documents| documents.get(&blob_id))
+                    .ok_or(DeferredDataError::Contract(
+                        DataContractError::DocumentNotFound(id),
+                    ))?;
+                let end = end.min(data.len() as u64);
+                let start = start.min(end);
+
+                Ok(data[start as usize..end as usize].to_vec())
+            }
+            DocumentData::Chunked { blob_id, size } => {
+                let end = end.min(size);
+                let start = start.min(end);
+                let mut data = Vec::with_capacity((end - start) as usize);
+                if start == end {
+                    return Ok(data);
+                }
+
+                let first_chunk = start / DOCUMENT_CHUNK_SIZE;
+                let last_chunk = (end - 1) / DOCUMENT_CHUNK_SIZE;
+                DOCUMENT_CHUNKS.with_borrow(|chunks| {
+                    for ((_, chunk_index), chunk) in
+                        chunks.range((blob_id, first_chunk)..=(blob_id, last_chunk))
+                    {
+                        let chunk_start = chunk_index * DOCUMENT_CHUNK_SIZE;
+                        let from = start.saturating_sub(chunk_start) as usize;
+                        let to = (end - chunk_start).min(chunk.len() as u64) as usize;
+                        data.extend_from_slice(&chunk[from..to]);
+                    }
+                });
+
+                if data.len() as u64 != end - start {
+                    return Err(DeferredDataError::StorageError);
+                }
+
+                Ok(data)
+            }
+        }
+    }
+
+    /// Get the SHA-256 digest of a document.
+    ///
+    /// Documents uploaded before digests were introduced have their digest computed on the fly.
+    pub fn get_document_digest(id: u64) -> DeferredDataResult<[u8; 32]> {
+        if let Some(digest) = DOCUMENT_DIGESTS.with_borrow(|digests| digests.get(&id)) {
+            return Ok(digest);
+        }
+
+        Self::get_document(id).map(|data| Self::digest(&data))
     }
 
     /// Upload a document into the storage.
     ///
-    /// Returns the ID of the uploaded document.
-    pub fn upload_document(data: Vec<u8>) -> DeferredDataResult<u64> {
-        // insert document
+    /// If a document with the same digest already exists, its data is shared with the new document.
+    ///
+    /// Returns the ID of the uploaded document and its SHA-256 digest.
+    pub fn upload_document(data: Vec<u8>) -> DeferredDataResult<(u64, [u8; 32])> {
+        let digest = Self::digest(&data);
         let next_id = Self::next_document_id()?;
-        DOCUMENTS.with_borrow_mut(|documents| {
-            documents.insert(next_id, data);
+
+        DOCUMENT_BLOBS.with_borrow_mut(|blobs| {
+            let blob = match blobs.get(&digest) {
+                Some(mut blob) => {
+                    blob.references += 1;
+                    blob
+                }
+                None => {
+                    // data is stored with its own key, so it outlives the document which uploaded it
+                    let blob_id = Self::next_document_id()?;
+                    DOCUMENT_CHUNKS.with_borrow_mut(|chunks| {
+                        for (chunk_index, chunk) in
+                            data.chunks(DOCUMENT_CHUNK_SIZE as usize).enumerate()
+                        {
+                            chunks.insert((blob_id, chunk_index as u64), chunk.to_vec());
+                        }
+                    });
+                    DocumentBlob {
+                        blob_id,
+                        references: 1,
+                        size: Some(data.len() as u64),
+                    }
+                }
+            };
+            blobs.insert(digest, blob);
+
+            Ok::<(), DeferredDataError>(())
+        })?;
+        DOCUMENT_DIGESTS.with_borrow_mut(|digests| {
+            digests.insert(next_id, digest);
+        });
+
+        Ok((next_id, digest))
+    }
+
+    /// Remove a document from the storage.
+    ///
+    /// The data is reclaimed once no other document references it.
+    pub fn remove_document(id: u64) {
+        let Some(digest) = DOCUMENT_DIGESTS.with_borrow_mut(|digests| digests.remove(&id)) else {
+            // document has no digest, so its data is not shared
+            DOCUMENTS.with_borrow_mut(|documents| {
+                documents.remove(&id);
+            });
+            return;
+        };
+
+        DOCUMENT_BLOBS.with_borrow_mut(|blobs| {
+            let Some(mut blob) = blobs.get(&digest) else {
+                return;
+            };
+
+            blob.references = blob.references.saturating_sub(1);
+            if blob.references == 0 {
+                blobs.remove(&digest);
+                if blob.size.is_some() {
+                    Self::remove_chunks(blob.blob_id);
+                } else {
+                    DOCUMENTS.with_borrow_mut(|documents| {
+                        documents.remove(&blob.blob_id);
+                    });
+                }
+            } else {
+                blobs.insert(digest, blob);
+            }
+        });
+    }
+
+    /// Remove the chunks of the data stored with `blob_id`
+    fn remove_chunks(blob_id: u64) {
+        DOCUMENT_CHUNKS.with_borrow_mut(|chunks| {
+            let keys: Vec<_> = chunks
+                .range((blob_id, 0)..=(blob_id, u64::MAX))
+                .map(|(key, _)| key)
+                .collect();
+            for key in keys {
+                chunks.remove(&key);
+            }
         });
+    }
+
+    /// Get where the document data is stored.
+    ///
+    /// Documents uploaded before digests were introduced are stored with their own ID.
+    fn document_data(id: u64) -> DocumentData {
+        let blob = DOCUMENT_DIGESTS
+            .with_borrow(|digests| digests.get(&id))
+            .and_then(|digest| DOCUMENT_BLOBS.with_borrow(|blobs| blobs.get(&digest)));
 
-        Ok(next_id)
+        match blob {
+            Some(DocumentBlob {
+                blob_id,
+                size: Some(size),
+                ..
+            }) => DocumentData::Chunked { blob_id, size },
+            Some(blob) => DocumentData::Single {
+                blob_id: blob.blob_id,
+            },
+            None => DocumentData::Single { blob_id: id },
+        }
+    }
+
+    /// Compute the SHA-256 digest of the document data
+    fn digest(data: &[u8]) -> [u8; 32] {
+        Sha256::digest(data).into()
     }
 
     /// Get next document ID and increment it
@@ -49,10 +246,141 @@ mod test {
     #[test]
     fn test_document_storage() {
         let data = vec![1, 2, 3, 4, 5];
-        let id = DocumentStorage::upload_document(data.clone()).unwrap();
+        let (id, _) = DocumentStorage::upload_document(data.clone()).unwrap();
         let stored_data = DocumentStorage::get_document(id).unwrap();
 
         assert_eq!(data, stored_data);
-        assert_eq!(DocumentStorage::next_document_id().unwrap(), 1);
+        // one ID for the document and one for its data
+        assert_eq!(DocumentStorage::next_document_id().unwrap(), 2);
+    }
+
+    #[test]
+    fn test_should_get_document_range() {
+        let data: Vec<u8> = (0..(DOCUMENT_CHUNK_SIZE * 2 + 10))
+            .map(|i| (i % 256) as u8)
+            .collect();
+        let (id, _) = DocumentStorage::upload_document(data.clone()).unwrap();
+        assert_eq!(DOCUMENT_CHUNKS.with_borrow(|chunks| chunks.len()), 3);
+
+        // range across the chunk boundaries
+        let start = DOCUMENT_CHUNK_SIZE - 5;
+        let end = DOCUMENT_CHUNK_SIZE * 2 + 5;
+        assert_eq!(
+            DocumentStorage::get_document_range(id, start, end).unwrap(),
+            data[start as usize..end as usize].to_vec()
+        );
+        // range is clamped to the document size
+        assert_eq!(
+            DocumentStorage::get_document_range(id, DOCUMENT_CHUNK_SIZE * 2, u64::MAX).unwrap(),
+            data[(DOCUMENT_CHUNK_SIZE * 2) as usize..].to_vec()
+        );
+        assert!(DocumentStorage::get_document_range(id, u64::MAX, u64::MAX)
+            .unwrap()
+            .is_empty());
+        assert_eq!(DocumentStorage::get_document(id).unwrap(), data);
+
+        DocumentStorage::remove_document(id);
+        assert_eq!(DOCUMENT_CHUNKS.with_borrow(|chunks| chunks.len()), 0);
+    }
+
+    #[test]
+    fn test_should_get_empty_document() {
+        let (id, _) = DocumentStorage::upload_document(vec![]).unwrap();
+
+        assert!(DocumentStorage::get_document(id).unwrap().is_empty());
+    }
+
+    #[test]
+    fn test_should_remove_document() {
+        let (id, _) = DocumentStorage::upload_document(vec![1, 2, 3]).unwrap();
+        DocumentStorage::remove_document(id);
+
+        assert_eq!(
+            DocumentStorage::get_document(id).unwrap_err(),
+            DeferredDataError::Contract(DataContractError::DocumentNotFound(id))
+        );
+    }
+
+    #[test]
+    fn test_should_compute_document_digest() {
+        let (id, digest) = DocumentStorage::upload_document(b"hello".to_vec()).unwrap();
+
+        assert_eq!(
+            hex::encode(digest),
+            "2cf24dba5fb0a30e26e83b2ac5b9e29e1b161e5c1fa7425e73043362938b9824"
+        );
+        assert_eq!(DocumentStorage::get_document_digest(id).unwrap(), digest);
+    }
+
+    #[test]
+    fn test_should_compute_digest_of_documents_without_digest() {
+        DOCUMENTS.with_borrow_mut(|documents| {
+            documents.insert(100, b"hello".to_vec());
+        });
+
+        assert_eq!(
+            hex::encode(DocumentStorage::get_document_digest(100).unwrap()),
+            "2cf24dba5fb0a30e26e83b2ac5b9e29e1b161e5c1fa7425e73043362938b9824"
+        );
+        assert_eq!(DocumentStorage::get_document(100).unwrap(), b"hello");
+        assert_eq!(
+            DocumentStorage::get_document_range(100, 1, 3).unwrap(),
+            b"el"
+        );
+
+        DocumentStorage::remove_document(100);
+        assert!(DocumentStorage::get_document(100).is_err());
+    }
+
+    #[test]
+    fn test_should_get_documents_stored_before_chunking() {
+        let digest = DocumentStorage::digest(b"hello");
+        DOCUMENTS.with_borrow_mut(|documents| {
+            documents.insert(200, b"hello".to_vec());
+        });
+        DOCUMENT_BLOBS.with_borrow_mut(|blobs| {
+            blobs.insert(
+                digest,
+                DocumentBlob {
+                    blob_id: 200,
+                    references: 1,
+                    size: None,
+                },
+            );
+        });
+        DOCUMENT_DIGESTS.with_borrow_mut(|digests| {
+            digests.insert(201, digest);
+        });
+
+        assert_eq!(DocumentStorage::get_document(201).unwrap(), b"hello");
+        assert_eq!(
+            DocumentStorage::get_document_range(201, 3, 10).unwrap(),
+            b"lo"
+        );
+
+        DocumentStorage::remove_document(201);
+        assert!(DOCUMENTS.with_borrow(|documents| documents.is_empty()));
+    }
+
+    #[test]
+    fn test_should_deduplicate_documents() {
+        let (first_id, first_digest) = DocumentStorage::upload_document(vec![1, 2, 3]).unwrap();
+        let (second_id, second_digest) = DocumentStorage::upload_document(vec![1, 2, 3]).unwrap();
+
+        assert_ne!(first_id, second_id);
+        assert_eq!(first_digest, second_digest);
+        assert_eq!(DOCUMENT_CHUNKS.with_borrow(|chunks| chunks.len()), 1);
+
+        // data is kept until the last reference is removed
+        DocumentStorage::remove_document(first_id);
+        assert!(DocumentStorage::get_document(first_id).is_err());
+        assert_eq!(
+            DocumentStorage::get_document(second_id).unwrap(),
+            vec![1, 2, 3]
+        );
+
+        DocumentStorage::remove_document(second_id);
+        assert!(DocumentStorage::get_document(second_id).is_err());
+        assert_eq!(DOCUMENT_CHUNKS.with_borrow(|chunks| chunks.len()), 0);
     }
 }
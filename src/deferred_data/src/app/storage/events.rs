@@ -0,0 +1,177 @@
+use std::borrow::Cow;
+
+use did::deferred::{Contract, DeferredDataResult};
+use did::ID;
+use ic_stable_structures::storable::Bound;
+use ic_stable_structures::Storable;
+
+use super::{
+    contract_id_key, with_contracts, AuditLogStorage, CONTRACT_EVENTS, CONTRACT_ID_KEY_SIZE,
+};
+
+/// What happened to a contract
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum ContractEventKind {
+    Created,
+    Closed,
+}
+
+/// An event in the life of a contract, as published in the contract feed
+#[derive(Debug, Clone, PartialEq)]
+pub struct ContractEvent {
+    pub kind: ContractEventKind,
+    /// Time of the event (nanoseconds since UNIX epoch)
+    pub timestamp: u64,
+    pub contract: Contract,
+}
+
+/// Key of the event index, sorted by time, then by contract ID and kind
+#[derive(Clone, Debug, PartialEq, Eq, PartialOrd, Ord)]
+pub struct EventKey {
+    timestamp: u64,
+    contract_id: [u8; CONTRACT_ID_KEY_SIZE],
+    kind: u8,
+}
+
+impl EventKey {
+    fn new(timestamp: u64, contract_id: &ID, kind: ContractEventKind) -> DeferredDataResult<Self> {
+        Ok(Self {
+            timestamp,
+            contract_id: contract_id_key(contract_id)?,
+            kind: match kind {
+                ContractEventKind::Created => 0,
+                ContractEventKind::Closed => 1,
+            },
+        })
+    }
+
+    fn kind(&self) -> ContractEventKind {
+        match self.kind {
+            0 => ContractEventKind::Created,
+            _ => ContractEventKind::Closed,
+        }
+    }
+}
+
+impl Storable for EventKey {
+    const BOUND: Bound = Bound::Bounded {
+        max_size: 8 + CONTRACT_ID_KEY_SIZE as u32 + 1,
+        is_fixed_size: true,
+    };
+
+    fn to_bytes(&self) -> Cow<[u8]> {
+        let mut bytes = Vec::with_capacity(8 + CONTRACT_ID_KEY_SIZE + 1);
+        bytes.extend_from_slice(&self.timestamp.to_be_bytes());
+        bytes.extend_from_slice(&self.contract_id);
+        bytes.push(self.kind);
+
+        bytes.into()
+    }
+
+    fn from_bytes(bytes: Cow<[u8]>) -> Self {
+        let mut timestamp = [0; 8];
+        timestamp.copy_from_slice(&bytes[..8]);
+        let mut contract_id = [0; CONTRACT_ID_KEY_SIZE];
+        contract_id.copy_from_slice(&bytes[8..8 + CONTRACT_ID_KEY_SIZE]);
+
+        Self {
+            timestamp: u64::from_be_bytes(timestamp),
+            contract_id,
+            kind: bytes[8 + CONTRACT_ID_KEY_SIZE],
+        }
+    }
+}
+
+/// Time index of the creations and closures of the contracts, published in the contract feed
+pub struct ContractEventStorage;
+
+impl ContractEventStorage {
+    /// Update the events of a contract changed from `previous` to `contract` at time `now`.
+    ///
+    /// The contract is closed at `now` only if it was open before.
+    pub fn update_contract(
+        previous: Option<&Contract>,
+        contract: &Contract,
+        now: u64,
+    ) -> DeferredDataResult<()> {
+        CONTRACT_EVENTS.with_borrow_mut(|events| {
+            let previous_created_at = previous.and_then(|previous| previous.created_at);
+            if previous_created_at != contract.created_at {
+                if let Some(timestamp) = previous_created_at {
+                    events.remove(&EventKey::new(
+                        timestamp,
+                        &contract.id,
+                        ContractEventKind::Created,
+                    )?);
+                }
+                if let Some(timestamp) = contract.created_at {
+                    events.insert(
+                        EventKey::new(timestamp, &contract.id, ContractEventKind::Created)?,
+                        contract.id.clone().into(),
+                    );
+                }
+            }
+
+            if contract.closed && previous.is_some_and(|previous| !previous.closed) {
+                events.insert(
+                    EventKey::new(now, &contract.id, ContractEventKind::Closed)?,
+                    contract.id.clone().into(),
+                );
+            }
+
+            Ok(())
+        })
+    }
+
+    /// Index the events of a contract stored before the index was introduced.
+    ///
+    /// The closing time is taken from the audit log.
+    pub fn index_contract(contract: &Contract) -> DeferredDataResult<()> {
+        CONTRACT_EVENTS.with_borrow_mut(|events| {
+            if let Some(timestamp) = contract.created_at {
+                events.insert(
+                    EventKey::new(timestamp, &contract.id, ContractEventKind::Created)?,
+                    contract.id.clone().into(),
+                );
+            }
+            if let Some(timestamp) = contract
+                .closed
+                .then(|| AuditLogStorage::get_closing_time(&contract.id))
+                .flatten()
+            {
+                events.insert(
+                    EventKey::new(timestamp, &contract.id, ContractEventKind::Closed)?,
+                    contract.id.clone().into(),
+                );
+            }
+
+            Ok(())
+        })
+    }
+
+    /// Get the latest events of the contracts matching the filter, from the newest
+    pub fn get_events(filter: impl Fn(&Contract) -> bool, limit: usize) -> Vec<ContractEvent> {
+        CONTRACT_EVENTS.with_borrow(|events| {
+            with_contracts(|contracts| {
+                events
+                    .iter()
+                    .rev()
+                    .filter_map(|(key, contract_id)| {
+                        let contract = contracts.get(&contract_id)?;
+                        filter(&contract).then(|| ContractEvent {
+                            kind: key.kind(),
+                            timestamp: key.timestamp,
+                            contract,
+                        })
+                    })
+                    .take(limit)
+                    .collect()
+            })
+        })
+    }
+
+    /// Returns whether no event is indexed
+    pub fn is_empty() -> bool {
+        CONTRACT_EVENTS.with_borrow(|events| events.is_empty())
+    }
+}
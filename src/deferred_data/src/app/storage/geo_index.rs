@@ -0,0 +1,307 @@
+use std::borrow::Cow;
+use std::collections::{BTreeMap, BTreeSet};
+
+use candid::{CandidType, Decode, Deserialize, Encode};
+use did::deferred::{Contract, DeferredDataResult, GeoCluster, GeoPoint};
+use did::ID;
+use ic_stable_structures::storable::Bound;
+use ic_stable_structures::Storable;
+
+use super::{contract_id_key, CONTRACT_ID_KEY_SIZE, GEO_INDEX};
+use crate::app::geo::{contract_position, BoundingBox};
+
+/// Cells per degree of the grid the contracts are indexed by (cells of about 11 km)
+const CELLS_PER_DEGREE: u16 = 10;
+const ROWS: u16 = 180 * CELLS_PER_DEGREE;
+const COLUMNS: u16 = 360 * CELLS_PER_DEGREE;
+/// Key of the geographic index, sorted by cell row, cell column and then by contract ID
+#[derive(Clone, Debug, PartialEq, Eq, PartialOrd, Ord)]
+pub struct GeoKey {
+    row: u16,
+    column: u16,
+    contract_id: [u8; CONTRACT_ID_KEY_SIZE],
+}
+
+impl GeoKey {
+    fn new(position: GeoPoint, contract_id: &ID) -> DeferredDataResult<Self> {
+        Ok(Self {
+            row: row(position.latitude),
+            column: column(position.longitude),
+            contract_id: contract_id_key(contract_id)?,
+        })
+    }
+
+    /// Get the range of keys of the cells between `from_column` and `to_column` in `row`
+    fn range(row: u16, from_column: u16, to_column: u16) -> std::ops::RangeInclusive<Self> {
+        Self {
+            row,
+            column: from_column,
+            contract_id: [0; CONTRACT_ID_KEY_SIZE],
+        }..=Self {
+            row,
+            column: to_column,
+            contract_id: [u8::MAX; CONTRACT_ID_KEY_SIZE],
+        }
+    }
+}
+
+impl Storable for GeoKey {
+    const BOUND: Bound = Bound::Bounded {
+        max_size: 4 + CONTRACT_ID_KEY_SIZE as u32,
+        is_fixed_size: true,
+    };
+
+    fn to_bytes(&self) -> Cow<[u8]> {
+        let mut bytes = Vec::with_capacity(4 + CONTRACT_ID_KEY_SIZE);
+        bytes.extend_from_slice(&self.row.to_be_bytes());
+        bytes.extend_from_slice(&self.column.to_be_bytes());
+        bytes.extend_from_slice(&self.contract_id);
+
+        bytes.into()
+    }
+
+    fn from_bytes(bytes: Cow<[u8]>) -> Self {
+        let mut contract_id = [0; CONTRACT_ID_KEY_SIZE];
+        contract_id.copy_from_slice(&bytes[4..]);
+
+        Self {
+            row: u16::from_be_bytes([bytes[0], bytes[1]]),
+            column: u16::from_be_bytes([bytes[2], bytes[3]]),
+            contract_id,
+        }
+    }
+}
+
+/// A contract in the geographic index, with its exact position
+#[derive(Clone, Debug, CandidType, Deserialize, PartialEq)]
+pub struct GeoEntry {
+    pub contract_id: ID,
+    pub position: GeoPoint,
+}
+
+impl Storable for GeoEntry {
+    const BOUND: Bound = Bound::Unbounded;
+
+    fn to_bytes(&self) -> Cow<[u8]> {
+        Encode!(&self).unwrap().into()
+    }
+
+    fn from_bytes(bytes: Cow<[u8]>) -> Self {
+        Decode!(&bytes, Self).unwrap()
+    }
+}
+
+/// Grid index of the positions of the open contracts
+pub struct GeoIndexStorage;
+
+impl GeoIndexStorage {
+    /// Get the IDs of the open contracts inside `bbox`
+    pub fn get_contracts(bbox: &BoundingBox) -> BTreeSet<ID> {
+        let mut contracts = BTreeSet::new();
+        Self::scan(bbox, |entry| {
+            contracts.insert(entry.contract_id);
+        });
+
+        contracts
+    }
+
+    /// Count the open contracts inside `bbox` in the cells of a grid of `cell_size` degrees.
+    ///
+    /// Only the contracts for which `include` returns true are counted.
+    pub fn get_clusters(
+        bbox: &BoundingBox,
+        cell_size: f64,
+        include: impl Fn(&ID) -> bool,
+    ) -> Vec<GeoCluster> {
+        // sum of latitudes, sum of longitudes and count of each cell
+        let mut cells: BTreeMap<(u64, u64), (f64, f64, u64)> = BTreeMap::new();
+        Self::scan(bbox, |entry| {
+            if !include(&entry.contract_id) {
+                return;
+            }
+            let cell = (
+                ((entry.position.latitude + 90.0) / cell_size) as u64,
+                ((entry.position.longitude + 180.0) / cell_size) as u64,
+            );
+            let (latitudes, longitudes, count) = cells.entry(cell).or_default();
+            *latitudes += entry.position.latitude;
+            *longitudes += entry.position.longitude;
+            *count += 1;
+        });
+
+        cells
+            .into_values()
+            .map(|(latitudes, longitudes, count)| GeoCluster {
+                position: GeoPoint {
+                    latitude: latitudes / count as f64,
+                    longitude: longitudes / count as f64,
+                },
+                count,
+            })
+            .collect()
+    }
+
+    /// Update the index after a contract has changed.
+    ///
+    /// Contracts are indexed while they are open and have a position.
+    pub fn update_contract(
+        previous: Option<&Contract>,
+        contract: &Contract,
+    ) -> DeferredDataResult<()> {
+        let indexed_position =
+            |contract: &Contract| contract_position(contract).filter(|_| !contract.closed);
+        let previous_position = previous.and_then(indexed_position);
+        let position = indexed_position(contract);
+        if previous_position == position {
+            return Ok(());
+        }
+
+        GEO_INDEX.with_borrow_mut(|index| {
+            if let Some(previous_position) = previous_position {
+                index.remove(&GeoKey::new(previous_position, &contract.id)?);
+            }
+            if let Some(position) = position {
+                index.insert(
+                    GeoKey::new(position, &contract.id)?,
+                    GeoEntry {
+                        contract_id: contract.id.clone(),
+                        position,
+                    },
+                );
+            }
+
+            Ok(())
+        })
+    }
+
+    /// Returns whether no contract is indexed
+    pub fn is_empty() -> bool {
+        GEO_INDEX.with_borrow(|index| index.is_empty())
+    }
+
+    /// Visit the entries inside `bbox`
+    fn scan(bbox: &BoundingBox, mut f: impl FnMut(GeoEntry)) {
+        let columns = if bbox.crosses_antimeridian() {
+            vec![
+                (column(bbox.min_longitude), COLUMNS - 1),
+                (0, column(bbox.max_longitude)),
+            ]
+        } else {
+            vec![(column(bbox.min_longitude), column(bbox.max_longitude))]
+        };
+
+        GEO_INDEX.with_borrow(|index| {
+            for row in row(bbox.min_latitude)..=row(bbox.max_latitude) {
+                for (from_column, to_column) in columns.iter().copied() {
+                    index
+                        .range(GeoKey::range(row, from_column, to_column))
+                        .map(|(_, entry)| entry)
+                        // cells on the border of the box are partially outside
+                        .filter(|entry| bbox.contains(entry.position))
+                        .for_each(&mut f);
+                }
+            }
+        });
+    }
+}
+
+/// Get the row of the grid cell of a latitude
+fn row(latitude: f64) -> u16 {
+    (((latitude + 90.0) * f64::from(CELLS_PER_DEGREE)) as u16).min(ROWS - 1)
+}
+
+/// Get the column of the grid cell of a longitude
+fn column(longitude: f64) -> u16 {
+    (((longitude + 180.0) * f64::from(CELLS_PER_DEGREE)) as u16).min(COLUMNS - 1)
+}
+
+#[cfg(test)]
+mod test {
+
+    use did::deferred::GenericValue;
+    use pretty_assertions::assert_eq;
+
+    use super::*;
+    use crate::app::storage::ContractStorage;
+    use crate::app::test_utils::with_mock_contract;
+
+    #[test]
+    fn test_should_get_contracts_in_bounding_box() {
+        insert_contract_at(1, 45.46, 9.19); // Milan
+        insert_contract_at(2, 45.07, 7.68); // Turin
+        insert_contract_at(3, 41.90, 12.49); // Rome
+
+        assert_eq!(
+            GeoIndexStorage::get_contracts(&"44.5,7,46,10".parse().unwrap()),
+            BTreeSet::from([ID::from(1u64), 2u64.into()])
+        );
+        assert_eq!(
+            GeoIndexStorage::get_contracts(&BoundingBox::WORLD),
+            BTreeSet::from([ID::from(1u64), 2u64.into(), 3u64.into()])
+        );
+        // in the same cell, but outside the box
+        assert!(
+            GeoIndexStorage::get_contracts(&"45.40,9.10,45.45,9.15".parse().unwrap()).is_empty()
+        );
+    }
+
+    #[test]
+    fn test_should_update_position() {
+        insert_contract_at(1, 45.46, 9.19);
+
+        ContractStorage::update_contract_property(
+            &1u64.into(),
+            "contract:latitude".to_string(),
+            GenericValue::FloatContent(41.90),
+        )
+        .unwrap();
+        ContractStorage::update_contract_property(
+            &1u64.into(),
+            "contract:longitude".to_string(),
+            GenericValue::FloatContent(12.49),
+        )
+        .unwrap();
+
+        assert!(GeoIndexStorage::get_contracts(&"44.5,7,46,10".parse().unwrap()).is_empty());
+        assert_eq!(
+            GeoIndexStorage::get_contracts(&"41,12,42,13".parse().unwrap()),
+            BTreeSet::from([ID::from(1u64)])
+        );
+
+        // closed contracts are removed from the index
+        ContractStorage::close_contract(&1u64.into()).unwrap();
+        assert!(GeoIndexStorage::is_empty());
+    }
+
+    #[test]
+    fn test_should_get_clusters() {
+        insert_contract_at(1, 45.46, 9.19);
+        insert_contract_at(2, 45.48, 9.21);
+        insert_contract_at(3, 41.90, 12.49);
+
+        let clusters = GeoIndexStorage::get_clusters(&BoundingBox::WORLD, 1.0, |_| true);
+        assert_eq!(clusters.len(), 2);
+        assert_eq!(clusters[0].count, 1);
+        assert_eq!(clusters[1].count, 2);
+        assert!((clusters[1].position.latitude - 45.47).abs() < 1e-9);
+
+        let clusters = GeoIndexStorage::get_clusters(&BoundingBox::WORLD, 1.0, |id| *id != 3u64);
+        assert_eq!(clusters.len(), 1);
+    }
+
+    fn insert_contract_at(id: u64, latitude: f64, longitude: f64) {
+        ContractStorage::insert_contract(with_mock_contract(id, 1, |contract| {
+            contract.properties = vec![
+                (
+                    "contract:latitude".to_string(),
+                    GenericValue::FloatContent(latitude),
+                ),
+                (
+                    "contract:longitude".to_string(),
+                    GenericValue::FloatContent(longitude),
+                ),
+            ];
+        }))
+        .unwrap();
+    }
+}
@@ -0,0 +1,209 @@
+use candid::{CandidType, Decode, Deserialize, Encode};
+use did::deferred::{ContractImage, DataContractError, DeferredDataError, DeferredDataResult};
+use did::{StorableNat, ID};
+use ic_stable_structures::storable::Bound;
+use ic_stable_structures::Storable;
+
+use super::{ContractStorage, CONTRACT_IMAGES, IMAGES, IMAGE_THUMBNAILS, NEXT_IMAGE_ID};
+use crate::app::gallery::ImageVariant;
+
+/// The images of the gallery of a contract, in upload order
+#[derive(Clone, Debug, Default, CandidType, Deserialize, PartialEq)]
+pub struct ContractImages(pub Vec<ContractImage>);
+
+impl Storable for ContractImages {
+    const BOUND: Bound = Bound::Unbounded;
+
+    fn to_bytes(&self) -> std::borrow::Cow<[u8]> {
+        Encode!(&self).unwrap().into()
+    }
+
+    fn from_bytes(bytes: std::borrow::Cow<[u8]>) -> Self {
+        Decode!(&bytes, Self).unwrap()
+    }
+}
+
+pub struct ImageStorage;
+
+impl ImageStorage {
+    /// Get the images of the gallery of a contract
+    pub fn get_images(contract_id: &ID) -> Vec<ContractImage> {
+        CONTRACT_IMAGES
+            .with_borrow(|images| images.get(&StorableNat::from(contract_id.clone())))
+            .unwrap_or_default()
+            .0
+    }
+
+    /// Get an image of the gallery of a contract
+    pub fn get_image(contract_id: &ID, image_id: u64) -> DeferredDataResult<ContractImage> {
+        Self::get_images(contract_id)
+            .into_iter()
+            .find(|image| image.id == image_id)
+            .ok_or(DeferredDataError::Contract(
+                DataContractError::ImageNotFound(image_id),
+            ))
+    }
+
+    /// Get the data of a version of an image
+    pub fn get_image_data(image_id: u64, variant: ImageVariant) -> DeferredDataResult<Vec<u8>> {
+        let storage = match variant {
+            ImageVariant::Original => &IMAGES,
+            ImageVariant::Thumbnail => &IMAGE_THUMBNAILS,
+        };
+
+        storage
+            .with_borrow(|images| images.get(&image_id))
+            .ok_or(DeferredDataError::Contract(
+                DataContractError::ImageNotFound(image_id),
+            ))
+    }
+
+    /// Add an image with its thumbnail to the gallery of an open contract.
+    ///
+    /// The ID of the image is set by the storage and returned.
+    pub fn insert_image(
+        contract_id: &ID,
+        mut image: ContractImage,
+        data: Vec<u8>,
+        thumbnail: Vec<u8>,
+    ) -> DeferredDataResult<u64> {
+        if ContractStorage::get_contract(contract_id).is_none() {
+            return Err(DeferredDataError::Contract(
+                DataContractError::ContractNotFound(contract_id.clone()),
+            ));
+        }
+
+        image.id = Self::next_image_id()?;
+        IMAGES.with_borrow_mut(|images| {
+            images.insert(image.id, data);
+        });
+        IMAGE_THUMBNAILS.with_borrow_mut(|thumbnails| {
+            thumbnails.insert(image.id, thumbnail);
+        });
+
+        let image_id = image.id;
+        let key = StorableNat::from(contract_id.clone());
+        CONTRACT_IMAGES.with_borrow_mut(|images| {
+            let mut contract_images = images.get(&key).unwrap_or_default();
+            contract_images.0.push(image);
+            images.insert(key, contract_images);
+        });
+
+        Ok(image_id)
+    }
+
+    /// Remove an image from the gallery of a contract, with its data
+    pub fn remove_image(contract_id: &ID, image_id: u64) -> DeferredDataResult<()> {
+        let key = StorableNat::from(contract_id.clone());
+
+        CONTRACT_IMAGES.with_borrow_mut(|images| {
+            let mut contract_images = images.get(&key).unwrap_or_default();
+            let Some(index) = contract_images
+                .0
+                .iter()
+                .position(|image| image.id == image_id)
+            else {
+                return Err(DeferredDataError::Contract(
+                    DataContractError::ImageNotFound(image_id),
+                ));
+            };
+
+            contract_images.0.remove(index);
+            if contract_images.0.is_empty() {
+                images.remove(&key);
+            } else {
+                images.insert(key, contract_images);
+            }
+
+            Ok(())
+        })?;
+
+        IMAGES.with_borrow_mut(|images| {
+            images.remove(&image_id);
+        });
+        IMAGE_THUMBNAILS.with_borrow_mut(|thumbnails| {
+            thumbnails.remove(&image_id);
+        });
+
+        Ok(())
+    }
+
+    /// Get next image ID and increment it
+    fn next_image_id() -> DeferredDataResult<u64> {
+        NEXT_IMAGE_ID.with_borrow_mut(|id| {
+            let next_id = *id.get();
+            id.set(next_id + 1u64)
+                .map_err(|_| DeferredDataError::StorageError)?;
+
+            Ok(next_id)
+        })
+    }
+}
+
+#[cfg(test)]
+mod test {
+
+    use pretty_assertions::assert_eq;
+
+    use super::*;
+    use crate::app::test_utils::{mock_image, store_mock_contract};
+
+    #[test]
+    fn test_should_insert_image() {
+        store_mock_contract(1, 100);
+
+        let image_id =
+            ImageStorage::insert_image(&1u64.into(), mock_image(), vec![1, 2, 3], vec![4]).unwrap();
+        let second_id =
+            ImageStorage::insert_image(&1u64.into(), mock_image(), vec![5, 6], vec![7]).unwrap();
+        assert_ne!(image_id, second_id);
+
+        let images = ImageStorage::get_images(&1u64.into());
+        assert_eq!(
+            images.iter().map(|image| image.id).collect::<Vec<_>>(),
+            vec![image_id, second_id]
+        );
+        assert_eq!(
+            ImageStorage::get_image(&1u64.into(), image_id).unwrap(),
+            ContractImage {
+                id: image_id,
+                ..mock_image()
+            }
+        );
+        assert_eq!(
+            ImageStorage::get_image_data(image_id, ImageVariant::Original).unwrap(),
+            vec![1, 2, 3]
+        );
+        assert_eq!(
+            ImageStorage::get_image_data(image_id, ImageVariant::Thumbnail).unwrap(),
+            vec![4]
+        );
+        // images belong to their contract
+        assert!(ImageStorage::get_image(&2u64.into(), image_id).is_err());
+    }
+
+    #[test]
+    fn test_should_not_insert_image_of_unknown_contract() {
+        assert_eq!(
+            ImageStorage::insert_image(&1u64.into(), mock_image(), vec![1], vec![1]).unwrap_err(),
+            DeferredDataError::Contract(DataContractError::ContractNotFound(1u64.into()))
+        );
+    }
+
+    #[test]
+    fn test_should_remove_image() {
+        store_mock_contract(1, 100);
+        let image_id =
+            ImageStorage::insert_image(&1u64.into(), mock_image(), vec![1, 2, 3], vec![4]).unwrap();
+
+        ImageStorage::remove_image(&1u64.into(), image_id).unwrap();
+        assert!(ImageStorage::get_images(&1u64.into()).is_empty());
+        assert!(ImageStorage::get_image_data(image_id, ImageVariant::Original).is_err());
+        assert!(ImageStorage::get_image_data(image_id, ImageVariant::Thumbnail).is_err());
+
+        assert_eq!(
+            ImageStorage::remove_image(&1u64.into(), image_id).unwrap_err(),
+            DeferredDataError::Contract(DataContractError::ImageNotFound(image_id))
+        );
+    }
+}
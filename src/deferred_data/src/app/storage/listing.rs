@@ -0,0 +1,276 @@
+use std::cmp::Ordering;
+use std::fmt;
+
+use did::deferred::{
+    Contract, ContractSortBy, ContractSummary, DeferredDataError, DeferredDataResult, GenericValue,
+    GeoPoint, SortOrder,
+};
+use did::ID;
+
+use super::ImageStorage;
+use crate::app::gallery::{self, ImageVariant};
+use crate::app::geo::{contract_position, distance_km};
+
+const CONTRACT_CITY: &str = "contract:city";
+const CONTRACT_IMAGE: &str = "contract:image";
+
+/// The value contracts are sorted by.
+///
+/// All the keys of a listing have the same variant.
+#[derive(Debug, Clone, PartialEq, Eq, PartialOrd, Ord)]
+enum SortKey {
+    Id,
+    Value(u64),
+    Expiration(String),
+    /// Distance in meters; `u64::MAX` for contracts without a position
+    Distance(u64),
+}
+
+/// The position of a contract in a sorted listing.
+///
+/// Contracts with the same sort key are sorted by ID, so the position is unique.
+/// Formatted as `i:<id>`, `v:<value>:<id>`, `e:<expiration>:<id>` or `d:<meters>:<id>`.
+#[derive(Debug, Clone, PartialEq, Eq, PartialOrd, Ord)]
+pub struct ContractCursor {
+    key: SortKey,
+    pub id: ID,
+}
+
+impl ContractCursor {
+    /// Get the position of `contract` when sorted by `sort_by`.
+    ///
+    /// Distances are measured from `origin`.
+    pub fn new(sort_by: ContractSortBy, origin: Option<GeoPoint>, contract: &Contract) -> Self {
+        let key = match sort_by {
+            ContractSortBy::Id => SortKey::Id,
+            ContractSortBy::Value => SortKey::Value(contract.value),
+            ContractSortBy::Expiration => SortKey::Expiration(contract.expiration.clone()),
+            ContractSortBy::Distance => SortKey::Distance(
+                origin
+                    .zip(contract_position(contract))
+                    .map(|(origin, position)| (distance_km(origin, position) * 1000.0) as u64)
+                    .unwrap_or(u64::MAX),
+            ),
+        };
+
+        Self {
+            key,
+            id: contract.id.clone(),
+        }
+    }
+
+    /// Parse a cursor returned by a listing sorted by `sort_by`
+    pub fn parse(sort_by: ContractSortBy, cursor: &str) -> DeferredDataResult<Self> {
+        let (key, id) = match (sort_by, cursor.split_once(':')) {
+            (ContractSortBy::Id, Some(("i", id))) => (SortKey::Id, id),
+            (ContractSortBy::Value, Some(("v", position))) => {
+                let (value, id) = Self::parse_number_key(position)?;
+                (SortKey::Value(value), id)
+            }
+            (ContractSortBy::Distance, Some(("d", position))) => {
+                let (distance, id) = Self::parse_number_key(position)?;
+                (SortKey::Distance(distance), id)
+            }
+            (ContractSortBy::Expiration, Some(("e", position))) => {
+                let (expiration, id) = position
+                    .rsplit_once(':')
+                    .ok_or(DeferredDataError::InvalidCursor)?;
+                (SortKey::Expiration(expiration.to_string()), id)
+            }
+            _ => return Err(DeferredDataError::InvalidCursor),
+        };
+        let id = id
+            .parse::<u64>()
+            .map_err(|_| DeferredDataError::InvalidCursor)?;
+
+        Ok(Self { key, id: id.into() })
+    }
+
+    /// Parse a `<number>:<id>` position
+    fn parse_number_key(position: &str) -> DeferredDataResult<(u64, &str)> {
+        let (key, id) = position
+            .split_once(':')
+            .ok_or(DeferredDataError::InvalidCursor)?;
+        let key = key.parse().map_err(|_| DeferredDataError::InvalidCursor)?;
+
+        Ok((key, id))
+    }
+
+    /// Compare the position of two contracts in a listing sorted in `order`
+    pub fn compare(&self, other: &Self, order: SortOrder) -> Ordering {
+        match order {
+            SortOrder::Ascending => self.cmp(other),
+            SortOrder::Descending => other.cmp(self),
+        }
+    }
+}
+
+impl fmt::Display for ContractCursor {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match &self.key {
+            SortKey::Id => write!(f, "i:{}", self.id.0),
+            SortKey::Value(value) => write!(f, "v:{value}:{}", self.id.0),
+            SortKey::Expiration(expiration) => write!(f, "e:{expiration}:{}", self.id.0),
+            SortKey::Distance(distance) => write!(f, "d:{distance}:{}", self.id.0),
+        }
+    }
+}
+
+/// Get the summary of a contract.
+///
+/// Without a `contract:image` property, the thumbnail is the one of the first image of the gallery.
+pub fn contract_summary(contract: &Contract) -> ContractSummary {
+    ContractSummary {
+        id: contract.id.clone(),
+        r#type: contract.r#type.clone(),
+        value: contract.value,
+        currency: contract.currency.clone(),
+        city: text_property(contract, CONTRACT_CITY),
+        // inline images would make the summary as heavy as the contract
+        thumbnail: text_property(contract, CONTRACT_IMAGE)
+            .filter(|image| !image.starts_with("data:"))
+            .or_else(|| {
+                ImageStorage::get_images(&contract.id).first().map(|image| {
+                    gallery::image_url(&contract.id, image.id, ImageVariant::Thumbnail)
+                })
+            }),
+        expiration: contract.expiration.clone(),
+    }
+}
+
+fn text_property(contract: &Contract, key: &str) -> Option<String> {
+    contract
+        .properties
+        .iter()
+        .find_map(|(property, value)| match value {
+            GenericValue::TextContent(text) if property == key => Some(text.clone()),
+            _ => None,
+        })
+}
+
+#[cfg(test)]
+mod test {
+
+    use pretty_assertions::assert_eq;
+
+    use super::*;
+    use crate::app::test_utils::{mock_image, store_mock_contract, with_mock_contract};
+
+    #[test]
+    fn test_should_format_and_parse_cursor() {
+        let contract = with_mock_contract(12, 100, |contract| {
+            contract.value = 250_000;
+            contract.expiration = "2078-01-01".to_string();
+        });
+
+        for (sort_by, expected) in [
+            (ContractSortBy::Id, "i:12"),
+            (ContractSortBy::Value, "v:250000:12"),
+            (ContractSortBy::Expiration, "e:2078-01-01:12"),
+        ] {
+            let cursor = ContractCursor::new(sort_by, None, &contract);
+            assert_eq!(cursor.to_string(), expected);
+            assert_eq!(ContractCursor::parse(sort_by, expected).unwrap(), cursor);
+        }
+    }
+
+    #[test]
+    fn test_should_sort_by_distance() {
+        let origin = GeoPoint {
+            latitude: 45.0,
+            longitude: 9.0,
+        };
+        let contract_at = |id: u64, longitude: f64| {
+            with_mock_contract(id, 100, |contract| {
+                contract.properties = vec![
+                    (
+                        "contract:latitude".to_string(),
+                        GenericValue::FloatContent(45.0),
+                    ),
+                    (
+                        "contract:longitude".to_string(),
+                        GenericValue::FloatContent(longitude),
+                    ),
+                ];
+            })
+        };
+
+        let near =
+            ContractCursor::new(ContractSortBy::Distance, Some(origin), &contract_at(2, 9.1));
+        let far = ContractCursor::new(
+            ContractSortBy::Distance,
+            Some(origin),
+            &contract_at(1, 10.0),
+        );
+        assert!(near < far);
+        assert!(near.to_string().starts_with("d:7"));
+        assert_eq!(
+            ContractCursor::parse(ContractSortBy::Distance, &near.to_string()).unwrap(),
+            near
+        );
+
+        // contracts without a position come last
+        let unknown = ContractCursor::new(
+            ContractSortBy::Distance,
+            Some(origin),
+            &with_mock_contract(3, 100, |contract| contract.properties.clear()),
+        );
+        assert!(far < unknown);
+    }
+
+    #[test]
+    fn test_should_not_parse_cursor_of_another_sorting() {
+        assert_eq!(
+            ContractCursor::parse(ContractSortBy::Id, "v:250000:12").unwrap_err(),
+            DeferredDataError::InvalidCursor
+        );
+        assert_eq!(
+            ContractCursor::parse(ContractSortBy::Value, "v:foo:12").unwrap_err(),
+            DeferredDataError::InvalidCursor
+        );
+        assert_eq!(
+            ContractCursor::parse(ContractSortBy::Id, "12").unwrap_err(),
+            DeferredDataError::InvalidCursor
+        );
+    }
+
+    #[test]
+    fn test_should_get_contract_summary() {
+        let contract = with_mock_contract(1, 100, |contract| {
+            contract.properties = vec![
+                (
+                    CONTRACT_CITY.to_string(),
+                    GenericValue::TextContent("Rome".to_string()),
+                ),
+                (
+                    CONTRACT_IMAGE.to_string(),
+                    GenericValue::TextContent("data:image/png;base64,iVBORw0KGgo=".to_string()),
+                ),
+            ];
+        });
+
+        let summary = contract_summary(&contract);
+        assert_eq!(summary.id, contract.id);
+        assert_eq!(summary.city.as_deref(), Some("Rome"));
+        assert_eq!(summary.thumbnail, None);
+        assert_eq!(summary.expiration, contract.expiration);
+    }
+
+    #[test]
+    fn test_should_get_gallery_thumbnail_in_summary() {
+        store_mock_contract(1, 100);
+        let contract = with_mock_contract(1, 100, |contract| contract.properties.clear());
+        assert_eq!(contract_summary(&contract).thumbnail, None);
+
+        let image_id =
+            ImageStorage::insert_image(&contract.id, mock_image(), vec![1, 2, 3], vec![4]).unwrap();
+        assert_eq!(
+            contract_summary(&contract).thumbnail,
+            Some(gallery::image_url(
+                &contract.id,
+                image_id,
+                ImageVariant::Thumbnail
+            ))
+        );
+    }
+}
@@ -0,0 +1,147 @@
+use did::deferred::{DeferredDataError, DeferredDataResult};
+use sha2::{Digest as _, Sha256};
+
+use super::{NONCES, NONCE_EXPIRATIONS};
+
+/// Time a nonce can be used for after being issued (10 minutes)
+const NONCE_TTL: u64 = 10 * 60 * 1_000_000_000;
+/// Maximum amount of nonces which can be pending at the same time
+const MAX_PENDING_NONCES: u64 = 10_000;
+
+/// Key of the nonce expiration index (expiration time, digest of the nonce)
+pub type NonceExpirationKey = (u64, [u8; 32]);
+
+pub struct NonceStorage;
+
+impl NonceStorage {
+    /// Store a nonce issued at `now`.
+    ///
+    /// Expired nonces are removed before storing the new one.
+    /// Fails if too many nonces are pending.
+    pub fn insert_nonce(nonce: &str, now: u64) -> DeferredDataResult<()> {
+        Self::remove_expired_nonces(now);
+
+        if NONCES.with_borrow(|nonces| nonces.len()) >= MAX_PENDING_NONCES {
+            return Err(DeferredDataError::TooManyNonces);
+        }
+
+        let digest = Self::digest(nonce);
+        let expires_at = now.saturating_add(NONCE_TTL);
+        NONCES.with_borrow_mut(|nonces| {
+            nonces.insert(digest, expires_at);
+        });
+        NONCE_EXPIRATIONS.with_borrow_mut(|expirations| {
+            expirations.insert((expires_at, digest), ());
+        });
+
+        Ok(())
+    }
+
+    /// Verify that `nonce` has been issued by the canister and has neither expired nor been used
+    pub fn verify_nonce(nonce: &str, now: u64) -> DeferredDataResult<()> {
+        match NONCES.with_borrow(|nonces| nonces.get(&Self::digest(nonce))) {
+            Some(expires_at) if expires_at > now => Ok(()),
+            _ => Err(DeferredDataError::InvalidSignedMessage(
+                "nonce has not been issued, has expired or has already been used".to_string(),
+            )),
+        }
+    }
+
+    /// Use a nonce, so it can't be used again
+    pub fn use_nonce(nonce: &str, now: u64) -> DeferredDataResult<()> {
+        Self::verify_nonce(nonce, now)?;
+
+        let digest = Self::digest(nonce);
+        if let Some(expires_at) = NONCES.with_borrow_mut(|nonces| nonces.remove(&digest)) {
+            NONCE_EXPIRATIONS.with_borrow_mut(|expirations| {
+                expirations.remove(&(expires_at, digest));
+            });
+        }
+
+        Ok(())
+    }
+
+    /// Remove all the expired nonces.
+    ///
+    /// Only the expired prefix of the expiration index is visited.
+    fn remove_expired_nonces(now: u64) {
+        let expired: Vec<NonceExpirationKey> = NONCE_EXPIRATIONS.with_borrow(|expirations| {
+            expirations
+                .range(..=(now, [u8::MAX; 32]))
+                .map(|(key, _)| key)
+                .collect()
+        });
+
+        for key in expired {
+            NONCE_EXPIRATIONS.with_borrow_mut(|expirations| {
+                expirations.remove(&key);
+            });
+            NONCES.with_borrow_mut(|nonces| {
+                nonces.remove(&key.1);
+            });
+        }
+    }
+
+    /// Compute the SHA-256 digest of a nonce
+    fn digest(nonce: &str) -> [u8; 32] {
+        Sha256::digest(nonce.as_bytes()).into()
+    }
+}
+
+#[cfg(test)]
+mod test {
+
+    use pretty_assertions::assert_eq;
+
+    use super::*;
+
+    #[test]
+    fn test_should_verify_issued_nonce() {
+        NonceStorage::insert_nonce("32891756", 0).unwrap();
+
+        assert!(NonceStorage::verify_nonce("32891756", 0).is_ok());
+        assert!(NonceStorage::verify_nonce("12345678", 0).is_err());
+    }
+
+    #[test]
+    fn test_should_not_reuse_nonce() {
+        NonceStorage::insert_nonce("32891756", 0).unwrap();
+
+        assert!(NonceStorage::use_nonce("32891756", 0).is_ok());
+        assert!(NonceStorage::verify_nonce("32891756", 0).is_err());
+        assert!(NonceStorage::use_nonce("32891756", 0).is_err());
+    }
+
+    #[test]
+    fn test_should_expire_nonce() {
+        NonceStorage::insert_nonce("32891756", 0).unwrap();
+
+        assert!(NonceStorage::verify_nonce("32891756", NONCE_TTL).is_err());
+
+        // expired nonces are swept when a new nonce is issued
+        NonceStorage::insert_nonce("12345678", NONCE_TTL).unwrap();
+        assert_eq!(NONCES.with_borrow(|nonces| nonces.len()), 1);
+        assert_eq!(
+            NONCE_EXPIRATIONS.with_borrow(|expirations| expirations.len()),
+            1
+        );
+    }
+
+    #[test]
+    fn test_should_limit_pending_nonces() {
+        for nonce in 0..MAX_PENDING_NONCES {
+            NonceStorage::insert_nonce(&nonce.to_string(), 0).unwrap();
+        }
+
+        assert_eq!(
+            NonceStorage::insert_nonce("32891756", 0).unwrap_err(),
+            DeferredDataError::TooManyNonces
+        );
+
+        // used and expired nonces are no longer pending
+        NonceStorage::use_nonce("0", 0).unwrap();
+        assert!(NonceStorage::insert_nonce("32891756", 0).is_ok());
+        assert!(NonceStorage::insert_nonce("12345678", NONCE_TTL).is_ok());
+        assert_eq!(NONCES.with_borrow(|nonces| nonces.len()), 1);
+    }
+}
@@ -0,0 +1,399 @@
+use std::borrow::Cow;
+use std::collections::{BTreeMap, BTreeSet};
+
+use candid::{CandidType, Decode, Deserialize, Encode};
+use did::deferred::{Contract, DeferredDataResult, SearchSuggestion};
+use did::{StorableNat, ID};
+use ic_stable_structures::storable::Bound;
+use ic_stable_structures::Storable;
+
+use super::{contract_id_key, CONTRACTS, CONTRACT_ID_KEY_SIZE, SEARCH_INDEX};
+use crate::app::search::{tokenize, SearchField};
+
+/// Maximum size of an indexed word; longer words are truncated
+const TERM_SIZE: usize = 32;
+/// Maximum amount of index entries read to suggest the places
+const SUGGESTION_SCAN_LIMIT: usize = 1000;
+
+/// Key of the full-text index, sorted by word, field and then by contract ID
+#[derive(Clone, Debug, PartialEq, Eq, PartialOrd, Ord)]
+pub struct SearchKey {
+    term: [u8; TERM_SIZE],
+    field: u8,
+    contract_id: [u8; CONTRACT_ID_KEY_SIZE],
+}
+
+impl SearchKey {
+    fn new(term: &str, field: SearchField, contract_id: &ID) -> DeferredDataResult<Self> {
+        Ok(Self {
+            term: term_bytes(term, 0),
+            field: field.to_byte(),
+            contract_id: contract_id_key(contract_id)?,
+        })
+    }
+
+    /// Get the range of the keys of the words starting with `prefix`
+    fn prefix_range(prefix: &str) -> std::ops::RangeInclusive<Self> {
+        Self {
+            term: term_bytes(prefix, 0),
+            field: 0,
+            contract_id: [0; CONTRACT_ID_KEY_SIZE],
+        }..=Self {
+            term: term_bytes(prefix, u8::MAX),
+            field: u8::MAX,
+            contract_id: [u8::MAX; CONTRACT_ID_KEY_SIZE],
+        }
+    }
+
+    /// Get the range of the keys of the word
+    fn term_range(term: &str) -> std::ops::RangeInclusive<Self> {
+        let term = term_bytes(term, 0);
+        Self {
+            term,
+            field: 0,
+            contract_id: [0; CONTRACT_ID_KEY_SIZE],
+        }..=Self {
+            term,
+            field: u8::MAX,
+            contract_id: [u8::MAX; CONTRACT_ID_KEY_SIZE],
+        }
+    }
+}
+
+impl Storable for SearchKey {
+    const BOUND: Bound = Bound::Bounded {
+        max_size: (TERM_SIZE + 1 + CONTRACT_ID_KEY_SIZE) as u32,
+        is_fixed_size: true,
+    };
+
+    fn to_bytes(&self) -> Cow<[u8]> {
+        let mut bytes = Vec::with_capacity(TERM_SIZE + 1 + CONTRACT_ID_KEY_SIZE);
+        bytes.extend_from_slice(&self.term);
+        bytes.push(self.field);
+        bytes.extend_from_slice(&self.contract_id);
+
+        bytes.into()
+    }
+
+    fn from_bytes(bytes: Cow<[u8]>) -> Self {
+        let mut term = [0; TERM_SIZE];
+        term.copy_from_slice(&bytes[..TERM_SIZE]);
+        let mut contract_id = [0; CONTRACT_ID_KEY_SIZE];
+        contract_id.copy_from_slice(&bytes[TERM_SIZE + 1..]);
+
+        Self {
+            term,
+            field: bytes[TERM_SIZE],
+            contract_id,
+        }
+    }
+}
+
+/// Occurrences of a word in a field of a contract
+#[derive(Clone, Debug, CandidType, Deserialize, PartialEq)]
+pub struct SearchPosting {
+    pub contract_id: ID,
+    pub frequency: u32,
+}
+
+impl Storable for SearchPosting {
+    const BOUND: Bound = Bound::Unbounded;
+
+    fn to_bytes(&self) -> Cow<[u8]> {
+        Encode!(&self).unwrap().into()
+    }
+
+    fn from_bytes(bytes: Cow<[u8]>) -> Self {
+        Decode!(&bytes, Self).unwrap()
+    }
+}
+
+/// Inverted index of the words in the text properties of the open contracts
+pub struct SearchIndexStorage;
+
+impl SearchIndexStorage {
+    /// Get the open contracts containing all the words of the query, sorted by relevance.
+    ///
+    /// Each match is weighted by the field it is in and by how rare the word is.
+    pub fn search(query: &str) -> Vec<ID> {
+        let mut results = Self::scores(query).into_iter().collect::<Vec<_>>();
+        results.sort_by(|(a_id, a_score), (b_id, b_score)| {
+            b_score.total_cmp(a_score).then_with(|| a_id.cmp(b_id))
+        });
+
+        results.into_iter().map(|(id, _)| id).collect()
+    }
+
+    /// Get the open contracts containing all the words of the query
+    pub fn get_contracts(query: &str) -> BTreeSet<ID> {
+        Self::scores(query).into_keys().collect()
+    }
+
+    /// Suggest the cities and zones of the open contracts starting with `text`, sorted by amount of contracts
+    pub fn suggest(text: &str, limit: usize) -> Vec<SearchSuggestion> {
+        let words = tokenize(text);
+        let Some(first_word) = words.first() else {
+            return vec![];
+        };
+        let text = words.join(" ");
+
+        let places = SEARCH_INDEX.with_borrow(|index| {
+            index
+                .range(SearchKey::prefix_range(first_word))
+                .filter(|(key, _)| {
+                    SearchField::from_byte(key.field)
+                        .is_some_and(|field| SearchField::PLACES.contains(&field))
+                })
+                .take(SUGGESTION_SCAN_LIMIT)
+                .filter_map(|(key, posting)| {
+                    SearchField::from_byte(key.field).map(|field| (field, posting.contract_id))
+                })
+                .collect::<BTreeSet<_>>()
+        });
+
+        // count the contracts of each place, by normalized value
+        let mut suggestions: BTreeMap<(SearchField, String), SearchSuggestion> = BTreeMap::new();
+        CONTRACTS.with_borrow(|contracts| {
+            for (field, contract_id) in places {
+                let Some(contract) = contracts.get(&StorableNat::from(contract_id)) else {
+                    continue;
+                };
+                let Some(value) = field.value(&contract) else {
+                    continue;
+                };
+                // the text must start at the beginning of a word of the value
+                let normalized = tokenize(value).join(" ");
+                if !normalized.starts_with(&text) && !normalized.contains(&format!(" {text}")) {
+                    continue;
+                }
+
+                suggestions
+                    .entry((field, normalized))
+                    .or_insert_with(|| SearchSuggestion {
+                        property: field.property().to_string(),
+                        value: value.to_string(),
+                        count: 0,
+                    })
+                    .count += 1;
+            }
+        });
+
+        let mut suggestions = suggestions.into_values().collect::<Vec<_>>();
+        suggestions.sort_by(|a, b| b.count.cmp(&a.count).then_with(|| a.value.cmp(&b.value)));
+        suggestions.truncate(limit);
+
+        suggestions
+    }
+
+    /// Update the index after a contract has changed.
+    ///
+    /// Contracts are indexed while they are open.
+    pub fn update_contract(
+        previous: Option<&Contract>,
+        contract: &Contract,
+    ) -> DeferredDataResult<()> {
+        let previous_postings = previous.map(postings).unwrap_or_default();
+        let postings = postings(contract);
+        if previous_postings == postings {
+            return Ok(());
+        }
+
+        SEARCH_INDEX.with_borrow_mut(|index| {
+            for (term, field) in previous_postings.keys() {
+                if !postings.contains_key(&(term.clone(), *field)) {
+                    index.remove(&SearchKey::new(term, *field, &contract.id)?);
+                }
+            }
+            for ((term, field), frequency) in postings {
+                index.insert(
+                    SearchKey::new(&term, field, &contract.id)?,
+                    SearchPosting {
+                        contract_id: contract.id.clone(),
+                        frequency,
+                    },
+                );
+            }
+
+            Ok(())
+        })
+    }
+
+    /// Returns whether no contract is indexed
+    pub fn is_empty() -> bool {
+        SEARCH_INDEX.with_borrow(|index| index.is_empty())
+    }
+
+    /// Get the relevance of the contracts containing all the words of the query
+    fn scores(query: &str) -> BTreeMap<ID, f64> {
+        let terms = tokenize(query)
+            .into_iter()
+            .map(|term| truncate(&term).to_string())
+            .collect::<BTreeSet<_>>();
+        if terms.is_empty() {
+            return BTreeMap::new();
+        }
+        let total_contracts = CONTRACTS.with_borrow(|contracts| contracts.len()) as f64;
+
+        let mut scores: Option<BTreeMap<ID, f64>> = None;
+        SEARCH_INDEX.with_borrow(|index| {
+            for term in terms {
+                // weighted occurrences of the word in each contract
+                let mut occurrences: BTreeMap<ID, f64> = BTreeMap::new();
+                for (key, posting) in index.range(SearchKey::term_range(&term)) {
+                    let weight = SearchField::from_byte(key.field)
+                        .map(|field| field.weight())
+                        .unwrap_or_default();
+                    *occurrences.entry(posting.contract_id).or_default() +=
+                        weight * f64::from(posting.frequency);
+                }
+                let idf = (1.0 + total_contracts / occurrences.len().max(1) as f64).ln();
+
+                scores = Some(match scores.take() {
+                    None => occurrences
+                        .into_iter()
+                        .map(|(id, occurrences)| (id, occurrences * idf))
+                        .collect(),
+                    Some(scores) => scores
+                        .into_iter()
+                        .filter_map(|(id, score)| {
+                            occurrences
+                                .get(&id)
+                                .map(|occurrences| (id, score + occurrences * idf))
+                        })
+                        .collect(),
+                });
+            }
+        });
+
+        scores.unwrap_or_default()
+    }
+}
+
+/// Get the occurrences of the words in each field of the contract; closed contracts have none
+fn postings(contract: &Contract) -> BTreeMap<(String, SearchField), u32> {
+    let mut postings = BTreeMap::new();
+    if contract.closed {
+        return postings;
+    }
+
+    for field in SearchField::ALL {
+        let Some(value) = field.value(contract) else {
+            continue;
+        };
+        for term in tokenize(value) {
+            *postings
+                .entry((truncate(&term).to_string(), field))
+                .or_default() += 1;
+        }
+    }
+
+    postings
+}
+
+/// Truncate a word to [`TERM_SIZE`] bytes, on a char boundary
+fn truncate(term: &str) -> &str {
+    let mut end = term.len().min(TERM_SIZE);
+    while !term.is_char_boundary(end) {
+        end -= 1;
+    }
+
+    &term[..end]
+}
+
+/// Get the word as index key bytes, padded with `padding`
+fn term_bytes(term: &str, padding: u8) -> [u8; TERM_SIZE] {
+    let term = truncate(term).as_bytes();
+    let mut bytes = [padding; TERM_SIZE];
+    bytes[..term.len()].copy_from_slice(term);
+
+    bytes
+}
+
+#[cfg(test)]
+mod test {
+
+    use did::deferred::GenericValue;
+    use pretty_assertions::assert_eq;
+
+    use super::*;
+    use crate::app::storage::ContractStorage;
+    use crate::app::test_utils::with_mock_contract;
+
+    #[test]
+    fn test_should_search_contracts_by_relevance() {
+        insert_contract_with(1, &[("description", "Villa with garden near Milano")]);
+        insert_contract_with(2, &[("name", "Villa Milano"), ("city", "Milano")]);
+        insert_contract_with(3, &[("name", "Attico"), ("city", "Roma")]);
+
+        assert_eq!(
+            SearchIndexStorage::search("villa MILANO"),
+            vec![ID::from(2u64), 1u64.into()]
+        );
+        assert_eq!(
+            SearchIndexStorage::get_contracts("garden"),
+            BTreeSet::from([ID::from(1u64)])
+        );
+        assert!(SearchIndexStorage::search("villa roma").is_empty());
+        assert!(SearchIndexStorage::search(" ").is_empty());
+    }
+
+    #[test]
+    fn test_should_update_index() {
+        insert_contract_with(1, &[("city", "Milano")]);
+
+        ContractStorage::update_contract_property(
+            &1u64.into(),
+            "contract:city".to_string(),
+            GenericValue::TextContent("Città di Castello".to_string()),
+        )
+        .unwrap();
+        assert!(SearchIndexStorage::search("milano").is_empty());
+        assert_eq!(SearchIndexStorage::search("citta"), vec![ID::from(1u64)]);
+
+        // closed contracts are removed from the index
+        ContractStorage::close_contract(&1u64.into()).unwrap();
+        assert!(SearchIndexStorage::is_empty());
+    }
+
+    #[test]
+    fn test_should_suggest_places() {
+        insert_contract_with(1, &[("city", "Milano"), ("zone", "Navigli")]);
+        insert_contract_with(2, &[("city", "milano"), ("zone", "Brera")]);
+        insert_contract_with(3, &[("city", "Città di Castello")]);
+        insert_contract_with(4, &[("name", "Casa Milano")]);
+
+        let suggestions = SearchIndexStorage::suggest("MIL", 10);
+        assert_eq!(suggestions.len(), 1);
+        assert_eq!(suggestions[0].property, "contract:city");
+        assert_eq!(suggestions[0].count, 2);
+
+        let suggestions = SearchIndexStorage::suggest("di cas", 10);
+        assert_eq!(suggestions.len(), 1);
+        assert_eq!(suggestions[0].value, "Città di Castello");
+
+        assert!(SearchIndexStorage::suggest("castello di", 10).is_empty());
+        assert_eq!(SearchIndexStorage::suggest("n", 10)[0].value, "Navigli");
+    }
+
+    #[test]
+    fn test_should_truncate_long_words() {
+        let word = "è".repeat(20);
+        assert_eq!(truncate(&word).len(), 32);
+        assert_eq!(truncate("villa"), "villa");
+    }
+
+    fn insert_contract_with(id: u64, properties: &[(&str, &str)]) {
+        ContractStorage::insert_contract(with_mock_contract(id, 1, |contract| {
+            contract.properties = properties
+                .iter()
+                .map(|(key, value)| {
+                    (
+                        format!("contract:{key}"),
+                        GenericValue::TextContent(value.to_string()),
+                    )
+                })
+                .collect();
+        }))
+        .unwrap();
+    }
+}
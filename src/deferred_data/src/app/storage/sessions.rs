@@ -0,0 +1,155 @@
+use candid::{CandidType, Decode, Deserialize, Encode};
+use did::deferred::{DeferredDataError, DeferredDataResult};
+use did::{H160, ID};
+use ic_stable_structures::storable::Bound;
+use ic_stable_structures::Storable;
+use sha2::{Digest as _, Sha256};
+
+use super::{SESSIONS, SESSION_EXPIRATIONS};
+
+/// Key of the session expiration index (expiration time, digest of the token)
+pub type SessionExpirationKey = (u64, [u8; 32]);
+
+/// A session created from a signed message.
+///
+/// Sessions are stored by the digest of their token, so the token itself is never persisted.
+#[derive(Clone, Debug, CandidType, Deserialize, PartialEq)]
+pub struct Session {
+    /// The contract the session grants access to
+    pub contract_id: ID,
+    /// The address which signed the message
+    pub address: H160,
+    /// Time when the session expires (nanoseconds)
+    pub expires_at: u64,
+}
+
+impl Session {
+    /// Returns whether the session has expired
+    fn is_expired(&self, now: u64) -> bool {
+        self.expires_at <= now
+    }
+}
+
+impl Storable for Session {
+    const BOUND: Bound = Bound::Unbounded;
+
+    fn to_bytes(&self) -> std::borrow::Cow<[u8]> {
+        Encode!(&self).unwrap().into()
+    }
+
+    fn from_bytes(bytes: std::borrow::Cow<[u8]>) -> Self {
+        Decode!(&bytes, Self).unwrap()
+    }
+}
+
+pub struct SessionStorage;
+
+impl SessionStorage {
+    /// Store a new session for `token`.
+    ///
+    /// Expired sessions are removed before storing the new one.
+    pub fn insert_session(token: &str, session: Session, now: u64) {
+        Self::remove_expired_sessions(now);
+
+        let digest = Self::digest(token);
+        SESSION_EXPIRATIONS.with_borrow_mut(|expirations| {
+            expirations.insert((session.expires_at, digest), ());
+        });
+        SESSIONS.with_borrow_mut(|sessions| {
+            sessions.insert(digest, session);
+        });
+    }
+
+    /// Get the session of `token`.
+    ///
+    /// Fails if the session doesn't exist or has expired.
+    pub fn get_session(token: &str, now: u64) -> DeferredDataResult<Session> {
+        let session = SESSIONS
+            .with_borrow(|sessions| sessions.get(&Self::digest(token)))
+            .ok_or(DeferredDataError::Unauthorized)?;
+
+        if session.is_expired(now) {
+            return Err(DeferredDataError::SessionExpired);
+        }
+
+        Ok(session)
+    }
+
+    /// Remove all the expired sessions.
+    ///
+    /// Only the expired prefix of the expiration index is visited.
+    fn remove_expired_sessions(now: u64) {
+        let expired: Vec<SessionExpirationKey> = SESSION_EXPIRATIONS.with_borrow(|expirations| {
+            expirations
+                .range(..=(now, [u8::MAX; 32]))
+                .map(|(key, _)| key)
+                .collect()
+        });
+
+        for key in expired {
+            SESSION_EXPIRATIONS.with_borrow_mut(|expirations| {
+                expirations.remove(&key);
+            });
+            SESSIONS.with_borrow_mut(|sessions| {
+                sessions.remove(&key.1);
+            });
+        }
+    }
+
+    /// Compute the SHA-256 digest of a token
+    fn digest(token: &str) -> [u8; 32] {
+        Sha256::digest(token.as_bytes()).into()
+    }
+}
+
+#[cfg(test)]
+mod test {
+
+    use pretty_assertions::assert_eq;
+
+    use super::*;
+    use crate::app::test_utils::signer_address;
+
+    #[test]
+    fn test_should_get_session() {
+        SessionStorage::insert_session("token", session(100), 0);
+
+        assert_eq!(
+            SessionStorage::get_session("token", 0).unwrap(),
+            session(100)
+        );
+        assert_eq!(
+            SessionStorage::get_session("another-token", 0).unwrap_err(),
+            DeferredDataError::Unauthorized
+        );
+        // sessions are stored by the digest of the token
+        assert!(SESSIONS
+            .with_borrow(|sessions| sessions.contains_key(&SessionStorage::digest("token"))));
+    }
+
+    #[test]
+    fn test_should_expire_session() {
+        SessionStorage::insert_session("token", session(100), 0);
+
+        assert_eq!(
+            SessionStorage::get_session("token", 100).unwrap_err(),
+            DeferredDataError::SessionExpired
+        );
+
+        // expired sessions are swept when a new session is created
+        SessionStorage::insert_session("new-token", session(200), 100);
+        assert_eq!(SESSIONS.with_borrow(|sessions| sessions.len()), 1);
+        assert_eq!(
+            SESSION_EXPIRATIONS.with_borrow(|expirations| expirations.len()),
+            1
+        );
+    }
+
+    fn session(expires_at: u64) -> Session {
+        Session {
+            contract_id: 1u64.into(),
+            address: signer_address(),
+            expires_at,
+        }
+    }
+}
@@ -0,0 +1,301 @@
+use candid::{CandidType, Decode, Deserialize, Encode};
+use did::deferred::{ContractDocument, DataContractError, DeferredDataError, DeferredDataResult};
+use did::ID;
+use ic_stable_structures::storable::Bound;
+use ic_stable_structures::Storable;
+
+use super::{NEXT_UPLOAD_ID, UPLOADS, UPLOAD_CHUNKS};
+
+/// Upload sessions which are not finalized within this time are considered abandoned (1 hour)
+const UPLOAD_SESSION_TTL: u64 = 60 * 60 * 1_000_000_000;
+
+/// Key of a chunk of a pending upload (upload ID, chunk index)
+pub type UploadChunkKey = (u64, u64);
+
+/// A pending chunked upload of a contract document
+#[derive(Clone, Debug, CandidType, Deserialize, PartialEq)]
+pub struct UploadSession {
+    /// The contract the document will be associated to
+    pub contract_id: ID,
+    /// The document header provided when the upload has started
+    pub document: ContractDocument,
+    /// Amount of bytes received so far
+    pub received: u64,
+    /// Time when the upload has started (nanoseconds)
+    pub created_at: u64,
+}
+
+impl UploadSession {
+    /// Returns whether the upload session has been abandoned
+    fn is_expired(&self, now: u64) -> bool {
+        now.saturating_sub(self.created_at) > UPLOAD_SESSION_TTL
+    }
+}
+
+impl Storable for UploadSession {
+    const BOUND: Bound = Bound::Unbounded;
+
+    fn to_bytes(&self) -> std::borrow::Cow<[u8]> {
+        Encode!(&self).unwrap().into()
+    }
+
+    fn from_bytes(bytes: std::borrow::Cow<[u8]>) -> Self {
+        Decode!(&bytes, Self).unwrap()
+    }
+}
+
+pub struct UploadStorage;
+
+impl UploadStorage {
+    /// Start a new upload session for a document.
+    ///
+    /// Expired sessions are removed before creating the new one.
+    ///
+    /// Returns the ID of the upload session.
+    pub fn begin_upload(
+        contract_id: ID,
+        document: ContractDocument,
+        now: u64,
+    ) -> DeferredDataResult<u64> {
+        Self::remove_expired_uploads(now);
+
+        let upload_id = Self::next_upload_id()?;
+        UPLOADS.with_borrow_mut(|uploads| {
+            uploads.insert(
+                upload_id,
+                UploadSession {
+                    contract_id,
+                    document,
+                    received: 0,
+                    created_at: now,
+                },
+            );
+        });
+
+        Ok(upload_id)
+    }
+
+    /// Get an upload session.
+    ///
+    /// If the session has expired, it is removed and an error is returned.
+    pub fn get_upload(upload_id: u64, now: u64) -> DeferredDataResult<UploadSession> {
+        let session = UPLOADS
+            .with_borrow(|uploads| uploads.get(&upload_id))
+            .ok_or(DeferredDataError::Contract(
+                DataContractError::UploadNotFound(upload_id),
+            ))?;
+
+        if session.is_expired(now) {
+            Self::remove_upload(upload_id);
+            return Err(DeferredDataError::Contract(
+                DataContractError::UploadExpired(upload_id),
+            ));
+        }
+
+        Ok(session)
+    }
+
+    /// Put a chunk into the upload session.
+    ///
+    /// If a chunk with the same index has already been uploaded, it is replaced.
+    /// Fails if the received data exceeds the size declared in the document header.
+    pub fn put_chunk(
+        upload_id: u64,
+        chunk_index: u64,
+        data: Vec<u8>,
+        now: u64,
+    ) -> DeferredDataResult<()> {
+        let mut session = Self::get_upload(upload_id, now)?;
+
+        let previous_chunk_size = UPLOAD_CHUNKS
+            .with_borrow(|chunks| chunks.get(&(upload_id, chunk_index)))
+            .map(|chunk| chunk.len() as u64)
+            .unwrap_or_default();
+        let received = session.received - previous_chunk_size + data.len() as u64;
+        if received > session.document.size {
+            return Err(DeferredDataError::Contract(
+                DataContractError::DocumentSizeMismatch(session.document.size, received),
+            ));
+        }
+
+        UPLOAD_CHUNKS.with_borrow_mut(|chunks| {
+            chunks.insert((upload_id, chunk_index), data);
+        });
+        session.received = received;
+        UPLOADS.with_borrow_mut(|uploads| {
+            uploads.insert(upload_id, session);
+        });
+
+        Ok(())
+    }
+
+    /// Assemble the chunks of an upload session into the document data.
+    ///
+    /// Chunks must be numbered contiguously starting from 0.
+    /// The session is kept in the storage, so missing chunks can still be uploaded.
+    pub fn assemble_upload(
+        upload_id: u64,
+        now: u64,
+    ) -> DeferredDataResult<(UploadSession, Vec<u8>)> {
+        let session = Self::get_upload(upload_id, now)?;
+
+        let mut data = Vec::with_capacity(session.received as usize);
+        UPLOAD_CHUNKS.with_borrow(|chunks| {
+            for (expected_index, ((_, chunk_index), chunk)) in chunks
+                .range((upload_id, 0)..=(upload_id, u64::MAX))
+                .enumerate()
+            {
+                if chunk_index != expected_index as u64 {
+                    return Err(DeferredDataError::Contract(
+                        DataContractError::UploadChunkMissing(expected_index as u64),
+                    ));
+                }
+                data.extend_from_slice(&chunk);
+            }
+
+            Ok(())
+        })?;
+
+        Ok((session, data))
+    }
+
+    /// Remove an upload session with all of its chunks
+    pub fn remove_upload(upload_id: u64) {
+        UPLOADS.with_borrow_mut(|uploads| {
+            uploads.remove(&upload_id);
+        });
+
+        UPLOAD_CHUNKS.with_borrow_mut(|chunks| {
+            let keys: Vec<_> = chunks
+                .range((upload_id, 0)..=(upload_id, u64::MAX))
+                .map(|(key, _)| key)
+                .collect();
+            for key in keys {
+                chunks.remove(&key);
+            }
+        });
+    }
+
+    /// Remove all the abandoned upload sessions
+    fn remove_expired_uploads(now: u64) {
+        let expired: Vec<u64> = UPLOADS.with_borrow(|uploads| {
+            uploads
+                .iter()
+                .filter(|(_, session)| session.is_expired(now))
+                .map(|(upload_id, _)| upload_id)
+                .collect()
+        });
+
+        for upload_id in expired {
+            log::debug!("removing expired upload {upload_id}");
+            Self::remove_upload(upload_id);
+        }
+    }
+
+    /// Get next upload ID and increment it
+    fn next_upload_id() -> DeferredDataResult<u64> {
+        NEXT_UPLOAD_ID.with_borrow_mut(|id| {
+            let next_id = *id.get();
+            id.set(next_id + 1u64)
+                .map_err(|_| DeferredDataError::StorageError)?;
+
+            Ok(next_id)
+        })
+    }
+}
+
+#[cfg(test)]
+mod test {
+
+    use did::deferred::RestrictionLevel;
+    use pretty_assertions::assert_eq;
+
+    use super::*;
+
+    #[test]
+    fn test_should_upload_document_in_chunks() {
+        let upload_id = UploadStorage::begin_upload(1u64.into(), document(6), 0).unwrap();
+
+        UploadStorage::put_chunk(upload_id, 1, vec![4, 5, 6], 0).unwrap();
+        UploadStorage::put_chunk(upload_id, 0, vec![1, 2, 3], 0).unwrap();
+
+        let (session, data) = UploadStorage::assemble_upload(upload_id, 0).unwrap();
+        assert_eq!(session.contract_id, ID::from(1u64));
+        assert_eq!(session.received, 6);
+        assert_eq!(data, vec![1, 2, 3, 4, 5, 6]);
+
+        UploadStorage::remove_upload(upload_id);
+        assert_eq!(
+            UploadStorage::get_upload(upload_id, 0).unwrap_err(),
+            DeferredDataError::Contract(DataContractError::UploadNotFound(upload_id))
+        );
+    }
+
+    #[test]
+    fn test_should_replace_chunk() {
+        let upload_id = UploadStorage::begin_upload(1u64.into(), document(4), 0).unwrap();
+
+        UploadStorage::put_chunk(upload_id, 0, vec![1, 2, 3, 4], 0).unwrap();
+        UploadStorage::put_chunk(upload_id, 0, vec![4, 3, 2, 1], 0).unwrap();
+
+        let (_, data) = UploadStorage::assemble_upload(upload_id, 0).unwrap();
+        assert_eq!(data, vec![4, 3, 2, 1]);
+    }
+
+    #[test]
+    fn test_should_not_exceed_declared_size() {
+        let upload_id = UploadStorage::begin_upload(1u64.into(), document(4), 0).unwrap();
+
+        UploadStorage::put_chunk(upload_id, 0, vec![1, 2, 3], 0).unwrap();
+        assert_eq!(
+            UploadStorage::put_chunk(upload_id, 1, vec![4, 5], 0).unwrap_err(),
+            DeferredDataError::Contract(DataContractError::DocumentSizeMismatch(4, 5))
+        );
+    }
+
+    #[test]
+    fn test_should_not_assemble_with_missing_chunk() {
+        let upload_id = UploadStorage::begin_upload(1u64.into(), document(4), 0).unwrap();
+
+        UploadStorage::put_chunk(upload_id, 0, vec![1, 2], 0).unwrap();
+        UploadStorage::put_chunk(upload_id, 2, vec![3, 4], 0).unwrap();
+
+        assert_eq!(
+            UploadStorage::assemble_upload(upload_id, 0).unwrap_err(),
+            DeferredDataError::Contract(DataContractError::UploadChunkMissing(1))
+        );
+    }
+
+    #[test]
+    fn test_should_expire_abandoned_uploads() {
+        let upload_id = UploadStorage::begin_upload(1u64.into(), document(4), 0).unwrap();
+        UploadStorage::put_chunk(upload_id, 0, vec![1, 2], 0).unwrap();
+
+        let now = UPLOAD_SESSION_TTL + 1;
+        assert_eq!(
+            UploadStorage::put_chunk(upload_id, 1, vec![3, 4], now).unwrap_err(),
+            DeferredDataError::Contract(DataContractError::UploadExpired(upload_id))
+        );
+        assert_eq!(
+            UploadStorage::get_upload(upload_id, now).unwrap_err(),
+            DeferredDataError::Contract(DataContractError::UploadNotFound(upload_id))
+        );
+
+        // abandoned uploads are swept when a new upload begins
+        let abandoned_id = UploadStorage::begin_upload(1u64.into(), document(4), 0).unwrap();
+        UploadStorage::begin_upload(1u64.into(), document(4), now).unwrap();
+        assert!(UPLOADS.with_borrow(|uploads| !uploads.contains_key(&abandoned_id)));
+    }
+
+    fn document(size: u64) -> ContractDocument {
+        ContractDocument {
+            access_list: vec![RestrictionLevel::Public],
+            mime_type: "application/pdf".to_string(),
+            name: "deed.pdf".to_string(),
+            size,
+            previous_version: None,
+            superseded_by: None,
+            digest: None,
+        }
+    }
+}
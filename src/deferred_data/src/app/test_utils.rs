@@ -1,8 +1,21 @@
 use candid::Principal;
-use did::deferred::{Agency, Contract, GenericValue, RestrictedProperty, RestrictionLevel, Seller};
+use did::deferred::{
+    Agency, Contract, ContractImage, GenericValue, RestrictedProperty, RestrictionLevel, Seller,
+    SignedMessage,
+};
 use did::H160;
+use ethers_core::k256::ecdsa::SigningKey;
+use image::{DynamicImage, ImageFormat, RgbaImage};
+use time::OffsetDateTime;
 
-use super::storage::ContractStorage;
+use super::configuration::Configuration;
+use super::eip712::{AddressLink, ContractAccess};
+use super::siwe::SiweMessage;
+use super::storage::{ContractStorage, NonceStorage};
+use crate::utils::{id, time};
+
+/// Private key of the Ethereum account used to sign messages in tests
+const SIGNER_PRIVATE_KEY: [u8; 32] = [0xaa; 32];
 
 pub fn mock_contract(id: u64, installments: u64) -> Contract {
     Contract {
@@ -32,6 +45,7 @@ pub fn mock_contract(id: u64, installments: u64) -> Contract {
         agency: Some(mock_agency()),
         expiration: "2078-01-01".to_string(),
         closed: false,
+        created_at: None,
     }
 }
 
@@ -67,7 +81,7 @@ where
     let mut contract = mock_contract(contract_id, installments);
     contract_fn(&mut contract);
 
-    ContractStorage::insert_contract(contract);
+    ContractStorage::insert_contract(contract).unwrap();
 }
 
 pub fn with_mock_contract<F>(id: u64, installments: u64, f: F) -> Contract
@@ -79,6 +93,156 @@ where
     contract
 }
 
+pub fn mock_image() -> ContractImage {
+    ContractImage {
+        id: 0,
+        name: "living-room.jpg".to_string(),
+        mime_type: "image/jpeg".to_string(),
+        size: 3,
+        width: 640,
+        height: 480,
+        digest: "00".to_string(),
+    }
+}
+
+/// Get a blank PNG image
+pub fn mock_png(width: u32, height: u32) -> Vec<u8> {
+    let mut data = std::io::Cursor::new(Vec::new());
+    DynamicImage::ImageRgba8(RgbaImage::new(width, height))
+        .write_to(&mut data, ImageFormat::Png)
+        .unwrap();
+
+    data.into_inner()
+}
+
 pub fn alice() -> Principal {
     Principal::from_text("be2us-64aaa-aaaaa-qaabq-cai").unwrap()
 }
+
+/// Address of the Ethereum account used to sign messages in tests
+pub fn signer_address() -> H160 {
+    H160::from_hex_str("0x8fd379246834eac74B8419FfdA202CF8051F7A03").unwrap()
+}
+
+/// Sign a message with `personal_sign`, returning the hex-encoded signature
+pub fn sign(message: &str) -> String {
+    sign_hash(&ethers_core::utils::hash_message(message).0)
+}
+
+/// Sign a hash, returning the hex-encoded signature
+pub fn sign_hash(hash: &[u8; 32]) -> String {
+    let key = SigningKey::from_slice(&SIGNER_PRIVATE_KEY).unwrap();
+    let (signature, recovery_id) = key.sign_prehash_recoverable(hash).unwrap();
+
+    let mut bytes = signature.to_bytes().to_vec();
+    bytes.push(recovery_id.to_byte() + 27);
+
+    format!("0x{}", hex::encode(bytes))
+}
+
+/// A SIWE message granting access to `contract_id`, valid for the next hour
+pub fn signed_message(contract_id: u64) -> SignedMessage {
+    signed_siwe_message(
+        &format!("Grant read access to contract #{contract_id}"),
+        &SiweMessage::contract_resource(id(), &contract_id.into()),
+    )
+}
+
+/// A SIWE message linking the signer address to `principal`, valid for the next hour
+pub fn signed_link_message(principal: Principal) -> SignedMessage {
+    signed_siwe_message(
+        &format!("Link this address to principal {principal}"),
+        &SiweMessage::principal_resource(id(), principal),
+    )
+}
+
+fn signed_siwe_message(statement: &str, resource: &str) -> SignedMessage {
+    let now = time();
+    let message = format!(
+        r#"ekoke.io wants you to sign in with your Ethereum account:
+{address}
+
+{statement}
+
+URI: https://ekoke.io
+Version: 1
+Chain ID: {chain_id}
+Nonce: {nonce}
+Issued At: {issued_at}
+Expiration Time: {expiration_time}
+Resources:
+- {resource}"#,
+        address = signer_address(),
+        chain_id = Configuration::get_chain_id(),
+        nonce = issue_nonce(),
+        issued_at = rfc3339(now),
+        expiration_time = rfc3339(now + 60 * 60 * 1_000_000_000),
+    );
+    let signature = sign(&message);
+
+    SignedMessage::Siwe { message, signature }
+}
+
+/// EIP-712 typed data granting access to `contract_id`, valid for the next hour and signed for `chain_id`
+pub fn signed_typed_data(contract_id: u64, chain_id: u64) -> SignedMessage {
+    let issued_at = time() / 1_000_000_000;
+    let expires_at = issued_at + 60 * 60;
+    let nonce = issue_nonce();
+    let access = ContractAccess {
+        contract_id: &contract_id.into(),
+        issued_at,
+        expires_at,
+        nonce: &nonce,
+    };
+    let signature = sign_hash(&access.signing_hash(id(), chain_id).unwrap());
+
+    SignedMessage::TypedData {
+        issued_at,
+        expires_at,
+        nonce,
+        signature,
+    }
+}
+
+/// EIP-712 typed data linking the signer address to `principal`, valid for the next hour and signed for `chain_id`
+pub fn signed_link_typed_data(principal: Principal, chain_id: u64) -> SignedMessage {
+    let issued_at = time() / 1_000_000_000;
+    let expires_at = issued_at + 60 * 60;
+    let nonce = issue_nonce();
+    let link = AddressLink {
+        principal,
+        issued_at,
+        expires_at,
+        nonce: &nonce,
+    };
+    let signature = sign_hash(&link.signing_hash(id(), chain_id));
+
+    SignedMessage::TypedData {
+        issued_at,
+        expires_at,
+        nonce,
+        signature,
+    }
+}
+
+/// Issue the nonce used by the signed messages in tests
+fn issue_nonce() -> String {
+    let nonce = "32891756".to_string();
+    NonceStorage::insert_nonce(&nonce, time()).unwrap();
+
+    nonce
+}
+
+fn rfc3339(nanos: u64) -> String {
+    let time = OffsetDateTime::from_unix_timestamp_nanos(nanos as i128).unwrap();
+
+    format!(
+        "{:04}-{:02}-{:02}T{:02}:{:02}:{:02}Z",
+        time.year(),
+        time.month() as u8,
+        time.day(),
+        time.hour(),
+        time.minute(),
+        time.second()
+    )
+}
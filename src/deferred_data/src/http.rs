@@ -1,61 +1,139 @@
 mod contract_filter;
+mod export;
+mod feed;
+mod metadata;
 
-use std::str::FromStr;
+use std::borrow::Cow;
+use std::collections::{BTreeMap, HashMap};
 
-use did::{HttpRequest, HttpResponse};
-use ethers_core::abi::ethereum_types::H520;
-use route_recognizer::Router;
+use candid::Func;
+use did::deferred::{
+    ContractDocument, ContractListQuery, ContractSortBy, DeferredDataResult, GeoPoint,
+    SignedMessage, SortOrder,
+};
+use did::{
+    HttpRequest, HttpResponse, StreamingCallbackHttpResponse, StreamingCallbackToken,
+    StreamingStrategy, ID,
+};
+use ic_log::did::Pagination;
+use route_recognizer::{Params, Router};
+use serde_bytes::ByteBuf;
 use url::Url;
 
 use self::contract_filter::Filters;
-use crate::app::{ContractStorage, DeferredData, SignedMessage};
+use crate::app::gallery::{self, ImageVariant, THUMBNAIL_MIME_TYPE};
+use crate::app::geo::BoundingBox;
+use crate::app::{
+    ContractStorage, Credential, DeferredData, GeoIndexStorage, HttpCertification, ImageStorage,
+    SearchIndexStorage,
+};
+use crate::utils;
 
 const ROUTE_CONTRACTS: &str = "Contracts";
+const ROUTE_CONTRACT_SUMMARIES: &str = "ContractSummaries";
+const ROUTE_CONTRACT_CLUSTERS: &str = "ContractClusters";
+const ROUTE_CONTRACT_SUGGESTIONS: &str = "ContractSuggestions";
+const ROUTE_CONTRACT_FEED: &str = "ContractFeed";
 const ROUTE_CONTRACT: &str = "Contract";
+const ROUTE_CONTRACT_METADATA: &str = "ContractMetadata";
+const ROUTE_TOKEN_METADATA: &str = "TokenMetadata";
 const ROUTE_DOCUMENT: &str = "Document";
+const ROUTE_DOCUMENT_VERIFY: &str = "DocumentVerify";
+const ROUTE_IMAGES: &str = "Images";
+const ROUTE_IMAGE: &str = "Image";
+const ROUTE_IMAGE_THUMBNAIL: &str = "ImageThumbnail";
+const ROUTE_AUDIT: &str = "Audit";
+const ROUTE_ARCHIVED_CONTRACT: &str = "ArchivedContract";
+
+const HTTP_OK: u16 = 200;
+const HTTP_PARTIAL_CONTENT: u16 = 206;
+const HTTP_NOT_MODIFIED: u16 = 304;
+const HTTP_RANGE_NOT_SATISFIABLE: u16 = 416;
+
+/// Maximum size of the body chunk served by a single response.
+/// Larger bodies are streamed through [`STREAMING_CALLBACK_METHOD`].
+const STREAMING_CHUNK_SIZE: u64 = 1024 * 1024;
+const STREAMING_CALLBACK_METHOD: &str = "http_request_streaming_callback";
+
+/// Gallery images never change once uploaded, so they can be cached for a year
+const IMAGE_CACHE_CONTROL: &str = "public, max-age=31536000, immutable";
+
+/// Amount of audit log entries returned when `count` is not provided
+const DEFAULT_AUDIT_LOG_PAGE_SIZE: usize = 100;
+
+/// Maximum zoom level of the contract clusters; at zoom `z` clusters are `360 / 2^(z + 3)` degrees wide
+const MAX_CLUSTER_ZOOM: u32 = 20;
+
+/// Amount of autocomplete suggestions returned when `limit` is not provided
+const DEFAULT_SUGGESTIONS: usize = 10;
+const MAX_SUGGESTIONS: usize = 50;
+
+/// Amount of entries of the contract feed when `limit` is not provided
+const DEFAULT_FEED_ENTRIES: usize = 50;
+const MAX_FEED_ENTRIES: usize = 100;
+
+/// Headers of requests and responses
+type HeaderMap = HashMap<Cow<'static, str>, Cow<'static, str>>;
+
+/// Byte range requested with the `Range` header
+#[derive(Debug, PartialEq, Eq)]
+enum RequestedRange {
+    /// The whole body
+    Full,
+    /// The bytes between `start` and `end` (exclusive)
+    Partial { start: u64, end: u64 },
+    /// The range can't be served
+    NotSatisfiable,
+}
 
 pub struct HttpApi;
 
 impl HttpApi {
-    /// Handles an HTTP request
+    /// Handles an HTTP request, attaching the certificate of the response
     pub async fn handle_http_request(req: HttpRequest) -> HttpResponse {
+        let mut response = Self::route_request(&req);
+        if let Ok(url) = Self::parse_url(&req.url) {
+            HttpCertification::certify_response(url.path(), &mut response);
+        }
+
+        response
+    }
+
+    /// Route the request to its handler
+    fn route_request(req: &HttpRequest) -> HttpResponse {
         // handle CORS preflight request
         if req.method == "OPTIONS" {
             return HttpResponse::ok("".to_string());
         }
 
-        // must be a GET request
-        if req.method != "GET" {
-            return HttpResponse::bad_request("expected GET method".to_string());
+        // must be a GET or HEAD request
+        if req.method != "GET" && req.method != "HEAD" {
+            return HttpResponse::bad_request("expected GET or HEAD method".to_string());
         }
+        // the certified responses are the full responses to GET requests,
+        // so HEAD requests get the full response unless served by the raw domain
+        let is_head = req.method == "HEAD" && Self::is_raw_domain(&req.headers);
 
-        // convert URL to valid URL
-        let url = if req.url.starts_with("/") {
-            format!("http://localhost{}", req.url)
-        } else {
-            req.url.clone()
-        };
         // parse url
-        let Ok(url) = Url::parse(&url) else {
-            return HttpResponse::bad_request(format!("Invalid URL: {url}"));
+        let url = match Self::parse_url(&req.url) {
+            Ok(url) => url,
+            Err(err) => return HttpResponse::bad_request(err),
         };
 
-        let mut router = Router::new();
-        router.add("/contracts", ROUTE_CONTRACTS);
-        router.add("/contract/:id", ROUTE_CONTRACT);
-        router.add(
-            "/contract/:contract_id/document/:document_id",
-            ROUTE_DOCUMENT,
-        );
-
+        let router = Self::router();
         let Ok(route_match) = router.recognize(url.path()) else {
             return HttpResponse::not_found();
         };
+        let credential = Self::credential(&req.headers, &url);
 
         let handler = **route_match.handler();
         let params = route_match.params();
-        match handler {
+        let mut response = match handler {
             ROUTE_CONTRACTS => Self::get_contracts(&url),
+            ROUTE_CONTRACT_SUMMARIES => Self::list_contracts(&url),
+            ROUTE_CONTRACT_CLUSTERS => Self::get_contract_clusters(&url),
+            ROUTE_CONTRACT_SUGGESTIONS => Self::get_contract_suggestions(&url),
+            ROUTE_CONTRACT_FEED => Self::get_contract_feed(&url),
             ROUTE_CONTRACT => {
                 let Some(id) = params.find("id") else {
                     return HttpResponse::bad_request("missing contract ID".to_string());
@@ -63,109 +141,1039 @@ impl HttpApi {
                 let Ok(id) = id.parse::<u64>() else {
                     return HttpResponse::bad_request("invalid contract ID".to_string());
                 };
-                Self::get_contract(url, id)
+                Self::get_contract(id, credential)
             }
-            ROUTE_DOCUMENT => {
-                let Some(contract_id) = params.find("contract_id") else {
+            ROUTE_CONTRACT_METADATA => {
+                let Some(id) = params.find("id") else {
+                    return HttpResponse::bad_request("missing contract ID".to_string());
+                };
+                let Ok(id) = id.parse::<u64>() else {
+                    return HttpResponse::bad_request("invalid contract ID".to_string());
+                };
+                Self::get_token_metadata(id, None)
+            }
+            ROUTE_TOKEN_METADATA => {
+                let Some(id) = params.find("id") else {
                     return HttpResponse::bad_request("missing contract ID".to_string());
                 };
-                let Ok(contract_id) = contract_id.parse::<u64>() else {
+                let Ok(id) = id.parse::<u64>() else {
                     return HttpResponse::bad_request("invalid contract ID".to_string());
                 };
+                let Ok(token_id) = params.find("token_id").unwrap_or_default().parse::<u64>()
+                else {
+                    return HttpResponse::bad_request("invalid token ID".to_string());
+                };
+                Self::get_token_metadata(id, Some(token_id))
+            }
+            ROUTE_DOCUMENT => {
+                let (contract_id, document_id) = match Self::document_params(params) {
+                    Ok(ids) => ids,
+                    Err(err) => return HttpResponse::bad_request(err),
+                };
 
-                let Some(document_id) = params.find("document_id") else {
-                    return HttpResponse::bad_request("missing document ID".to_string());
+                Self::get_contract_document(req, contract_id, document_id, credential, is_head)
+            }
+            ROUTE_DOCUMENT_VERIFY => {
+                let (contract_id, document_id) = match Self::document_params(params) {
+                    Ok(ids) => ids,
+                    Err(err) => return HttpResponse::bad_request(err),
                 };
-                let Ok(document_id) = document_id.parse::<u64>() else {
-                    return HttpResponse::bad_request("invalid document ID".to_string());
+
+                Self::verify_contract_document(&url, contract_id, document_id, credential)
+            }
+            ROUTE_IMAGES => {
+                let Some(id) = params.find("id") else {
+                    return HttpResponse::bad_request("missing contract ID".to_string());
+                };
+                let Ok(id) = id.parse::<u64>() else {
+                    return HttpResponse::bad_request("invalid contract ID".to_string());
+                };
+                HttpResponse::ok(DeferredData::get_contract_images(&id.into()))
+            }
+            ROUTE_IMAGE | ROUTE_IMAGE_THUMBNAIL => {
+                let (contract_id, image_id) = match Self::image_params(params) {
+                    Ok(ids) => ids,
+                    Err(err) => return HttpResponse::bad_request(err),
+                };
+                let variant = if handler == ROUTE_IMAGE {
+                    ImageVariant::Original
+                } else {
+                    ImageVariant::Thumbnail
                 };
 
-                Self::get_contract_document(url, contract_id, document_id)
+                Self::get_contract_image(req, contract_id, image_id, variant)
+            }
+            ROUTE_AUDIT => {
+                let Some(id) = params.find("id") else {
+                    return HttpResponse::bad_request("missing contract ID".to_string());
+                };
+                let Ok(id) = id.parse::<u64>() else {
+                    return HttpResponse::bad_request("invalid contract ID".to_string());
+                };
+                Self::get_contract_audit_log(&url, id, credential)
+            }
+            ROUTE_ARCHIVED_CONTRACT => {
+                let Some(id) = params.find("id") else {
+                    return HttpResponse::bad_request("missing contract ID".to_string());
+                };
+                let Ok(id) = id.parse::<u64>() else {
+                    return HttpResponse::bad_request("invalid contract ID".to_string());
+                };
+                Self::get_archived_contract(id, credential)
             }
             _ => HttpResponse::not_found(),
+        };
+
+        // HEAD responses have no body
+        if is_head {
+            response.body = ByteBuf::default();
+            response.streaming_strategy = None;
         }
+
+        response
     }
 
+    /// Get the next chunk of a streamed document.
+    ///
+    /// Access to the document is verified again using the credential of the original request.
+    pub fn http_request_streaming_callback(
+        token: StreamingCallbackToken,
+    ) -> StreamingCallbackHttpResponse {
+        let url = match Self::parse_url(&token.url) {
+            Ok(url) => url,
+            Err(_) => ic_cdk::trap("invalid streaming token URL"),
+        };
+
+        let router = Self::router();
+        let (contract_id, document_id) = match router.recognize(url.path()) {
+            Ok(route_match) if **route_match.handler() == ROUTE_DOCUMENT => {
+                match Self::document_params(route_match.params()) {
+                    Ok(ids) => ids,
+                    Err(_) => ic_cdk::trap("invalid streaming token URL"),
+                }
+            }
+            _ => ic_cdk::trap("invalid streaming token URL"),
+        };
+
+        let credential = Self::credential(&token.headers, &url);
+        if let Err(err) =
+            DeferredData::get_contract_document_info(&contract_id.into(), document_id, credential)
+        {
+            ic_cdk::trap(&format!("unable to stream document: {err}"));
+        }
+
+        let (body, next_offset) =
+            match Self::body_chunk(contract_id, document_id, token.offset, token.end) {
+                Ok(chunk) => chunk,
+                Err(err) => ic_cdk::trap(&format!("unable to stream document: {err}")),
+            };
+
+        StreamingCallbackHttpResponse {
+            body,
+            token: next_offset.map(|offset| StreamingCallbackToken { offset, ..token }),
+        }
+    }
+
+    fn router() -> Router<&'static str> {
+        let mut router = Router::new();
+        router.add("/contracts", ROUTE_CONTRACTS);
+        router.add("/contracts/summaries", ROUTE_CONTRACT_SUMMARIES);
+        router.add("/contracts/clusters", ROUTE_CONTRACT_CLUSTERS);
+        router.add("/contracts/autocomplete", ROUTE_CONTRACT_SUGGESTIONS);
+        router.add("/contracts/feed", ROUTE_CONTRACT_FEED);
+        router.add("/contract/:id", ROUTE_CONTRACT);
+        router.add("/contract/:id/metadata", ROUTE_CONTRACT_METADATA);
+        router.add("/contract/:id/metadata/:token_id", ROUTE_TOKEN_METADATA);
+        router.add("/contract/:id/images", ROUTE_IMAGES);
+        router.add("/contract/:contract_id/image/:image_id", ROUTE_IMAGE);
+        router.add(
+            "/contract/:contract_id/image/:image_id/thumbnail",
+            ROUTE_IMAGE_THUMBNAIL,
+        );
+        router.add("/contract/:id/audit", ROUTE_AUDIT);
+        router.add("/archive/contract/:id", ROUTE_ARCHIVED_CONTRACT);
+        router.add(
+            "/contract/:contract_id/document/:document_id",
+            ROUTE_DOCUMENT,
+        );
+        router.add(
+            "/contract/:contract_id/document/:document_id/verify",
+            ROUTE_DOCUMENT_VERIFY,
+        );
+
+        router
+    }
+
+    /// Parse the request URL.
+    ///
+    /// Returns the error message of the bad request on failure
+    fn parse_url(url: &str) -> Result<Url, String> {
+        // convert URL to valid URL
+        let url = if url.starts_with("/") {
+            format!("http://localhost{url}")
+        } else {
+            url.to_string()
+        };
+
+        Url::parse(&url).map_err(|_| format!("Invalid URL: {url}"))
+    }
+
+    /// Get contract and document ID from the document route params.
+    ///
+    /// Returns the error message of the bad request on failure
+    fn document_params(params: &Params) -> Result<(u64, u64), String> {
+        let Some(contract_id) = params.find("contract_id") else {
+            return Err("missing contract ID".to_string());
+        };
+        let Ok(contract_id) = contract_id.parse::<u64>() else {
+            return Err("invalid contract ID".to_string());
+        };
+
+        let Some(document_id) = params.find("document_id") else {
+            return Err("missing document ID".to_string());
+        };
+        let Ok(document_id) = document_id.parse::<u64>() else {
+            return Err("invalid document ID".to_string());
+        };
+
+        Ok((contract_id, document_id))
+    }
+
+    /// Get contract and image ID from the image route params.
+    ///
+    /// Returns the error message of the bad request on failure
+    fn image_params(params: &Params) -> Result<(u64, u64), String> {
+        let Some(contract_id) = params.find("contract_id") else {
+            return Err("missing contract ID".to_string());
+        };
+        let Ok(contract_id) = contract_id.parse::<u64>() else {
+            return Err("invalid contract ID".to_string());
+        };
+
+        let Some(image_id) = params.find("image_id") else {
+            return Err("missing image ID".to_string());
+        };
+        let Ok(image_id) = image_id.parse::<u64>() else {
+            return Err("invalid image ID".to_string());
+        };
+
+        Ok((contract_id, image_id))
+    }
+
+    /// Get the IDs of the contracts matching the filters.
+    ///
+    /// With a full-text query (`q`), the contracts are sorted by relevance.
+    /// With `format=geojson` or `format=csv`, the contracts are exported instead of their IDs.
     fn get_contracts(url: &Url) -> HttpResponse {
-        let filters = Filters::from(url);
+        let filters = match Filters::try_from(url) {
+            Ok(filters) => filters,
+            Err(err) => return HttpResponse::bad_request(err),
+        };
+
+        let mut contracts =
+            ContractStorage::get_contracts_filter(filters.candidates().as_ref(), |contract| {
+                filters.check(contract)
+            });
+        if let Some(query) = filters.query() {
+            let ranking = SearchIndexStorage::search(query)
+                .into_iter()
+                .enumerate()
+                .map(|(rank, id)| (id, rank))
+                .collect::<BTreeMap<_, _>>();
+            contracts.sort_by_key(|id| ranking.get(id).copied().unwrap_or(usize::MAX));
+        }
+
+        let read_contracts = |contracts: Vec<ID>| {
+            contracts
+                .iter()
+                .filter_map(ContractStorage::get_contract)
+                .collect::<Vec<_>>()
+        };
+        match Self::get_query_param(url, "format").as_deref() {
+            None | Some("json") => HttpResponse::ok(contracts),
+            Some("geojson") => {
+                HttpResponse::geojson(export::contracts_geojson(&read_contracts(contracts)))
+            }
+            Some("csv") => HttpResponse::csv(export::contracts_csv(&read_contracts(contracts))),
+            Some(_) => HttpResponse::bad_request("format must be json, geojson or csv".to_string()),
+        }
+    }
+
+    /// Suggest the cities and zones starting with the `q` query param, for the autocomplete.
+    ///
+    /// At most `limit` suggestions are returned; [`DEFAULT_SUGGESTIONS`] by default, up to [`MAX_SUGGESTIONS`].
+    fn get_contract_suggestions(url: &Url) -> HttpResponse {
+        let Some(query) = Self::get_query_param(url, "q") else {
+            return HttpResponse::bad_request("missing q".to_string());
+        };
+        let Ok(limit) = Self::get_query_param(url, "limit")
+            .map(|limit| limit.parse::<usize>())
+            .transpose()
+        else {
+            return HttpResponse::bad_request("invalid limit".to_string());
+        };
+        let limit = limit
+            .unwrap_or(DEFAULT_SUGGESTIONS)
+            .clamp(1, MAX_SUGGESTIONS);
+
+        HttpResponse::ok(SearchIndexStorage::suggest(&query, limit))
+    }
+
+    /// Get the Atom feed of the latest created and closed contracts matching the filters.
+    ///
+    /// At most `limit` entries are returned; [`DEFAULT_FEED_ENTRIES`] by default, up to [`MAX_FEED_ENTRIES`].
+    fn get_contract_feed(url: &Url) -> HttpResponse {
+        let filters = match Filters::try_from(url) {
+            Ok(filters) => filters,
+            Err(err) => return HttpResponse::bad_request(err),
+        };
+        let Ok(limit) = Self::get_query_param(url, "limit")
+            .map(|limit| limit.parse::<usize>())
+            .transpose()
+        else {
+            return HttpResponse::bad_request("invalid limit".to_string());
+        };
+        let limit = limit
+            .unwrap_or(DEFAULT_FEED_ENTRIES)
+            .clamp(1, MAX_FEED_ENTRIES);
+
+        let events =
+            ContractStorage::get_contract_events(|contract| filters.check(contract), limit);
+
+        HttpResponse::atom(feed::contracts_feed(&events))
+    }
+
+    /// List a page of the contracts matching the filters, as summaries.
+    ///
+    /// Pages are selected with the `sortBy` (`id`, `value`, `expiration` or `distance`), `order` (`asc` or `desc`),
+    /// `cursor` and `limit` query params.
+    /// Distances are measured from the `latitude` and `longitude` query params.
+    fn list_contracts(url: &Url) -> HttpResponse {
+        let sort_by = match Self::get_query_param(url, "sortBy").as_deref() {
+            None | Some("id") => ContractSortBy::Id,
+            Some("value") => ContractSortBy::Value,
+            Some("expiration") => ContractSortBy::Expiration,
+            Some("distance") => ContractSortBy::Distance,
+            Some(_) => return HttpResponse::bad_request("invalid sortBy".to_string()),
+        };
+        let order = match Self::get_query_param(url, "order").as_deref() {
+            None | Some("asc") => SortOrder::Ascending,
+            Some("desc") => SortOrder::Descending,
+            Some(_) => return HttpResponse::bad_request("invalid order".to_string()),
+        };
+        let Ok(limit) = Self::get_query_param(url, "limit")
+            .map(|limit| limit.parse::<u64>())
+            .transpose()
+        else {
+            return HttpResponse::bad_request("invalid limit".to_string());
+        };
+        let origin = match (
+            Self::get_query_param(url, "latitude"),
+            Self::get_query_param(url, "longitude"),
+        ) {
+            _ if sort_by != ContractSortBy::Distance => None,
+            (Some(latitude), Some(longitude)) => match (latitude.parse(), longitude.parse()) {
+                (Ok(latitude), Ok(longitude)) => Some(GeoPoint {
+                    latitude,
+                    longitude,
+                }),
+                _ => return HttpResponse::bad_request("invalid origin".to_string()),
+            },
+            _ => {
+                return HttpResponse::bad_request(
+                    "sorting by distance requires latitude and longitude".to_string(),
+                )
+            }
+        };
+        let query = ContractListQuery {
+            sort_by,
+            order,
+            origin,
+            cursor: Self::get_query_param(url, "cursor"),
+            limit,
+        };
 
-        HttpResponse::ok(ContractStorage::get_contracts_filter(|contract| {
+        let filters = match Filters::try_from(url) {
+            Ok(filters) => filters,
+            Err(err) => return HttpResponse::bad_request(err),
+        };
+        match DeferredData::list_contracts(query, filters.candidates().as_ref(), |contract| {
             filters.check(contract)
-        }))
+        }) {
+            Ok(page) => HttpResponse::ok(page),
+            Err(err) => HttpResponse::bad_request(err.to_string()),
+        }
     }
 
-    fn get_contract(url: Url, id: u64) -> HttpResponse {
-        let signed_message = Self::signed_message(url);
+    /// Count the contracts matching the filters in the cells of a grid.
+    ///
+    /// The cells are `360 / 2^(zoom + 3)` degrees wide, with `zoom` up to [`MAX_CLUSTER_ZOOM`];
+    /// only the cells inside `bbox` are returned.
+    fn get_contract_clusters(url: &Url) -> HttpResponse {
+        let Ok(zoom) = Self::get_query_param(url, "zoom")
+            .map(|zoom| zoom.parse::<u32>())
+            .transpose()
+        else {
+            return HttpResponse::bad_request("invalid zoom".to_string());
+        };
+        let zoom = zoom.unwrap_or_default();
+        if zoom > MAX_CLUSTER_ZOOM {
+            return HttpResponse::bad_request(format!("zoom must be at most {MAX_CLUSTER_ZOOM}"));
+        }
+        let bbox = match Self::get_query_param(url, "bbox")
+            .map(|bbox| bbox.parse::<BoundingBox>())
+            .transpose()
+        {
+            Ok(bbox) => bbox.unwrap_or(BoundingBox::WORLD),
+            Err(err) => return HttpResponse::bad_request(err),
+        };
+        let cell_size = 360.0 / f64::from(2u32.pow(zoom + 3));
 
-        DeferredData::get_contract(&id.into(), signed_message)
+        let filters = match Filters::try_from(url) {
+            Ok(filters) => filters,
+            Err(err) => return HttpResponse::bad_request(err),
+        };
+        let candidates = filters.candidates();
+        let resolved = filters.is_resolved_by_indexes();
+
+        HttpResponse::ok(GeoIndexStorage::get_clusters(&bbox, cell_size, |id| {
+            if candidates
+                .as_ref()
+                .is_some_and(|candidates| !candidates.contains(id))
+            {
+                return false;
+            }
+
+            resolved
+                || ContractStorage::get_contract(id)
+                    .is_some_and(|contract| filters.check(&contract))
+        }))
+    }
+
+    fn get_contract(id: u64, credential: Option<Credential>) -> HttpResponse {
+        DeferredData::get_contract(&id.into(), credential)
             .map(HttpResponse::ok)
             .unwrap_or_else(HttpResponse::not_found)
     }
 
-    fn get_contract_document(url: Url, contract_id: u64, document_id: u64) -> HttpResponse {
-        let signed_message = Self::signed_message(url);
+    /// Get the ERC-721 metadata of the tokens of a contract, or of one of its tokens.
+    ///
+    /// Closed contracts keep their metadata, since the token URIs still point here after the sale.
+    /// Without a `contract:image` property, the image is the first image of the gallery, which is served for open contracts only.
+    fn get_token_metadata(id: u64, token_id: Option<u64>) -> HttpResponse {
+        let Some(contract) = ContractStorage::get_any_contract(&id.into()) else {
+            return HttpResponse::not_found();
+        };
+
+        let mut metadata = metadata::token_metadata(&contract, token_id);
+        if metadata.image.is_none() && !contract.closed {
+            metadata.image = ImageStorage::get_images(&contract.id)
+                .first()
+                .map(|image| gallery::image_url(&contract.id, image.id, ImageVariant::Original));
+        }
+
+        HttpResponse::ok(metadata)
+    }
+
+    /// Get a contract, including closed contracts
+    fn get_archived_contract(id: u64, credential: Option<Credential>) -> HttpResponse {
+        DeferredData::get_archived_contract(&id.into(), credential)
+            .map(HttpResponse::ok)
+            .unwrap_or_else(|_| HttpResponse::not_found())
+    }
+
+    /// Serve a contract document as raw bytes.
+    ///
+    /// Supports the `Range` header through the raw domain only, since partial responses can't be certified;
+    /// bodies larger than [`STREAMING_CHUNK_SIZE`] are streamed.
+    fn get_contract_document(
+        req: &HttpRequest,
+        contract_id: u64,
+        document_id: u64,
+        credential: Option<Credential>,
+        is_head: bool,
+    ) -> HttpResponse {
+        let credential_headers = Self::credential_headers(credential.as_ref());
+        let Ok(document) =
+            DeferredData::get_contract_document_info(&contract_id.into(), document_id, credential)
+        else {
+            return HttpResponse::not_found();
+        };
+
+        let requested_range = if Self::is_raw_domain(&req.headers) {
+            Self::requested_range(req, document.size)
+        } else {
+            RequestedRange::Full
+        };
+        let (status_code, start, end) = match requested_range {
+            RequestedRange::Full => (HTTP_OK, 0, document.size),
+            RequestedRange::Partial { start, end } => (HTTP_PARTIAL_CONTENT, start, end),
+            RequestedRange::NotSatisfiable => {
+                return HttpResponse::new(
+                    HTTP_RANGE_NOT_SATISFIABLE,
+                    HashMap::from([(
+                        "content-range".into(),
+                        format!("bytes */{}", document.size).into(),
+                    )]),
+                    ByteBuf::default(),
+                    None,
+                );
+            }
+        };
+
+        let mut headers = Self::document_headers(&document);
+        headers.insert("content-length".into(), (end - start).to_string().into());
+        if status_code == HTTP_PARTIAL_CONTENT {
+            headers.insert(
+                "content-range".into(),
+                format!("bytes {start}-{}/{}", end - 1, document.size).into(),
+            );
+        }
+
+        if is_head {
+            return HttpResponse::new(status_code, headers, ByteBuf::default(), None);
+        }
+
+        let (body, next_offset) = match Self::body_chunk(contract_id, document_id, start, end) {
+            Ok(chunk) => chunk,
+            Err(err) => return HttpResponse::internal_error(err.to_string()),
+        };
+        let mut response = HttpResponse::new(status_code, headers, body, None);
+        response.streaming_strategy = next_offset.map(|offset| StreamingStrategy::Callback {
+            callback: Func {
+                principal: utils::id(),
+                method: STREAMING_CALLBACK_METHOD.to_string(),
+            },
+            token: StreamingCallbackToken {
+                url: req.url.clone(),
+                headers: credential_headers,
+                offset,
+                end,
+            },
+        });
+
+        response
+    }
+
+    /// Serve an image of the gallery of an open contract, or its thumbnail.
+    ///
+    /// Images are immutable, so they are served with long-lived caching headers and
+    /// requests with a matching `If-None-Match` header get a `304 Not Modified` response.
+    fn get_contract_image(
+        req: &HttpRequest,
+        contract_id: u64,
+        image_id: u64,
+        variant: ImageVariant,
+    ) -> HttpResponse {
+        let contract_id = contract_id.into();
+        if ContractStorage::get_contract(&contract_id).is_none() {
+            return HttpResponse::not_found();
+        }
+        let Ok(image) = ImageStorage::get_image(&contract_id, image_id) else {
+            return HttpResponse::not_found();
+        };
+
+        let (mime_type, etag) = match variant {
+            ImageVariant::Original => (image.mime_type, format!("\"{}\"", image.digest)),
+            ImageVariant::Thumbnail => (
+                THUMBNAIL_MIME_TYPE.to_string(),
+                format!("\"{}-thumbnail\"", image.digest),
+            ),
+        };
+        let mut headers = HeaderMap::from([
+            ("cache-control".into(), IMAGE_CACHE_CONTROL.into()),
+            ("etag".into(), etag.clone().into()),
+        ]);
+
+        // weak validators match as well, since the image bytes never change
+        let not_modified = Self::get_header(&req.headers, "if-none-match").is_some_and(|tags| {
+            tags.split(',')
+                .map(|tag| tag.trim().trim_start_matches("W/"))
+                .any(|tag| tag == "*" || tag == etag)
+        });
+        if not_modified {
+            return HttpResponse::new(HTTP_NOT_MODIFIED, headers, ByteBuf::default(), None);
+        }
+
+        let data = match ImageStorage::get_image_data(image_id, variant) {
+            Ok(data) => data,
+            Err(err) => return HttpResponse::internal_error(err.to_string()),
+        };
+        headers.insert("content-type".into(), mime_type.into());
+        headers.insert("content-length".into(), data.len().to_string().into());
+
+        HttpResponse::new(HTTP_OK, headers, ByteBuf::from(data), None)
+    }
+
+    /// Verify the SHA-256 digest provided with the `sha256` query parameter against the stored document
+    fn verify_contract_document(
+        url: &Url,
+        contract_id: u64,
+        document_id: u64,
+        credential: Option<Credential>,
+    ) -> HttpResponse {
+        let Some(digest) = url
+            .query_pairs()
+            .find(|(key, _)| key == "sha256")
+            .map(|(_, value)| value.to_string())
+        else {
+            return HttpResponse::bad_request("missing sha256 digest".to_string());
+        };
+        DeferredData::verify_contract_document(contract_id.into(), document_id, &digest, credential)
+            .map(HttpResponse::ok)
+            .unwrap_or_else(|_| HttpResponse::not_found())
+    }
+
+    /// Get the audit log of a contract, paginated with the `offset` and `count` query parameters
+    fn get_contract_audit_log(url: &Url, id: u64, credential: Option<Credential>) -> HttpResponse {
+        let Ok(offset) = Self::get_query_param(url, "offset")
+            .map(|offset| offset.parse::<usize>())
+            .transpose()
+        else {
+            return HttpResponse::bad_request("invalid offset".to_string());
+        };
+        let Ok(count) = Self::get_query_param(url, "count")
+            .map(|count| count.parse::<usize>())
+            .transpose()
+        else {
+            return HttpResponse::bad_request("invalid count".to_string());
+        };
+        let pagination = Pagination {
+            offset: offset.unwrap_or_default(),
+            count: count.unwrap_or(DEFAULT_AUDIT_LOG_PAGE_SIZE),
+        };
+        DeferredData::get_contract_audit_log(&id.into(), pagination, credential)
+            .map(HttpResponse::ok)
+            .unwrap_or_else(|_| HttpResponse::not_found())
+    }
+
+    /// Get the chunk of a document starting at `offset`, without exceeding `end`.
+    ///
+    /// Only the bytes of the chunk are read from the storage.
+    ///
+    /// Returns the chunk and the offset of the next chunk, if any.
+    fn body_chunk(
+        contract_id: u64,
+        document_id: u64,
+        offset: u64,
+        end: u64,
+    ) -> DeferredDataResult<(ByteBuf, Option<u64>)> {
+        let offset = offset.min(end);
+        let chunk_end = end.min(offset.saturating_add(STREAMING_CHUNK_SIZE));
+
+        let body = ContractStorage::get_contract_document_range(
+            &contract_id.into(),
+            document_id,
+            offset,
+            chunk_end,
+        )?;
+        let next_offset = if chunk_end < end {
+            Some(chunk_end)
+        } else {
+            None
+        };
+
+        Ok((ByteBuf::from(body), next_offset))
+    }
+
+    /// Headers describing a document
+    fn document_headers(document: &ContractDocument) -> HeaderMap {
+        // fallback filename must be quoted ascii
+        let ascii_filename: String = document
+            .name
+            .chars()
+            .map(|c| {
+                if c.is_ascii() && !c.is_ascii_control() && c != '"' && c != '\\' {
+                    c
+                } else {
+                    '_'
+                }
+            })
+            .collect();
+        let encoded_filename: String = document
+            .name
+            .bytes()
+            .map(|b| {
+                if b.is_ascii_alphanumeric() || b"!#$&+-.^_`|~".contains(&b) {
+                    (b as char).to_string()
+                } else {
+                    format!("%{b:02X}")
+                }
+            })
+            .collect();
+
+        HashMap::from([
+            ("content-type".into(), document.mime_type.clone().into()),
+            (
+                "content-disposition".into(),
+                format!(
+                    "inline; filename=\"{ascii_filename}\"; filename*=UTF-8''{encoded_filename}"
+                )
+                .into(),
+            ),
+            ("accept-ranges".into(), "bytes".into()),
+        ])
+    }
+
+    /// Get the byte range requested with the `Range` header.
+    ///
+    /// Only single ranges are supported; unsupported or malformed ranges are ignored as per RFC 9110.
+    fn requested_range(req: &HttpRequest, size: u64) -> RequestedRange {
+        let Some(range) = Self::get_header(&req.headers, "range") else {
+            return RequestedRange::Full;
+        };
+        let Some(range) = range.trim().strip_prefix("bytes=") else {
+            return RequestedRange::Full;
+        };
+        if range.contains(',') {
+            return RequestedRange::Full;
+        }
+        let Some((first, last)) = range.split_once('-') else {
+            return RequestedRange::Full;
+        };
+
+        let (start, end) = match (first.trim(), last.trim()) {
+            ("", suffix) => {
+                // last N bytes
+                let Ok(suffix) = suffix.parse::<u64>() else {
+                    return RequestedRange::Full;
+                };
+                if suffix == 0 {
+                    return RequestedRange::NotSatisfiable;
+                }
+                (size.saturating_sub(suffix), size)
+            }
+            (first, "") => {
+                let Ok(start) = first.parse::<u64>() else {
+                    return RequestedRange::Full;
+                };
+                (start, size)
+            }
+            (first, last) => {
+                let (Ok(start), Ok(last)) = (first.parse::<u64>(), last.parse::<u64>()) else {
+                    return RequestedRange::Full;
+                };
+                if last < start {
+                    return RequestedRange::Full;
+                }
+                (start, size.min(last.saturating_add(1)))
+            }
+        };
+
+        if start >= size {
+            RequestedRange::NotSatisfiable
+        } else {
+            RequestedRange::Partial { start, end }
+        }
+    }
+
+    /// Get the credential of the request.
+    ///
+    /// A session token provided with the `Authorization: Bearer <token>` header takes precedence
+    /// over the signed message provided with the query parameters.
+    fn credential(headers: &HeaderMap, url: &Url) -> Option<Credential> {
+        let authorization = Self::get_header(headers, "authorization")
+            .and_then(|authorization| authorization.trim().split_once(' '));
+        if let Some((scheme, token)) = authorization {
+            // the authentication scheme is case insensitive
+            if scheme.eq_ignore_ascii_case("bearer") {
+                return Some(Credential::Session(token.trim().to_string()));
+            }
+        }
+
+        Self::signed_message(url).map(Credential::from)
+    }
+
+    /// Headers carrying `credential` into the streaming callback token.
+    ///
+    /// Only the session token is carried, since signed messages are already part of the request URL.
+    fn credential_headers(credential: Option<&Credential>) -> HeaderMap {
+        match credential {
+            Some(Credential::Session(token)) => {
+                HeaderMap::from([("authorization".into(), format!("Bearer {token}").into())])
+            }
+            _ => HeaderMap::new(),
+        }
+    }
+
+    /// Get signed message from URL.
+    ///
+    /// SIWE messages are provided with `message`, while typed data with `issued_at`, `expires_at` and `nonce`.
+    fn signed_message(url: &Url) -> Option<SignedMessage> {
+        let signature = Self::get_query_param(url, "signature")?;
+
+        if let Some(message) = Self::get_query_param(url, "message") {
+            return Some(SignedMessage::Siwe { message, signature });
+        }
+
+        Some(SignedMessage::TypedData {
+            issued_at: Self::get_query_param(url, "issued_at")?.parse().ok()?,
+            expires_at: Self::get_query_param(url, "expires_at")?.parse().ok()?,
+            nonce: Self::get_query_param(url, "nonce")?,
+            signature,
+        })
+    }
+
+    /// Whether the request is served by the raw domain (e.g. `<canister-id>.raw.icp0.io`),
+    /// whose responses are not verified by the HTTP gateway
+    fn is_raw_domain(headers: &HeaderMap) -> bool {
+        Self::get_header(headers, "host").is_some_and(|host| host.split('.').nth(1) == Some("raw"))
+    }
+
+    /// Get a header value; header names are case insensitive
+    fn get_header<'a>(headers: &'a HeaderMap, name: &str) -> Option<&'a str> {
+        headers
+            .iter()
+            .find(|(key, _)| key.eq_ignore_ascii_case(name))
+            .map(|(_, value)| value.as_ref())
+    }
+
+    fn get_query_param(url: &Url, key: &str) -> Option<String> {
+        url.query_pairs().find_map(|(k, value)| {
+            if k == key {
+                Some(value.to_string())
+            } else {
+                None
+            }
+        })
+    }
+}
+
+#[cfg(test)]
+mod test {
+
+    use std::borrow::Cow;
+    use std::collections::HashMap;
+
+    use candid::{Nat, Principal};
+    use did::deferred::{
+        ContractDocument, ContractImage, ContractSummaryPage, GenericValue, GeoCluster,
+        RestrictionLevel, SearchSuggestion, Seller, TokenMetadata,
+    };
+    use did::{FeatureCollection, H160};
+    use pretty_assertions::assert_eq;
+
+    use super::*;
+    use crate::app::test_utils::{
+        mock_image, signed_message, signed_typed_data, signer_address, store_mock_contract,
+        store_mock_contract_with,
+    };
+
+    const RAW_HOST: &str = "2m6dw-uaaaa-aaaal-arumq-cai.raw.icp0.io";
+    const CERTIFIED_HOST: &str = "2m6dw-uaaaa-aaaal-arumq-cai.icp0.io";
+
+    #[tokio::test]
+    async fn test_should_get_contract() {
+        store_mock_contract_with(1u64, 100u64, |contract| {
+            contract.restricted_properties = vec![];
+        });
+
+        let url = Url::parse("http://localhost/contract/1").unwrap();
+
+        let req = HttpRequest {
+            method: Cow::from("GET".to_string()),
+            url: url.to_string(),
+            headers: HashMap::default(),
+            body: Default::default(),
+        };
+
+        let res = HttpApi::handle_http_request(req).await;
+        assert_eq!(res.status_code, 200);
+
+        let contract: did::deferred::Contract = serde_json::from_slice(&res.body).unwrap();
+
+        let contract_from_storage = ContractStorage::get_contract(&Nat::from(1u64)).unwrap();
+        assert_eq!(contract, contract_from_storage);
+    }
+
+    #[tokio::test]
+    async fn test_should_verify_contract_document() {
+        store_mock_contract(1u64, 100u64);
+        let document_id = upload_document(b"hello".to_vec());
+
+        let req = HttpRequest {
+            method: Cow::from("GET".to_string()),
+            url: format!(
+                "/contract/1/document/{document_id}/verify?sha256=2cf24dba5fb0a30e26e83b2ac5b9e29e1b161e5c1fa7425e73043362938b9824"
+            ),
+            headers: HashMap::default(),
+            body: Default::default(),
+        };
+        let res = HttpApi::handle_http_request(req).await;
+        assert_eq!(res.status_code, 200);
+        assert!(serde_json::from_slice::<bool>(&res.body).unwrap());
+
+        let req = HttpRequest {
+            method: Cow::from("GET".to_string()),
+            url: format!("/contract/1/document/{document_id}/verify?sha256=00"),
+            headers: HashMap::default(),
+            body: Default::default(),
+        };
+        let res = HttpApi::handle_http_request(req).await;
+        assert_eq!(res.status_code, 200);
+        assert!(!serde_json::from_slice::<bool>(&res.body).unwrap());
+    }
+
+    #[tokio::test]
+    async fn test_should_get_contract_audit_log() {
+        store_mock_contract(1u64, 100u64);
+        for city in ["Milan", "Turin", "Naples"] {
+            ContractStorage::update_contract_property(
+                &1u64.into(),
+                "contract:city".to_string(),
+                GenericValue::TextContent(city.to_string()),
+            )
+            .unwrap();
+        }
+
+        let req = HttpRequest {
+            method: Cow::from("GET".to_string()),
+            url: "/contract/1/audit?offset=1&count=5".to_string(),
+            headers: HashMap::default(),
+            body: Default::default(),
+        };
+        let res = HttpApi::handle_http_request(req).await;
+        assert_eq!(res.status_code, 200);
+
+        let log: Vec<did::deferred::AuditLogEntry> = serde_json::from_slice(&res.body).unwrap();
+        assert_eq!(log.len(), 2);
+        assert_eq!(
+            log[1].operation,
+            did::deferred::AuditOperation::SetProperty {
+                key: "contract:city".to_string(),
+                previous: Some(GenericValue::TextContent("Turin".to_string())),
+                new: Some(GenericValue::TextContent("Naples".to_string())),
+            }
+        );
+    }
+
+    #[tokio::test]
+    async fn test_should_get_archived_contract() {
+        store_mock_contract_with(1u64, 100u64, |contract| {
+            contract.sellers = vec![Seller {
+                address: signer_address(),
+                quota: 100,
+            }];
+            contract.closed = true;
+        });
+
+        let req = HttpRequest {
+            method: Cow::from("GET".to_string()),
+            url: "/archive/contract/1".to_string(),
+            headers: HashMap::default(),
+            body: Default::default(),
+        };
+        let res = HttpApi::handle_http_request(req).await;
+        assert_eq!(res.status_code, 404);
+
+        let SignedMessage::Siwe { message, signature } = signed_message(1) else {
+            panic!("expected SIWE message");
+        };
+        let url = Url::parse_with_params(
+            "http://localhost/archive/contract/1",
+            &[("message", message), ("signature", signature)],
+        )
+        .unwrap();
+        let req = HttpRequest {
+            method: Cow::from("GET".to_string()),
+            url: url.to_string(),
+            headers: HashMap::default(),
+            body: Default::default(),
+        };
+        let res = HttpApi::handle_http_request(req).await;
+        assert_eq!(res.status_code, 200);
 
-        DeferredData::get_contract_document(contract_id.into(), document_id, signed_message)
-            .map(HttpResponse::ok)
-            .unwrap_or_else(|_| HttpResponse::not_found())
+        let contract: did::deferred::Contract = serde_json::from_slice(&res.body).unwrap();
+        assert!(contract.closed);
+        // sellers can read the seller address
+        assert_eq!(contract.restricted_properties.len(), 1);
     }
 
-    /// Get signed message from URL
-    fn signed_message(url: Url) -> Option<SignedMessage> {
-        let message = Self::get_query_param(&url, "message")?;
-        let signature =
-            Self::get_query_param(&url, "signature").and_then(|s| H520::from_str(&s).ok())?;
+    #[tokio::test]
+    async fn test_should_get_contract_with_typed_data_signature() {
+        store_mock_contract_with(1u64, 100u64, |contract| {
+            contract.sellers = vec![Seller {
+                address: signer_address(),
+                quota: 100,
+            }];
+        });
 
-        Some(SignedMessage { message, signature })
-    }
+        // chain ID is not configured
+        let SignedMessage::TypedData {
+            issued_at,
+            expires_at,
+            nonce,
+            signature,
+        } = signed_typed_data(1, 0)
+        else {
+            panic!("expected typed data");
+        };
+        let url = Url::parse_with_params(
+            "http://localhost/contract/1",
+            &[
+                ("issued_at", issued_at.to_string()),
+                ("expires_at", expires_at.to_string()),
+                ("nonce", nonce),
+                ("signature", signature),
+            ],
+        )
+        .unwrap();
+        let req = HttpRequest {
+            method: Cow::from("GET".to_string()),
+            url: url.to_string(),
+            headers: HashMap::default(),
+            body: Default::default(),
+        };
+        let res = HttpApi::handle_http_request(req).await;
+        assert_eq!(res.status_code, 200);
 
-    fn get_query_param(url: &Url, key: &str) -> Option<String> {
-        url.query_pairs().find_map(|(k, value)| {
-            if k == key {
-                Some(value.to_string())
-            } else {
-                None
-            }
-        })
+        let contract: did::deferred::Contract = serde_json::from_slice(&res.body).unwrap();
+        // sellers can read the seller address
+        assert_eq!(contract.restricted_properties.len(), 1);
     }
-}
-
-#[cfg(test)]
-mod test {
-
-    use std::borrow::Cow;
-    use std::collections::HashMap;
-
-    use candid::{Nat, Principal};
-    use did::deferred::{ContractDocument, GenericValue, RestrictionLevel, Seller};
-    use did::H160;
-    use pretty_assertions::assert_eq;
-
-    use super::*;
-    use crate::app::test_utils::{store_mock_contract, store_mock_contract_with};
 
     #[tokio::test]
-    async fn test_should_get_contract() {
+    async fn test_should_get_contract_with_session_token() {
         store_mock_contract_with(1u64, 100u64, |contract| {
-            contract.restricted_properties = vec![];
+            contract.sellers = vec![Seller {
+                address: signer_address(),
+                quota: 100,
+            }];
         });
+        store_mock_contract(2u64, 100u64);
 
-        let url = Url::parse("http://localhost/contract/1").unwrap();
+        let session = DeferredData::create_contract_session(1u64.into(), signed_message(1))
+            .await
+            .unwrap();
+        assert_eq!(session.access_level, RestrictionLevel::Seller);
 
-        let req = HttpRequest {
+        let request = |url: &str, token: &str| HttpRequest {
             method: Cow::from("GET".to_string()),
             url: url.to_string(),
-            headers: HashMap::default(),
+            headers: HashMap::from([("Authorization".into(), format!("Bearer {token}").into())]),
             body: Default::default(),
         };
 
-        let res = HttpApi::handle_http_request(req).await;
+        let res =
+            HttpApi::handle_http_request(request("http://localhost/contract/1", &session.token))
+                .await;
         assert_eq!(res.status_code, 200);
+        let contract: did::deferred::Contract = serde_json::from_slice(&res.body).unwrap();
+        // sellers can read the seller address
+        assert_eq!(contract.restricted_properties.len(), 1);
 
+        // unknown token
+        let res =
+            HttpApi::handle_http_request(request("http://localhost/contract/1", "unknown")).await;
         let contract: did::deferred::Contract = serde_json::from_slice(&res.body).unwrap();
+        assert!(contract.restricted_properties.is_empty());
 
-        let contract_from_storage = ContractStorage::get_contract(&Nat::from(1u64)).unwrap();
-        assert_eq!(contract, contract_from_storage);
+        // the session is scoped to the contract
+        let res =
+            HttpApi::handle_http_request(request("http://localhost/contract/2", &session.token))
+                .await;
+        let contract: did::deferred::Contract = serde_json::from_slice(&res.body).unwrap();
+        assert!(contract.restricted_properties.is_empty());
     }
 
     #[tokio::test]
@@ -186,17 +1194,7 @@ mod test {
     #[tokio::test]
     async fn test_should_get_contract_document() {
         store_mock_contract(1u64, 100u64);
-        let document_id = ContractStorage::upload_contract_document(
-            &Nat::from(1u64),
-            ContractDocument {
-                access_list: vec![RestrictionLevel::Public],
-                mime_type: "application/pdf".to_string(),
-                name: "document".to_string(),
-                size: 4,
-            },
-            vec![0x01, 0x02, 0x03, 0x04],
-        )
-        .expect("Failed to upload document");
+        let document_id = upload_document(vec![0x01, 0x02, 0x03, 0x04]);
 
         let url = Url::parse(&format!(
             "http://localhost/contract/1/document/{document_id}"
@@ -212,12 +1210,181 @@ mod test {
 
         let res = HttpApi::handle_http_request(req).await;
         assert_eq!(res.status_code, 200);
+        assert_eq!(res.headers.get("content-type").unwrap(), "application/pdf");
+        assert_eq!(res.headers.get("content-length").unwrap(), "4");
+        assert_eq!(
+            res.headers.get("content-disposition").unwrap(),
+            "inline; filename=\"document.pdf\"; filename*=UTF-8''document.pdf"
+        );
+        assert_eq!(res.body.as_slice(), &[0x01, 0x02, 0x03, 0x04]);
+        assert!(res.streaming_strategy.is_none());
+    }
+
+    #[tokio::test]
+    async fn test_should_get_contract_document_head() {
+        store_mock_contract(1u64, 100u64);
+        let document_id = upload_document(vec![0x01, 0x02, 0x03, 0x04]);
+
+        let req = HttpRequest {
+            method: Cow::from("HEAD".to_string()),
+            url: format!("/contract/1/document/{document_id}"),
+            headers: HashMap::from([("Host".into(), RAW_HOST.into())]),
+            body: Default::default(),
+        };
+
+        let res = HttpApi::handle_http_request(req).await;
+        assert_eq!(res.status_code, 200);
+        assert_eq!(res.headers.get("content-length").unwrap(), "4");
+        assert_eq!(res.headers.get("accept-ranges").unwrap(), "bytes");
+        assert!(res.body.is_empty());
+
+        // the certified domain gets the certified response to GET
+        let req = HttpRequest {
+            method: Cow::from("HEAD".to_string()),
+            url: format!("/contract/1/document/{document_id}"),
+            headers: HashMap::from([("Host".into(), CERTIFIED_HOST.into())]),
+            body: Default::default(),
+        };
+
+        let res = HttpApi::handle_http_request(req).await;
+        assert_eq!(res.status_code, 200);
+        assert_eq!(res.body.as_slice(), &[0x01, 0x02, 0x03, 0x04]);
+    }
+
+    #[tokio::test]
+    async fn test_should_get_contract_document_range() {
+        store_mock_contract(1u64, 100u64);
+        let document_id = upload_document(vec![0x01, 0x02, 0x03, 0x04]);
+
+        let req = HttpRequest {
+            method: Cow::from("GET".to_string()),
+            url: format!("/contract/1/document/{document_id}"),
+            headers: HashMap::from([
+                ("Host".into(), RAW_HOST.into()),
+                ("Range".into(), "bytes=1-2".into()),
+            ]),
+            body: Default::default(),
+        };
+
+        let res = HttpApi::handle_http_request(req).await;
+        assert_eq!(res.status_code, 206);
+        assert_eq!(res.headers.get("content-range").unwrap(), "bytes 1-2/4");
+        assert_eq!(res.body.as_slice(), &[0x02, 0x03]);
+
+        let req = HttpRequest {
+            method: Cow::from("GET".to_string()),
+            url: format!("/contract/1/document/{document_id}"),
+            headers: HashMap::from([
+                ("Host".into(), RAW_HOST.into()),
+                ("range".into(), "bytes=10-".into()),
+            ]),
+            body: Default::default(),
+        };
+
+        let res = HttpApi::handle_http_request(req).await;
+        assert_eq!(res.status_code, 416);
+        assert_eq!(res.headers.get("content-range").unwrap(), "bytes */4");
+
+        // the range is ignored by the certified domain
+        let req = HttpRequest {
+            method: Cow::from("GET".to_string()),
+            url: format!("/contract/1/document/{document_id}"),
+            headers: HashMap::from([
+                ("Host".into(), CERTIFIED_HOST.into()),
+                ("Range".into(), "bytes=1-2".into()),
+            ]),
+            body: Default::default(),
+        };
+
+        let res = HttpApi::handle_http_request(req).await;
+        assert_eq!(res.status_code, 200);
+        assert!(!res.headers.contains_key("content-range"));
+        assert_eq!(res.body.as_slice(), &[0x01, 0x02, 0x03, 0x04]);
+    }
+
+    #[tokio::test]
+    async fn test_should_stream_contract_document() {
+        store_mock_contract(1u64, 100u64);
+        let data: Vec<u8> = (0..(STREAMING_CHUNK_SIZE * 2 + 10))
+            .map(|i| (i % 256) as u8)
+            .collect();
+        let document_id = upload_document(data.clone());
+
+        let req = HttpRequest {
+            method: Cow::from("GET".to_string()),
+            url: format!("/contract/1/document/{document_id}"),
+            headers: HashMap::from([("Cookie".into(), "session=secret".into())]),
+            body: Default::default(),
+        };
+
+        let res = HttpApi::handle_http_request(req).await;
+        assert_eq!(res.status_code, 200);
+        assert_eq!(res.body.len() as u64, STREAMING_CHUNK_SIZE);
+
+        let mut body = res.body.to_vec();
+        let Some(StreamingStrategy::Callback { callback, token }) = res.streaming_strategy else {
+            panic!("expected streaming strategy");
+        };
+        assert_eq!(callback.method, STREAMING_CALLBACK_METHOD);
+        // the headers of the request are not carried by the token
+        assert!(token.headers.is_empty());
+
+        let mut next_token = Some(token);
+        while let Some(token) = next_token {
+            let chunk = HttpApi::http_request_streaming_callback(token);
+            body.extend_from_slice(&chunk.body);
+            next_token = chunk.token;
+        }
+
+        assert_eq!(body, data);
+    }
 
-        let document: did::deferred::ContractDocumentData =
-            serde_json::from_slice(&res.body).unwrap();
+    #[test]
+    fn test_should_carry_only_session_token_into_streaming_token() {
+        assert_eq!(
+            HttpApi::credential_headers(Some(&Credential::Session("token".to_string()))),
+            HeaderMap::from([("authorization".into(), "Bearer token".into())])
+        );
+        assert!(HttpApi::credential_headers(None).is_empty());
+    }
+
+    #[test]
+    fn test_should_parse_requested_range() {
+        let req = |range: &str| HttpRequest {
+            method: Cow::from("GET".to_string()),
+            url: "/".to_string(),
+            headers: HashMap::from([("range".into(), range.to_string().into())]),
+            body: Default::default(),
+        };
 
-        assert_eq!(document.mime_type, "application/pdf".to_string());
-        assert_eq!(document.data, vec![0x01, 0x02, 0x03, 0x04]);
+        assert_eq!(
+            HttpApi::requested_range(&req("bytes=0-99"), 50),
+            RequestedRange::Partial { start: 0, end: 50 }
+        );
+        assert_eq!(
+            HttpApi::requested_range(&req("bytes=10-"), 50),
+            RequestedRange::Partial { start: 10, end: 50 }
+        );
+        assert_eq!(
+            HttpApi::requested_range(&req("bytes=-5"), 50),
+            RequestedRange::Partial { start: 45, end: 50 }
+        );
+        assert_eq!(
+            HttpApi::requested_range(&req("bytes=50-60"), 50),
+            RequestedRange::NotSatisfiable
+        );
+        assert_eq!(
+            HttpApi::requested_range(&req("bytes=0-1,4-5"), 50),
+            RequestedRange::Full
+        );
+        assert_eq!(
+            HttpApi::requested_range(&req("items=0-1"), 50),
+            RequestedRange::Full
+        );
+        assert_eq!(
+            HttpApi::requested_range(&req("bytes=5-1"), 50),
+            RequestedRange::Full
+        );
     }
 
     #[tokio::test]
@@ -370,4 +1537,399 @@ mod test {
         let contracts: Vec<Nat> = serde_json::from_slice(&res.body).unwrap();
         assert_eq!(contracts.len(), 1);
     }
+
+    #[tokio::test]
+    async fn test_should_search_contracts_and_suggest_places() {
+        for (id, name, city) in [
+            (1u64, "Bilocale", "Milano"),
+            (2, "Villa a Milano", "Milano"),
+            (3, "Villa", "Roma"),
+        ] {
+            store_mock_contract_with(id, 100, |contract| {
+                contract.properties = vec![
+                    (
+                        "contract:name".to_string(),
+                        GenericValue::TextContent(name.to_string()),
+                    ),
+                    (
+                        "contract:city".to_string(),
+                        GenericValue::TextContent(city.to_string()),
+                    ),
+                ];
+            });
+        }
+        let get = |url: &str| {
+            HttpApi::handle_http_request(HttpRequest {
+                method: Cow::from("GET".to_string()),
+                url: url.to_string(),
+                headers: HashMap::default(),
+                body: Default::default(),
+            })
+        };
+
+        let res = get("/contracts?q=milano").await;
+        assert_eq!(res.status_code, 200);
+        let contracts: Vec<Nat> = serde_json::from_slice(&res.body).unwrap();
+        assert_eq!(contracts, vec![Nat::from(2u64), Nat::from(1u64)]);
+
+        // the query is combined with the other filters
+        let res = get("/contracts?q=villa&city=roma").await;
+        let contracts: Vec<Nat> = serde_json::from_slice(&res.body).unwrap();
+        assert_eq!(contracts, vec![Nat::from(3u64)]);
+
+        let res = get("/contracts/autocomplete?q=mi").await;
+        assert_eq!(res.status_code, 200);
+        let suggestions: Vec<SearchSuggestion> = serde_json::from_slice(&res.body).unwrap();
+        assert_eq!(suggestions.len(), 1);
+        assert_eq!(suggestions[0].value, "Milano");
+        assert_eq!(suggestions[0].count, 2);
+
+        let res = get("/contracts/autocomplete").await;
+        assert_eq!(res.status_code, 400);
+    }
+
+    #[tokio::test]
+    async fn test_should_export_contracts() {
+        store_mock_contract_with(1, 100, |contract| {
+            contract.properties = vec![
+                (
+                    "contract:latitude".to_string(),
+                    GenericValue::FloatContent(45.46),
+                ),
+                (
+                    "contract:longitude".to_string(),
+                    GenericValue::FloatContent(9.19),
+                ),
+            ];
+        });
+        store_mock_contract_with(2, 100, |contract| contract.properties = vec![]);
+        let get = |url: &str| {
+            HttpApi::handle_http_request(HttpRequest {
+                method: Cow::from("GET".to_string()),
+                url: url.to_string(),
+                headers: HashMap::default(),
+                body: Default::default(),
+            })
+        };
+
+        let res = get("/contracts?format=geojson").await;
+        assert_eq!(res.status_code, 200);
+        assert_eq!(
+            res.headers.get("content-type").unwrap(),
+            "application/geo+json"
+        );
+        let collection: FeatureCollection = serde_json::from_slice(&res.body).unwrap();
+        assert_eq!(collection.features.len(), 1);
+        assert_eq!(collection.features[0].geometry.coordinates, [9.19, 45.46]);
+
+        let res = get("/contracts?format=csv").await;
+        assert_eq!(res.status_code, 200);
+        assert_eq!(
+            res.headers.get("content-type").unwrap(),
+            "text/csv; charset=utf-8"
+        );
+        let csv = String::from_utf8(res.body.to_vec()).unwrap();
+        let mut lines = csv.lines();
+        assert_eq!(
+            lines.next().unwrap(),
+            "id,type,value,deposit,currency,installments,expiration,agency,contract:latitude,contract:longitude"
+        );
+        assert_eq!(lines.count(), 2);
+
+        let res = get("/contracts?format=xml").await;
+        assert_eq!(res.status_code, 400);
+    }
+
+    #[tokio::test]
+    async fn test_should_get_contract_feed() {
+        store_mock_contract_with(1, 100, |contract| {
+            contract.value = 100;
+            contract.created_at = Some(1_000_000_000);
+        });
+        store_mock_contract_with(2, 100, |contract| {
+            contract.value = 200;
+            contract.created_at = Some(2_000_000_000);
+        });
+        ContractStorage::close_contract(&1u64.into()).unwrap();
+        let get = |url: &str| {
+            HttpApi::handle_http_request(HttpRequest {
+                method: Cow::from("GET".to_string()),
+                url: url.to_string(),
+                headers: HashMap::default(),
+                body: Default::default(),
+            })
+        };
+
+        let res = get("/contracts/feed").await;
+        assert_eq!(res.status_code, 200);
+        assert_eq!(
+            res.headers.get("content-type").unwrap(),
+            "application/atom+xml; charset=utf-8"
+        );
+        let feed = String::from_utf8(res.body.to_vec()).unwrap();
+        let position = |id: &str| feed.find(&format!("<id>urn:ekoke:contract:{id}</id>"));
+        assert!(position("1:closed").is_some());
+        assert!(position("1:closed") < position("2:created"));
+        assert!(position("2:created") < position("1:created"));
+        assert!(feed.contains("/contract/2/metadata"));
+        // the link of the closed entry still resolves
+        let closed_entry = &feed[feed.find("urn:ekoke:contract:1:closed").unwrap()..];
+        let href = &closed_entry[closed_entry.find("href=\"").unwrap() + 6..];
+        let link = &href[..href.find('"').unwrap()];
+        let path = &link[link.find("/contract/").unwrap()..];
+        assert_eq!(path, "/contract/1/metadata");
+        assert_eq!(get(path).await.status_code, 200);
+
+        let res = get("/contracts/feed?minPrice=150&limit=10").await;
+        let feed = String::from_utf8(res.body.to_vec()).unwrap();
+        assert_eq!(feed.matches("<entry>").count(), 1);
+        assert!(feed.contains("urn:ekoke:contract:2:created"));
+
+        let res = get("/contracts/feed?limit=1").await;
+        let feed = String::from_utf8(res.body.to_vec()).unwrap();
+        assert_eq!(feed.matches("<entry>").count(), 1);
+
+        assert_eq!(get("/contracts/feed?limit=many").await.status_code, 400);
+        assert_eq!(get("/contracts/feed?type=lease").await.status_code, 400);
+    }
+
+    #[tokio::test]
+    async fn test_should_get_contract_images() {
+        store_mock_contract(1, 100);
+        let image_id =
+            ImageStorage::insert_image(&1u64.into(), mock_image(), vec![1, 2, 3], vec![4]).unwrap();
+        let get = |url: String, headers: HeaderMap| {
+            HttpApi::handle_http_request(HttpRequest {
+                method: Cow::from("GET".to_string()),
+                url,
+                headers,
+                body: Default::default(),
+            })
+        };
+
+        let res = get("/contract/1/images".to_string(), HashMap::default()).await;
+        assert_eq!(res.status_code, 200);
+        let images: Vec<ContractImage> = serde_json::from_slice(&res.body).unwrap();
+        assert_eq!(images.len(), 1);
+        assert_eq!(images[0].id, image_id);
+
+        let res = get(format!("/contract/1/image/{image_id}"), HashMap::default()).await;
+        assert_eq!(res.status_code, 200);
+        assert_eq!(res.body.as_slice(), &[1, 2, 3]);
+        assert_eq!(res.headers.get("content-type").unwrap(), "image/jpeg");
+        assert_eq!(
+            res.headers.get("cache-control").unwrap(),
+            IMAGE_CACHE_CONTROL
+        );
+        let etag = res.headers.get("etag").unwrap().to_string();
+
+        let res = get(
+            format!("/contract/1/image/{image_id}/thumbnail"),
+            HashMap::default(),
+        )
+        .await;
+        assert_eq!(res.status_code, 200);
+        assert_eq!(res.body.as_slice(), &[4]);
+        assert_ne!(res.headers.get("etag").unwrap(), &etag);
+
+        // cached image
+        let res = get(
+            format!("/contract/1/image/{image_id}"),
+            HashMap::from([("If-None-Match".into(), etag.into())]),
+        )
+        .await;
+        assert_eq!(res.status_code, 304);
+        assert!(res.body.is_empty());
+
+        let res = get("/contract/1/image/100".to_string(), HashMap::default()).await;
+        assert_eq!(res.status_code, 404);
+        let res = get(format!("/contract/2/image/{image_id}"), HashMap::default()).await;
+        assert_eq!(res.status_code, 404);
+        let res = get("/contract/1/image/foo".to_string(), HashMap::default()).await;
+        assert_eq!(res.status_code, 400);
+    }
+
+    #[tokio::test]
+    async fn test_should_get_token_metadata() {
+        store_mock_contract(1, 100);
+        let get = |url: &str| {
+            HttpApi::handle_http_request(HttpRequest {
+                method: Cow::from("GET".to_string()),
+                url: url.to_string(),
+                headers: HashMap::default(),
+                body: Default::default(),
+            })
+        };
+
+        let res = get("/contract/1/metadata").await;
+        assert_eq!(res.status_code, 200);
+        let metadata: TokenMetadata = serde_json::from_slice(&res.body).unwrap();
+        assert_eq!(metadata.name, "Deferred contract #1");
+        assert!(!metadata.attributes.is_empty());
+
+        let res = get("/contract/1/metadata/42").await;
+        assert_eq!(res.status_code, 200);
+        let metadata: TokenMetadata = serde_json::from_slice(&res.body).unwrap();
+        assert_eq!(metadata.name, "Deferred contract #1 - token #42");
+
+        assert_eq!(get("/contract/2/metadata").await.status_code, 404);
+        assert_eq!(get("/contract/1/metadata/foo").await.status_code, 400);
+
+        // token URIs keep pointing at the metadata after the contract is closed
+        ContractStorage::close_contract(&1u64.into()).unwrap();
+        let res = get("/contract/1/metadata/42").await;
+        assert_eq!(res.status_code, 200);
+        let metadata: TokenMetadata = serde_json::from_slice(&res.body).unwrap();
+        assert_eq!(metadata.name, "Deferred contract #1 - token #42");
+    }
+
+    #[tokio::test]
+    async fn test_should_reject_malformed_contract_filters() {
+        store_mock_contract(1, 100);
+
+        for url in [
+            "/contracts?rooms=many",
+            "/contracts/summaries?garden=maybe",
+            "/contracts/clusters?energyClass=Z",
+        ] {
+            let res = HttpApi::handle_http_request(HttpRequest {
+                method: Cow::from("GET".to_string()),
+                url: url.to_string(),
+                headers: HashMap::default(),
+                body: Default::default(),
+            })
+            .await;
+            assert_eq!(res.status_code, 400, "{url}");
+        }
+    }
+
+    #[tokio::test]
+    async fn test_should_list_contract_summaries() {
+        for (id, value) in [(1u64, 300u64), (2, 100), (3, 200)] {
+            store_mock_contract_with(id, 100, |contract| {
+                contract.value = value;
+            });
+        }
+
+        let list = |url: &str| {
+            HttpApi::handle_http_request(HttpRequest {
+                method: Cow::from("GET".to_string()),
+                url: url.to_string(),
+                headers: HashMap::default(),
+                body: Default::default(),
+            })
+        };
+        let ids = |page: &ContractSummaryPage| {
+            page.contracts
+                .iter()
+                .map(|summary| summary.id.clone())
+                .collect::<Vec<_>>()
+        };
+
+        let res = list("/contracts/summaries?sortBy=value&order=desc&limit=2").await;
+        assert_eq!(res.status_code, 200);
+        let page: ContractSummaryPage = serde_json::from_slice(&res.body).unwrap();
+        assert_eq!(ids(&page), vec![Nat::from(1u64), Nat::from(3u64)]);
+        let cursor = page.next_cursor.unwrap();
+
+        let res = list(&format!(
+            "/contracts/summaries?sortBy=value&order=desc&limit=2&cursor={cursor}"
+        ))
+        .await;
+        let page: ContractSummaryPage = serde_json::from_slice(&res.body).unwrap();
+        assert_eq!(ids(&page), vec![Nat::from(2u64)]);
+        assert!(page.next_cursor.is_none());
+
+        // filters apply to the listing
+        let res = list("/contracts/summaries?minPrice=150").await;
+        let page: ContractSummaryPage = serde_json::from_slice(&res.body).unwrap();
+        assert_eq!(ids(&page), vec![Nat::from(1u64), Nat::from(3u64)]);
+
+        // a cursor is valid only with its sorting
+        let res = list(&format!("/contracts/summaries?sortBy=id&cursor={cursor}")).await;
+        assert_eq!(res.status_code, 400);
+        let res = list("/contracts/summaries?sortBy=rooms").await;
+        assert_eq!(res.status_code, 400);
+        let res = list("/contracts/summaries?sortBy=distance").await;
+        assert_eq!(res.status_code, 400);
+    }
+
+    #[tokio::test]
+    async fn test_should_list_contracts_by_distance_and_get_clusters() {
+        for (id, value, latitude, longitude) in [
+            (1u64, 100u64, 41.90, 12.49), // Rome
+            (2, 200, 45.46, 9.19),        // Milan
+            (3, 300, 45.48, 9.21),        // Milan
+        ] {
+            store_mock_contract_with(id, 100, |contract| {
+                contract.value = value;
+                contract.properties = vec![
+                    (
+                        "contract:latitude".to_string(),
+                        GenericValue::FloatContent(latitude),
+                    ),
+                    (
+                        "contract:longitude".to_string(),
+                        GenericValue::FloatContent(longitude),
+                    ),
+                ];
+            });
+        }
+        let get = |url: &str| {
+            HttpApi::handle_http_request(HttpRequest {
+                method: Cow::from("GET".to_string()),
+                url: url.to_string(),
+                headers: HashMap::default(),
+                body: Default::default(),
+            })
+        };
+
+        let res = get("/contracts/summaries?sortBy=distance&latitude=45.47&longitude=9.2").await;
+        assert_eq!(res.status_code, 200);
+        let page: ContractSummaryPage = serde_json::from_slice(&res.body).unwrap();
+        let ids = page
+            .contracts
+            .iter()
+            .map(|summary| summary.id.clone())
+            .collect::<Vec<_>>();
+        assert_eq!(ids.last(), Some(&Nat::from(1u64)));
+
+        let res = get("/contracts/clusters?zoom=2").await;
+        assert_eq!(res.status_code, 200);
+        let clusters: Vec<GeoCluster> = serde_json::from_slice(&res.body).unwrap();
+        let mut counts = clusters
+            .iter()
+            .map(|cluster| cluster.count)
+            .collect::<Vec<_>>();
+        counts.sort();
+        assert_eq!(counts, vec![1, 2]);
+
+        // filters apply to the clusters
+        let res = get("/contracts/clusters?zoom=2&bbox=44,8,46,10&minPrice=250").await;
+        let clusters: Vec<GeoCluster> = serde_json::from_slice(&res.body).unwrap();
+        assert_eq!(clusters.len(), 1);
+        assert_eq!(clusters[0].count, 1);
+
+        let res = get("/contracts/clusters?zoom=21").await;
+        assert_eq!(res.status_code, 400);
+        let res = get("/contracts/clusters?bbox=1,2,3").await;
+        assert_eq!(res.status_code, 400);
+    }
+
+    fn upload_document(data: Vec<u8>) -> u64 {
+        ContractStorage::upload_contract_document(
+            &Nat::from(1u64),
+            ContractDocument {
+                access_list: vec![RestrictionLevel::Public],
+                mime_type: "application/pdf".to_string(),
+                name: "document.pdf".to_string(),
+                size: data.len() as u64,
+                previous_version: None,
+                superseded_by: None,
+                digest: None,
+            },
+            data,
+        )
+        .expect("Failed to upload document")
+    }
 }
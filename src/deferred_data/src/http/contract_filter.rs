@@ -1,18 +1,33 @@
+use std::collections::BTreeSet;
+use std::str::FromStr;
+
 use candid::Principal;
-use did::deferred::Contract;
-use did::H160;
+use did::deferred::{Contract, ContractType, GeoPoint};
+use did::{H160, ID};
+use time::Date;
 use url::Url;
 
+use crate::app::geo::{self, BoundingBox};
+use crate::app::search::{tokenize, SearchField};
+use crate::app::{ContractIndex, ContractIndexStorage, GeoIndexStorage, SearchIndexStorage};
+
 const FILTER_SELLER: &str = "seller";
 const FILTER_BUYER: &str = "buyer";
 const FILTER_AGENT: &str = "agent";
+const FILTER_CURRENCY: &str = "currency";
+const FILTER_TYPE: &str = "type";
+const FILTER_QUERY: &str = "q";
 
 const FILTER_MIN_PRICE: &str = "minPrice";
 const FILTER_MAX_PRICE: &str = "maxPrice";
 
+const FILTER_MIN_EXPIRATION: &str = "minExpiration";
+const FILTER_MAX_EXPIRATION: &str = "maxExpiration";
+
 const FILTER_POSITION_LATITUDE: &str = "latitude";
 const FILTER_POSITION_LONGITUDE: &str = "longitude";
 const FILTER_POSITION_RADIUS: &str = "radius";
+const FILTER_BOUNDING_BOX: &str = "bbox";
 
 const FILTER_PROPERTY_NAME: &str = "name";
 const FILTER_PROPERTY_DESCRIPTION: &str = "description";
@@ -34,33 +49,127 @@ const FILTER_PROPERTY_POOL: &str = "pool";
 const FILTER_PROPERTY_GARAGE: &str = "garage";
 const FILTER_PROPERTY_PARKING: &str = "parking";
 const FILTER_PROPERTY_ENERGYCLASS: &str = "energyClass";
+const FILTER_PROPERTY_MIN_ENERGYCLASS: &str = "minEnergyClass";
 const FILTER_PROPERTY_YOUTUBEURL: &str = "youtubeUrl";
 
-const CONTRACT_LATITUDE: &str = "contract:latitude";
-const CONTRACT_LONGITUDE: &str = "contract:longitude";
+/// Properties whose value must contain the filter value
+const TEXT_PROPERTIES: [&str; 11] = [
+    FILTER_PROPERTY_NAME,
+    FILTER_PROPERTY_DESCRIPTION,
+    FILTER_PROPERTY_IMAGE,
+    FILTER_PROPERTY_ADDRESS,
+    FILTER_PROPERTY_COUNTRY,
+    FILTER_PROPERTY_CONTINENT,
+    FILTER_PROPERTY_REGION,
+    FILTER_PROPERTY_ZIPCODE,
+    FILTER_PROPERTY_ZONE,
+    FILTER_PROPERTY_CITY,
+    FILTER_PROPERTY_YOUTUBEURL,
+];
+/// Properties compared as numbers; they can be bounded with the `min` and `max` prefixes (e.g. `minRooms`)
+const NUMERIC_PROPERTIES: [&str; 5] = [
+    FILTER_PROPERTY_SQUAREMETERS,
+    FILTER_PROPERTY_ROOMS,
+    FILTER_PROPERTY_BATHROOMS,
+    FILTER_PROPERTY_FLOORS,
+    FILTER_PROPERTY_BALCONIES,
+];
+/// Properties compared as booleans
+const FLAG_PROPERTIES: [&str; 4] = [
+    FILTER_PROPERTY_GARDEN,
+    FILTER_PROPERTY_POOL,
+    FILTER_PROPERTY_GARAGE,
+    FILTER_PROPERTY_PARKING,
+];
+
+/// Separator of the alternative values of a filter
+const ALTERNATIVES_SEPARATOR: char = '|';
+
+/// Energy class of a building, from the most to the least efficient
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord)]
+enum EnergyClass {
+    A4,
+    A3,
+    A2,
+    A1,
+    A,
+    B,
+    C,
+    D,
+    E,
+    F,
+    G,
+}
+
+impl FromStr for EnergyClass {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s.trim().to_uppercase().as_str() {
+            "A4" => Ok(Self::A4),
+            "A3" => Ok(Self::A3),
+            "A2" => Ok(Self::A2),
+            "A1" => Ok(Self::A1),
+            "A" => Ok(Self::A),
+            "B" => Ok(Self::B),
+            "C" => Ok(Self::C),
+            "D" => Ok(Self::D),
+            "E" => Ok(Self::E),
+            "F" => Ok(Self::F),
+            "G" => Ok(Self::G),
+            _ => Err(format!("invalid energy class: {s}")),
+        }
+    }
+}
 
 /// Filter type to filter a contract
 enum ContractFilter {
     /// Always accept the request.
     Always,
-    /// Has property with the given name and the value is contained in the property value.
+    /// Has property `contract:{name}` and the value is contained in the property value.
     HasProperty { name: String, value: String },
+    /// Has numeric property `contract:{name}` with the value between `min` and `max`, inclusive.
+    NumericProperty {
+        name: String,
+        min: Option<u64>,
+        max: Option<u64>,
+    },
+    /// Has boolean property `contract:{name}` with the given value.
+    FlagProperty { name: String, value: bool },
+    /// Energy class
+    EnergyClass(EnergyClass),
+    /// Energy class at least as efficient as the given one
+    MinEnergyClass(EnergyClass),
+    /// Contract type
+    Type(ContractType),
     /// Seller
     Seller(H160),
     /// Buyer
     Buyer(H160),
     /// Agent
     Agent(Principal),
+    /// Currency symbol, case insensitive
+    Currency(String),
     /// Min price
     MinPrice(u64),
     /// Max price
     MaxPrice(u64),
+    /// Expiring on or after the date
+    MinExpiration(Date),
+    /// Expiring on or before the date
+    MaxExpiration(Date),
     /// Position
     Position {
         latitude: f64,
         longitude: f64,
         radius: f64,
     },
+    /// Inside the bounding box
+    BoundingBox(BoundingBox),
+    /// Has all the words of the query in the searchable text properties
+    Search(String),
+    /// Satisfies at least one of the filters
+    Any(Vec<ContractFilter>),
 }
 
 impl ContractFilter {
@@ -68,13 +177,25 @@ impl ContractFilter {
     fn check(&self, contract: &Contract) -> bool {
         match self {
             ContractFilter::Always => true,
-            ContractFilter::HasProperty { name, value } => contract
-                .properties
-                .iter()
-                .find(|(k, _)| k == name)
-                .map_or(false, |(_, v)| {
-                    v.to_string().to_lowercase().contains(&value.to_lowercase())
-                }),
+            ContractFilter::HasProperty { name, value } => Self::property(contract, name)
+                .is_some_and(|v| v.to_lowercase().contains(&value.to_lowercase())),
+            ContractFilter::NumericProperty { name, min, max } => {
+                Self::property_as::<u64>(contract, name).is_some_and(|value| {
+                    !min.is_some_and(|min| value < min) && !max.is_some_and(|max| value > max)
+                })
+            }
+            ContractFilter::FlagProperty { name, value } => {
+                Self::property_as::<bool>(contract, name) == Some(*value)
+            }
+            ContractFilter::EnergyClass(class) => {
+                Self::property_as::<EnergyClass>(contract, FILTER_PROPERTY_ENERGYCLASS)
+                    == Some(*class)
+            }
+            ContractFilter::MinEnergyClass(class) => {
+                Self::property_as::<EnergyClass>(contract, FILTER_PROPERTY_ENERGYCLASS)
+                    .is_some_and(|contract_class| contract_class <= *class)
+            }
+            ContractFilter::Type(r#type) => contract.r#type == *r#type,
             ContractFilter::Seller(addr) => contract
                 .sellers
                 .iter()
@@ -85,13 +206,85 @@ impl ContractFilter {
                 .as_ref()
                 .map(|agency| agency.owner == *agent)
                 .unwrap_or_default(),
+            ContractFilter::Currency(currency) => contract.currency.eq_ignore_ascii_case(currency),
             ContractFilter::MinPrice(min_price) => contract.value >= *min_price,
             ContractFilter::MaxPrice(max_price) => contract.value <= *max_price,
+            ContractFilter::MinExpiration(date) => contract
+                .expiration()
+                .is_ok_and(|expiration| expiration >= *date),
+            ContractFilter::MaxExpiration(date) => contract
+                .expiration()
+                .is_ok_and(|expiration| expiration <= *date),
             ContractFilter::Position {
                 latitude,
                 longitude,
                 radius,
             } => self.check_in_range(contract, *latitude, *longitude, *radius),
+            ContractFilter::BoundingBox(bbox) => {
+                geo::contract_position(contract).is_some_and(|position| bbox.contains(position))
+            }
+            ContractFilter::Search(query) => {
+                let words = SearchField::ALL
+                    .into_iter()
+                    .filter_map(|field| field.value(contract))
+                    .flat_map(tokenize)
+                    .collect::<BTreeSet<_>>();
+                tokenize(query).iter().all(|word| words.contains(word))
+            }
+            ContractFilter::Any(filters) => filters.iter().any(|filter| filter.check(contract)),
+        }
+    }
+
+    /// Get the IDs of the contracts which may satisfy the filter, looked up in the indexes, if any
+    fn candidates(&self) -> Option<BTreeSet<ID>> {
+        let index = match self {
+            ContractFilter::Seller(addr) => ContractIndex::Seller(*addr),
+            ContractFilter::Buyer(addr) => ContractIndex::Buyer(*addr),
+            ContractFilter::Agent(agent) => ContractIndex::Agency(*agent),
+            ContractFilter::Currency(currency) => ContractIndex::Currency(currency.clone()),
+            ContractFilter::Position {
+                latitude,
+                longitude,
+                radius,
+            } => {
+                let center = GeoPoint {
+                    latitude: *latitude,
+                    longitude: *longitude,
+                };
+                return Some(GeoIndexStorage::get_contracts(&BoundingBox::around(
+                    center, *radius,
+                )));
+            }
+            ContractFilter::BoundingBox(bbox) => return Some(GeoIndexStorage::get_contracts(bbox)),
+            ContractFilter::Search(query) => return Some(SearchIndexStorage::get_contracts(query)),
+            // the alternatives are indexed only if all of them are
+            ContractFilter::Any(filters) => {
+                return filters
+                    .iter()
+                    .map(ContractFilter::candidates)
+                    .reduce(|candidates, contracts| Some(&candidates? | &contracts?))
+                    .flatten();
+            }
+            _ => return None,
+        };
+
+        Some(ContractIndexStorage::get_contracts(&index))
+    }
+
+    /// Returns whether the indexes select exactly the contracts satisfying the filter
+    fn is_resolved_by_index(&self) -> bool {
+        match self {
+            ContractFilter::Always
+            | ContractFilter::Seller(_)
+            | ContractFilter::Buyer(_)
+            | ContractFilter::Agent(_)
+            | ContractFilter::Currency(_)
+            | ContractFilter::BoundingBox(_)
+            | ContractFilter::Search(_) => true,
+            ContractFilter::Any(filters) => {
+                filters.iter().all(ContractFilter::is_resolved_by_index)
+            }
+            _ => false,
         }
     }
 
@@ -104,50 +297,129 @@ impl ContractFilter {
         radius: f64,
     ) -> bool {
         // get the position of the contract
-        let Some(contract_latitude) =
-            Self::get_contract_property_as::<f64>(contract, CONTRACT_LATITUDE)
-        else {
+        let Some(position) = geo::contract_position(contract) else {
             return false;
         };
-        let Some(contract_longitude) =
-            Self::get_contract_property_as::<f64>(contract, CONTRACT_LONGITUDE)
-        else {
-            return false;
+        let center = GeoPoint {
+            latitude,
+            longitude,
         };
 
-        const EARTH_RADIUS_KM: f64 = 6371.0;
+        geo::distance_km(center, position) <= radius
+    }
 
-        // convert to radians
-        let latitude = latitude.to_radians();
-        let longitude = longitude.to_radians();
-        let contract_latitude = contract_latitude.to_radians();
-        let contract_longitude = contract_longitude.to_radians();
+    /// Get the value of the `contract:{name}` property as text
+    fn property(contract: &Contract, name: &str) -> Option<String> {
+        contract
+            .properties
+            .iter()
+            .find(|(k, _)| k.strip_prefix("contract:") == Some(name))
+            .map(|(_, v)| v.to_string())
+    }
 
-        // calculate the distance
-        let delta_latitude = contract_latitude - latitude;
-        let delta_longitude = contract_longitude - longitude;
+    /// Get the value of the `contract:{name}` property as the given type.
+    fn property_as<T>(contract: &Contract, name: &str) -> Option<T>
+    where
+        T: FromStr,
+    {
+        Self::property(contract, name).and_then(|v| v.parse().ok())
+    }
 
-        // haversine formula
-        let a = (delta_latitude / 2.0).sin().powi(2)
-            + latitude.cos() * contract_latitude.cos() * (delta_longitude / 2.0).sin().powi(2);
-        let c = 2.0 * a.sqrt().atan2((1.0 - a).sqrt());
+    /// Parse the filter of a query param; returns `None` if the param is not a filter.
+    ///
+    /// The position filter is made of several params, so it is parsed by [`Filters`].
+    fn parse(name: &str, value: &str) -> Result<Option<Self>, String> {
+        let invalid = || format!("invalid {name} filter: {value}");
+
+        let filter = match name {
+            FILTER_AGENT => {
+                ContractFilter::Agent(Principal::from_text(value).map_err(|_| invalid())?)
+            }
+            FILTER_CURRENCY => ContractFilter::Currency(value.to_string()),
+            FILTER_BUYER => {
+                ContractFilter::Buyer(H160::from_hex_str(value).map_err(|_| invalid())?)
+            }
+            FILTER_SELLER => {
+                ContractFilter::Seller(H160::from_hex_str(value).map_err(|_| invalid())?)
+            }
+            FILTER_TYPE => match value.to_lowercase().as_str() {
+                "sell" => ContractFilter::Type(ContractType::Sell),
+                "financing" => ContractFilter::Type(ContractType::Financing),
+                _ => return Err(invalid()),
+            },
+            FILTER_MIN_PRICE => ContractFilter::MinPrice(value.parse().map_err(|_| invalid())?),
+            FILTER_MAX_PRICE => ContractFilter::MaxPrice(value.parse().map_err(|_| invalid())?),
+            FILTER_MIN_EXPIRATION => {
+                ContractFilter::MinExpiration(Self::parse_date(value).map_err(|_| invalid())?)
+            }
+            FILTER_MAX_EXPIRATION => {
+                ContractFilter::MaxExpiration(Self::parse_date(value).map_err(|_| invalid())?)
+            }
+            FILTER_BOUNDING_BOX => ContractFilter::BoundingBox(value.parse()?),
+            FILTER_PROPERTY_ENERGYCLASS => ContractFilter::EnergyClass(value.parse()?),
+            FILTER_PROPERTY_MIN_ENERGYCLASS => ContractFilter::MinEnergyClass(value.parse()?),
+            name if TEXT_PROPERTIES.contains(&name) => ContractFilter::HasProperty {
+                name: name.to_string(),
+                value: value.to_string(),
+            },
+            name if NUMERIC_PROPERTIES.contains(&name) => {
+                let value = value.parse().map_err(|_| invalid())?;
+                ContractFilter::NumericProperty {
+                    name: name.to_string(),
+                    min: Some(value),
+                    max: Some(value),
+                }
+            }
+            name if FLAG_PROPERTIES.contains(&name) => ContractFilter::FlagProperty {
+                name: name.to_string(),
+                // a flag without value means true
+                value: match value {
+                    "" | "true" => true,
+                    "false" => false,
+                    _ => return Err(invalid()),
+                },
+            },
+            name => {
+                if let Some(property) = Self::bounded_property(name, "min") {
+                    ContractFilter::NumericProperty {
+                        name: property.to_string(),
+                        min: Some(value.parse().map_err(|_| invalid())?),
+                        max: None,
+                    }
+                } else if let Some(property) = Self::bounded_property(name, "max") {
+                    ContractFilter::NumericProperty {
+                        name: property.to_string(),
+                        min: None,
+                        max: Some(value.parse().map_err(|_| invalid())?),
+                    }
+                } else {
+                    return Ok(None);
+                }
+            }
+        };
 
-        // distance in km
-        let distance = EARTH_RADIUS_KM * c;
+        Ok(Some(filter))
+    }
 
-        distance <= radius
+    /// Get the numeric property bounded by a `{prefix}{Property}` param
+    fn bounded_property(name: &str, prefix: &str) -> Option<&'static str> {
+        let suffix = name.strip_prefix(prefix)?;
+
+        NUMERIC_PROPERTIES.into_iter().find(|property| {
+            let mut chars = property.chars();
+            chars
+                .next()
+                .is_some_and(|first| suffix.starts_with(first.to_ascii_uppercase()))
+                && &suffix[1..] == chars.as_str()
+        })
     }
 
-    /// Get the property value of the contract as the given type.
-    fn get_contract_property_as<T>(contract: &Contract, property: &str) -> Option<T>
-    where
-        T: std::str::FromStr,
-    {
-        contract
-            .properties
-            .iter()
-            .find(|(k, _)| k == property)
-            .and_then(|(_, v)| v.to_string().parse().ok())
+    /// Parse a `YYYY-MM-DD` date
+    fn parse_date(value: &str) -> Result<Date, time::error::Parse> {
+        Date::parse(
+            value,
+            time::macros::format_description!("[year]-[month]-[day]"),
+        )
     }
 }
 
@@ -155,93 +427,61 @@ pub struct Filters {
     filters: Vec<ContractFilter>,
 }
 
-impl From<&Url> for Filters {
-    fn from(url: &Url) -> Self {
+impl TryFrom<&Url> for Filters {
+    type Error = String;
+
+    /// Parse the filters in the query params of the URL.
+    ///
+    /// Values separated by `|` are alternatives, and the filter is satisfied if any of them is.
+    /// Returns an error if a filter is malformed.
+    fn try_from(url: &Url) -> Result<Self, Self::Error> {
         let mut filters = vec![ContractFilter::Always];
 
-        // check if there is position (search for latitude, longitude and radius)
-        if let (Some(latitude), Some(longitude), Some(radius)) = (
+        // the position filter requires latitude, longitude and radius
+        let param = |key: &str| {
             url.query_pairs()
-                .find(|(name, _)| name == FILTER_POSITION_LATITUDE),
-            url.query_pairs()
-                .find(|(name, _)| name == FILTER_POSITION_LONGITUDE),
-            url.query_pairs()
-                .find(|(name, _)| name == FILTER_POSITION_RADIUS),
-        ) {
-            if let (Ok(latitude), Ok(longitude), Ok(radius)) =
-                (latitude.1.parse(), longitude.1.parse(), radius.1.parse())
-            {
-                filters.push(ContractFilter::Position {
-                    latitude,
-                    longitude,
-                    radius,
-                });
-            }
+                .find(|(name, _)| name == key)
+                .map(|(_, value)| value)
+        };
+        if let Some(radius) = param(FILTER_POSITION_RADIUS) {
+            let (Some(latitude), Some(longitude)) = (
+                param(FILTER_POSITION_LATITUDE),
+                param(FILTER_POSITION_LONGITUDE),
+            ) else {
+                return Err("position filter requires latitude, longitude and radius".to_string());
+            };
+            let (Ok(latitude), Ok(longitude), Ok(radius)) =
+                (latitude.parse(), longitude.parse(), radius.parse())
+            else {
+                return Err("invalid position filter".to_string());
+            };
+            filters.push(ContractFilter::Position {
+                latitude,
+                longitude,
+                radius,
+            });
+        }
+
+        // the full-text query is not split into alternatives
+        if let Some(query) = param(FILTER_QUERY).filter(|query| !tokenize(query).is_empty()) {
+            filters.push(ContractFilter::Search(query.to_string()));
         }
 
         for (name, value) in url.query_pairs() {
-            match name.as_ref() {
-                FILTER_AGENT => {
-                    if let Ok(agent) = Principal::from_text(value) {
-                        filters.push(ContractFilter::Agent(agent));
-                    }
-                }
-                FILTER_BUYER => {
-                    if let Ok(addr) = H160::from_hex_str(value.as_ref()) {
-                        filters.push(ContractFilter::Buyer(addr));
-                    }
-                }
-                FILTER_SELLER => {
-                    if let Ok(addr) = H160::from_hex_str(value.as_ref()) {
-                        filters.push(ContractFilter::Seller(addr));
-                    }
-                }
-                FILTER_MIN_PRICE => {
-                    if let Ok(min_price) = value.parse() {
-                        filters.push(ContractFilter::MinPrice(min_price));
-                    }
-                }
-                FILTER_MAX_PRICE => {
-                    if let Ok(max_price) = value.parse() {
-                        filters.push(ContractFilter::MaxPrice(max_price));
-                    }
-                }
-                FILTER_PROPERTY_NAME
-                | FILTER_PROPERTY_DESCRIPTION
-                | FILTER_PROPERTY_IMAGE
-                | FILTER_PROPERTY_ADDRESS
-                | FILTER_PROPERTY_COUNTRY
-                | FILTER_PROPERTY_CONTINENT
-                | FILTER_PROPERTY_REGION
-                | FILTER_PROPERTY_ZIPCODE
-                | FILTER_PROPERTY_ZONE
-                | FILTER_PROPERTY_CITY
-                | FILTER_PROPERTY_SQUAREMETERS
-                | FILTER_PROPERTY_ROOMS
-                | FILTER_PROPERTY_BATHROOMS
-                | FILTER_PROPERTY_FLOORS
-                | FILTER_PROPERTY_BALCONIES
-                | FILTER_PROPERTY_GARDEN
-                | FILTER_PROPERTY_POOL
-                | FILTER_PROPERTY_GARAGE
-                | FILTER_PROPERTY_PARKING
-                | FILTER_PROPERTY_ENERGYCLASS
-                | FILTER_PROPERTY_YOUTUBEURL => {
-                    let value = if value.is_empty() {
-                        "true".to_string()
-                    } else {
-                        value.to_string()
-                    };
-                    filters.push(ContractFilter::HasProperty {
-                        name: format!("contract:{name}"),
-                        value,
-                    });
-                }
-                _ => {}
+            let mut alternatives = value
+                .split(ALTERNATIVES_SEPARATOR)
+                .map(|value| ContractFilter::parse(&name, value))
+                .collect::<Result<Option<Vec<_>>, _>>()?
+                .unwrap_or_default();
+
+            if alternatives.len() > 1 {
+                filters.push(ContractFilter::Any(alternatives));
+            } else if let Some(filter) = alternatives.pop() {
+                filters.push(filter);
             }
         }
 
-        Filters { filters }
+        Ok(Filters { filters })
     }
 }
 
@@ -250,6 +490,31 @@ impl Filters {
     pub fn check(&self, contract: &Contract) -> bool {
         self.filters.iter().all(|filter| filter.check(contract))
     }
+
+    /// Get the IDs of the contracts which may satisfy the filters, looked up in the secondary and geographic indexes.
+    ///
+    /// Returns `None` if no filter is indexed, so all the contracts must be checked.
+    pub fn candidates(&self) -> Option<BTreeSet<ID>> {
+        self.filters
+            .iter()
+            .filter_map(ContractFilter::candidates)
+            .reduce(|candidates, contracts| &candidates & &contracts)
+    }
+
+    /// Get the full-text query, if any
+    pub fn query(&self) -> Option<&str> {
+        self.filters.iter().find_map(|filter| match filter {
+            ContractFilter::Search(query) => Some(query.as_str()),
+            _ => None,
+        })
+    }
+
+    /// Returns whether the [`Filters::candidates`] satisfy all the filters, so they don't need to be checked
+    pub fn is_resolved_by_indexes(&self) -> bool {
+        self.filters
+            .iter()
+            .all(ContractFilter::is_resolved_by_index)
+    }
 }
 
 #[cfg(test)]
@@ -260,13 +525,14 @@ mod test {
 
     use super::*;
     use crate::app::test_utils::with_mock_contract;
+    use crate::app::ContractStorage;
 
     #[test]
     fn test_should_get_position_filter_from_url() {
         let url =
             Url::parse("http://example.com/?latitude=45.0&longitude=9.0&radius=10.0").unwrap();
 
-        let filters = Filters::from(&url);
+        let filters = Filters::try_from(&url).unwrap();
         let position = filters.filters.iter().find_map(|filter| match filter {
             ContractFilter::Position {
                 latitude,
@@ -279,6 +545,62 @@ mod test {
         assert_eq!(position, Some((45.0, 9.0, 10.0)));
     }
 
+    #[test]
+    fn test_should_get_candidates_from_indexes() {
+        let buyer = H160::from_hex_str("0x0b24F78CF0033FAbf1977D9aA61f583fBF7586D9").unwrap();
+        ContractStorage::insert_contract(with_mock_contract(1, 1, |contract| {
+            contract.buyers = vec![buyer];
+            contract.currency = "EUR".to_string();
+        }))
+        .unwrap();
+        ContractStorage::insert_contract(with_mock_contract(2, 1, |contract| {
+            contract.buyers = vec![buyer];
+            contract.currency = "USD".to_string();
+        }))
+        .unwrap();
+
+        let url = Url::parse(&format!("http://example.com/?buyer={buyer}&currency=eur")).unwrap();
+        assert_eq!(
+            Filters::try_from(&url).unwrap().candidates(),
+            Some(BTreeSet::from([ID::from(1u64)]))
+        );
+
+        // no indexed filter
+        let url = Url::parse("http://example.com/?minPrice=100").unwrap();
+        assert_eq!(Filters::try_from(&url).unwrap().candidates(), None);
+        assert!(!Filters::try_from(&url).unwrap().is_resolved_by_indexes());
+    }
+
+    #[test]
+    fn test_should_get_candidates_in_bounding_box() {
+        for (id, latitude, longitude) in [(1u64, 45.46, 9.19), (2, 41.90, 12.49)] {
+            ContractStorage::insert_contract(with_mock_contract(id, 1, |contract| {
+                contract.properties = vec![
+                    (
+                        "contract:latitude".to_string(),
+                        GenericValue::FloatContent(latitude),
+                    ),
+                    (
+                        "contract:longitude".to_string(),
+                        GenericValue::FloatContent(longitude),
+                    ),
+                ];
+            }))
+            .unwrap();
+        }
+
+        let url = Url::parse("http://example.com/?bbox=44,8,46,10").unwrap();
+        let filters = Filters::try_from(&url).unwrap();
+        assert_eq!(filters.candidates(), Some(BTreeSet::from([ID::from(1u64)])));
+        assert!(filters.is_resolved_by_indexes());
+
+        // the radius is checked on the candidates of the enclosing box
+        let url = Url::parse("http://example.com/?latitude=41.9&longitude=12.5&radius=5").unwrap();
+        let filters = Filters::try_from(&url).unwrap();
+        assert_eq!(filters.candidates(), Some(BTreeSet::from([ID::from(2u64)])));
+        assert!(!filters.is_resolved_by_indexes());
+    }
+
     #[test]
     fn test_should_check_in_position() {
         let contract = with_mock_contract(1, 100, |contract| {
@@ -308,4 +630,127 @@ mod test {
 
         assert_eq!(filter.check(&contract), false);
     }
+
+    #[test]
+    fn test_should_check_typed_property_filters() {
+        let contract = with_mock_contract(1, 100, |contract| {
+            contract.expiration = "2030-06-01".to_string();
+            contract.properties = vec![
+                ("contract:rooms".to_string(), GenericValue::Nat64Content(12)),
+                (
+                    "contract:garden".to_string(),
+                    GenericValue::BoolContent(true),
+                ),
+                (
+                    "contract:energyClass".to_string(),
+                    GenericValue::TextContent("B".to_string()),
+                ),
+                (
+                    "contract:city".to_string(),
+                    GenericValue::TextContent("Rome".to_string()),
+                ),
+            ];
+        });
+        let check = |query: &str| {
+            let url = Url::parse(&format!("http://example.com/?{query}")).unwrap();
+            Filters::try_from(&url).unwrap().check(&contract)
+        };
+
+        assert!(check("rooms=12"));
+        assert!(!check("rooms=2"));
+        assert!(check("minRooms=10&maxRooms=12"));
+        assert!(!check("minRooms=13"));
+        assert!(!check("minSquareMeters=1"));
+        assert!(check("garden"));
+        assert!(!check("garden=false"));
+        assert!(!check("pool=true"));
+        assert!(check("energyClass=b"));
+        assert!(check("minEnergyClass=C"));
+        assert!(!check("minEnergyClass=A1"));
+        assert!(check("type=financing"));
+        assert!(!check("type=sell"));
+        assert!(check("minExpiration=2030-01-01&maxExpiration=2030-06-01"));
+        assert!(!check("maxExpiration=2030-05-31"));
+        // alternatives
+        assert!(check("city=milan|rome"));
+        assert!(check("rooms=2|12"));
+        assert!(!check("energyClass=A|C"));
+    }
+
+    #[test]
+    fn test_should_search_text_properties() {
+        let contract = with_mock_contract(1, 100, |contract| {
+            contract.properties = vec![(
+                "contract:description".to_string(),
+                GenericValue::TextContent("Appartamento in centro, Città di Castello".to_string()),
+            )];
+        });
+        ContractStorage::insert_contract(contract.clone()).unwrap();
+
+        let url = Url::parse("http://example.com/?q=citta%20CENTRO").unwrap();
+        let filters = Filters::try_from(&url).unwrap();
+        assert!(filters.check(&contract));
+        assert_eq!(filters.query(), Some("citta CENTRO"));
+        assert_eq!(filters.candidates(), Some(BTreeSet::from([ID::from(1u64)])));
+
+        let url = Url::parse("http://example.com/?q=citta%20periferia").unwrap();
+        let filters = Filters::try_from(&url).unwrap();
+        assert!(!filters.check(&contract));
+        assert_eq!(filters.candidates(), Some(BTreeSet::new()));
+
+        // empty queries are ignored
+        let url = Url::parse("http://example.com/?q=%20").unwrap();
+        assert_eq!(Filters::try_from(&url).unwrap().query(), None);
+    }
+
+    #[test]
+    fn test_should_reject_malformed_filters() {
+        for query in [
+            "rooms=two",
+            "minRooms=-1",
+            "garden=yes",
+            "energyClass=Z",
+            "type=rent",
+            "minPrice=cheap",
+            "maxExpiration=2030-13-01",
+            "seller=0x1234",
+            "agent=not-a-principal",
+            "bbox=1,2,3",
+            "radius=10",
+            "latitude=45&longitude=north&radius=10",
+            "city=rome&rooms=1|x",
+        ] {
+            let url = Url::parse(&format!("http://example.com/?{query}")).unwrap();
+            assert!(Filters::try_from(&url).is_err(), "{query}");
+        }
+
+        // params which are not filters are ignored
+        let url = Url::parse("http://example.com/?sortBy=value&latitude=45").unwrap();
+        assert!(Filters::try_from(&url).is_ok());
+    }
+
+    #[test]
+    fn test_should_get_candidates_of_alternatives() {
+        for (id, currency) in [(1u64, "EUR"), (2, "USD"), (3, "GBP")] {
+            ContractStorage::insert_contract(with_mock_contract(id, 1, |contract| {
+                contract.currency = currency.to_string();
+            }))
+            .unwrap();
+        }
+
+        let url = Url::parse("http://example.com/?currency=eur|usd").unwrap();
+        let filters = Filters::try_from(&url).unwrap();
+        assert_eq!(
+            filters.candidates(),
+            Some(BTreeSet::from([ID::from(1u64), 2u64.into()]))
+        );
+        assert!(filters.is_resolved_by_indexes());
+
+        // an alternative is not indexed
+        let url = Url::parse("http://example.com/?currency=eur|usd&city=rome|milan").unwrap();
+        assert_eq!(
+            Filters::try_from(&url).unwrap().candidates(),
+            Some(BTreeSet::from([ID::from(1u64), 2u64.into()]))
+        );
+    }
 }
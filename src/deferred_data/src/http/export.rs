@@ -0,0 +1,189 @@
+//! Export of the contracts as GeoJSON features and CSV rows
+
+use std::collections::BTreeSet;
+
+use did::deferred::{Contract, GenericValue};
+use did::{CsvTable, Feature, FeatureCollection};
+use serde_json::{Map, Value};
+
+use crate::app::geo;
+
+/// Columns exported for every contract, before the contract properties
+const CONTRACT_COLUMNS: [&str; 8] = [
+    "id",
+    "type",
+    "value",
+    "deposit",
+    "currency",
+    "installments",
+    "expiration",
+    "agency",
+];
+
+/// Export the contracts with a position as a GeoJSON feature collection
+pub fn contracts_geojson(contracts: &[Contract]) -> FeatureCollection {
+    contracts
+        .iter()
+        .filter_map(|contract| {
+            let position = geo::contract_position(contract)?;
+            Some(Feature::new(
+                contract.id.0.to_string(),
+                position.longitude,
+                position.latitude,
+                contract_record(contract),
+            ))
+        })
+        .collect()
+}
+
+/// Export the contracts as CSV, with a column for each of their properties
+pub fn contracts_csv(contracts: &[Contract]) -> CsvTable {
+    let properties = contracts
+        .iter()
+        .flat_map(|contract| contract.properties.iter().map(|(key, _)| key.as_str()))
+        .collect::<BTreeSet<_>>();
+    let columns = CONTRACT_COLUMNS
+        .into_iter()
+        .chain(properties)
+        .map(str::to_string)
+        .collect();
+
+    let records = contracts.iter().map(contract_record).collect::<Vec<_>>();
+
+    CsvTable::from_records(columns, &records)
+}
+
+/// The exported fields of a contract and its properties.
+///
+/// Restricted properties are never exported.
+fn contract_record(contract: &Contract) -> Map<String, Value> {
+    let mut record = Map::new();
+    record.insert("id".to_string(), Value::from(contract.id.0.to_string()));
+    record.insert(
+        "type".to_string(),
+        serde_json::to_value(&contract.r#type).unwrap_or_default(),
+    );
+    record.insert("value".to_string(), Value::from(contract.value));
+    record.insert("deposit".to_string(), Value::from(contract.deposit));
+    record.insert(
+        "currency".to_string(),
+        Value::from(contract.currency.clone()),
+    );
+    record.insert(
+        "installments".to_string(),
+        Value::from(contract.installments),
+    );
+    record.insert(
+        "expiration".to_string(),
+        Value::from(contract.expiration.clone()),
+    );
+    record.insert(
+        "agency".to_string(),
+        contract
+            .agency
+            .as_ref()
+            .map(|agency| Value::from(agency.name.clone()))
+            .unwrap_or_default(),
+    );
+
+    for (key, value) in &contract.properties {
+        record.insert(key.clone(), json_value(value));
+    }
+
+    record
+}
+
+/// Convert a property value to JSON; numbers too large for JSON are converted to text
+fn json_value(value: &GenericValue) -> Value {
+    match value {
+        GenericValue::BoolContent(v) => Value::from(*v),
+        GenericValue::TextContent(v) => Value::from(v.clone()),
+        GenericValue::Principal(v) => Value::from(v.to_text()),
+        GenericValue::Nat8Content(v) => Value::from(*v),
+        GenericValue::Nat16Content(v) => Value::from(*v),
+        GenericValue::Nat32Content(v) => Value::from(*v),
+        GenericValue::Nat64Content(v) => Value::from(*v),
+        GenericValue::NatContent(v) => u64::try_from(&v.0)
+            .map(Value::from)
+            .unwrap_or_else(|_| Value::from(v.0.to_string())),
+        GenericValue::Int8Content(v) => Value::from(*v),
+        GenericValue::Int16Content(v) => Value::from(*v),
+        GenericValue::Int32Content(v) => Value::from(*v),
+        GenericValue::Int64Content(v) => Value::from(*v),
+        GenericValue::IntContent(v) => i64::try_from(&v.0)
+            .map(Value::from)
+            .unwrap_or_else(|_| Value::from(v.0.to_string())),
+        GenericValue::FloatContent(v) => Value::from(*v),
+    }
+}
+
+#[cfg(test)]
+mod test {
+
+    use pretty_assertions::assert_eq;
+
+    use super::*;
+    use crate::app::test_utils::with_mock_contract;
+
+    fn located_contract(id: u64) -> Contract {
+        with_mock_contract(id, 1, |contract| {
+            contract.properties = vec![
+                (
+                    "contract:latitude".to_string(),
+                    GenericValue::FloatContent(41.9),
+                ),
+                (
+                    "contract:longitude".to_string(),
+                    GenericValue::FloatContent(12.5),
+                ),
+                (
+                    "contract:city".to_string(),
+                    GenericValue::TextContent("Rome".to_string()),
+                ),
+            ];
+        })
+    }
+
+    #[test]
+    fn test_should_export_contracts_geojson() {
+        let unlocated = with_mock_contract(2, 1, |contract| contract.properties = vec![]);
+
+        let collection = contracts_geojson(&[located_contract(1), unlocated]);
+        assert_eq!(collection.features.len(), 1);
+
+        let feature = &collection.features[0];
+        assert_eq!(feature.id, "1");
+        assert_eq!(feature.geometry.coordinates, [12.5, 41.9]);
+        assert_eq!(
+            feature.properties.get("contract:city"),
+            Some(&Value::from("Rome"))
+        );
+        assert_eq!(feature.properties.get("id"), Some(&Value::from("1")));
+    }
+
+    #[test]
+    fn test_should_export_contracts_csv() {
+        let other = with_mock_contract(2, 1, |contract| {
+            contract.properties =
+                vec![("contract:rooms".to_string(), GenericValue::Nat64Content(3))];
+        });
+
+        let table = contracts_csv(&[located_contract(1), other]);
+        assert_eq!(
+            &table.header[CONTRACT_COLUMNS.len()..],
+            &[
+                "contract:city",
+                "contract:latitude",
+                "contract:longitude",
+                "contract:rooms"
+            ]
+        );
+        assert_eq!(table.rows.len(), 2);
+        assert_eq!(table.rows[0][0], "1");
+        assert_eq!(
+            &table.rows[0][CONTRACT_COLUMNS.len()..],
+            &["Rome", "41.9", "12.5", ""]
+        );
+        assert_eq!(&table.rows[1][CONTRACT_COLUMNS.len()..], &["", "", "", "3"]);
+    }
+}
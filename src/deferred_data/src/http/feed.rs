@@ -0,0 +1,137 @@
+//! Atom feed of the newly created and closed contracts
+
+use time::OffsetDateTime;
+
+use super::metadata;
+use crate::app::{ContractEvent, ContractEventKind};
+use crate::utils;
+
+const FEED_TITLE: &str = "EKOKE deferred contracts";
+
+/// Render the contract events as an Atom feed.
+///
+/// Each entry links to the ERC-721 metadata of the contract.
+pub fn contracts_feed(events: &[ContractEvent]) -> String {
+    let base_url = format!("https://{}.raw.icp0.io", utils::id());
+    let updated = events
+        .first()
+        .map(|event| event.timestamp)
+        .unwrap_or_else(utils::time);
+
+    let mut feed = String::new();
+    feed.push_str(r#"<?xml version="1.0" encoding="utf-8"?>"#);
+    feed.push_str(r#"<feed xmlns="http://www.w3.org/2005/Atom">"#);
+    feed.push_str(&format!(
+        r#"<id>{base_url}/contracts/feed</id><title>{FEED_TITLE}</title><updated>{}</updated><link rel="self" type="application/atom+xml" href="{base_url}/contracts/feed"/>"#,
+        rfc3339(updated)
+    ));
+
+    for event in events {
+        let contract_id = &event.contract.id.0;
+        let token_metadata = metadata::token_metadata(&event.contract, None);
+        let (term, title) = match event.kind {
+            ContractEventKind::Created => ("created", "Listed"),
+            ContractEventKind::Closed => ("closed", "Closed"),
+        };
+        let timestamp = rfc3339(event.timestamp);
+
+        feed.push_str(&format!(
+            r#"<entry><id>urn:ekoke:contract:{contract_id}:{term}</id><title>{title}: {}</title><published>{timestamp}</published><updated>{timestamp}</updated><author><name>{}</name></author><category term="{term}"/><link rel="alternate" type="application/json" href="{base_url}/contract/{contract_id}/metadata"/><summary>{}</summary></entry>"#,
+            xml_escape(&token_metadata.name),
+            xml_escape(
+                event
+                    .contract
+                    .agency
+                    .as_ref()
+                    .map(|agency| agency.name.as_str())
+                    .unwrap_or(FEED_TITLE)
+            ),
+            xml_escape(&token_metadata.description),
+        ));
+    }
+
+    feed.push_str("</feed>");
+
+    feed
+}
+
+/// Format a timestamp in nanoseconds as an RFC 3339 date in UTC
+fn rfc3339(timestamp: u64) -> String {
+    let date = OffsetDateTime::from_unix_timestamp_nanos(timestamp as i128)
+        .unwrap_or(OffsetDateTime::UNIX_EPOCH);
+
+    format!(
+        "{:04}-{:02}-{:02}T{:02}:{:02}:{:02}Z",
+        date.year(),
+        u8::from(date.month()),
+        date.day(),
+        date.hour(),
+        date.minute(),
+        date.second()
+    )
+}
+
+fn xml_escape(text: &str) -> String {
+    let mut escaped = String::with_capacity(text.len());
+    for c in text.chars() {
+        match c {
+            '&' => escaped.push_str("&amp;"),
+            '<' => escaped.push_str("&lt;"),
+            '>' => escaped.push_str("&gt;"),
+            '"' => escaped.push_str("&quot;"),
+            '\'' => escaped.push_str("&apos;"),
+            c => escaped.push(c),
+        }
+    }
+
+    escaped
+}
+
+#[cfg(test)]
+mod test {
+
+    use did::deferred::GenericValue;
+    use pretty_assertions::assert_eq;
+
+    use super::*;
+    use crate::app::test_utils::with_mock_contract;
+
+    #[test]
+    fn test_should_format_rfc3339() {
+        assert_eq!(rfc3339(0), "1970-01-01T00:00:00Z");
+        assert_eq!(rfc3339(1_700_000_000_123_000_000), "2023-11-14T22:13:20Z");
+    }
+
+    #[test]
+    fn test_should_render_contracts_feed() {
+        let contract = with_mock_contract(1, 1, |contract| {
+            contract.properties = vec![(
+                "contract:name".to_string(),
+                GenericValue::TextContent("Flat <Rome> & garden".to_string()),
+            )];
+        });
+        let events = vec![
+            ContractEvent {
+                kind: ContractEventKind::Closed,
+                timestamp: 2_000_000_000,
+                contract: contract.clone(),
+            },
+            ContractEvent {
+                kind: ContractEventKind::Created,
+                timestamp: 1_000_000_000,
+                contract,
+            },
+        ];
+
+        let feed = contracts_feed(&events);
+        assert!(feed.starts_with(r#"<?xml version="1.0" encoding="utf-8"?><feed"#));
+        assert!(feed.contains("<updated>1970-01-01T00:00:02Z</updated>"));
+        assert_eq!(feed.matches("<entry>").count(), 2);
+        assert!(feed.contains("<id>urn:ekoke:contract:1:closed</id>"));
+        assert!(feed.contains("<id>urn:ekoke:contract:1:created</id>"));
+        assert!(feed.contains("<title>Listed: Flat &lt;Rome&gt; &amp; garden</title>"));
+        assert!(feed.contains(r#"href="https://"#));
+        assert!(feed.contains(r#".raw.icp0.io/contract/1/metadata"/>"#));
+        assert!(feed.ends_with("</feed>"));
+    }
+}
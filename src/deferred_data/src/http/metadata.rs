@@ -0,0 +1,241 @@
+use did::deferred::{Contract, ContractType, GenericValue, TokenAttribute, TokenMetadata};
+use serde_json::Value;
+
+use crate::utils;
+
+const CONTRACT_NAME: &str = "contract:name";
+const CONTRACT_DESCRIPTION: &str = "contract:description";
+const CONTRACT_IMAGE: &str = "contract:image";
+
+const DISPLAY_NUMBER: &str = "number";
+/// Unix timestamp in seconds
+const DISPLAY_DATE: &str = "date";
+
+/// Properties which are not shown as attributes
+const HIDDEN_PROPERTIES: [&str; 4] = [
+    CONTRACT_NAME,
+    CONTRACT_DESCRIPTION,
+    CONTRACT_IMAGE,
+    "contract:youtubeUrl",
+];
+
+/// Get the ERC-721 metadata of the tokens of a contract.
+///
+/// If `token_id` is set, the metadata are specific to that token.
+/// Only the public properties of the contract are used.
+pub fn token_metadata(contract: &Contract, token_id: Option<u64>) -> TokenMetadata {
+    let contract_id = &contract.id.0;
+    let name = text_property(contract, CONTRACT_NAME)
+        .unwrap_or_else(|| format!("Deferred contract #{contract_id}"));
+    let name = match token_id {
+        Some(token_id) => format!("{name} - token #{token_id}"),
+        None => name,
+    };
+    let r#type = match contract.r#type {
+        ContractType::Financing => "Financing",
+        ContractType::Sell => "Sell",
+    };
+    let description = text_property(contract, CONTRACT_DESCRIPTION).unwrap_or_else(|| {
+        format!(
+            "{} contract of {} {} in {} installments",
+            r#type, contract.value, contract.currency, contract.installments
+        )
+    });
+
+    let mut attributes = vec![
+        attribute("Type", r#type, None),
+        attribute("Value", contract.value, Some(DISPLAY_NUMBER)),
+        attribute("Currency", contract.currency.as_str(), None),
+        attribute("Installments", contract.installments, Some(DISPLAY_NUMBER)),
+        attribute("Token price", token_price(contract), Some(DISPLAY_NUMBER)),
+    ];
+    if let Ok(expiration) = contract.expiration() {
+        let timestamp = expiration.midnight().assume_utc().unix_timestamp();
+        attributes.push(attribute("Expiration", timestamp, Some(DISPLAY_DATE)));
+    }
+    if let Some(agency) = &contract.agency {
+        attributes.push(attribute("Agency", agency.name.as_str(), None));
+    }
+    if let Some(token_id) = token_id {
+        attributes.push(attribute("Token ID", token_id, None));
+    }
+    attributes.extend(
+        contract
+            .properties
+            .iter()
+            .filter(|(key, _)| !HIDDEN_PROPERTIES.contains(&key.as_str()))
+            .filter_map(|(key, value)| {
+                key.strip_prefix("contract:")
+                    .map(|key| property_attribute(key, value))
+            }),
+    );
+
+    TokenMetadata {
+        name,
+        description,
+        image: text_property(contract, CONTRACT_IMAGE),
+        external_url: format!("https://{}.raw.icp0.io/contract/{contract_id}", utils::id()),
+        attributes,
+    }
+}
+
+/// Get the fiat price of a token of the contract; the value is split evenly among the installments
+fn token_price(contract: &Contract) -> u64 {
+    contract
+        .value
+        .checked_div(contract.installments)
+        .unwrap_or_default()
+}
+
+fn attribute(
+    trait_type: &str,
+    value: impl Into<Value>,
+    display_type: Option<&str>,
+) -> TokenAttribute {
+    TokenAttribute {
+        trait_type: trait_type.to_string(),
+        value: value.into(),
+        display_type: display_type.map(str::to_string),
+    }
+}
+
+/// Get the attribute of a contract property; numbers are displayed as such, booleans as `Yes` or `No`
+fn property_attribute(key: &str, value: &GenericValue) -> TokenAttribute {
+    let trait_type = trait_type(key);
+    let number = match value {
+        GenericValue::Nat8Content(v) => Some(Value::from(*v)),
+        GenericValue::Nat16Content(v) => Some(Value::from(*v)),
+        GenericValue::Nat32Content(v) => Some(Value::from(*v)),
+        GenericValue::Nat64Content(v) => Some(Value::from(*v)),
+        GenericValue::NatContent(v) => u64::try_from(&v.0).ok().map(Value::from),
+        GenericValue::Int8Content(v) => Some(Value::from(*v)),
+        GenericValue::Int16Content(v) => Some(Value::from(*v)),
+        GenericValue::Int32Content(v) => Some(Value::from(*v)),
+        GenericValue::Int64Content(v) => Some(Value::from(*v)),
+        GenericValue::IntContent(v) => i64::try_from(&v.0).ok().map(Value::from),
+        GenericValue::FloatContent(v) => Some(Value::from(*v)),
+        GenericValue::BoolContent(_)
+        | GenericValue::Principal(_)
+        | GenericValue::TextContent(_) => None,
+    };
+
+    match (number, value) {
+        (Some(number), _) => attribute(&trait_type, number, Some(DISPLAY_NUMBER)),
+        (None, GenericValue::BoolContent(v)) => {
+            attribute(&trait_type, if *v { "Yes" } else { "No" }, None)
+        }
+        // numbers too large for JSON are shown as text
+        (None, value) => attribute(&trait_type, value.to_string(), None),
+    }
+}
+
+/// Convert a camel case property key into a trait name (e.g. `squareMeters` => `Square meters`)
+fn trait_type(key: &str) -> String {
+    let mut trait_type = String::with_capacity(key.len() + 4);
+    for (i, c) in key.chars().enumerate() {
+        if i == 0 {
+            trait_type.extend(c.to_uppercase());
+        } else if c.is_uppercase() {
+            trait_type.push(' ');
+            trait_type.extend(c.to_lowercase());
+        } else {
+            trait_type.push(c);
+        }
+    }
+
+    trait_type
+}
+
+fn text_property(contract: &Contract, key: &str) -> Option<String> {
+    contract
+        .properties
+        .iter()
+        .find_map(|(property, value)| match value {
+            GenericValue::TextContent(text) if property == key => Some(text.clone()),
+            _ => None,
+        })
+}
+
+#[cfg(test)]
+mod test {
+
+    use pretty_assertions::assert_eq;
+    use serde_json::json;
+
+    use super::*;
+    use crate::app::test_utils::with_mock_contract;
+
+    #[test]
+    fn test_should_get_contract_metadata() {
+        let contract = with_mock_contract(7, 120, |contract| {
+            contract.value = 240_000;
+            contract.currency = "EUR".to_string();
+            contract.r#type = ContractType::Sell;
+            contract.properties = vec![
+                (
+                    CONTRACT_NAME.to_string(),
+                    GenericValue::TextContent("Villa Bianca".to_string()),
+                ),
+                (
+                    CONTRACT_IMAGE.to_string(),
+                    GenericValue::TextContent("https://example.com/villa.png".to_string()),
+                ),
+                (
+                    "contract:squareMeters".to_string(),
+                    GenericValue::Nat64Content(180),
+                ),
+                (
+                    "contract:garden".to_string(),
+                    GenericValue::BoolContent(true),
+                ),
+            ];
+        });
+
+        let metadata = token_metadata(&contract, None);
+        assert_eq!(metadata.name, "Villa Bianca");
+        assert_eq!(
+            metadata.description,
+            "Sell contract of 240000 EUR in 120 installments"
+        );
+        assert_eq!(
+            metadata.image.as_deref(),
+            Some("https://example.com/villa.png")
+        );
+        assert!(metadata.external_url.ends_with(".raw.icp0.io/contract/7"));
+
+        let value_of = |trait_type: &str| {
+            metadata
+                .attributes
+                .iter()
+                .find(|attribute| attribute.trait_type == trait_type)
+                .map(|attribute| attribute.value.clone())
+        };
+        assert_eq!(value_of("Token price"), Some(json!(2000)));
+        assert_eq!(value_of("Installments"), Some(json!(120)));
+        assert_eq!(value_of("Square meters"), Some(json!(180)));
+        assert_eq!(value_of("Garden"), Some(json!("Yes")));
+        assert_eq!(value_of("Name"), None);
+        // 2078-01-01
+        assert_eq!(value_of("Expiration"), Some(json!(3408220800i64)));
+    }
+
+    #[test]
+    fn test_should_get_token_metadata() {
+        let contract = with_mock_contract(7, 120, |contract| contract.properties.clear());
+
+        let metadata = token_metadata(&contract, Some(42));
+        assert_eq!(metadata.name, "Deferred contract #7 - token #42");
+        assert_eq!(metadata.image, None);
+        assert!(metadata
+            .attributes
+            .iter()
+            .any(|attribute| attribute.trait_type == "Token ID" && attribute.value == json!(42)));
+    }
+
+    #[test]
+    fn test_should_convert_key_to_trait_type() {
+        assert_eq!(trait_type("squareMeters"), "Square meters");
+        assert_eq!(trait_type("energyClass"), "Energy class");
+        assert_eq!(trait_type("rooms"), "Rooms");
+    }
+}
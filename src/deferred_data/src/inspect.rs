@@ -1,4 +1,8 @@
-use did::deferred::{GenericValue, RestrictedProperty};
+use candid::Principal;
+use did::deferred::{
+    AccessGroup, ContractDocument, GenericValue, PropertyOperation, RestrictedProperty,
+    RestrictionLevel,
+};
 use did::ID;
 use ic_cdk::api;
 use ic_cdk::api::call::ArgDecoderConfig;
@@ -38,6 +42,76 @@ fn inspect_message_impl() {
 
             Inspect::inspect_modify_contract(caller(), &contract_id).is_ok()
         }
+        "update_contract_properties" => {
+            let contract_id =
+                api::call::arg_data::<(ID, Vec<PropertyOperation>)>(ArgDecoderConfig::default()).0;
+
+            Inspect::inspect_modify_contract(caller(), &contract_id).is_ok()
+        }
+        "update_restricted_property_access" => {
+            let contract_id = api::call::arg_data::<(ID, String, Vec<RestrictionLevel>)>(
+                ArgDecoderConfig::default(),
+            )
+            .0;
+
+            Inspect::inspect_modify_contract(caller(), &contract_id).is_ok()
+        }
+        "update_document_access" => {
+            let contract_id = api::call::arg_data::<(ID, u64, Vec<RestrictionLevel>)>(
+                ArgDecoderConfig::default(),
+            )
+            .0;
+
+            Inspect::inspect_modify_contract(caller(), &contract_id).is_ok()
+        }
+        "update_contract_access_group" => {
+            let contract_id =
+                api::call::arg_data::<(ID, AccessGroup)>(ArgDecoderConfig::default()).0;
+
+            Inspect::inspect_modify_contract(caller(), &contract_id).is_ok()
+        }
+        "replace_contract_document" => {
+            let contract_id = api::call::arg_data::<(ID, u64, ContractDocument, Vec<u8>)>(
+                ArgDecoderConfig::default(),
+            )
+            .0;
+
+            Inspect::inspect_modify_contract(caller(), &contract_id).is_ok()
+        }
+        "delete_contract_document" => {
+            let contract_id = api::call::arg_data::<(ID, u64)>(ArgDecoderConfig::default()).0;
+
+            Inspect::inspect_modify_contract(caller(), &contract_id).is_ok()
+        }
+        "upload_contract_image" => {
+            let contract_id =
+                api::call::arg_data::<(ID, String, Vec<u8>)>(ArgDecoderConfig::default()).0;
+
+            Inspect::inspect_modify_contract(caller(), &contract_id).is_ok()
+        }
+        "delete_contract_image" => {
+            let contract_id = api::call::arg_data::<(ID, u64)>(ArgDecoderConfig::default()).0;
+
+            Inspect::inspect_modify_contract(caller(), &contract_id).is_ok()
+        }
+        "begin_contract_document_upload" => {
+            let contract_id =
+                api::call::arg_data::<(ID, ContractDocument)>(ArgDecoderConfig::default()).0;
+
+            Inspect::inspect_modify_contract(caller(), &contract_id).is_ok()
+        }
+        "upload_contract_document_chunk" => {
+            let upload_id =
+                api::call::arg_data::<(u64, u64, Vec<u8>)>(ArgDecoderConfig::default()).0;
+
+            Inspect::inspect_modify_upload(caller(), upload_id).is_ok()
+        }
+        "finalize_contract_document_upload" => {
+            let upload_id = api::call::arg_data::<(u64,)>(ArgDecoderConfig::default()).0;
+
+            Inspect::inspect_modify_upload(caller(), upload_id).is_ok()
+        }
+        "link_address" | "unlink_address" | "create_nonce" => caller() != Principal::anonymous(),
         _ => true,
     };
 
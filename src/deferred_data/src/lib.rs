@@ -1,9 +1,13 @@
 use candid::{candid_method, Nat, Principal};
 use did::deferred::{
-    Contract, ContractDocument, ContractDocumentData, DeferredDataInitData, DeferredDataResult,
-    GenericValue, RestrictedProperty,
+    AccessGroup, AuditLogEntry, Contract, ContractArchiveEntry, ContractDocument,
+    ContractDocumentData, ContractImage, ContractListQuery, ContractSession, ContractSummaryPage,
+    DeferredDataInitData, DeferredDataResult, GenericValue, PropertyOperation, PropertySchema,
+    RestrictedProperty, RestrictionLevel, SignedMessage,
+};
+use did::{
+    HttpRequest, HttpResponse, StreamingCallbackHttpResponse, StreamingCallbackToken, H160, ID,
 };
-use did::{HttpRequest, HttpResponse, ID};
 use ic_cdk::post_upgrade;
 use ic_cdk_macros::{init, query, update};
 
@@ -12,7 +16,7 @@ mod http;
 mod inspect;
 mod utils;
 
-use app::DeferredData;
+use app::{Credential, DeferredData};
 use ic_log::did::Pagination;
 use ic_log::writer::Logs;
 
@@ -33,12 +37,42 @@ pub fn admin_set_minter(minter: Principal) -> DeferredDataResult<()> {
     DeferredData::admin_set_minter(minter)
 }
 
+#[update]
+#[candid_method(update)]
+pub fn admin_set_chain_id(chain_id: u64) -> DeferredDataResult<()> {
+    DeferredData::admin_set_chain_id(chain_id)
+}
+
+#[update]
+#[candid_method(update)]
+pub fn admin_set_siwe_domain(domain: String) -> DeferredDataResult<()> {
+    DeferredData::admin_set_siwe_domain(domain)
+}
+
 #[query]
 #[candid_method(query)]
 pub fn admin_ic_logs(pagination: Pagination) -> Logs {
     DeferredData::admin_ic_logs(pagination)
 }
 
+#[update]
+#[candid_method(update)]
+pub fn admin_set_property_schema(schema: Vec<PropertySchema>) -> DeferredDataResult<()> {
+    DeferredData::admin_set_property_schema(schema)
+}
+
+#[query]
+#[candid_method(query)]
+pub fn get_property_schema() -> Vec<PropertySchema> {
+    DeferredData::get_property_schema()
+}
+
+#[query]
+#[candid_method(query)]
+pub fn validate_contract(contract: Contract) -> DeferredDataResult<()> {
+    DeferredData::validate_contract(&contract)
+}
+
 #[query]
 #[candid_method(query)]
 pub fn admin_cycles() -> Nat {
@@ -59,8 +93,17 @@ pub async fn minter_close_contract(contract_id: ID) -> DeferredDataResult<()> {
 
 #[query]
 #[candid_method(query)]
-pub fn get_contract(id: ID) -> Option<Contract> {
-    DeferredData::get_contract(&id, None)
+pub fn get_contract(id: ID, signature: Option<SignedMessage>) -> Option<Contract> {
+    DeferredData::get_contract(&id, signature.map(Credential::from))
+}
+
+#[query]
+#[candid_method(query)]
+pub fn get_archived_contract(
+    id: ID,
+    signature: Option<SignedMessage>,
+) -> DeferredDataResult<Contract> {
+    DeferredData::get_archived_contract(&id, signature.map(Credential::from))
 }
 
 #[query]
@@ -69,13 +112,126 @@ pub fn get_contracts() -> Vec<ID> {
     DeferredData::get_contracts()
 }
 
+#[query]
+#[candid_method(query)]
+pub fn list_contracts(query: ContractListQuery) -> DeferredDataResult<ContractSummaryPage> {
+    DeferredData::list_contracts(query, None, |_| true)
+}
+
+#[query]
+#[candid_method(query)]
+pub fn get_contract_archive() -> Vec<ContractArchiveEntry> {
+    DeferredData::get_contract_archive()
+}
+
 #[query]
 #[candid_method(query)]
 pub fn get_contract_document(
     contract_id: ID,
     document_id: u64,
+    signature: Option<SignedMessage>,
 ) -> DeferredDataResult<ContractDocumentData> {
-    DeferredData::get_contract_document(contract_id, document_id, None)
+    DeferredData::get_contract_document(contract_id, document_id, signature.map(Credential::from))
+}
+
+#[query]
+#[candid_method(query)]
+pub fn verify_contract_document(
+    contract_id: ID,
+    document_id: u64,
+    sha256: String,
+    signature: Option<SignedMessage>,
+) -> DeferredDataResult<bool> {
+    DeferredData::verify_contract_document(
+        contract_id,
+        document_id,
+        &sha256,
+        signature.map(Credential::from),
+    )
+}
+
+#[query]
+#[candid_method(query)]
+pub fn get_contract_audit_log(
+    contract_id: ID,
+    pagination: Pagination,
+    signature: Option<SignedMessage>,
+) -> DeferredDataResult<Vec<AuditLogEntry>> {
+    DeferredData::get_contract_audit_log(&contract_id, pagination, signature.map(Credential::from))
+}
+
+#[update]
+#[candid_method(update)]
+pub async fn create_nonce() -> DeferredDataResult<String> {
+    DeferredData::create_nonce().await
+}
+
+#[update]
+#[candid_method(update)]
+pub async fn create_contract_session(
+    contract_id: ID,
+    signature: SignedMessage,
+) -> DeferredDataResult<ContractSession> {
+    DeferredData::create_contract_session(contract_id, signature).await
+}
+
+#[update]
+#[candid_method(update)]
+pub fn link_address(signature: SignedMessage) -> DeferredDataResult<H160> {
+    DeferredData::link_address(signature)
+}
+
+#[update]
+#[candid_method(update)]
+pub fn unlink_address() -> DeferredDataResult<()> {
+    DeferredData::unlink_address()
+}
+
+#[query]
+#[candid_method(query)]
+pub fn get_linked_address() -> Option<H160> {
+    DeferredData::get_linked_address()
+}
+
+#[update]
+#[candid_method(update)]
+pub fn update_contract_properties(
+    contract_id: ID,
+    operations: Vec<PropertyOperation>,
+) -> DeferredDataResult<()> {
+    DeferredData::update_contract_properties(contract_id, operations)
+}
+
+#[update]
+#[candid_method(update)]
+pub fn update_document_access(
+    contract_id: ID,
+    document_id: u64,
+    access_list: Vec<RestrictionLevel>,
+) -> DeferredDataResult<()> {
+    DeferredData::update_document_access(contract_id, document_id, access_list)
+}
+
+#[update]
+#[candid_method(update)]
+pub fn update_contract_access_group(contract_id: ID, group: AccessGroup) -> DeferredDataResult<()> {
+    DeferredData::update_contract_access_group(contract_id, group)
+}
+
+#[query]
+#[candid_method(query)]
+pub fn get_contract_access_groups(contract_id: ID) -> DeferredDataResult<Vec<AccessGroup>> {
+    DeferredData::get_contract_access_groups(contract_id)
+}
+
+#[update]
+#[candid_method(update)]
+pub fn update_restricted_property_access(
+    contract_id: ID,
+    key: String,
+    access_list: Vec<RestrictionLevel>,
+) -> DeferredDataResult<()> {
+    DeferredData::update_restricted_property_access(contract_id, key, access_list)
 }
 
 #[update]
@@ -88,6 +244,70 @@ pub fn upload_contract_document(
     DeferredData::upload_contract_document(contract_id, document, data)
 }
 
+#[update]
+#[candid_method(update)]
+pub fn replace_contract_document(
+    contract_id: ID,
+    document_id: u64,
+    document: ContractDocument,
+    data: Vec<u8>,
+) -> DeferredDataResult<u64> {
+    DeferredData::replace_contract_document(contract_id, document_id, document, data)
+}
+
+#[update]
+#[candid_method(update)]
+pub fn delete_contract_document(contract_id: ID, document_id: u64) -> DeferredDataResult<()> {
+    DeferredData::delete_contract_document(contract_id, document_id)
+}
+
+#[update]
+#[candid_method(update)]
+pub fn begin_contract_document_upload(
+    contract_id: ID,
+    document: ContractDocument,
+) -> DeferredDataResult<u64> {
+    DeferredData::begin_contract_document_upload(contract_id, document)
+}
+
+#[update]
+#[candid_method(update)]
+pub fn upload_contract_document_chunk(
+    upload_id: u64,
+    chunk_index: u64,
+    data: Vec<u8>,
+) -> DeferredDataResult<()> {
+    DeferredData::upload_contract_document_chunk(upload_id, chunk_index, data)
+}
+
+#[update]
+#[candid_method(update)]
+pub fn finalize_contract_document_upload(upload_id: u64) -> DeferredDataResult<u64> {
+    DeferredData::finalize_contract_document_upload(upload_id)
+}
+
+#[update]
+#[candid_method(update)]
+pub fn upload_contract_image(
+    contract_id: ID,
+    name: String,
+    data: Vec<u8>,
+) -> DeferredDataResult<u64> {
+    DeferredData::upload_contract_image(contract_id, name, data)
+}
+
+#[update]
+#[candid_method(update)]
+pub fn delete_contract_image(contract_id: ID, image_id: u64) -> DeferredDataResult<()> {
+    DeferredData::delete_contract_image(contract_id, image_id)
+}
+
+#[query]
+#[candid_method(query)]
+pub fn get_contract_images(contract_id: ID) -> Vec<ContractImage> {
+    DeferredData::get_contract_images(&contract_id)
+}
+
 #[update]
 #[candid_method(update)]
 pub fn update_contract_property(
@@ -115,6 +335,14 @@ pub async fn http_request(req: HttpRequest) -> HttpResponse {
     http::HttpApi::handle_http_request(req).await
 }
 
+#[query]
+#[candid_method(query)]
+pub fn http_request_streaming_callback(
+    token: StreamingCallbackToken,
+) -> StreamingCallbackHttpResponse {
+    http::HttpApi::http_request_streaming_callback(token)
+}
+
 #[allow(dead_code)]
 fn main() {
     // The line below generates did types and service definition from the
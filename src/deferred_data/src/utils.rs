@@ -1,4 +1,5 @@
 use candid::{Nat, Principal};
+use did::deferred::DeferredDataResult;
 
 pub fn cycles() -> Nat {
     #[cfg(not(target_arch = "wasm32"))]
@@ -11,6 +12,33 @@ pub fn cycles() -> Nat {
     }
 }
 
+/// Returns current time in nanoseconds
+pub fn time() -> u64 {
+    #[cfg(not(target_arch = "wasm32"))]
+    {
+        let time = std::time::SystemTime::now()
+            .duration_since(std::time::UNIX_EPOCH)
+            .unwrap();
+        time.as_nanos() as u64
+    }
+    #[cfg(target_arch = "wasm32")]
+    {
+        ic_cdk::api::time()
+    }
+}
+
+/// Returns the principal of this canister
+pub fn id() -> Principal {
+    #[cfg(not(target_arch = "wasm32"))]
+    {
+        Principal::from_text("2m6dw-uaaaa-aaaal-arumq-cai").unwrap()
+    }
+    #[cfg(target_arch = "wasm32")]
+    {
+        ic_cdk::id()
+    }
+}
+
 pub fn caller() -> Principal {
     #[cfg(not(target_arch = "wasm32"))]
     {
@@ -22,3 +50,51 @@ pub fn caller() -> Principal {
         ic_cdk::caller()
     }
 }
+
+/// Sets the data certified by the subnet; can only be called by update calls
+pub fn set_certified_data(data: &[u8]) {
+    #[cfg(not(target_arch = "wasm32"))]
+    {
+        let _ = data;
+    }
+    #[cfg(target_arch = "wasm32")]
+    {
+        ic_cdk::api::set_certified_data(data)
+    }
+}
+
+/// Returns the certificate of the certified data; only available in query calls
+pub fn data_certificate() -> Option<Vec<u8>> {
+    #[cfg(not(target_arch = "wasm32"))]
+    {
+        None
+    }
+    #[cfg(target_arch = "wasm32")]
+    {
+        ic_cdk::api::data_certificate()
+    }
+}
+
+/// Returns 32 random bytes
+pub async fn random_bytes() -> DeferredDataResult<[u8; 32]> {
+    #[cfg(not(target_arch = "wasm32"))]
+    {
+        let mut bytes = [0; 32];
+        getrandom::getrandom(&mut bytes).expect("failed to get random bytes");
+        Ok(bytes)
+    }
+    #[cfg(target_arch = "wasm32")]
+    {
+        use did::deferred::DeferredDataError;
+
+        let (bytes,) = ic_cdk::api::management_canister::main::raw_rand()
+            .await
+            .map_err(|(code, message)| DeferredDataError::CanisterCall(code, message))?;
+        bytes.try_into().map_err(|_| {
+            DeferredDataError::CanisterCall(
+                ic_cdk::api::call::RejectionCode::CanisterError,
+                "unexpected amount of random bytes".to_string(),
+            )
+        })
+    }
+}
@@ -2,7 +2,8 @@ use candid::{Nat, Principal};
 use contract_id::ContractId;
 use data_client::DeferredDataClient;
 use did::deferred::{
-    Agency, Contract, ContractRegistration, DeferredMinterInitData, DeferredMinterResult, Role,
+    Agency, Contract, ContractRegistration, DeferredMinterInitData, DeferredMinterResult,
+    PropertyOperation, Role,
 };
 use did::ID;
 use ethereum::{DeferredErc721, EvmRpcClient, RewardPool, Wallet};
@@ -113,6 +114,9 @@ impl DeferredMinter {
         let contract = Self::contract_from_registration(contract_id.clone(), data);
         log::debug!("contract data: {contract:?}");
 
+        // validate contract properties before minting, since the data canister would reject them anyway
+        Self::deferred_data().validate_contract(&contract).await?;
+
         // get evm rpc client
         let evm_rpc_client = Self::evm_rpc_client();
 
@@ -155,13 +159,53 @@ impl DeferredMinter {
 
     /// Close a contract on both the ERC721 and the data canister
     pub async fn close_contract(contract_id: ID) -> DeferredMinterResult<()> {
+        Self::inspect_contract_agency(&contract_id).await?;
+
+        // close contract on erc721
+        let evm_rpc_client = Self::evm_rpc_client();
+        Self::deferred_erc721()
+            .close_contract(&Self::wallet(), &evm_rpc_client, contract_id.clone())
+            .await?;
+        log::debug!("closed contract {contract_id} on Ethereum");
+
+        // close contract on data canister
+        Self::deferred_data()
+            .close_contract(contract_id.clone())
+            .await?;
+        log::info!("Contract {contract_id} closed successfully");
+
+        Ok(())
+    }
+
+    /// Apply a batch of operations on the properties of a contract.
+    ///
+    /// The whole batch is forwarded to the data canister with a single call.
+    pub async fn update_contract_properties(
+        contract_id: ID,
+        operations: Vec<PropertyOperation>,
+    ) -> DeferredMinterResult<()> {
+        Self::inspect_contract_agency(&contract_id).await?;
+
+        let operations_count = operations.len();
+        Self::deferred_data()
+            .update_contract_properties(contract_id.clone(), operations)
+            .await?;
+        log::info!("Applied {operations_count} property operations to contract {contract_id}");
+
+        Ok(())
+    }
+
+    /// Check whether the caller can manage the contract.
+    ///
+    /// Custodians can manage any contract, while agents can only manage the contracts of their agency.
+    async fn inspect_contract_agency(contract_id: &ID) -> DeferredMinterResult<()> {
         if !Inspect::inspect_is_agent(caller()) && !Inspect::inspect_is_custodian(caller()) {
             ic_cdk::trap("Unauthorized");
         }
         // if we are an agent, we need to check whether we are the agency for the contract
         if RolesManager::is_agent(caller()) {
             log::debug!("caller is an agent");
-            let contract = Self::deferred_data().get_contract(&contract_id).await?;
+            let contract = Self::deferred_data().get_contract(contract_id).await?;
             if contract
                 .agency
                 .map(|agency| agency.owner != caller())
@@ -172,19 +216,6 @@ impl DeferredMinter {
             }
         }
 
-        // close contract on erc721
-        let evm_rpc_client = Self::evm_rpc_client();
-        Self::deferred_erc721()
-            .close_contract(&Self::wallet(), &evm_rpc_client, contract_id.clone())
-            .await?;
-        log::debug!("closed contract {contract_id} on Ethereum");
-
-        // close contract on data canister
-        Self::deferred_data()
-            .close_contract(contract_id.clone())
-            .await?;
-        log::info!("Contract {contract_id} closed successfully");
-
         Ok(())
     }
 
@@ -328,6 +359,7 @@ impl DeferredMinter {
             agency,
             expiration: data.expiration,
             closed: false,
+            created_at: None,
         }
     }
 }
@@ -335,7 +367,7 @@ impl DeferredMinter {
 #[cfg(test)]
 mod test {
 
-    use did::deferred::{Continent, EcdsaKey, Seller};
+    use did::deferred::{Continent, EcdsaKey, GenericValue, Seller};
     use did::H160;
     use ic_log::LogSettingsV2;
     use pretty_assertions::assert_eq;
@@ -569,6 +601,26 @@ mod test {
             .expect("failed to close contract");
     }
 
+    #[tokio::test]
+    async fn test_should_update_contract_properties() {
+        init();
+
+        DeferredMinter::update_contract_properties(
+            1u64.into(),
+            vec![
+                PropertyOperation::Set {
+                    key: "contract:city".to_string(),
+                    value: GenericValue::TextContent("Milan".to_string()),
+                },
+                PropertyOperation::Remove {
+                    key: "contract:zone".to_string(),
+                },
+            ],
+        )
+        .await
+        .expect("failed to update contract properties");
+    }
+
     fn init() {
         DeferredMinter::init(DeferredMinterInitData {
             allowed_currencies: vec!["USD".to_string()],
@@ -1,7 +1,7 @@
 use candid::Principal;
 use did::deferred::{
     Agency, Contract, ContractError, DeferredDataResult, DeferredMinterError, DeferredMinterResult,
-    GenericValue, Seller,
+    GenericValue, PropertyOperation, Seller, SignedMessage,
 };
 use did::{H160, ID};
 
@@ -61,13 +61,14 @@ impl DeferredDataClient {
                 }),
                 expiration: "2078-01-01".to_string(),
                 closed: false,
+                created_at: None,
             });
         }
 
         let (contract,) = ic_cdk::call::<_, (Option<Contract>,)>(
             self.principal,
             "get_contract",
-            (contract_id.clone(),),
+            (contract_id.clone(), None::<SignedMessage>),
         )
         .await
         .map_err(|(code, err)| did::deferred::DeferredMinterError::CanisterCall(code, err))?;
@@ -77,6 +78,23 @@ impl DeferredDataClient {
         ))
     }
 
+    /// Validate contract properties against the property schema of the data canister
+    pub async fn validate_contract(&self, contract: &Contract) -> DeferredMinterResult<()> {
+        if cfg!(test) {
+            return Ok(());
+        }
+
+        let (result,) = ic_cdk::call::<_, (DeferredDataResult<()>,)>(
+            self.principal,
+            "validate_contract",
+            (contract,),
+        )
+        .await
+        .map_err(|(code, err)| did::deferred::DeferredMinterError::CanisterCall(code, err))?;
+
+        result.map_err(DeferredMinterError::DataCanister)
+    }
+
     /// Create contract on data canister
     pub async fn create_contract(&self, contract: Contract) -> DeferredMinterResult<()> {
         if cfg!(test) {
@@ -110,4 +128,25 @@ impl DeferredDataClient {
 
         result.map_err(DeferredMinterError::DataCanister)
     }
+
+    /// Apply a batch of operations on the contract properties on data canister
+    pub async fn update_contract_properties(
+        &self,
+        contract_id: ID,
+        operations: Vec<PropertyOperation>,
+    ) -> DeferredMinterResult<()> {
+        if cfg!(test) {
+            return Ok(());
+        }
+
+        let (result,) = ic_cdk::call::<_, (DeferredDataResult<()>,)>(
+            self.principal,
+            "update_contract_properties",
+            (contract_id, operations),
+        )
+        .await
+        .map_err(|(code, err)| did::deferred::DeferredMinterError::CanisterCall(code, err))?;
+
+        result.map_err(DeferredMinterError::DataCanister)
+    }
 }
@@ -38,13 +38,8 @@ impl DeferredErc721 {
             return Ok(());
         }
 
-        let deferred_data_principal = Configuration::get_deferred_data_canister().to_text();
-        let metadata_uri = format!(
-            "https://{deferred_data_principal}.raw.icp0.io/contract/{}",
-            contract.id
-        );
-
         let contract_id = contract.id.0.to_u64().expect("Contract ID is too large");
+        let metadata_uri = Self::metadata_uri(contract_id);
         log::debug!("Metadata URI for contract_id {contract_id}: {metadata_uri}");
 
         let request = CreateContractRequest {
@@ -72,6 +67,13 @@ impl DeferredErc721 {
             .await
     }
 
+    /// Get the URI of the ERC-721 metadata of the tokens of a contract, served by the deferred data canister
+    fn metadata_uri(contract_id: u64) -> String {
+        let deferred_data_principal = Configuration::get_deferred_data_canister().to_text();
+
+        format!("https://{deferred_data_principal}.raw.icp0.io/contract/{contract_id}/metadata")
+    }
+
     /// Close a contract on the Deferred Erc721 contract
     pub async fn close_contract(
         &self,
@@ -1,4 +1,5 @@
 mod agents;
+mod export;
 
 use agents::{Filters, FILTER_PAGINATION_LIMIT, FILTER_PAGINATION_OFFSET};
 use candid::Principal;
@@ -11,6 +12,9 @@ use crate::app::DeferredMinter;
 const ROUTE_AGENTS: &str = "Agents";
 const ROUTE_AGENT: &str = "Agent";
 
+/// Query param selecting the format of the exported agencies
+const EXPORT_FORMAT: &str = "format";
+
 struct Pagination {
     offset: usize,
     limit: usize,
@@ -63,30 +67,42 @@ impl HttpApi {
         }
     }
 
+    /// Get the agencies matching the filters.
+    ///
+    /// With `format=geojson` or `format=csv`, the agencies are exported in that format.
     fn get_agencies(url: &Url) -> HttpResponse {
         let filters = Filters::from(url);
 
         // get pagination
         let pagination = Self::get_pagination(url);
 
-        HttpResponse::ok(
-            DeferredMinter::get_agencies()
-                .into_iter()
-                .filter(|agency| filters.check(agency))
-                .skip(
-                    pagination
-                        .as_ref()
-                        .map(|page| page.offset)
-                        .unwrap_or_default(),
-                )
-                .take(
-                    pagination
-                        .as_ref()
-                        .map(|page| page.limit)
-                        .unwrap_or(usize::MAX),
-                )
-                .collect::<Vec<_>>(),
-        )
+        let agencies = DeferredMinter::get_agencies()
+            .into_iter()
+            .filter(|agency| filters.check(agency))
+            .skip(
+                pagination
+                    .as_ref()
+                    .map(|page| page.offset)
+                    .unwrap_or_default(),
+            )
+            .take(
+                pagination
+                    .as_ref()
+                    .map(|page| page.limit)
+                    .unwrap_or(usize::MAX),
+            )
+            .collect::<Vec<_>>();
+
+        let format = url
+            .query_pairs()
+            .find(|(key, _)| key == EXPORT_FORMAT)
+            .map(|(_, value)| value);
+        match format.as_deref() {
+            None | Some("json") => HttpResponse::ok(agencies),
+            Some("geojson") => HttpResponse::geojson(export::agencies_geojson(&agencies)),
+            Some("csv") => HttpResponse::csv(export::agencies_csv(&agencies)),
+            Some(_) => HttpResponse::bad_request("format must be json, geojson or csv".to_string()),
+        }
     }
 
     fn get_agent(id: Principal) -> HttpResponse {
@@ -177,6 +193,38 @@ mod test {
         assert_eq!(got_agents[0], agent);
     }
 
+    #[tokio::test]
+    async fn test_should_export_agencies() {
+        let agent = with_mock_agency(|agent| {
+            agent.lat = Some("41.9".to_string());
+            agent.lng = Some("12.5".to_string());
+        });
+        Agents::insert_agency(agent.owner, agent.clone());
+
+        let get = |url: &str| {
+            HttpApi::handle_http_request(HttpRequest {
+                method: Cow::from("GET".to_string()),
+                url: url.to_string(),
+                headers: HashMap::default(),
+                body: Default::default(),
+            })
+        };
+
+        let res = get("/agents?format=geojson&city=Rome").await;
+        assert_eq!(res.status_code, 200);
+        let collection: did::FeatureCollection = serde_json::from_slice(&res.body).unwrap();
+        assert_eq!(collection.features.len(), 1);
+        assert_eq!(collection.features[0].id, agent.owner.to_text());
+
+        let res = get("/agents?format=csv").await;
+        assert_eq!(res.status_code, 200);
+        let csv = String::from_utf8(res.body.to_vec()).unwrap();
+        assert_eq!(csv.lines().count(), 2);
+
+        let res = get("/agents?format=kml").await;
+        assert_eq!(res.status_code, 400);
+    }
+
     #[tokio::test]
     async fn test_should_get_agent() {
         let agent = mock_agency();
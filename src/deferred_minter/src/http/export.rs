@@ -0,0 +1,125 @@
+//! Export of the agencies as GeoJSON features and CSV rows
+
+use std::str::FromStr as _;
+
+use did::deferred::Agency;
+use did::{CsvTable, Feature, FeatureCollection};
+use serde_json::{Map, Value};
+
+/// Exported columns of the agencies
+const AGENCY_COLUMNS: [&str; 16] = [
+    "owner",
+    "name",
+    "agent",
+    "address",
+    "city",
+    "region",
+    "zip_code",
+    "country",
+    "continent",
+    "lat",
+    "lng",
+    "email",
+    "mobile",
+    "website",
+    "vat",
+    "logo",
+];
+
+/// Export the agencies with a valid position as a GeoJSON feature collection
+pub fn agencies_geojson(agencies: &[Agency]) -> FeatureCollection {
+    agencies
+        .iter()
+        .filter_map(|agency| {
+            let latitude = agency.lat.as_deref().and_then(|x| f64::from_str(x).ok())?;
+            let longitude = agency.lng.as_deref().and_then(|x| f64::from_str(x).ok())?;
+            if !(-90.0..=90.0).contains(&latitude) || !(-180.0..=180.0).contains(&longitude) {
+                return None;
+            }
+
+            Some(Feature::new(
+                agency.owner.to_text(),
+                longitude,
+                latitude,
+                agency_record(agency),
+            ))
+        })
+        .collect()
+}
+
+/// Export the agencies as CSV
+pub fn agencies_csv(agencies: &[Agency]) -> CsvTable {
+    let records = agencies.iter().map(agency_record).collect::<Vec<_>>();
+
+    CsvTable::from_records(
+        AGENCY_COLUMNS.into_iter().map(str::to_string).collect(),
+        &records,
+    )
+}
+
+fn agency_record(agency: &Agency) -> Map<String, Value> {
+    [
+        ("owner", Some(agency.owner.to_text())),
+        ("name", Some(agency.name.clone())),
+        ("agent", Some(agency.agent.clone())),
+        ("address", Some(agency.address.clone())),
+        ("city", Some(agency.city.clone())),
+        ("region", Some(agency.region.clone())),
+        ("zip_code", Some(agency.zip_code.clone())),
+        ("country", Some(agency.country.clone())),
+        ("continent", Some(agency.continent.to_string())),
+        ("lat", agency.lat.clone()),
+        ("lng", agency.lng.clone()),
+        ("email", Some(agency.email.clone())),
+        ("mobile", Some(agency.mobile.clone())),
+        ("website", Some(agency.website.clone())),
+        ("vat", Some(agency.vat.clone())),
+        ("logo", agency.logo.clone()),
+    ]
+    .into_iter()
+    .map(|(key, value)| (key.to_string(), value.map(Value::from).unwrap_or_default()))
+    .collect()
+}
+
+#[cfg(test)]
+mod test {
+
+    use pretty_assertions::assert_eq;
+
+    use super::*;
+    use crate::app::test_utils::with_mock_agency;
+
+    #[test]
+    fn test_should_export_agencies_geojson() {
+        let located = with_mock_agency(|agency| {
+            agency.lat = Some("41.9".to_string());
+            agency.lng = Some("12.5".to_string());
+        });
+        let unlocated = with_mock_agency(|agency| {
+            agency.lat = None;
+        });
+
+        let collection = agencies_geojson(&[located.clone(), unlocated]);
+        assert_eq!(collection.features.len(), 1);
+        assert_eq!(collection.features[0].id, located.owner.to_text());
+        assert_eq!(collection.features[0].geometry.coordinates, [12.5, 41.9]);
+        assert_eq!(
+            collection.features[0].properties.get("name"),
+            Some(&Value::from(located.name))
+        );
+    }
+
+    #[test]
+    fn test_should_export_agencies_csv() {
+        let agency = with_mock_agency(|agency| {
+            agency.name = "Rossi, Immobili".to_string();
+            agency.logo = None;
+        });
+
+        let table = agencies_csv(&[agency]);
+        assert_eq!(table.header, AGENCY_COLUMNS);
+        assert_eq!(table.rows.len(), 1);
+        assert_eq!(table.rows[0][1], "Rossi, Immobili");
+        assert_eq!(table.rows[0][15], "");
+    }
+}
@@ -31,6 +31,9 @@ fn inspect_message_impl() {
         "close_contract" => {
             Inspect::inspect_is_custodian(caller()) || Inspect::inspect_is_custodian(caller())
         }
+        "update_contract_properties" => {
+            Inspect::inspect_is_agent(caller()) || Inspect::inspect_is_custodian(caller())
+        }
         _ => true,
     };
 
@@ -6,7 +6,8 @@
 
 use candid::{candid_method, Nat, Principal};
 use did::deferred::{
-    Agency, ContractRegistration, DeferredMinterInitData, DeferredMinterResult, Role,
+    Agency, ContractRegistration, DeferredMinterInitData, DeferredMinterResult, PropertyOperation,
+    Role,
 };
 use did::{HttpRequest, HttpResponse, ID};
 use ic_cdk::post_upgrade;
@@ -49,6 +50,15 @@ pub async fn close_contract(contract_id: ID) -> DeferredMinterResult<()> {
     DeferredMinter::close_contract(contract_id).await
 }
 
+#[update]
+#[candid_method(update)]
+pub async fn update_contract_properties(
+    contract_id: ID,
+    operations: Vec<PropertyOperation>,
+) -> DeferredMinterResult<()> {
+    DeferredMinter::update_contract_properties(contract_id, operations).await
+}
+
 #[query]
 #[candid_method(query)]
 pub fn get_agencies() -> Vec<Agency> {
@@ -2,6 +2,7 @@
 //!
 //! Common types
 
+mod export;
 mod h160;
 mod http;
 mod id;
@@ -9,8 +10,14 @@ mod log_settings;
 mod nat;
 mod principal;
 
+pub use export::{
+    CsvTable, Feature, FeatureCollection, FeatureCollectionType, FeatureType, Point, PointType,
+};
 pub use h160::H160;
-pub use http::{HttpRequest, HttpResponse};
+pub use http::{
+    HttpRequest, HttpResponse, StreamingCallbackHttpResponse, StreamingCallbackToken,
+    StreamingStrategy,
+};
 pub use id::ID;
 pub use log_settings::StorableLogSettings;
 pub use nat::StorableNat;
@@ -0,0 +1,180 @@
+use std::fmt;
+
+use serde::{Deserialize, Serialize};
+use serde_json::{Map, Value};
+
+/// A GeoJSON feature collection, as defined by RFC 7946.
+#[derive(Clone, Debug, Default, Serialize, Deserialize, PartialEq)]
+pub struct FeatureCollection {
+    #[serde(rename = "type")]
+    pub r#type: FeatureCollectionType,
+    pub features: Vec<Feature>,
+}
+
+impl FromIterator<Feature> for FeatureCollection {
+    fn from_iter<I: IntoIterator<Item = Feature>>(iter: I) -> Self {
+        Self {
+            r#type: FeatureCollectionType::FeatureCollection,
+            features: iter.into_iter().collect(),
+        }
+    }
+}
+
+#[derive(Clone, Copy, Debug, Default, Serialize, Deserialize, PartialEq, Eq)]
+pub enum FeatureCollectionType {
+    #[default]
+    FeatureCollection,
+}
+
+/// A GeoJSON feature located at a point.
+#[derive(Clone, Debug, Serialize, Deserialize, PartialEq)]
+pub struct Feature {
+    #[serde(rename = "type")]
+    pub r#type: FeatureType,
+    /// The ID of the located item
+    pub id: String,
+    pub geometry: Point,
+    pub properties: Map<String, Value>,
+}
+
+impl Feature {
+    /// Create a feature located at `longitude` and `latitude`.
+    pub fn new(id: String, longitude: f64, latitude: f64, properties: Map<String, Value>) -> Self {
+        Self {
+            r#type: FeatureType::Feature,
+            id,
+            geometry: Point {
+                r#type: PointType::Point,
+                coordinates: [longitude, latitude],
+            },
+            properties,
+        }
+    }
+}
+
+#[derive(Clone, Copy, Debug, Serialize, Deserialize, PartialEq, Eq)]
+pub enum FeatureType {
+    Feature,
+}
+
+/// A GeoJSON point geometry.
+#[derive(Clone, Debug, Serialize, Deserialize, PartialEq)]
+pub struct Point {
+    #[serde(rename = "type")]
+    pub r#type: PointType,
+    /// Longitude and latitude, in this order
+    pub coordinates: [f64; 2],
+}
+
+#[derive(Clone, Copy, Debug, Serialize, Deserialize, PartialEq, Eq)]
+pub enum PointType {
+    Point,
+}
+
+/// A table formatted as CSV, as defined by RFC 4180.
+#[derive(Clone, Debug, Default, PartialEq, Eq)]
+pub struct CsvTable {
+    pub header: Vec<String>,
+    pub rows: Vec<Vec<String>>,
+}
+
+impl CsvTable {
+    /// Create a table of `records`, with `columns` in the header.
+    ///
+    /// Each record is formatted as a row with the values of `columns`; missing values are left empty.
+    pub fn from_records(columns: Vec<String>, records: &[Map<String, Value>]) -> Self {
+        let rows = records
+            .iter()
+            .map(|record| {
+                columns
+                    .iter()
+                    .map(|column| match record.get(column) {
+                        None | Some(Value::Null) => String::new(),
+                        Some(Value::String(value)) => value.clone(),
+                        Some(value) => value.to_string(),
+                    })
+                    .collect()
+            })
+            .collect();
+
+        Self {
+            header: columns,
+            rows,
+        }
+    }
+
+    fn write_row(f: &mut fmt::Formatter<'_>, row: &[String]) -> fmt::Result {
+        for (index, field) in row.iter().enumerate() {
+            if index > 0 {
+                f.write_str(",")?;
+            }
+            if field.contains([',', '"', '\r', '\n']) {
+                write!(f, "\"{}\"", field.replace('"', "\"\""))?;
+            } else {
+                f.write_str(field)?;
+            }
+        }
+
+        f.write_str("\r\n")
+    }
+}
+
+impl fmt::Display for CsvTable {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        Self::write_row(f, &self.header)?;
+        for row in &self.rows {
+            Self::write_row(f, row)?;
+        }
+
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod test {
+
+    use pretty_assertions::assert_eq;
+    use serde_json::json;
+
+    use super::*;
+
+    #[test]
+    fn test_should_serialize_feature_collection() {
+        let mut properties = Map::new();
+        properties.insert("name".to_string(), json!("Villa Bianca"));
+        let collection =
+            FeatureCollection::from_iter([Feature::new("1".to_string(), 12.5, 41.9, properties)]);
+
+        assert_eq!(
+            serde_json::to_value(&collection).unwrap(),
+            json!({
+                "type": "FeatureCollection",
+                "features": [{
+                    "type": "Feature",
+                    "id": "1",
+                    "geometry": { "type": "Point", "coordinates": [12.5, 41.9] },
+                    "properties": { "name": "Villa Bianca" }
+                }]
+            })
+        );
+    }
+
+    #[test]
+    fn test_should_format_csv() {
+        let record = |value: Value| {
+            let mut record = Map::new();
+            record.insert("id".to_string(), json!(1));
+            record.insert("name".to_string(), value);
+            record
+        };
+        let table = CsvTable::from_records(
+            vec!["id".to_string(), "name".to_string(), "city".to_string()],
+            &[record(json!("Villa \"Bianca\", Rome")), record(Value::Null)],
+        );
+
+        assert_eq!(
+            table.to_string(),
+            "id,name,city\r\n1,\"Villa \"\"Bianca\"\", Rome\",\r\n1,,\r\n"
+        );
+    }
+}
@@ -1,10 +1,12 @@
 use std::borrow::Cow;
 use std::collections::HashMap;
 
-use candid::CandidType;
+use candid::{CandidType, Func};
 use serde::{Deserialize, Serialize};
 use serde_bytes::ByteBuf;
 
+use super::export::{CsvTable, FeatureCollection};
+
 const HTTP_OK: u16 = 200;
 const HTTP_UPGRADE: u16 = 204;
 const HTTP_BAD_REQUEST: u16 = 400;
@@ -22,6 +24,40 @@ pub struct HttpResponse {
     pub body: ByteBuf,
     /// Whether the query call should be upgraded to an update call.
     pub upgrade: Option<bool>,
+    /// The strategy to stream the remaining part of the body.
+    pub streaming_strategy: Option<StreamingStrategy>,
+}
+
+/// The strategy used to stream a response body which doesn't fit a single response.
+#[derive(Clone, Debug, CandidType, Deserialize)]
+pub enum StreamingStrategy {
+    /// The gateway calls `callback` with `token` to get the next chunk of the body.
+    Callback {
+        callback: Func,
+        token: StreamingCallbackToken,
+    },
+}
+
+/// The token passed to the streaming callback to get the next chunk of the body.
+#[derive(Clone, Debug, CandidType, Deserialize, PartialEq)]
+pub struct StreamingCallbackToken {
+    /// The URL of the original request.
+    pub url: String,
+    /// The headers of the original request.
+    pub headers: HashMap<Cow<'static, str>, Cow<'static, str>>,
+    /// Offset of the next chunk in the body.
+    pub offset: u64,
+    /// Offset where the streamed body ends (exclusive).
+    pub end: u64,
+}
+
+/// The response of a streaming callback.
+#[derive(Clone, Debug, CandidType, Deserialize)]
+pub struct StreamingCallbackHttpResponse {
+    /// The chunk of the body.
+    pub body: ByteBuf,
+    /// The token to get the next chunk. `None` if this is the last chunk.
+    pub token: Option<StreamingCallbackToken>,
 }
 
 impl HttpResponse {
@@ -51,6 +87,7 @@ impl HttpResponse {
             headers,
             body,
             upgrade,
+            streaming_strategy: None,
         }
     }
 
@@ -66,6 +103,7 @@ impl HttpResponse {
             headers: HashMap::from([("content-type".into(), "application/json".into())]),
             body,
             upgrade: None,
+            streaming_strategy: None,
         }
     }
 
@@ -81,6 +119,7 @@ impl HttpResponse {
             headers: HashMap::from([("content-type".into(), "application/json".into())]),
             body,
             upgrade: None,
+            streaming_strategy: None,
         }
     }
 
@@ -91,6 +130,7 @@ impl HttpResponse {
             headers: HashMap::from([("content-type".into(), "application/json".into())]),
             body: ByteBuf::from("Not Found".as_bytes()),
             upgrade: None,
+            streaming_strategy: None,
         }
     }
 
@@ -111,6 +151,43 @@ impl HttpResponse {
         )
     }
 
+    /// Returns an OK response with the given GeoJSON feature collection.
+    pub fn geojson(collection: FeatureCollection) -> Self {
+        let body = match serde_json::to_string(&collection) {
+            Ok(body) => body,
+            Err(e) => return HttpResponse::internal_error(e.to_string()),
+        };
+        Self::new(
+            HTTP_OK,
+            HashMap::from([("content-type".into(), "application/geo+json".into())]),
+            ByteBuf::from(body.as_bytes()),
+            None,
+        )
+    }
+
+    /// Returns an OK response with the given table formatted as CSV.
+    pub fn csv(table: CsvTable) -> Self {
+        Self::new(
+            HTTP_OK,
+            HashMap::from([("content-type".into(), "text/csv; charset=utf-8".into())]),
+            ByteBuf::from(table.to_string().as_bytes()),
+            None,
+        )
+    }
+
+    /// Returns an OK response with the given Atom feed.
+    pub fn atom(feed: String) -> Self {
+        Self::new(
+            HTTP_OK,
+            HashMap::from([(
+                "content-type".into(),
+                "application/atom+xml; charset=utf-8".into(),
+            )]),
+            ByteBuf::from(feed.into_bytes()),
+            None,
+        )
+    }
+
     /// Upgrade response to update call.
     pub fn upgrade_response() -> Self {
         Self::new(
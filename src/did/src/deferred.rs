@@ -9,12 +9,17 @@ pub type DeferredDataResult<T> = Result<T, DeferredDataError>;
 
 pub use self::contract::{
     Agency, Continent, Contract, ContractDocument, ContractDocumentData, ContractDocuments,
-    ContractProperties, ContractRegistration, ContractType, GenericValue,
-    RestrictedContractProperties, RestrictedProperty, RestrictionLevel, Seller, ID,
+    ContractProperties, ContractRegistration, ContractType, GenericValue, GenericValueKind,
+    PropertyOperation, RestrictedContractProperties, RestrictedProperty, RestrictionLevel, Seller,
+    ID,
 };
 pub use self::data::{
-    ConfigurationError as DataConfigurationError, ContractError as DataContractError,
-    DeferredDataError, DeferredDataInitData,
+    AccessGroup, AccessGroupMember, AuditLogEntry, AuditOperation, AuditRestrictedValue,
+    ConfigurationError as DataConfigurationError, ContractArchiveEntry,
+    ContractError as DataContractError, ContractImage, ContractListQuery, ContractSession,
+    ContractSortBy, ContractSummary, ContractSummaryPage, DeferredDataError, DeferredDataInitData,
+    GeoCluster, GeoPoint, PropertySchema, SearchSuggestion, SignedMessage, SortOrder,
+    StorablePropertySchema, TokenAttribute, TokenMetadata,
 };
 pub use self::minter::{
     CloseContractError, ConfigurationError, ContractError, DeferredMinterError,
@@ -88,6 +93,7 @@ mod test {
             }),
             expiration: "2040-01-01".to_string(),
             closed: false,
+            created_at: None,
         };
         let data = Encode!(&contract).unwrap();
         let decoded_contract = Decode!(&data, Contract).unwrap();
@@ -1,4 +1,4 @@
-use candid::{CandidType, Decode, Deserialize, Encode};
+use candid::{CandidType, Decode, Deserialize, Encode, Principal};
 use ic_stable_structures::storable::Bound;
 use ic_stable_structures::Storable;
 use serde::Serialize;
@@ -10,7 +10,7 @@ mod agency;
 mod generic_value;
 
 pub use self::agency::{Agency, Continent};
-pub use self::generic_value::GenericValue;
+pub use self::generic_value::{GenericValue, GenericValueKind};
 use super::{ContractError, DeferredMinterError, DeferredMinterResult};
 use crate::H160;
 
@@ -45,6 +45,8 @@ pub struct Contract {
     pub expiration: String,
     /// If the contract is closed
     pub closed: bool,
+    /// Time the contract has been created (nanoseconds since UNIX epoch). Set by the canister.
+    pub created_at: Option<u64>,
 }
 
 impl Contract {
@@ -100,6 +102,12 @@ pub struct ContractDocument {
     pub mime_type: String,
     pub name: String,
     pub size: u64,
+    /// The ID of the document revision replaced by this document. Set by the canister.
+    pub previous_version: Option<u64>,
+    /// The ID of the document revision which replaced this document. Set by the canister.
+    pub superseded_by: Option<u64>,
+    /// The hex-encoded SHA-256 digest of the document data. Set by the canister.
+    pub digest: Option<String>,
 }
 
 /// A struct which defines a document data
@@ -117,6 +125,24 @@ pub struct RestrictedProperty {
     pub value: GenericValue,
 }
 
+/// An operation on the properties of a contract.
+///
+/// A list of operations is applied atomically with `update_contract_properties`
+#[derive(Clone, Debug, CandidType, PartialEq, Serialize, Deserialize)]
+pub enum PropertyOperation {
+    /// Insert or update a public property
+    Set { key: String, value: GenericValue },
+    /// Remove a public property
+    Remove { key: String },
+    /// Insert or update a restricted property
+    SetRestricted {
+        key: String,
+        value: RestrictedProperty,
+    },
+    /// Remove a restricted property
+    RemoveRestricted { key: String },
+}
+
 /// A variant which defines the restriction level for a contract property
 #[derive(Clone, Debug, CandidType, PartialEq, Eq, Serialize, Deserialize)]
 pub enum RestrictionLevel {
@@ -128,6 +154,12 @@ pub enum RestrictionLevel {
     Agent,
     /// Public can access the property
     Public,
+    /// A specific address can access the property, even if it is not a party to the contract
+    Address(H160),
+    /// A specific principal can access the property
+    Principal(Principal),
+    /// The members of the named access group of the contract can access the property
+    Group(String),
 }
 
 /// A variant which defines the contract type
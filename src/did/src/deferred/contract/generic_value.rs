@@ -22,11 +22,71 @@ pub enum GenericValue {
     FloatContent(f64), // motoko only support f64
 }
 
+/// The variant of a [`GenericValue`], without its content
+#[derive(CandidType, Deserialize, Serialize, Clone, Copy, Debug, PartialEq, Eq)]
+pub enum GenericValueKind {
+    BoolContent,
+    TextContent,
+    Principal,
+    Nat8Content,
+    Nat16Content,
+    Nat32Content,
+    Nat64Content,
+    NatContent,
+    Int8Content,
+    Int16Content,
+    Int32Content,
+    Int64Content,
+    IntContent,
+    FloatContent,
+}
+
+impl From<&GenericValue> for GenericValueKind {
+    fn from(value: &GenericValue) -> Self {
+        match value {
+            GenericValue::BoolContent(_) => Self::BoolContent,
+            GenericValue::TextContent(_) => Self::TextContent,
+            GenericValue::Principal(_) => Self::Principal,
+            GenericValue::Nat8Content(_) => Self::Nat8Content,
+            GenericValue::Nat16Content(_) => Self::Nat16Content,
+            GenericValue::Nat32Content(_) => Self::Nat32Content,
+            GenericValue::Nat64Content(_) => Self::Nat64Content,
+            GenericValue::NatContent(_) => Self::NatContent,
+            GenericValue::Int8Content(_) => Self::Int8Content,
+            GenericValue::Int16Content(_) => Self::Int16Content,
+            GenericValue::Int32Content(_) => Self::Int32Content,
+            GenericValue::Int64Content(_) => Self::Int64Content,
+            GenericValue::IntContent(_) => Self::IntContent,
+            GenericValue::FloatContent(_) => Self::FloatContent,
+        }
+    }
+}
+
 impl GenericValue {
     /// Check if the value equals to the given string
     pub fn equals_str(&self, s: &str) -> bool {
         self.to_string() == s
     }
+
+    /// Get the value as a number, if the value is numeric
+    pub fn as_f64(&self) -> Option<f64> {
+        match self {
+            GenericValue::BoolContent(_)
+            | GenericValue::TextContent(_)
+            | GenericValue::Principal(_) => None,
+            GenericValue::Nat8Content(v) => Some(*v as f64),
+            GenericValue::Nat16Content(v) => Some(*v as f64),
+            GenericValue::Nat32Content(v) => Some(*v as f64),
+            GenericValue::Nat64Content(v) => Some(*v as f64),
+            GenericValue::Int8Content(v) => Some(*v as f64),
+            GenericValue::Int16Content(v) => Some(*v as f64),
+            GenericValue::Int32Content(v) => Some(*v as f64),
+            GenericValue::Int64Content(v) => Some(*v as f64),
+            GenericValue::NatContent(v) => v.to_string().replace('_', "").parse().ok(),
+            GenericValue::IntContent(v) => v.to_string().replace('_', "").parse().ok(),
+            GenericValue::FloatContent(v) => Some(*v),
+        }
+    }
 }
 
 impl fmt::Display for GenericValue {
@@ -55,6 +115,29 @@ mod test {
 
     use super::*;
 
+    #[test]
+    fn test_should_get_generic_value_kind() {
+        assert_eq!(
+            GenericValueKind::from(&GenericValue::FloatContent(1.5)),
+            GenericValueKind::FloatContent
+        );
+        assert_eq!(
+            GenericValueKind::from(&GenericValue::TextContent("a".to_string())),
+            GenericValueKind::TextContent
+        );
+    }
+
+    #[test]
+    fn test_should_get_generic_value_as_f64() {
+        assert_eq!(GenericValue::Nat64Content(3).as_f64(), Some(3.0));
+        assert_eq!(GenericValue::Int8Content(-3).as_f64(), Some(-3.0));
+        assert_eq!(
+            GenericValue::NatContent(Nat::from(1_000_000u64)).as_f64(),
+            Some(1_000_000.0)
+        );
+        assert_eq!(GenericValue::BoolContent(true).as_f64(), None);
+    }
+
     #[test]
     fn test_generic_value_to_string() {
         let v = GenericValue::BoolContent(true);
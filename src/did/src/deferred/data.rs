@@ -1,9 +1,24 @@
+mod audit;
 mod error;
+mod listing;
+mod metadata;
+mod schema;
 
 use candid::{CandidType, Deserialize, Principal};
 use ic_log::LogSettingsV2;
+use serde::Serialize;
 
+use crate::deferred::RestrictionLevel;
+use crate::{H160, ID};
+
+pub use self::audit::{AuditLogEntry, AuditOperation, AuditRestrictedValue};
 pub use self::error::{ConfigurationError, ContractError, DeferredDataError};
+pub use self::listing::{
+    ContractListQuery, ContractSortBy, ContractSummary, ContractSummaryPage, GeoCluster, GeoPoint,
+    SearchSuggestion, SortOrder,
+};
+pub use self::metadata::{TokenAttribute, TokenMetadata};
+pub use self::schema::{PropertySchema, StorablePropertySchema};
 
 /// These are the arguments which are taken by the deferred data canister at creation
 #[derive(Debug, Clone, CandidType, Deserialize)]
@@ -12,4 +27,89 @@ pub struct DeferredDataInitData {
     pub log_settings: LogSettingsV2,
     /// minter canister
     pub minter: Principal,
+    /// Chain ID of the EIP-712 domain used by typed data signatures
+    pub chain_id: u64,
+}
+
+/// An entry of the contract archive, listing both active and closed contracts
+#[derive(Debug, Clone, CandidType, Deserialize, PartialEq)]
+pub struct ContractArchiveEntry {
+    /// The contract ID
+    pub id: ID,
+    /// Whether the contract has been closed
+    pub closed: bool,
+}
+
+/// A message signed by a seller or a buyer, to prove the ownership of a contract
+#[derive(Debug, Clone, CandidType, Deserialize, PartialEq)]
+pub enum SignedMessage {
+    /// A Sign-In With Ethereum (EIP-4361) message signed with `personal_sign`
+    Siwe {
+        /// The signed message
+        message: String,
+        /// The hex-encoded signature of the message
+        signature: String,
+    },
+    /// EIP-712 typed data granting read access to the contract, signed with `eth_signTypedData_v4`
+    TypedData {
+        /// Time when the access has been granted (seconds since UNIX epoch)
+        issued_at: u64,
+        /// Time when the access expires (seconds since UNIX epoch)
+        expires_at: u64,
+        nonce: String,
+        /// The hex-encoded signature of the typed data
+        signature: String,
+    },
+}
+
+/// A session granting read access to a contract, created from a signed message
+#[derive(Debug, Clone, CandidType, Deserialize, PartialEq)]
+pub struct ContractSession {
+    /// The token to provide with the `Authorization: Bearer <token>` header
+    pub token: String,
+    /// The contract the session grants access to
+    pub contract_id: ID,
+    /// The access level of the signer
+    pub access_level: RestrictionLevel,
+    /// Time when the session expires (nanoseconds since UNIX epoch)
+    pub expires_at: u64,
+}
+
+/// A named group of addresses and principals defined for a contract.
+///
+/// Members of the group can access the properties and documents with the [`RestrictionLevel::Group`] level.
+#[derive(Debug, Clone, CandidType, Deserialize, PartialEq)]
+pub struct AccessGroup {
+    /// The name of the group, unique within the contract
+    pub name: String,
+    pub members: Vec<AccessGroupMember>,
+}
+
+/// A member of an [`AccessGroup`]
+#[derive(Debug, Clone, CandidType, Deserialize, PartialEq, Eq)]
+pub enum AccessGroupMember {
+    /// An address proving its identity with a signed message, a session or a linked principal
+    Address(H160),
+    /// A principal calling the canister
+    Principal(Principal),
+}
+
+/// An image of the gallery of a contract listing.
+///
+/// All the fields are set by the canister when the image is uploaded.
+#[derive(Debug, Clone, CandidType, Deserialize, Serialize, PartialEq)]
+pub struct ContractImage {
+    /// The image ID, unique among the images of all the contracts
+    pub id: u64,
+    pub name: String,
+    /// The mime type of the original image
+    pub mime_type: String,
+    /// Size of the original image in bytes
+    pub size: u64,
+    /// Width of the original image in pixels
+    pub width: u32,
+    /// Height of the original image in pixels
+    pub height: u32,
+    /// The hex-encoded SHA-256 digest of the original image
+    pub digest: String,
 }
@@ -0,0 +1,82 @@
+use candid::{CandidType, Decode, Deserialize, Encode, Principal};
+use ic_stable_structures::storable::Bound;
+use ic_stable_structures::Storable;
+use serde::Serialize;
+
+use crate::deferred::{ContractDocument, GenericValue, RestrictionLevel};
+use crate::ID;
+
+/// An entry of the audit log of a contract
+#[derive(Clone, Debug, CandidType, Deserialize, Serialize, PartialEq)]
+pub struct AuditLogEntry {
+    /// Sequential ID of the entry
+    pub id: u64,
+    /// The contract the entry refers to
+    pub contract_id: ID,
+    /// The principal which performed the operation
+    pub caller: Principal,
+    /// Time of the operation (nanoseconds since UNIX epoch)
+    pub timestamp: u64,
+    /// The operation performed on the contract
+    pub operation: AuditOperation,
+}
+
+impl Storable for AuditLogEntry {
+    const BOUND: Bound = Bound::Unbounded;
+
+    fn to_bytes(&self) -> std::borrow::Cow<[u8]> {
+        Encode!(&self).unwrap().into()
+    }
+
+    fn from_bytes(bytes: std::borrow::Cow<[u8]>) -> Self {
+        Decode!(&bytes, Self).unwrap()
+    }
+}
+
+/// An operation recorded in the audit log.
+///
+/// `None` values mean that the property didn't exist before the operation or has been removed.
+#[derive(Clone, Debug, CandidType, Deserialize, Serialize, PartialEq)]
+pub enum AuditOperation {
+    /// A public property has been set or removed
+    SetProperty {
+        key: String,
+        previous: Option<GenericValue>,
+        new: Option<GenericValue>,
+    },
+    /// A restricted property has been set or removed
+    SetRestrictedProperty {
+        key: String,
+        previous: Option<AuditRestrictedValue>,
+        new: Option<AuditRestrictedValue>,
+    },
+    /// A document has been uploaded
+    UploadDocument {
+        document_id: u64,
+        document: ContractDocument,
+    },
+    /// A document has been replaced by a new revision
+    ReplaceDocument {
+        previous_document_id: u64,
+        document_id: u64,
+        document: ContractDocument,
+    },
+    /// A document has been deleted
+    DeleteDocument { document_id: u64 },
+    /// The access list of a document has been changed
+    UpdateDocumentAccess {
+        document_id: u64,
+        previous: Vec<RestrictionLevel>,
+        new: Vec<RestrictionLevel>,
+    },
+    /// The contract has been closed
+    CloseContract,
+}
+
+/// A restricted property value recorded in the audit log
+#[derive(Clone, Debug, CandidType, Deserialize, Serialize, PartialEq)]
+pub struct AuditRestrictedValue {
+    pub access_list: Vec<RestrictionLevel>,
+    /// The property value; `None` if the caller can't access it
+    pub value: Option<GenericValue>,
+}
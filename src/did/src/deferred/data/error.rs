@@ -18,6 +18,20 @@ pub enum DeferredDataError {
     CanisterCall(RejectionCode, String),
     #[error("invalid signature")]
     InvalidSignature,
+    #[error("invalid signed message: {0}")]
+    InvalidSignedMessage(String),
+    #[error("signed message has expired")]
+    SignedMessageExpired,
+    #[error("no address is linked to the caller")]
+    AddressNotLinked,
+    #[error("session has expired")]
+    SessionExpired,
+    #[error("invalid cursor")]
+    InvalidCursor,
+    #[error("invalid query: {0}")]
+    InvalidQuery(String),
+    #[error("too many nonces are pending")]
+    TooManyNonces,
 }
 
 #[derive(Clone, Debug, Error, CandidType, PartialEq, Eq, Deserialize)]
@@ -30,6 +44,26 @@ pub enum ContractError {
     DocumentNotFound(u64),
     #[error("document size mismatch provided size: {0}, actual size: {1}")]
     DocumentSizeMismatch(u64, u64),
+    #[error("document {0} has been superseded by a newer version")]
+    DocumentSuperseded(u64),
+    #[error("property {0} not found")]
+    PropertyNotFound(String),
+    #[error("property {0} has an unexpected value type")]
+    PropertyTypeMismatch(String),
+    #[error("property {0} is out of the allowed range")]
+    PropertyOutOfRange(String),
+    #[error("property {0} is required")]
+    MissingRequiredProperty(String),
+    #[error("document upload {0} not found")]
+    UploadNotFound(u64),
+    #[error("document upload {0} has expired")]
+    UploadExpired(u64),
+    #[error("document upload is missing chunk {0}")]
+    UploadChunkMissing(u64),
+    #[error("image {0} not found")]
+    ImageNotFound(u64),
+    #[error("invalid image: {0}")]
+    InvalidImage(String),
 }
 
 #[derive(Clone, Debug, Error, CandidType, PartialEq, Eq, Deserialize)]
@@ -0,0 +1,92 @@
+use candid::{CandidType, Deserialize};
+use serde::Serialize;
+
+use crate::deferred::ContractType;
+use crate::ID;
+
+/// A lightweight view of a contract, returned by the paginated contract listing
+#[derive(Clone, Debug, CandidType, Deserialize, Serialize, PartialEq)]
+pub struct ContractSummary {
+    pub id: ID,
+    pub r#type: ContractType,
+    pub value: u64,
+    /// Currency symbol
+    pub currency: String,
+    /// The `contract:city` property
+    pub city: Option<String>,
+    /// URL of the image of the property, taken from the `contract:image` property.
+    ///
+    /// Inline (base64) images are omitted, to keep the summary lightweight.
+    pub thumbnail: Option<String>,
+    /// Contract expiration date YYYY-MM-DD
+    pub expiration: String,
+}
+
+/// The field contracts are sorted by when listed
+#[derive(Clone, Copy, Debug, Default, CandidType, Deserialize, PartialEq, Eq)]
+pub enum ContractSortBy {
+    #[default]
+    Id,
+    Value,
+    Expiration,
+    /// Distance from the origin of the query; contracts without a position come last
+    Distance,
+}
+
+/// A point on the earth surface, in degrees
+#[derive(Clone, Copy, Debug, Default, CandidType, Deserialize, Serialize, PartialEq)]
+pub struct GeoPoint {
+    pub latitude: f64,
+    pub longitude: f64,
+}
+
+/// The direction contracts are sorted in when listed
+#[derive(Clone, Copy, Debug, Default, CandidType, Deserialize, PartialEq, Eq)]
+pub enum SortOrder {
+    #[default]
+    Ascending,
+    Descending,
+}
+
+/// Parameters of a page of the contract listing
+#[derive(Clone, Debug, Default, CandidType, Deserialize, PartialEq)]
+pub struct ContractListQuery {
+    pub sort_by: ContractSortBy,
+    pub order: SortOrder,
+    /// The point distances are measured from; required to sort by distance
+    pub origin: Option<GeoPoint>,
+    /// The `next_cursor` of the previous page; `None` to get the first page.
+    ///
+    /// A cursor is valid only with the sorting it has been returned with.
+    pub cursor: Option<String>,
+    /// Maximum amount of contracts in the page
+    pub limit: Option<u64>,
+}
+
+/// A page of the contract listing
+#[derive(Clone, Debug, CandidType, Deserialize, Serialize, PartialEq)]
+pub struct ContractSummaryPage {
+    pub contracts: Vec<ContractSummary>,
+    /// The cursor to get the next page; `None` if this is the last page
+    pub next_cursor: Option<String>,
+}
+
+/// Pins of the contracts in a cell of the map grid
+#[derive(Clone, Debug, CandidType, Deserialize, Serialize, PartialEq)]
+pub struct GeoCluster {
+    /// Average position of the contracts in the cell
+    pub position: GeoPoint,
+    /// Amount of contracts in the cell
+    pub count: u64,
+}
+
+/// A place suggested by the contracts autocomplete
+#[derive(Clone, Debug, CandidType, Deserialize, Serialize, PartialEq, Eq)]
+pub struct SearchSuggestion {
+    /// Key of the property of the place (`contract:city` or `contract:zone`)
+    pub property: String,
+    /// Value of the property
+    pub value: String,
+    /// Amount of open contracts in the place
+    pub count: u64,
+}
@@ -0,0 +1,23 @@
+use serde::{Deserialize, Serialize};
+
+/// ERC-721 metadata of a deferred token, in the JSON format rendered by wallets and NFT marketplaces
+#[derive(Clone, Debug, Deserialize, Serialize, PartialEq)]
+pub struct TokenMetadata {
+    pub name: String,
+    pub description: String,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub image: Option<String>,
+    pub external_url: String,
+    pub attributes: Vec<TokenAttribute>,
+}
+
+/// A trait of a token, shown by the marketplaces
+#[derive(Clone, Debug, Deserialize, Serialize, PartialEq)]
+pub struct TokenAttribute {
+    pub trait_type: String,
+    /// Text or number
+    pub value: serde_json::Value,
+    /// How to display a numeric value (e.g. `number` or `date`)
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub display_type: Option<String>,
+}
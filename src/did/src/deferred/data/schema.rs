@@ -0,0 +1,82 @@
+use std::borrow::Cow;
+
+use candid::{CandidType, Decode, Deserialize, Encode};
+use ic_stable_structures::storable::Bound;
+use ic_stable_structures::Storable;
+use serde::Serialize;
+
+use crate::deferred::{ContractType, GenericValueKind};
+
+/// Schema of a contract property
+#[derive(Clone, Debug, CandidType, Deserialize, Serialize, PartialEq)]
+pub struct PropertySchema {
+    /// Property key (e.g. `contract:rooms`)
+    pub key: String,
+    /// Expected variant of the property value
+    pub kind: GenericValueKind,
+    /// Contract types for which the property is required
+    pub required_for: Vec<ContractType>,
+    /// Minimum allowed value for numeric properties, or minimum length for text properties
+    pub min: Option<f64>,
+    /// Maximum allowed value for numeric properties, or maximum length for text properties
+    pub max: Option<f64>,
+}
+
+impl PropertySchema {
+    /// Create the schema of an optional property without range
+    pub fn optional(key: &str, kind: GenericValueKind) -> Self {
+        Self {
+            key: key.to_string(),
+            kind,
+            required_for: vec![],
+            min: None,
+            max: None,
+        }
+    }
+
+    /// Set the allowed range for the property
+    pub fn with_range(mut self, min: f64, max: f64) -> Self {
+        self.min = Some(min);
+        self.max = Some(max);
+        self
+    }
+}
+
+/// Stable storage wrapper for the list of property schemas
+#[derive(Clone, Debug, Default, PartialEq)]
+pub struct StorablePropertySchema(pub Vec<PropertySchema>);
+
+impl Storable for StorablePropertySchema {
+    const BOUND: Bound = Bound::Unbounded;
+
+    fn to_bytes(&self) -> Cow<'_, [u8]> {
+        Encode!(&self.0).unwrap().into()
+    }
+
+    fn from_bytes(bytes: Cow<[u8]>) -> Self {
+        Self(Decode!(&bytes, Vec<PropertySchema>).unwrap())
+    }
+}
+
+#[cfg(test)]
+mod test {
+
+    use pretty_assertions::assert_eq;
+
+    use super::*;
+
+    #[test]
+    fn test_should_encode_and_decode_property_schema() {
+        let schema = StorablePropertySchema(vec![
+            PropertySchema::optional("contract:latitude", GenericValueKind::FloatContent)
+                .with_range(-90.0, 90.0),
+            PropertySchema {
+                required_for: vec![ContractType::Sell],
+                ..PropertySchema::optional("contract:city", GenericValueKind::TextContent)
+            },
+        ]);
+
+        let decoded = StorablePropertySchema::from_bytes(schema.to_bytes());
+        assert_eq!(decoded, schema);
+    }
+}
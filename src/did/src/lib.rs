@@ -6,5 +6,7 @@ mod common;
 pub mod deferred;
 
 pub use common::{
-    HttpRequest, HttpResponse, StorableLogSettings, StorableNat, StorablePrincipal, H160, ID,
+    CsvTable, Feature, FeatureCollection, FeatureCollectionType, FeatureType, HttpRequest,
+    HttpResponse, Point, PointType, StorableLogSettings, StorableNat, StorablePrincipal,
+    StreamingCallbackHttpResponse, StreamingCallbackToken, StreamingStrategy, H160, ID,
 };
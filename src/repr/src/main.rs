@@ -92,6 +92,9 @@ fn repr_contract() -> anyhow::Result<()> {
             mime_type: "application/pdf".to_string(),
             name: "Contract".to_string(),
             size: 1024,
+            previous_version: None,
+            superseded_by: None,
+            digest: None,
         },
     )];
 
@@ -110,6 +113,7 @@ fn repr_contract() -> anyhow::Result<()> {
         id: 1u64.into(),
         documents,
         closed: false,
+        created_at: None,
     };
 
     let encoded = serde_json::to_string_pretty(&contract)?;